@@ -1,74 +1,94 @@
 use criterion::{criterion_group, criterion_main, BatchSize};
 use criterion::Criterion;
-use matcher::{create_orders, OrderBook};
-use matcher::order::{IncomingOrder, OrderKind, OrderSide};
+use matcher::bench::{self, Scenario};
 use matcher::log::DummyLogger;
+use matcher::order::{Buy, IncomingOrder, Order, OrderId, OrderKind, OrderSide, Sell};
+use matcher::queues::{LinkedQueue, PriceLevelQueue, Queue, ReversedVec, VecDequeQueue};
+use std::cell::RefCell;
 use std::rc::Rc;
-use std::sync::RwLock;
 
-struct BenchInputData {
-    shared_book: Rc<RwLock<OrderBook>>,
-    order: IncomingOrder,
-}
+/// Orders on each side of the book of the queue benchmarks
+const DEPTH: u32 = 50000;
 
-fn reset_book(book: &mut OrderBook, reset_orders: &[IncomingOrder]) {
-    assert_eq!(book.bid().len(), 3500);
-    if book.ask().len() == 3500 {
-        return;
-    }
-    assert_eq!(book.ask().len(), 3500 - 20);
-    let mut logger = DummyLogger;
-    for order in reset_orders {
-        book.execute_order(order.clone(), &mut logger);
-    }
-    assert_eq!(book.ask().len(), 3500);
-}
+/// IoC orders submitted by one iteration of the IoC storm
+const STORM: usize = 100;
 
-impl BenchInputData {
-    pub fn new(shared_book: Rc<RwLock<OrderBook>>, incoming_order: &IncomingOrder, reset_orders: &[IncomingOrder]) -> Self {
-        let mut book = shared_book.write().unwrap();
-        reset_book(&mut book, reset_orders);
+fn criterion_benchmark(c: &mut Criterion) {
+    let scenario = Rc::new(RefCell::new(Scenario::sweep()));
 
-        Self {
-            shared_book: shared_book.clone(),
-            order: incoming_order.clone(),
-        }
-    }
+    c.bench_function("execute order", move |b| b.iter_batched(
+        || {
+            scenario.borrow_mut().reset();
+            scenario.clone()
+        },
+        |scenario| scenario.borrow_mut().execute(&mut DummyLogger),
+        BatchSize::PerIteration)
+    );
 }
 
-fn execute_order(data: BenchInputData) {
-    let mut logger = DummyLogger;
-    let mut book = data.shared_book.write().unwrap();
-    book.execute_order(data.order, &mut logger);
+fn limit(side: OrderSide, price_limit: i64, size: u64) -> IncomingOrder {
+    IncomingOrder { price_limit, size, user_id: 1, kind: OrderKind::Limit, side, expires_at: None, min_fill_size: None, reduce_only: false }
 }
 
-fn criterion_benchmark(c: &mut Criterion) {
-    let orders = create_orders();
-    let book = OrderBook::from_vec(orders);
+/// Inserts a limit order behind all resting orders of its side and cancels it
+fn insert_far<QB: 'static+Queue<Order<Buy>>, QA: 'static+Queue<Order<Sell>>>(c: &mut Criterion, queue: &str) {
+    let mut book = bench::deep_book::<QB, QA>(DEPTH);
+    let order = limit(OrderSide::Buy, 100000 - i64::from(DEPTH) - 10, 10);
+    c.bench_function(&format!("insert far from touch/{}", queue), move |b| b.iter(|| {
+        book.execute_order(order.clone(), &mut DummyLogger);
+        let order_id = book.last_order_id().expect("order is submitted");
+        book.cancel_order(order_id, &mut DummyLogger).expect("order rests");
+    }));
+}
 
-    let order = IncomingOrder {
-        price_limit: 10020,
-        size: 200,
-        user_id: 0,
-        kind: OrderKind::Limit,
-        side: OrderSide::Buy
-    };
+/// Cancels a random resting order and submits it again
+fn cancel_random<QB: 'static+Queue<Order<Buy>>, QA: 'static+Queue<Order<Sell>>>(c: &mut Criterion, queue: &str) {
+    let mut book = bench::deep_book::<QB, QA>(DEPTH);
+    let mut order_ids: Vec<OrderId> = (1..=2 * u64::from(DEPTH)).collect();
+    // Xorshift, so that every queue cancels the same sequence of orders
+    let mut state = 0x2545_f491_4f6c_dd1du64;
+    c.bench_function(&format!("cancel random order/{}", queue), move |b| b.iter(|| {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        let index = (state % order_ids.len() as u64) as usize;
+        let cancelled = book.cancel_order(order_ids[index], &mut DummyLogger).expect("order rests");
+        book.execute_order(cancelled.order, &mut DummyLogger);
+        order_ids[index] = book.last_order_id().expect("order is submitted");
+    }));
+}
 
-    let mut reset_orders = Vec::new();
-    for order in book.ask() {
-        reset_orders.insert(0, order.to_incoming());
-        if reset_orders.len() == 20 {
-            break;
+/// Submits IoC orders of size 1 against the touch of both sides, each replaced by a new resting order
+fn ioc_storm<QB: 'static+Queue<Order<Buy>>, QA: 'static+Queue<Order<Sell>>>(c: &mut Criterion, queue: &str) {
+    let mut book = bench::deep_book::<QB, QA>(DEPTH);
+    c.bench_function(&format!("ioc storm/{}", queue), move |b| b.iter(|| {
+        for i in 0..STORM {
+            let (side, price) = if i % 2 == 0 {
+                (OrderSide::Buy, book.ask().best_price())
+            } else {
+                (OrderSide::Sell, book.bid().best_price())
+            };
+            let price = price.expect("touch is not empty");
+            let ioc = IncomingOrder { kind: OrderKind::ImmediateOrCancel, user_id: 2, ..limit(side, price, 1) };
+            book.execute_order(ioc, &mut DummyLogger);
+            let side = if side == OrderSide::Buy { OrderSide::Sell } else { OrderSide::Buy };
+            book.execute_order(limit(side, price, 1), &mut DummyLogger);
         }
-    }
-    let shared_book = Rc::new(RwLock::new(book));
+    }));
+}
 
-    c.bench_function("execute order", move |b| b.iter_batched(
-        || BenchInputData::new(shared_book.clone(), &order, &reset_orders),
-        execute_order,
-        BatchSize::PerIteration)
-    );
+fn queue_benchmark<QB: 'static+Queue<Order<Buy>>, QA: 'static+Queue<Order<Sell>>>(c: &mut Criterion, queue: &str) {
+    insert_far::<QB, QA>(c, queue);
+    cancel_random::<QB, QA>(c, queue);
+    ioc_storm::<QB, QA>(c, queue);
+}
+
+fn queue_benchmarks(c: &mut Criterion) {
+    queue_benchmark::<ReversedVec<Buy>, ReversedVec<Sell>>(c, "reversed-vec");
+    queue_benchmark::<VecDequeQueue<Buy>, VecDequeQueue<Sell>>(c, "vec-deque");
+    queue_benchmark::<PriceLevelQueue<Buy>, PriceLevelQueue<Sell>>(c, "price-level");
+    queue_benchmark::<LinkedQueue<Buy>, LinkedQueue<Sell>>(c, "linked");
 }
 
-criterion_group!(benches, criterion_benchmark);
+criterion_group!(benches, criterion_benchmark, queue_benchmarks);
 criterion_main!(benches);
@@ -1,42 +1,51 @@
-use matcher::{create_orders, OrderBook};
-use matcher::order::{IncomingOrder, OrderKind, OrderSide};
-use matcher::log::DummyLogger;
+//! Throughput report of the benchmark scenario library
+//!
+//! Usage: `cargo run --release --example benchmark -- [--csv] [iterations]`
+//!
+//! Prints a JSON (default) or CSV report with orders/sec and fills/sec of every scenario.
 
-fn main() {
-    let orders = create_orders();
-    let mut book = OrderBook::from_vec(orders);
-    let mut logger = DummyLogger;
-    assert_eq!(book.bid().len(), 3500);
-    assert_eq!(book.ask().len(), 3500);
+use matcher::bench::{self, Measurement, Scenario};
+use matcher::log::CountingLogger;
+use std::env;
+use std::process;
 
-    let order = IncomingOrder {
-        price_limit: 10020,
-        size: 200,
-        user_id: 0,
-        kind: OrderKind::Limit,
-        side: OrderSide::Buy
-    };
+fn usage() -> ! {
+    eprintln!("Usage: benchmark [--csv] [iterations]");
+    process::exit(2);
+}
 
-    let mut reset_orders = Vec::new();
-    for order in book.ask() {
-        reset_orders.insert(0, order.to_incoming());
-        if reset_orders.len() == 20 {
-            break;
+fn main() {
+    let mut csv = false;
+    let mut iterations = 100000;
+    for arg in env::args().skip(1) {
+        match arg.as_str() {
+            "--csv" => csv = true,
+            _ => iterations = arg.parse().unwrap_or_else(|_| usage()),
         }
     }
 
-    for _ in 0..1000000 {
-        book.execute_order(order.clone(), &mut logger);
-
-        assert_eq!(book.bid().len(), 3500);
-        assert_eq!(book.ask().len(), 3500 - 20);
+    let mut results: Vec<(&str, Measurement)> = Vec::new();
+    for (name, mut scenario) in Scenario::library() {
+        let mut logger = CountingLogger::new();
+        let measurement = bench::measure(&mut scenario, iterations, &mut logger);
+        assert_eq!(logger.counts().fulfilled.count, measurement.fills);
+        results.push((name, measurement));
+    }
 
-        let mut logger = DummyLogger;
-        for order in &reset_orders {
-            book.execute_order(order.clone(), &mut logger);
+    if csv {
+        println!("scenario,iterations,fills,elapsed_ns,ns_per_order,orders_per_sec,fills_per_sec");
+        for (name, m) in &results {
+            println!("{},{},{},{},{},{:.0},{:.0}", name, m.iterations, m.fills, m.elapsed.as_nanos(),
+                m.per_order().as_nanos(), m.orders_per_second(), m.fills_per_second());
         }
-
-        assert_eq!(book.bid().len(), 3500);
-        assert_eq!(book.ask().len(), 3500);
+    } else {
+        println!("[");
+        for (i, (name, m)) in results.iter().enumerate() {
+            let separator = if i + 1 < results.len() { "," } else { "" };
+            println!("  {{\"scenario\":\"{}\",\"iterations\":{},\"fills\":{},\"elapsed_ns\":{},\"ns_per_order\":{},\"orders_per_sec\":{:.0},\"fills_per_sec\":{:.0}}}{}",
+                name, m.iterations, m.fills, m.elapsed.as_nanos(), m.per_order().as_nanos(),
+                m.orders_per_second(), m.fills_per_second(), separator);
+        }
+        println!("]");
     }
 }
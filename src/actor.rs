@@ -0,0 +1,130 @@
+//! Asynchronous order book actor
+//!
+//! [AsyncOrderBook](AsyncOrderBook) owns the book on a dedicated thread and accepts orders through
+//! a channel. Results are returned as futures, so the book can be used from async services
+//! without blocking their executors. The futures only rely on [Waker](std::task::Waker) and work
+//! with any runtime.
+
+use crate::OrderBook;
+use crate::config::RejectReason;
+use crate::log::DummyLogger;
+use crate::order::IncomingOrder;
+use crate::summary::ExecutionSummary;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::mpsc::{self, Sender};
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Waker};
+use std::thread::{self, JoinHandle};
+
+type ExecutionResult = Result<ExecutionSummary, RejectReason>;
+
+#[derive(Default)]
+struct SharedState {
+    result: Option<ExecutionResult>,
+    waker: Option<Waker>,
+}
+
+/// Future resolving to the [summary](ExecutionSummary) of an executed order
+pub struct ExecutionFuture {
+    state: Arc<Mutex<SharedState>>,
+}
+
+impl Future for ExecutionFuture {
+    type Output = ExecutionResult;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut state = self.state.lock().expect("execution state lock is poisoned");
+        match state.result.take() {
+            Some(result) => Poll::Ready(result),
+            None => {
+                state.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+/// Handle of the actor owning an order book
+pub struct AsyncOrderBook {
+    orders: Sender<(IncomingOrder, Arc<Mutex<SharedState>>)>,
+    actor: JoinHandle<OrderBook>,
+}
+
+impl AsyncOrderBook {
+    /// Starts the actor owning `book`
+    pub fn spawn(mut book: OrderBook) -> Self {
+        let (orders, requests) = mpsc::channel::<(IncomingOrder, Arc<Mutex<SharedState>>)>();
+        let actor = thread::spawn(move || {
+            for (order, state) in requests {
+                let result = book.try_execute_order(order, &mut DummyLogger);
+                let waker = {
+                    let mut state = state.lock().expect("execution state lock is poisoned");
+                    state.result = Some(result);
+                    state.waker.take()
+                };
+                if let Some(waker) = waker {
+                    waker.wake();
+                }
+            }
+            book
+        });
+        AsyncOrderBook { orders, actor }
+    }
+
+    /// Submits `order` for execution
+    ///
+    /// Orders are executed in submission order, whether the futures are polled or not.
+    pub fn execute_order(&self, order: IncomingOrder) -> ExecutionFuture {
+        let state = Arc::new(Mutex::new(SharedState::default()));
+        self.orders.send((order, state.clone())).expect("order book actor is running");
+        ExecutionFuture { state }
+    }
+
+    /// Stops the actor once the submitted orders are executed and returns the book
+    pub fn shutdown(self) -> OrderBook {
+        drop(self.orders);
+        self.actor.join().expect("order book actor panicked")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::BookConfig;
+    use std::task::Wake;
+
+    struct ThreadWaker(thread::Thread);
+
+    impl Wake for ThreadWaker {
+        fn wake(self: Arc<Self>) {
+            self.0.unpark();
+        }
+    }
+
+    fn block_on<F: Future>(future: F) -> F::Output {
+        let mut future = Box::pin(future);
+        let waker = Waker::from(Arc::new(ThreadWaker(thread::current())));
+        let mut cx = Context::from_waker(&waker);
+        loop {
+            if let Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+                return output;
+            }
+            thread::park();
+        }
+    }
+
+    #[test]
+    fn actor_executes_orders() {
+        let book = AsyncOrderBook::spawn(OrderBook::with_config(BookConfig::strict()));
+        let first = book.execute_order("Lim S $100 #5 u1".parse().unwrap());
+        let second = book.execute_order("IoC B $100 #7 u2".parse().unwrap());
+        let rejected = book.execute_order("Lim B $100 #0 u3".parse().unwrap());
+
+        let summary = block_on(second).unwrap();
+        assert_eq!((summary.order_id, summary.filled_size, summary.cancelled_size), (2, 5, 2));
+        assert_eq!(block_on(first).unwrap().resting_size, 5);
+        assert_eq!(block_on(rejected), Err(RejectReason::ZeroSize));
+        assert!(book.shutdown().to_vec().is_empty());
+    }
+}
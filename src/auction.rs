@@ -0,0 +1,552 @@
+//! Call auctions
+//!
+//! In a call auction orders are collected for a while and then executed together at a single
+//! clearing price instead of being matched continuously. The clearing price maximizes the executed
+//! volume; ties are broken by the smallest imbalance between demand and supply and then by taking
+//! the median of the remaining candidate prices.
+//!
+//! Allocation follows price-time priority (time being the [OrderId](OrderId) order).
+//! Self-trade prevention is not applied during uncrossing.
+//!
+//! Opening and closing auctions collect orders while the book is in the
+//! [AuctionOnly](crate::config::BookState::AuctionOnly) state and are held by
+//! [OrderBook::uncross](OrderBook::uncross). [BatchAuction](BatchAuction) runs frequent batch
+//! auctions alongside continuous matching.
+//!
+//! Execution results are logged for every auction order in the order of submission, each order
+//! followed by [finish](ExecutionLogger::finish). A trade between two auction orders is logged only on
+//! the later one, with the earlier one as the passive side, just like in continuous matching.
+
+use crate::OrderBook;
+use crate::config::BookState;
+use crate::queues::Queue;
+use crate::log::{DummyLogger, ExecutionLogger, LogItem, Trade};
+use crate::order::{Buy, IncomingOrder, Order, OrderId, OrderKind, OrderSide, Price, Sell, Size, TaggedOrder, UserId};
+use alloc::vec::Vec;
+
+/// Single trade of an auction
+#[allow(missing_docs)]
+#[derive(Debug, Clone, PartialEq)]
+pub struct AuctionTrade {
+    pub buy_order_id: OrderId,
+    pub sell_order_id: OrderId,
+    pub buyer: UserId,
+    pub seller: UserId,
+    pub size: Size,
+}
+
+/// Result of uncrossing
+#[derive(Debug, Clone, PartialEq)]
+pub struct AuctionResult {
+    /// Clearing price
+    pub price: Price,
+    /// Total executed size
+    pub volume: Size,
+    /// Trades in the order of allocation
+    pub trades: Vec<AuctionTrade>,
+}
+
+/// Order taking part in an auction
+#[derive(Clone)]
+pub(crate) struct AuctionOrder {
+    pub order_id: OrderId,
+    pub order: IncomingOrder,
+    /// Size removed by lot rounding, cancelled after the auction
+    pub odd_lot: Size,
+}
+
+struct Participant {
+    order_id: OrderId,
+    user_id: UserId,
+    price: Price,
+    // Market orders take part at any price but do not set the clearing price
+    market: bool,
+    size: Size,
+    // Index into the auction orders, `None` for resting orders
+    auction_index: Option<usize>,
+    allocated: Size,
+}
+
+/// Returns clearing price and volume for the sorted participants
+fn clearing_price(buys: &[Participant], sells: &[Participant]) -> Option<(Price, u64)> {
+    let mut prices: Vec<Price> = buys.iter().chain(sells).filter(|p| !p.market).map(|p| p.price).collect();
+    prices.sort_unstable();
+    prices.dedup();
+
+    let mut best: Vec<Price> = Vec::new();
+    let mut best_key = (0, 0);
+    for price in prices {
+        let total = |participants: &[Participant], crosses: &dyn Fn(Price) -> bool| participants.iter()
+            .filter(|p| crosses(p.price))
+            .fold(0u64, |total, p| total.saturating_add(p.size));
+        let demand = total(buys, &|bid| bid >= price);
+        let supply = total(sells, &|ask| ask <= price);
+        let volume = demand.min(supply);
+        if volume == 0 {
+            continue;
+        }
+        // Larger volume first, then smaller imbalance
+        let key = (volume, u64::MAX - (demand.max(supply) - volume));
+        if key > best_key {
+            best_key = key;
+            best.clear();
+        }
+        if key == best_key {
+            best.push(price);
+        }
+    }
+    best.get(best.len().saturating_sub(1) / 2).map(|&price| (price, best_key.0))
+}
+
+fn allocate(participants: &mut [Participant], mut volume: Size) {
+    for participant in participants {
+        participant.allocated = participant.size.min(volume);
+        volume -= participant.allocated;
+    }
+}
+
+impl<QB: Queue<Order<Buy>>, QA: Queue<Order<Sell>>> OrderBook<QB, QA> {
+    /// Holds the call auction of the orders parked while trading was not open
+    ///
+    /// The parked orders are executed together with the resting orders at a single clearing price;
+    /// the trading state is left unchanged, so an opening auction is usually followed by
+    /// [set_state](OrderBook::set_state) with [Open](crate::config::BookState::Open). Returns `None`
+    /// if nothing was traded.
+    pub fn uncross(&mut self, logger: &mut impl ExecutionLogger) -> Option<AuctionResult> {
+        let orders = core::mem::take(&mut self.auction_orders);
+        if orders.is_empty() {
+            return None;
+        }
+        self.sequenced(logger, |book, logger| book.uncross_orders(orders, logger))
+    }
+
+    /// Returns the clearing price and volume the parked orders would be uncrossed at now
+    ///
+    /// The auction is simulated on a copy of the book, without reporting its fills to the risk checker.
+    pub fn indicative_auction(&self) -> Option<(Price, u64)> {
+        let mut book = self.clone();
+        #[cfg(feature = "std")]
+        {
+            book.risk = None;
+        }
+        book.uncross_orders(self.auction_orders.clone(), &mut DummyLogger)
+            .map(|result| (result.price, result.volume))
+    }
+
+    /// Executes `orders` together with the resting orders at a single clearing price
+    ///
+    /// Remainders of limit and iceberg orders are added to the book; remainders of other orders are cancelled.
+    /// Resting iceberg orders take part with their hidden reserves.
+    /// Fill-or-kill orders which cannot be filled completely do not take part in the auction.
+    pub(crate) fn uncross_orders(&mut self, orders: Vec<AuctionOrder>, logger: &mut impl ExecutionLogger) -> Option<AuctionResult> {
+        let mut excluded = vec![false; orders.len()];
+        let (mut buys, mut sells, clearing) = loop {
+            let mut buys: Vec<Participant> = self.bid.into_iter().map(|o| Participant {
+                order_id: o.order_id, user_id: o.user_id, price: o.price_limit, market: false, size: o.total_size(),
+                auction_index: None, allocated: 0,
+            }).collect();
+            let mut sells: Vec<Participant> = self.ask.into_iter().map(|o| Participant {
+                order_id: o.order_id, user_id: o.user_id, price: o.price_limit, market: false, size: o.total_size(),
+                auction_index: None, allocated: 0,
+            }).collect();
+            for (index, auction_order) in orders.iter().enumerate().filter(|&(index, _)| !excluded[index]) {
+                let participant = Participant {
+                    order_id: auction_order.order_id,
+                    user_id: auction_order.order.user_id,
+                    price: match (auction_order.order.kind, auction_order.order.side) {
+                        (OrderKind::Market, OrderSide::Buy) => Price::MAX,
+                        (OrderKind::Market, OrderSide::Sell) => Price::MIN,
+                        _ => auction_order.order.price_limit,
+                    },
+                    market: auction_order.order.kind == OrderKind::Market,
+                    size: auction_order.order.size,
+                    auction_index: Some(index),
+                    allocated: 0,
+                };
+                match auction_order.order.side {
+                    OrderSide::Buy => buys.push(participant),
+                    OrderSide::Sell => sells.push(participant),
+                }
+            }
+            // Resting orders come in queue order, which is consumed below, followed by the auction orders in
+            // the order of submission; the stable sort keeps the resting orders first at every price
+            buys.sort_by_key(|p| core::cmp::Reverse(p.price));
+            sells.sort_by_key(|p| p.price);
+
+            let clearing = clearing_price(&buys, &sells);
+            let volume = clearing.map_or(0, |(_, volume)| volume);
+            allocate(&mut buys, volume);
+            allocate(&mut sells, volume);
+
+            let partial_fill_or_kill = buys.iter().chain(&sells)
+                .filter_map(|p| p.auction_index.filter(|_| p.allocated < p.size))
+                .find(|&index| orders[index].order.kind == OrderKind::FillOrKill);
+            match partial_fill_or_kill {
+                Some(index) => excluded[index] = true,
+                None => break (buys, sells, clearing),
+            }
+        };
+
+        let mut filled = vec![0; orders.len()];
+        let (mut resting_bid, mut resting_ask) = (0, 0);
+        for participant in &buys {
+            match participant.auction_index {
+                Some(index) => filled[index] = participant.allocated,
+                None => resting_bid += participant.allocated,
+            }
+        }
+        for participant in &sells {
+            match participant.auction_index {
+                Some(index) => filled[index] = participant.allocated,
+                None => resting_ask += participant.allocated,
+            }
+        }
+        self.bid.consume_front(resting_bid);
+        self.ask.consume_front(resting_ask);
+
+        let mut items: Vec<Vec<LogItem>> = orders.iter().map(|_| Vec::new()).collect();
+        let mut trades = Vec::new();
+        if let Some((price, volume)) = clearing {
+            if volume > 0 {
+                self.last_trade_price = Some(price);
+            }
+            let (mut b, mut s) = (0, 0);
+            while b < buys.len() && s < sells.len() && buys[b].allocated > 0 && sells[s].allocated > 0 {
+                let (buy, sell) = (&mut buys[b], &mut sells[s]);
+                let size = buy.allocated.min(sell.allocated);
+                buy.allocated -= size;
+                sell.allocated -= size;
+                trades.push(AuctionTrade {
+                    buy_order_id: buy.order_id,
+                    sell_order_id: sell.order_id,
+                    buyer: buy.user_id,
+                    seller: sell.user_id,
+                    size,
+                });
+
+                let (aggressor, passive, taker_side) = match (buy.auction_index, sell.auction_index) {
+                    (Some(_), Some(_)) if sell.order_id > buy.order_id => (&*sell, &*buy, OrderSide::Sell),
+                    (Some(_), _) => (&*buy, &*sell, OrderSide::Buy),
+                    _ => (&*sell, &*buy, OrderSide::Sell),
+                };
+                self.trade_sequence += 1;
+                let trade = Trade {
+                    sequence: self.trade_sequence,
+                    maker_order_id: passive.order_id,
+                    taker_order_id: aggressor.order_id,
+                    maker_user_id: passive.user_id,
+                    taker_user_id: aggressor.user_id,
+                    taker_side,
+                    price,
+                    size,
+                };
+                #[cfg(feature = "std")]
+                if let Some(risk) = &self.risk {
+                    risk.lock().expect("risk checker lock is poisoned").on_fill(&trade);
+                }
+                if let Some(tape) = &mut self.tape {
+                    tape.push(trade);
+                }
+                if let Some(index) = aggressor.auction_index {
+                    items[index].push(LogItem::Fulfilled {
+                        size,
+                        price,
+                        user_id: passive.user_id,
+                        order_id: passive.order_id,
+                    });
+                }
+
+                if buys[b].allocated == 0 {
+                    b += 1;
+                }
+                if sells[s].allocated == 0 {
+                    s += 1;
+                }
+            }
+        }
+
+        for (index, auction_order) in orders.into_iter().enumerate() {
+            let mut items = core::mem::take(&mut items[index]);
+            let remainder = auction_order.order.size - filled[index];
+            if matches!(auction_order.order.kind, OrderKind::Limit | OrderKind::Iceberg { .. } | OrderKind::Hidden) {
+                // Part of the order not filled by its own trades rests in the book and
+                // may be filled by later auction orders
+                let own_fills: Size = items.iter().map(|item| match *item {
+                    LogItem::Fulfilled { size, .. } => size,
+                    _ => 0,
+                }).sum();
+                if own_fills < auction_order.order.size {
+                    let size = auction_order.order.size - own_fills;
+                    items.push(LogItem::enqueued(&auction_order.order, auction_order.order_id, size));
+                }
+                if remainder > 0 {
+                    let resting = IncomingOrder { size: remainder, ..auction_order.order.clone() };
+                    self.enqueue(TaggedOrder::new(resting, auction_order.order_id));
+                }
+            } else if remainder > 0 {
+                items.push(LogItem::cancelled(&auction_order.order, auction_order.order_id, remainder));
+            }
+            if auction_order.odd_lot > 0 {
+                items.push(LogItem::cancelled(&auction_order.order, auction_order.order_id, auction_order.odd_lot));
+            }
+
+            let mut submitted = auction_order.order;
+            submitted.size += auction_order.odd_lot;
+            for item in &items {
+                logger.log(item.clone());
+            }
+            self.record_observers(auction_order.order_id, &submitted, &items);
+            logger.finish();
+        }
+        self.trigger_stops(logger);
+        logger.finish();
+
+        clearing.map(|(price, volume)| AuctionResult { price, volume, trades })
+    }
+}
+
+/// Frequent batch auction
+///
+/// Marketable orders are not matched on arrival but collected into batches which are uncrossed
+/// at the end of every `interval` of time. Orders which cannot trade immediately are passed to the book
+/// directly, so the book itself never crosses. Time is supplied by the caller.
+pub struct BatchAuction {
+    interval: u64,
+    batch_end: Option<u64>,
+    pending: Vec<AuctionOrder>,
+}
+
+impl BatchAuction {
+    /// Constructs batch auction with batches ending at multiples of `interval`
+    pub fn new(interval: u64) -> Self {
+        BatchAuction {
+            interval: interval.max(1),
+            batch_end: None,
+            pending: Vec::new(),
+        }
+    }
+
+    /// Returns the number of orders waiting for the current batch
+    pub fn pending_len(&self) -> usize {
+        self.pending.len()
+    }
+
+    /// Returns the end of the current batch, `None` if there are no pending orders
+    pub fn batch_end(&self) -> Option<u64> {
+        self.batch_end
+    }
+
+    fn is_marketable(&self, book: &OrderBook, order: &IncomingOrder) -> bool {
+        // Market orders cross any price of the opposite side
+        let crosses = |side: OrderSide, price: Price, market: bool| side != order.side && match order.side {
+            _ if market || order.kind == OrderKind::Market => true,
+            OrderSide::Buy => price <= order.price_limit,
+            OrderSide::Sell => price >= order.price_limit,
+        };
+        let best_bid = book.bid.into_iter().next().map(|o| (OrderSide::Buy, o.price_limit));
+        let best_ask = book.ask.into_iter().next().map(|o| (OrderSide::Sell, o.price_limit));
+        best_bid.into_iter().chain(best_ask).any(|(side, price)| crosses(side, price, false))
+            || self.pending.iter().any(|p| crosses(p.order.side, p.order.price_limit, p.order.kind == OrderKind::Market))
+    }
+
+    /// Submits `order` at time `now` and returns its identifier
+    ///
+    /// Marketable orders are added to the current batch even if its end has passed,
+    /// so [advance](BatchAuction::advance) should be called first. They are checked against the
+    /// limits of the book and the price collar like orders executed by the book. Other orders, including
+    /// stop, post-only, pegged and reduce-only orders, orders which do not pass the checks and all orders
+    /// submitted while trading is not open, are executed immediately.
+    pub fn submit(&mut self, book: &mut OrderBook, order: IncomingOrder, now: u64, logger: &mut impl ExecutionLogger) -> OrderId {
+        let accepted = book.config.validate(&order)
+            .and_then(|_| book.check_limits(&order))
+            .and_then(|_| book.config.apply_collar(&order, book.collar_reference()))
+            .and_then(|price| book.config.round_to_lot(order.size).map(|size| IncomingOrder { price_limit: price, size, ..order.clone() }));
+        match accepted {
+            Ok(batched) if book.state == BookState::Open
+                && order.kind.trigger_price().is_none()
+                && !matches!(order.kind, OrderKind::PostOnly | OrderKind::Pegged { .. })
+                && !order.reduce_only
+                && self.is_marketable(book, &batched) => {
+                let odd_lot = order.size - batched.size;
+                let order_id = book.take_order_id();
+                self.pending.push(AuctionOrder {
+                    order_id,
+                    order: batched,
+                    odd_lot,
+                });
+                if self.batch_end.is_none() {
+                    self.batch_end = Some((now / self.interval + 1).saturating_mul(self.interval));
+                }
+                order_id
+            }
+            _ => {
+                book.execute_order(order, logger);
+                book.last_order_id().expect("order id is assigned on execution")
+            }
+        }
+    }
+
+    /// Uncrosses the current batch if it has ended by `now`
+    pub fn advance(&mut self, book: &mut OrderBook, now: u64, logger: &mut impl ExecutionLogger) -> Option<AuctionResult> {
+        match self.batch_end {
+            Some(end) if now >= end => self.flush(book, logger),
+            _ => None,
+        }
+    }
+
+    /// Uncrosses the current batch immediately
+    ///
+    /// If trading is no longer open, the orders of the batch are parked or rejected by the book like
+    /// new orders instead. Returns `None` if nothing was traded.
+    pub fn flush(&mut self, book: &mut OrderBook, logger: &mut impl ExecutionLogger) -> Option<AuctionResult> {
+        self.batch_end = None;
+        let orders = core::mem::take(&mut self.pending);
+        if orders.is_empty() {
+            return None;
+        }
+        if book.state != BookState::Open {
+            book.sequenced(logger, |book, logger| {
+                for auction_order in orders {
+                    let order = IncomingOrder { size: auction_order.order.size + auction_order.odd_lot, ..auction_order.order };
+                    book.submit(order, auction_order.order_id, logger);
+                }
+            });
+            return None;
+        }
+        book.sequenced(logger, |book, logger| book.uncross_orders(orders, logger))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::BookConfig;
+    use crate::log::VectorLogger;
+    use crate::status::OrderStatus;
+    use crate::tests::{check_log, OrderBookExt};
+
+    #[test]
+    fn batch_is_uncrossed_at_single_price() {
+        let mut book = OrderBook::new();
+        book.set_status_tracking(true);
+        book.set_trade_tape(Some(10));
+        let mut auction = BatchAuction::new(100);
+        let mut logger = VectorLogger::new();
+        for s in &["Lim S $102 #5 u1", "Lim S $100 #5 u2", "Lim B $98 #5 u3"] {
+            auction.submit(&mut book, s.parse().unwrap(), 10, &mut logger);
+        }
+        assert_eq!(auction.pending_len(), 0);
+
+        let first = auction.submit(&mut book, "Lim B $103 #4 u4".parse().unwrap(), 20, &mut logger);
+        let second = auction.submit(&mut book, "IoC B $101 #3 u5".parse().unwrap(), 30, &mut logger);
+        let third = auction.submit(&mut book, "FoK S $97 #20 u6".parse().unwrap(), 40, &mut logger);
+        let fourth = auction.submit(&mut book, "Lim S $99 #2 u7".parse().unwrap(), 50, &mut logger);
+        assert_eq!(auction.pending_len(), 4);
+        assert_eq!(auction.batch_end(), Some(100));
+        assert!(auction.advance(&mut book, 99, &mut logger).is_none());
+
+        let mut logger = VectorLogger::new();
+        let result = auction.advance(&mut book, 100, &mut logger).unwrap();
+        // Demand and supply are balanced at 100 and 101, the fill-or-kill order is excluded
+        assert_eq!((result.price, result.volume), (100, 7));
+        assert_eq!(result.trades, [
+            AuctionTrade { buy_order_id: first, sell_order_id: fourth, buyer: 4, seller: 7, size: 2 },
+            AuctionTrade { buy_order_id: first, sell_order_id: 2, buyer: 4, seller: 2, size: 2 },
+            AuctionTrade { buy_order_id: second, sell_order_id: 2, buyer: 5, seller: 2, size: 3 },
+        ]);
+        check_log(logger.as_slice(), &["F #2 $100 u2 o2", "Q B #2 $103 u4 o4", "F #3 $100 u2 o2", "C S #20 $97 u6 o6", "F #2 $100 u4 o4"]);
+        let tape: Vec<_> = book.trades_since(0)
+            .map(|trade| (trade.sequence, trade.taker_order_id, trade.maker_order_id, trade.taker_side, trade.price, trade.size))
+            .collect();
+        assert_eq!(tape, [
+            (1, fourth, first, OrderSide::Sell, 100, 2),
+            (2, first, 2, OrderSide::Buy, 100, 2),
+            (3, second, 2, OrderSide::Buy, 100, 3),
+        ]);
+
+        book.check_ask_list(&["Lim S $102 #5 u1"]);
+        book.check_bid_list(&["Lim B $98 #5 u3"]);
+        assert_eq!(book.order_status(first), Some(OrderStatus::Filled));
+        assert_eq!(book.order_status(third), Some(OrderStatus::Cancelled));
+        assert_eq!(book.order_status(2), Some(OrderStatus::Filled));
+        assert_eq!(auction.flush(&mut book, &mut logger), None);
+    }
+
+    #[test]
+    fn market_orders_are_batched_against_any_liquidity() {
+        let mut book = OrderBook::from_orders(&["Lim S $100 #5 u1"]);
+        let mut auction = BatchAuction::new(100);
+        let mut logger = VectorLogger::new();
+        for s in &["Mkt B $0 #3 u2", "Lim B $101 #2 u3", "Mkt S $0 #1 u4", "Lim S $102 #1 u5"] {
+            auction.submit(&mut book, s.parse().unwrap(), 10, &mut logger);
+        }
+        // The limit sell is marketable against the pending market buy
+        assert_eq!(auction.pending_len(), 4);
+        assert!(logger.as_slice().is_empty());
+    }
+
+    #[test]
+    fn batches_follow_the_book_state() {
+        let mut book = OrderBook::from_orders(&["Lim S $100 #5 u1"]);
+        let mut auction = BatchAuction::new(100);
+        let mut logger = VectorLogger::new();
+        auction.submit(&mut book, "Lim B $101 #2 u2".parse().unwrap(), 10, &mut logger);
+        book.set_state(BookState::Halted, &mut logger);
+        auction.submit(&mut book, "Lim B $101 #3 u3".parse().unwrap(), 20, &mut logger);
+        assert_eq!(auction.pending_len(), 1);
+        assert_eq!(auction.flush(&mut book, &mut logger), None);
+        check_log(logger.as_slice(), &["S halted", "R #3 halted", "R #2 halted"]);
+        book.check_ask_list(&["Lim S $100 #5 u1"]);
+
+        // Orders over the limits of the book are rejected by the book
+        book.set_state(BookState::Open, &mut DummyLogger);
+        book.update_config(BookConfig { max_open_orders: Some(0), ..BookConfig::default() }).unwrap();
+        let mut logger = VectorLogger::new();
+        auction.submit(&mut book, "Lim B $101 #2 u2".parse().unwrap(), 30, &mut logger);
+        assert_eq!(auction.pending_len(), 0);
+        check_log(logger.as_slice(), &["R #2 too-many-open-orders"]);
+    }
+
+    #[test]
+    fn resting_orders_are_allocated_in_queue_order() {
+        let mut book = OrderBook::from_orders(&["Lim B $100 #5 u1", "Lim B $100 #5 u2"]);
+        // Increasing the size moves the order behind the second one at the same price
+        book.amend_order(1, 100, 6, &mut DummyLogger).unwrap();
+        book.set_state(BookState::AuctionOnly, &mut DummyLogger);
+        book.execute_order("Lim S $100 #5 u3".parse().unwrap(), &mut DummyLogger);
+
+        let mut logger = VectorLogger::new();
+        let result = book.uncross(&mut logger).unwrap();
+        assert_eq!(result.trades, [AuctionTrade { buy_order_id: 2, sell_order_id: 3, buyer: 2, seller: 3, size: 5 }]);
+        check_log(logger.as_slice(), &["F #5 $100 u2 o2"]);
+        book.check_bid_list(&["Lim B $100 #6 u1"]);
+    }
+
+    #[test]
+    fn opening_auction() {
+        let mut book = OrderBook::from_orders(&["Lim S $105 #5 u1"]);
+        book.set_state(BookState::AuctionOnly, &mut DummyLogger);
+        let mut logger = VectorLogger::new();
+        for s in &["Lim B $103 #4 u2", "Lim S $101 #6 u3", "Mkt B $0 #3 u4", "Lim B $100 #2 u5"] {
+            book.execute_order(s.parse().unwrap(), &mut logger);
+        }
+        check_log(logger.as_slice(), &["P #4", "P #6", "P #3", "P #2"]);
+        book.check_ask_list(&["Lim S $105 #5 u1"]);
+        assert_eq!(book.indicative_auction(), Some((101, 6)));
+
+        let mut logger = VectorLogger::new();
+        let result = book.uncross(&mut logger).unwrap();
+        assert_eq!((result.price, result.volume), (101, 6));
+        // The market buy is filled first, the rest of the sell fills the earlier limit buy
+        check_log(logger.as_slice(), &["Q B #4 $103 u2 o2", "F #3 $101 u2 o2", "Q S #3 $101 u3 o3", "F #3 $101 u3 o3", "Q B #2 $100 u5 o5"]);
+        assert_eq!(book.state(), BookState::AuctionOnly);
+        book.check_ask_list(&["Lim S $105 #5 u1"]);
+        book.check_bid_list(&["Lim B $103 #1 u2", "Lim B $100 #2 u5"]);
+        assert_eq!(book.uncross(&mut logger), None);
+
+        book.set_state(BookState::Open, &mut DummyLogger);
+        let mut logger = VectorLogger::new();
+        book.execute_order("Lim S $100 #2 u3".parse().unwrap(), &mut logger);
+        check_log(logger.as_slice(), &["F #1 $103 u2 o2", "F #1 $100 u5 o5"]);
+    }
+}
@@ -0,0 +1,73 @@
+//! Size conservation audit
+//!
+//! In audit mode every incoming order must be fully accounted for: the sizes of its fills,
+//! enqueued remainder and cancelled remainder must add up to the submitted size, and the total size
+//! resting in the book must change exactly by the enqueued size minus the filled size.
+//! Rejected orders are accounted as cancelled; parked stop orders are accounted without resting in the book.
+
+use crate::log::{ExecutionLogger, LogItem};
+use crate::order::Size;
+
+/// Checks the items logged for one order and logs an integrity error for each violated invariant
+pub(crate) fn check(items: &[LogItem], submitted: Size, resting_before: Size, resting_after: Size,
+                    logger: &mut impl ExecutionLogger) {
+    let mut filled: Size = 0;
+    let mut enqueued: Size = 0;
+    let mut cancelled: Size = 0;
+    let mut parked: Size = 0;
+    for item in items {
+        match *item {
+            LogItem::Enqueued { size, .. } => enqueued = enqueued.saturating_add(size),
+            LogItem::Fulfilled { size, .. } => filled = filled.saturating_add(size),
+            LogItem::Cancelled { size, .. } | LogItem::Rejected { size, .. } => cancelled = cancelled.saturating_add(size),
+            LogItem::Parked { size } => parked = parked.saturating_add(size),
+            LogItem::ForceCancelled { .. } | LogItem::Amended { .. } | LogItem::Triggered { .. } | LogItem::Refilled { .. }
+            | LogItem::Expired { .. } | LogItem::IntegrityError { .. } | LogItem::StateChanged { .. } => {}
+        }
+    }
+
+    let accounted = filled
+        .saturating_add(enqueued)
+        .saturating_add(cancelled)
+        .saturating_add(parked);
+    if accounted != submitted {
+        logger.log(LogItem::IntegrityError {
+            expected: submitted,
+            actual: accounted,
+        });
+    }
+
+    let expected = resting_before
+        .saturating_add(enqueued)
+        .saturating_sub(filled);
+    if expected != resting_after {
+        logger.log(LogItem::IntegrityError {
+            expected,
+            actual: resting_after,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::check;
+    use crate::log::{VectorLogger, LogItem};
+    use crate::order::OrderSide;
+    use crate::tests::check_log;
+
+    #[test]
+    fn audit_reports_mismatches() {
+        let items = [
+            LogItem::Fulfilled { size: 2, price: 100, user_id: 1, order_id: 1 },
+            LogItem::Enqueued { size: 2, price: 100, side: OrderSide::Buy, user_id: 2, order_id: 2 },
+        ];
+        let mut logger = VectorLogger::new();
+        check(&items, 5, 10, 10, &mut logger);
+        check_log(logger.as_slice(), &["E #5 #4"]);
+
+        let items = [LogItem::Cancelled { size: 3, price: 100, side: OrderSide::Buy, user_id: 2, order_id: 2 }];
+        let mut logger = VectorLogger::new();
+        check(&items, 3, 10, 9, &mut logger);
+        check_log(logger.as_slice(), &["E #10 #9"]);
+    }
+}
@@ -0,0 +1,262 @@
+//! Benchmark harness
+//!
+//! Shared setup and measurement helpers, so that the example, the criterion benchmark and
+//! benchmarks of other book implementations follow the same methodology: an order is executed
+//! against a prepared book, then the book is restored by re-submitting the consumed resting orders.
+
+use crate::OrderBook;
+use crate::log::{ExecutionLogger, LogItem, VectorLogger, DummyLogger};
+use crate::order::{Buy, IncomingOrder, Order, OrderKind, OrderSide, Sell};
+use crate::queues::Queue;
+use std::time::{Duration, Instant};
+
+/// Returns a vector representing order book contents for benchmark
+///
+/// The book has 3500 orders of size 10 on each side, one order per price level around 10000.
+pub fn create_orders() -> Vec<IncomingOrder> {
+    let price = 10000;
+    let mut orders = Vec::new();
+
+    let mut user_id = 10;
+    for i in 0..3500 {
+        user_id += 1;
+        let order = IncomingOrder {
+            price_limit: price + i + 1,
+            size: 10,
+            user_id,
+            kind: OrderKind::Limit,
+            side: OrderSide::Sell,
+            expires_at: None,
+            min_fill_size: None,
+            reduce_only: false,
+        };
+        orders.push(order);
+        user_id += 1;
+        let order = IncomingOrder {
+            price_limit: price - i,
+            size: 10,
+            user_id,
+            kind: OrderKind::Limit,
+            side: OrderSide::Buy,
+            expires_at: None,
+            min_fill_size: None,
+            reduce_only: false,
+        };
+        orders.push(order);
+    }
+    orders
+}
+
+/// Returns a book with `depth` orders of size 10 on each side, one order per price level around 100000
+///
+/// The orders are added from the worst price, so that building the book is cheap for every queue.
+///
+/// # Panics
+///
+/// Panics if `depth` is greater than 100000.
+pub fn deep_book<QB: Queue<Order<Buy>>, QA: Queue<Order<Sell>>>(depth: u32) -> OrderBook<QB, QA> {
+    assert!(depth <= 100000, "book is too deep");
+    let price = 100000;
+    let mut book = OrderBook::with_queues();
+    let order = |side, price_limit, user_id| IncomingOrder { price_limit, size: 10, user_id, kind: OrderKind::Limit, side, expires_at: None, min_fill_size: None, reduce_only: false };
+    for i in (0..i64::from(depth)).rev() {
+        book.execute_order(order(OrderSide::Sell, price + i + 1, 2 * i as u64 + 11), &mut DummyLogger);
+        book.execute_order(order(OrderSide::Buy, price - i, 2 * i as u64 + 12), &mut DummyLogger);
+    }
+    book
+}
+
+/// Benchmark scenario: a book and an order executed against it repeatedly
+pub struct Scenario {
+    book: OrderBook,
+    order: IncomingOrder,
+    reset_orders: Vec<IncomingOrder>,
+    bid_len: usize,
+    ask_len: usize,
+}
+
+impl Scenario {
+    /// Constructs scenario executing `order` against `book`
+    ///
+    /// The order must be filled completely and consume whole resting orders,
+    /// otherwise the book cannot be restored by [reset](Scenario::reset).
+    pub fn new(book: OrderBook, order: IncomingOrder) -> Self {
+        let mut probe = book.clone();
+        let mut logger = VectorLogger::new();
+        probe.execute_order(order.clone(), &mut logger);
+
+        let side = match order.side {
+            OrderSide::Buy => OrderSide::Sell,
+            OrderSide::Sell => OrderSide::Buy,
+        };
+        // Consumed orders are re-added starting from the worst price
+        let reset_orders = logger.as_slice().iter().rev().filter_map(|item| match *item {
+            LogItem::Fulfilled { size, price, user_id, .. } => Some(IncomingOrder {
+                price_limit: price,
+                size,
+                user_id,
+                kind: OrderKind::Limit,
+                side,
+                expires_at: None,
+                min_fill_size: None,
+                reduce_only: false,
+            }),
+            _ => None,
+        }).collect();
+
+        Scenario {
+            bid_len: book.bid().len(),
+            ask_len: book.ask().len(),
+            book,
+            order,
+            reset_orders,
+        }
+    }
+
+    /// Constructs the standard scenario: a buy order sweeping 20 price levels of the [create_orders] book
+    pub fn sweep() -> Self {
+        let order = IncomingOrder {
+            price_limit: 10020,
+            size: 200,
+            user_id: 0,
+            kind: OrderKind::Limit,
+            side: OrderSide::Buy,
+            expires_at: None,
+            min_fill_size: None,
+            reduce_only: false,
+        };
+        Self::new(OrderBook::from_vec(create_orders()), order)
+    }
+
+    /// Returns named scenarios against the [create_orders] book
+    ///
+    /// Includes [sweep](Scenario::sweep), a sell sweep and orders taking a single price level.
+    pub fn library() -> Vec<(&'static str, Scenario)> {
+        let order = |side, price_limit, size| IncomingOrder { price_limit, size, user_id: 0, kind: OrderKind::Limit, side, expires_at: None, min_fill_size: None, reduce_only: false };
+        let book = OrderBook::from_vec(create_orders());
+        vec![
+            ("sweep-buy", Self::sweep()),
+            ("sweep-sell", Self::new(book.clone(), order(OrderSide::Sell, 9981, 200))),
+            ("touch-buy", Self::new(book.clone(), order(OrderSide::Buy, 10001, 10))),
+            ("touch-ioc-sell", Self::new(book, IncomingOrder { kind: OrderKind::ImmediateOrCancel, ..order(OrderSide::Sell, 10000, 10) })),
+        ]
+    }
+
+    /// Returns the book
+    pub fn book(&self) -> &OrderBook {
+        &self.book
+    }
+
+    /// Returns the executed order
+    pub fn order(&self) -> &IncomingOrder {
+        &self.order
+    }
+
+    /// Returns resting orders consumed by one execution
+    pub fn reset_orders(&self) -> &[IncomingOrder] {
+        &self.reset_orders
+    }
+
+    /// Checks whether the book is in its initial state
+    pub fn is_reset(&self) -> bool {
+        self.book.bid().len() == self.bid_len && self.book.ask().len() == self.ask_len
+    }
+
+    /// Executes the order once
+    pub fn execute(&mut self, logger: &mut impl ExecutionLogger) {
+        self.book.execute_order(self.order.clone(), logger);
+    }
+
+    /// Restores the book after [execute](Scenario::execute), does nothing if it is already restored
+    pub fn reset(&mut self) {
+        if self.is_reset() {
+            return;
+        }
+        let mut logger = DummyLogger;
+        for order in &self.reset_orders {
+            self.book.execute_order(order.clone(), &mut logger);
+        }
+        assert!(self.is_reset(), "book was not restored");
+    }
+}
+
+/// Result of [measure]
+#[derive(Debug, Clone, PartialEq)]
+pub struct Measurement {
+    /// Number of executed orders
+    pub iterations: u64,
+    /// Number of fills produced by the executed orders
+    pub fills: u64,
+    /// Total time spent in order execution, excluding resets
+    pub elapsed: Duration,
+}
+
+impl Measurement {
+    /// Returns average execution time of one order
+    pub fn per_order(&self) -> Duration {
+        if self.iterations == 0 {
+            Duration::default()
+        } else {
+            Duration::from_nanos((self.elapsed.as_nanos() / u128::from(self.iterations)) as u64)
+        }
+    }
+
+    /// Returns number of executed orders per second
+    pub fn orders_per_second(&self) -> f64 {
+        self.iterations as f64 / self.elapsed.as_secs_f64()
+    }
+
+    /// Returns number of fills per second
+    pub fn fills_per_second(&self) -> f64 {
+        self.fills as f64 / self.elapsed.as_secs_f64()
+    }
+}
+
+/// Executes the scenario order `iterations` times, resetting the book after every execution
+pub fn measure(scenario: &mut Scenario, iterations: u64, logger: &mut impl ExecutionLogger) -> Measurement {
+    let mut elapsed = Duration::default();
+    for _ in 0..iterations {
+        scenario.reset();
+        let start = Instant::now();
+        scenario.execute(logger);
+        elapsed += start.elapsed();
+    }
+    scenario.reset();
+    let fills = iterations * scenario.reset_orders.len() as u64;
+    Measurement { iterations, fills, elapsed }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::log::CountingLogger;
+
+    #[test]
+    fn sweep_scenario_is_restored() {
+        let mut scenario = Scenario::sweep();
+        assert_eq!(scenario.reset_orders().len(), 20);
+        let mut logger = CountingLogger::new();
+        let measurement = measure(&mut scenario, 3, &mut logger);
+        assert_eq!((measurement.iterations, measurement.fills), (3, 60));
+        assert!(scenario.is_reset());
+        assert_eq!(logger.counts().fulfilled.size, 600);
+
+        for (name, mut scenario) in Scenario::library() {
+            assert!(!scenario.reset_orders().is_empty(), "{} does not trade", name);
+            scenario.execute(&mut DummyLogger);
+            scenario.reset();
+            assert!(scenario.is_reset(), "{} is not restored", name);
+        }
+    }
+
+    #[test]
+    fn deep_book_has_one_order_per_level() {
+        use crate::queues::{LinkedQueue, ReversedVec};
+
+        let book = deep_book::<ReversedVec<Buy>, LinkedQueue<Sell>>(1000);
+        assert_eq!((book.bid().len(), book.ask().len()), (1000, 1000));
+        assert_eq!((book.bid().best_price(), book.ask().best_price()), (Some(100000), Some(100001)));
+        assert_eq!(book.bid_levels().count(), 1000);
+        assert_eq!(book.ask_levels().last().map(|level| level.price()), Some(101000));
+    }
+}
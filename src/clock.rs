@@ -0,0 +1,30 @@
+//! Time sources for timestamps of the execution log
+
+#[cfg(feature = "std")]
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Source of the current time
+///
+/// The unit and the epoch are up to the implementation; the book only copies the values into the log.
+pub trait Clock {
+    /// Returns the current time
+    fn now(&self) -> u64;
+}
+
+/// Wall clock returning nanoseconds since the Unix epoch
+#[cfg(feature = "std")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+#[cfg(feature = "std")]
+impl Clock for SystemClock {
+    fn now(&self) -> u64 {
+        SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |elapsed| elapsed.as_nanos() as u64)
+    }
+}
+
+impl<F: Fn() -> u64> Clock for F {
+    fn now(&self) -> u64 {
+        self()
+    }
+}
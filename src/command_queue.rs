@@ -0,0 +1,136 @@
+//! Command queue with priority lanes
+//!
+//! During bursts a plain FIFO makes cancels wait behind new orders, leaving participants exposed
+//! to fills they are trying to avoid. [CommandQueue](CommandQueue) keeps a separate lane for every
+//! [Priority](Priority): commands of a higher priority overtake queued commands of lower priorities,
+//! while the order within a lane is preserved.
+
+use crate::decode::Command;
+use alloc::collections::VecDeque;
+
+/// Command priority, from the highest to the lowest
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Priority {
+    /// Risk-reducing commands (cancels, mass cancels)
+    High,
+    /// Everything else
+    Normal,
+}
+
+const LANES: usize = 2;
+
+impl Priority {
+    fn lane(self) -> usize {
+        self as usize
+    }
+}
+
+/// Item with a queue priority
+pub trait Prioritized {
+    /// Returns priority of the item
+    fn priority(&self) -> Priority;
+}
+
+impl Prioritized for Command {
+    fn priority(&self) -> Priority {
+        match self {
+            Command::New(_) => Priority::Normal,
+            Command::Cancel(_) | Command::Amend { .. } => Priority::High,
+        }
+    }
+}
+
+/// Queue of commands with priority lanes
+pub struct CommandQueue<T> {
+    lanes: [VecDeque<T>; LANES],
+}
+
+impl<T: Prioritized> CommandQueue<T> {
+    /// Constructs an empty queue
+    pub fn new() -> Self {
+        CommandQueue {
+            lanes: [VecDeque::new(), VecDeque::new()],
+        }
+    }
+
+    /// Adds `item` to the end of its lane
+    pub fn push(&mut self, item: T) {
+        self.lanes[item.priority().lane()].push_back(item);
+    }
+
+    /// Removes the oldest item of the highest non-empty lane
+    pub fn pop(&mut self) -> Option<T> {
+        self.lanes.iter_mut().find_map(|lane| lane.pop_front())
+    }
+
+    /// Returns the number of queued items
+    pub fn len(&self) -> usize {
+        self.lanes.iter().map(VecDeque::len).sum()
+    }
+
+    /// Returns the number of queued items of `priority`
+    pub fn lane_len(&self, priority: Priority) -> usize {
+        self.lanes[priority.lane()].len()
+    }
+
+    /// Checks whether the queue is empty
+    pub fn is_empty(&self) -> bool {
+        self.lanes.iter().all(VecDeque::is_empty)
+    }
+}
+
+impl<T: Prioritized> Default for CommandQueue<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: Prioritized> Extend<T> for CommandQueue<T> {
+    fn extend<I: IntoIterator<Item=T>>(&mut self, iter: I) {
+        for item in iter {
+            self.push(item);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, PartialEq)]
+    enum TestCommand {
+        New(u32),
+        Cancel(u32),
+    }
+
+    impl Prioritized for TestCommand {
+        fn priority(&self) -> Priority {
+            match self {
+                TestCommand::New(_) => Priority::Normal,
+                TestCommand::Cancel(_) => Priority::High,
+            }
+        }
+    }
+
+    #[test]
+    fn cancels_overtake_new_orders() {
+        let mut queue = CommandQueue::new();
+        queue.extend(vec![TestCommand::New(1), TestCommand::New(2), TestCommand::Cancel(1)]);
+        queue.push(TestCommand::Cancel(2));
+        assert_eq!((queue.len(), queue.lane_len(Priority::High)), (4, 2));
+        assert_eq!(queue.pop(), Some(TestCommand::Cancel(1)));
+        assert_eq!(queue.pop(), Some(TestCommand::Cancel(2)));
+        queue.push(TestCommand::New(3));
+        assert_eq!(queue.pop(), Some(TestCommand::New(1)));
+        queue.push(TestCommand::Cancel(3));
+        assert_eq!(queue.pop(), Some(TestCommand::Cancel(3)));
+        assert_eq!(queue.pop(), Some(TestCommand::New(2)));
+        assert_eq!(queue.pop(), Some(TestCommand::New(3)));
+        assert!(queue.is_empty());
+        assert_eq!(queue.pop(), None);
+
+        let mut queue = CommandQueue::new();
+        queue.push(Command::New("Lim B $1 #1 u1".parse().unwrap()));
+        assert_eq!(queue.lane_len(Priority::Normal), 1);
+    }
+}
@@ -0,0 +1,545 @@
+//! Order book configuration and order validation
+
+use crate::log::LogItemParseError;
+use crate::math::BPS_SCALE;
+use crate::order::{IncomingOrder, OrderKind, Price, Size};
+use crate::tick::{TickError, TickSize};
+use core::fmt;
+use core::str::FromStr;
+
+/// Reason for rejecting an incoming order
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum RejectReason {
+    /// Order size is zero
+    ZeroSize,
+    /// Order price limit is zero
+    ZeroPrice,
+    /// Order price limit is negative while negative prices are not allowed
+    NegativePrice,
+    /// Order price limit exceeds the configured maximum price
+    PriceTooHigh,
+    /// Order size exceeds the configured maximum size
+    SizeTooLarge,
+    /// Order size is not a multiple of the lot size
+    OddLot,
+    /// Trading is halted
+    Halted,
+    /// Cancelled or amended order is not resting in the book
+    UnknownOrder,
+    /// Display size of an iceberg order is zero
+    ZeroDisplaySize,
+    /// Post-only order would match on arrival
+    WouldCross,
+    /// Order price limit is too far from the last trade price or outside the price collar
+    PriceOutOfBand,
+    /// User has the maximum number of open orders
+    TooManyOpenOrders,
+    /// Order size is below the configured minimum size
+    SizeTooSmall,
+    /// Order was refused by the [risk checker](crate::risk::RiskChecker)
+    RiskLimit,
+    /// Order size together with the open orders exceeds the maximum size
+    Overflow,
+    /// Offset of a trailing stop order is not positive
+    NonPositiveOffset,
+    /// Minimum fill size of the order exceeds its size
+    MinFillTooLarge,
+    /// Reduce-only order would increase the position of the user
+    WouldIncreasePosition,
+    /// Resting orders of the user would exceed the maximum notional
+    NotionalLimit,
+}
+
+impl fmt::Display for RejectReason {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let s = match self {
+            RejectReason::ZeroSize => "zero-size",
+            RejectReason::ZeroPrice => "zero-price",
+            RejectReason::NegativePrice => "negative-price",
+            RejectReason::PriceTooHigh => "price-too-high",
+            RejectReason::SizeTooLarge => "size-too-large",
+            RejectReason::OddLot => "odd-lot",
+            RejectReason::Halted => "halted",
+            RejectReason::UnknownOrder => "unknown-order",
+            RejectReason::ZeroDisplaySize => "zero-display-size",
+            RejectReason::WouldCross => "would-cross",
+            RejectReason::PriceOutOfBand => "price-out-of-band",
+            RejectReason::TooManyOpenOrders => "too-many-open-orders",
+            RejectReason::SizeTooSmall => "size-too-small",
+            RejectReason::RiskLimit => "risk-limit",
+            RejectReason::Overflow => "overflow",
+            RejectReason::NonPositiveOffset => "non-positive-offset",
+            RejectReason::MinFillTooLarge => "min-fill-too-large",
+            RejectReason::WouldIncreasePosition => "would-increase-position",
+            RejectReason::NotionalLimit => "notional-limit",
+        };
+        f.write_str(s)
+    }
+}
+
+impl FromStr for RejectReason {
+    type Err = LogItemParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "zero-size" => RejectReason::ZeroSize,
+            "zero-price" => RejectReason::ZeroPrice,
+            "negative-price" => RejectReason::NegativePrice,
+            "price-too-high" => RejectReason::PriceTooHigh,
+            "size-too-large" => RejectReason::SizeTooLarge,
+            "odd-lot" => RejectReason::OddLot,
+            "halted" => RejectReason::Halted,
+            "unknown-order" => RejectReason::UnknownOrder,
+            "zero-display-size" => RejectReason::ZeroDisplaySize,
+            "would-cross" => RejectReason::WouldCross,
+            "price-out-of-band" => RejectReason::PriceOutOfBand,
+            "too-many-open-orders" => RejectReason::TooManyOpenOrders,
+            "size-too-small" => RejectReason::SizeTooSmall,
+            "risk-limit" => RejectReason::RiskLimit,
+            "overflow" => RejectReason::Overflow,
+            "non-positive-offset" => RejectReason::NonPositiveOffset,
+            "min-fill-too-large" => RejectReason::MinFillTooLarge,
+            "would-increase-position" => RejectReason::WouldIncreasePosition,
+            "notional-limit" => RejectReason::NotionalLimit,
+            _ => return Err(LogItemParseError),
+        })
+    }
+}
+
+/// Handling of orders whose size is not a multiple of the lot size
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum OddLotPolicy {
+    /// Odd lots are accepted as is
+    #[default]
+    Accept,
+    /// Order size is rounded down to a multiple of the lot size, the odd part is cancelled
+    RoundDown,
+    /// Orders with odd lots are rejected
+    Reject,
+}
+
+/// Handling of post-only orders which would match on arrival
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum PostOnlyPolicy {
+    /// Orders are rejected with [WouldCross](RejectReason::WouldCross)
+    #[default]
+    Reject,
+    /// Price limit is moved one tick away from the opposite touch, so that the order rests at the best
+    /// non-crossing price
+    Reprice,
+}
+
+/// Allocation of incoming orders among the resting orders of a price level
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum MatchingAlgorithm {
+    /// Resting orders are filled in time priority
+    #[default]
+    Fifo,
+    /// Resting orders are filled in proportion to their displayed sizes
+    ///
+    /// Allocations smaller than `min_fill` are dropped; the size left over by rounding and by dropped
+    /// allocations is allocated in time priority.
+    ProRata {
+        #[allow(missing_docs)]
+        min_fill: Size,
+    },
+}
+
+/// Trading state of the order book, see [set_state](crate::OrderBook::set_state)
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum BookState {
+    /// Orders are matched continuously
+    #[default]
+    Open,
+    /// Orders are rejected or parked according to [HaltPolicy](HaltPolicy)
+    Halted,
+    /// Orders are collected without matching and uncrossed in an auction when trading reopens
+    AuctionOnly,
+}
+
+impl fmt::Display for BookState {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            BookState::Open => "open",
+            BookState::Halted => "halted",
+            BookState::AuctionOnly => "auction-only",
+        })
+    }
+}
+
+impl FromStr for BookState {
+    type Err = LogItemParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "open" => Ok(BookState::Open),
+            "halted" => Ok(BookState::Halted),
+            "auction-only" => Ok(BookState::AuctionOnly),
+            _ => Err(LogItemParseError),
+        }
+    }
+}
+
+/// Handling of orders submitted while the book is [halted](BookState::Halted)
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum HaltPolicy {
+    /// Orders are rejected with [Halted](RejectReason::Halted)
+    #[default]
+    Reject,
+    /// Orders are parked and uncrossed in an auction when trading reopens
+    Park,
+}
+
+/// Automatic change of the book state on large price moves
+///
+/// Requires a [clock](crate::clock::Clock) on the book.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CircuitBreaker {
+    /// Maximum move of the last trade price within `window`, in basis points of the earlier price
+    pub max_move_bps: u64,
+    /// Length of the observed time window
+    pub window: u64,
+    /// State the book is switched to when the move exceeds the maximum
+    pub state: BookState,
+}
+
+/// Reference price of the [price collar](PriceCollar)
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum CollarReference {
+    /// Price of the last trade
+    #[default]
+    LastTrade,
+    /// Middle of the best bid and ask prices, rounded down
+    MidPrice,
+}
+
+/// Handling of orders outside the [price collar](PriceCollar)
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum CollarPolicy {
+    /// Orders are rejected with [PriceOutOfBand](RejectReason::PriceOutOfBand)
+    #[default]
+    Reject,
+    /// Price limit is moved to the nearest edge of the collar
+    Clamp,
+}
+
+/// Percentage band around a reference price which price limits of incoming orders must fall into
+///
+/// Not checked while the reference price is unknown.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct PriceCollar {
+    /// Maximum distance from the reference price in basis points of the reference price
+    pub max_deviation_bps: u64,
+    #[allow(missing_docs)]
+    pub reference: CollarReference,
+    #[allow(missing_docs)]
+    pub policy: CollarPolicy,
+}
+
+impl PriceCollar {
+    /// Returns the lowest and the highest price of the collar around `reference`
+    pub fn bounds(&self, reference: Price) -> (Price, Price) {
+        // The product is below 2^127 and the quotient always fits into i128
+        let deviation = (i128::from(reference).unsigned_abs() * u128::from(self.max_deviation_bps) / BPS_SCALE) as i128;
+        let clamp = |price: i128| price.clamp(i128::from(Price::MIN), i128::from(Price::MAX)) as Price;
+        (clamp(i128::from(reference) - deviation), clamp(i128::from(reference) + deviation))
+    }
+}
+
+/// Order book configuration
+///
+/// Default configuration accepts every order with a non-negative price,
+/// which matches the behavior of the previous versions.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct BookConfig {
+    /// Reject orders with zero size
+    pub reject_zero_size: bool,
+    /// Reject orders with zero price limit
+    pub reject_zero_price: bool,
+    /// Accept orders with negative price limits (e.g. for spread instruments)
+    pub allow_negative_prices: bool,
+    /// Maximum allowed price limit
+    pub max_price: Option<Price>,
+    /// Maximum allowed order size
+    pub max_size: Option<Size>,
+    /// Minimum allowed order size
+    pub min_size: Option<Size>,
+    /// Decimal value of one price tick
+    ///
+    /// Prices of orders are expressed in ticks; the tick size is only used to convert them
+    /// to and from decimal display prices, so every price is a multiple of the tick size.
+    pub tick_size: TickSize,
+    /// Decimal value of one size unit
+    ///
+    /// Sizes are expressed in units like prices in ticks, e.g. a unit of `0.001` allows sizes
+    /// with three fractional digits.
+    pub size_unit: TickSize,
+    /// Size of one round lot in size units, `0` and `1` disable lot handling
+    pub lot_size: Size,
+    /// Handling of sizes which are not a multiple of `lot_size`
+    pub odd_lot: OddLotPolicy,
+    /// Handling of post-only orders which would match on arrival
+    pub post_only: PostOnlyPolicy,
+    /// Allocation of incoming orders within a price level
+    pub matching: MatchingAlgorithm,
+    /// Fill the [top order](crate::OrderQueue::top_order) of a level before allocating the rest of the level
+    ///
+    /// Time priority already fills it first, so only [pro-rata](MatchingAlgorithm::ProRata) matching is affected.
+    pub top_priority: bool,
+    /// Maximum distance in ticks between price limits of incoming orders and the last trade price
+    ///
+    /// Not checked before the first trade.
+    pub price_band: Option<Price>,
+    /// Maximum number of resting and stop orders of one user
+    pub max_open_orders: Option<usize>,
+    /// Maximum total notional of the resting orders of one user, in price ticks times size units
+    ///
+    /// Orders which may rest are rejected if their notional at the price limit would exceed it.
+    pub max_resting_notional: Option<u128>,
+    /// Percentage band around the last trade or mid price
+    pub price_collar: Option<PriceCollar>,
+    /// Handling of orders submitted while the book is [halted](BookState::Halted)
+    pub halt_policy: HaltPolicy,
+    /// Halt or auction trigger on large price moves
+    pub circuit_breaker: Option<CircuitBreaker>,
+    /// Minimum time between two repricings of one [pegged](crate::order::OrderKind::Pegged) order
+    ///
+    /// Measured by the book [clock](crate::clock::Clock); pegged orders repriced more recently keep
+    /// their prices until a later operation. Without a clock they are repriced after every operation.
+    pub repeg_interval: Option<u64>,
+}
+
+impl BookConfig {
+    /// Constructs configuration which rejects degenerate orders
+    pub fn strict() -> Self {
+        BookConfig {
+            reject_zero_size: true,
+            reject_zero_price: true,
+            ..BookConfig::default()
+        }
+    }
+
+    /// Checks whether `order` is acceptable under this configuration
+    ///
+    /// Price limits of market and stop orders are not checked. Iceberg orders with zero display size
+    /// are always rejected.
+    pub fn validate(&self, order: &IncomingOrder) -> Result<(), RejectReason> {
+        if order.kind == (OrderKind::Iceberg { display_size: 0 }) {
+            return Err(RejectReason::ZeroDisplaySize);
+        }
+        if let OrderKind::TrailingStop { offset, .. } = order.kind {
+            if offset <= 0 {
+                return Err(RejectReason::NonPositiveOffset);
+            }
+        }
+        if order.min_fill_size.is_some_and(|min_fill_size| min_fill_size > order.size) {
+            return Err(RejectReason::MinFillTooLarge);
+        }
+        if self.reject_zero_size && order.size == 0 {
+            return Err(RejectReason::ZeroSize);
+        }
+        if let Some(max_size) = self.max_size {
+            if order.size > max_size {
+                return Err(RejectReason::SizeTooLarge);
+            }
+        }
+        if let Some(min_size) = self.min_size {
+            if order.size < min_size {
+                return Err(RejectReason::SizeTooSmall);
+            }
+        }
+        if matches!(order.kind, OrderKind::Market | OrderKind::Stop { .. } | OrderKind::TrailingStop { .. }) {
+            return Ok(());
+        }
+        if self.reject_zero_price && order.price_limit == 0 {
+            return Err(RejectReason::ZeroPrice);
+        }
+        if !self.allow_negative_prices && order.price_limit < 0 {
+            return Err(RejectReason::NegativePrice);
+        }
+        if let Some(max_price) = self.max_price {
+            if order.price_limit > max_price {
+                return Err(RejectReason::PriceTooHigh);
+            }
+        }
+        Ok(())
+    }
+
+    /// Checks the price limit of `order` against the price band around `last_trade_price`
+    pub fn check_band(&self, order: &IncomingOrder, last_trade_price: Option<Price>) -> Result<(), RejectReason> {
+        if matches!(order.kind, OrderKind::Market | OrderKind::Stop { .. } | OrderKind::TrailingStop { .. }) {
+            return Ok(());
+        }
+        match (self.price_band, last_trade_price) {
+            (Some(band), Some(reference)) if (i128::from(order.price_limit) - i128::from(reference)).abs() > i128::from(band) => {
+                Err(RejectReason::PriceOutOfBand)
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Checks the price limit of `order` against the price collar around `reference`,
+    /// returning the accepted price limit
+    ///
+    /// Price limits of market and stop orders are not checked.
+    pub fn apply_collar(&self, order: &IncomingOrder, reference: Option<Price>) -> Result<Price, RejectReason> {
+        let (collar, reference) = match (self.price_collar, reference) {
+            (Some(collar), Some(reference)) if !matches!(order.kind, OrderKind::Market | OrderKind::Stop { .. } | OrderKind::TrailingStop { .. }) => (collar, reference),
+            _ => return Ok(order.price_limit),
+        };
+        let (low, high) = collar.bounds(reference);
+        if (low..=high).contains(&order.price_limit) {
+            return Ok(order.price_limit);
+        }
+        match collar.policy {
+            CollarPolicy::Reject => Err(RejectReason::PriceOutOfBand),
+            CollarPolicy::Clamp => Ok(order.price_limit.clamp(low, high)),
+        }
+    }
+
+    /// Applies the odd lot policy to `size`, returning the accepted size
+    pub fn round_to_lot(&self, size: Size) -> Result<Size, RejectReason> {
+        if self.lot_size <= 1 || size.is_multiple_of(self.lot_size) {
+            return Ok(size);
+        }
+        match self.odd_lot {
+            OddLotPolicy::Accept => Ok(size),
+            OddLotPolicy::RoundDown if size >= self.lot_size => Ok(size - size % self.lot_size),
+            OddLotPolicy::RoundDown | OddLotPolicy::Reject => Err(RejectReason::OddLot),
+        }
+    }
+
+    /// Returns the number of whole lots in `size`
+    pub fn lots(&self, size: Size) -> u64 {
+        if self.lot_size <= 1 {
+            size
+        } else {
+            size / self.lot_size
+        }
+    }
+}
+
+/// Error of a configuration update
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ConfigError {
+    /// Prices of resting orders cannot be converted to the new tick size
+    TickSize(TickError),
+    /// Size unit can't change while the book has orders
+    SizeUnit,
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ConfigError::TickSize(e) => write!(f, "can't change tick size: {}", e),
+            ConfigError::SizeUnit => f.write_str("can't change size unit of a book with orders"),
+        }
+    }
+}
+
+/// Configuration change applied to a running book
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConfigChange {
+    /// Configuration version, incremented by every change
+    pub version: u64,
+    /// Configuration before the change
+    pub previous: BookConfig,
+    /// Configuration after the change
+    pub current: BookConfig,
+}
+
+#[test]
+fn test_validate() {
+    let order: IncomingOrder = "Lim B $0 #0 u1".parse().unwrap();
+    assert_eq!(BookConfig::default().validate(&order), Ok(()));
+    assert_eq!(BookConfig::strict().validate(&order), Err(RejectReason::ZeroSize));
+
+    let order: IncomingOrder = "Lim B $0 #1 u1".parse().unwrap();
+    assert_eq!(BookConfig::strict().validate(&order), Err(RejectReason::ZeroPrice));
+    let config = BookConfig {
+        reject_zero_price: false,
+        ..BookConfig::strict()
+    };
+    assert_eq!(config.validate(&order), Ok(()));
+}
+
+#[test]
+fn test_validate_bounds() {
+    let config = BookConfig {
+        max_price: Some(1000),
+        max_size: Some(50),
+        min_size: Some(10),
+        ..BookConfig::default()
+    };
+    let order: IncomingOrder = "Lim B $1000 #50 u1".parse().unwrap();
+    assert_eq!(config.validate(&order), Ok(()));
+    let order: IncomingOrder = "Lim B $1000 #9 u1".parse().unwrap();
+    assert_eq!(config.validate(&order), Err(RejectReason::SizeTooSmall));
+    let order: IncomingOrder = "Lim B $9223372036854775807 #50 u1".parse().unwrap();
+    assert_eq!(config.validate(&order), Err(RejectReason::PriceTooHigh));
+    let order: IncomingOrder = "Lim S $10 #51 u1".parse().unwrap();
+    assert_eq!(config.validate(&order), Err(RejectReason::SizeTooLarge));
+}
+
+#[test]
+fn test_validate_negative_price() {
+    let order: IncomingOrder = "Lim B $-5 #1 u1".parse().unwrap();
+    assert_eq!(BookConfig::default().validate(&order), Err(RejectReason::NegativePrice));
+    let config = BookConfig {
+        allow_negative_prices: true,
+        ..BookConfig::strict()
+    };
+    assert_eq!(config.validate(&order), Ok(()));
+}
+
+#[test]
+fn test_check_band() {
+    let config = BookConfig {
+        price_band: Some(10),
+        ..BookConfig::default()
+    };
+    let order: IncomingOrder = "Lim B $110 #1 u1".parse().unwrap();
+    assert_eq!(config.check_band(&order, None), Ok(()));
+    assert_eq!(config.check_band(&order, Some(100)), Ok(()));
+    assert_eq!(config.check_band(&order, Some(99)), Err(RejectReason::PriceOutOfBand));
+    let order: IncomingOrder = "Mkt B $0 #1 u1".parse().unwrap();
+    assert_eq!(config.check_band(&order, Some(99)), Ok(()));
+}
+
+#[test]
+fn test_apply_collar() {
+    let mut collar = PriceCollar { max_deviation_bps: 500, ..PriceCollar::default() };
+    assert_eq!(collar.bounds(200), (190, 210));
+    assert_eq!(collar.bounds(-200), (-210, -190));
+    let mut config = BookConfig { price_collar: Some(collar), ..BookConfig::default() };
+    let order: IncomingOrder = "Lim B $211 #1 u1".parse().unwrap();
+    assert_eq!(config.apply_collar(&order, None), Ok(211));
+    assert_eq!(config.apply_collar(&order, Some(201)), Ok(211));
+    assert_eq!(config.apply_collar(&order, Some(200)), Err(RejectReason::PriceOutOfBand));
+    let market: IncomingOrder = "Mkt B $1000 #1 u1".parse().unwrap();
+    assert_eq!(config.apply_collar(&market, Some(200)), Ok(1000));
+
+    collar.policy = CollarPolicy::Clamp;
+    config.price_collar = Some(collar);
+    assert_eq!(config.apply_collar(&order, Some(200)), Ok(210));
+    let order: IncomingOrder = "Lim S $150 #1 u1".parse().unwrap();
+    assert_eq!(config.apply_collar(&order, Some(200)), Ok(190));
+}
+
+#[test]
+fn test_round_to_lot() {
+    let mut config = BookConfig {
+        lot_size: 100,
+        ..BookConfig::default()
+    };
+    assert_eq!(config.round_to_lot(250), Ok(250));
+    assert_eq!(config.lots(250), 2);
+    config.odd_lot = OddLotPolicy::RoundDown;
+    assert_eq!(config.round_to_lot(250), Ok(200));
+    assert_eq!(config.round_to_lot(50), Err(RejectReason::OddLot));
+    config.odd_lot = OddLotPolicy::Reject;
+    assert_eq!(config.round_to_lot(300), Ok(300));
+    assert_eq!(config.round_to_lot(250), Err(RejectReason::OddLot));
+}
@@ -0,0 +1,483 @@
+//! Decoders turning raw input records into book commands
+//!
+//! Every ingestion path (CLI, network servers, gateways) decodes its input through the
+//! [OrderDecoder](OrderDecoder) trait, so new wire formats only need a new decoder.
+
+use crate::order::{IncomingOrder, OrderId, OrderKind, OrderSide, PegReference, Price, Size};
+use core::fmt;
+use core::str;
+use alloc::vec::Vec;
+
+/// Operation on the order book
+#[derive(Debug, Clone, PartialEq)]
+pub enum Command {
+    /// Execute new incoming order
+    New(IncomingOrder),
+    /// Cancel resting order
+    Cancel(OrderId),
+    /// Amend price and remaining size of resting order
+    #[allow(missing_docs)]
+    Amend {
+        order_id: OrderId,
+        price: Price,
+        size: Size,
+    },
+}
+
+impl From<IncomingOrder> for Command {
+    fn from(order: IncomingOrder) -> Self {
+        Command::New(order)
+    }
+}
+
+/// Decoding error
+#[derive(Debug, Clone, PartialEq)]
+pub enum DecodeError {
+    /// Input is not valid UTF-8
+    InvalidUtf8,
+    /// Input has unexpected length
+    InvalidLength,
+    /// Required field is missing
+    MissingField(&'static str),
+    /// Field has invalid value
+    InvalidField(&'static str),
+    /// Input is malformed
+    Malformed,
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DecodeError::InvalidUtf8 => write!(f, "invalid UTF-8"),
+            DecodeError::InvalidLength => write!(f, "invalid record length"),
+            DecodeError::MissingField(field) => write!(f, "missing field '{}'", field),
+            DecodeError::InvalidField(field) => write!(f, "invalid field '{}'", field),
+            DecodeError::Malformed => write!(f, "malformed record"),
+        }
+    }
+}
+
+/// Decoder of a single input record (line, datagram, frame) into a [Command](Command)
+pub trait OrderDecoder {
+    /// Decodes one record
+    fn decode(&mut self, bytes: &[u8]) -> Result<Command, DecodeError>;
+}
+
+fn to_str(bytes: &[u8]) -> Result<&str, DecodeError> {
+    str::from_utf8(bytes).map(str::trim).map_err(|_| DecodeError::InvalidUtf8)
+}
+
+/// Decoder of the terse text format, e.g. `Lim B $100 #5 u1`
+///
+/// Cancels are written as `Cxl o<order id>`, e.g. `Cxl o5`;
+/// amendments as `Amd o<order id> $<price> #<size>`, e.g. `Amd o5 $101 #3`.
+#[derive(Default)]
+pub struct TextDecoder;
+
+impl OrderDecoder for TextDecoder {
+    fn decode(&mut self, bytes: &[u8]) -> Result<Command, DecodeError> {
+        let s = to_str(bytes)?;
+        if let Some(order_id) = s.strip_prefix("Cxl o") {
+            return order_id.parse().map(Command::Cancel).map_err(|_| DecodeError::InvalidField("order_id"));
+        }
+        if let Some(amendment) = s.strip_prefix("Amd ") {
+            return parse_amendment(amendment);
+        }
+        let order = s.parse().map_err(|_| DecodeError::Malformed)?;
+        Ok(Command::New(order))
+    }
+}
+
+fn parse_amendment(s: &str) -> Result<Command, DecodeError> {
+    let mut parts = s.split_whitespace();
+    let mut field = |prefix: char, name: &'static str| {
+        parts.next()
+            .and_then(|part| part.strip_prefix(prefix))
+            .ok_or(DecodeError::MissingField(name))
+    };
+    let order_id = field('o', "order_id")?.parse().map_err(|_| DecodeError::InvalidField("order_id"))?;
+    let price = field('$', "price")?.parse().map_err(|_| DecodeError::InvalidField("price"))?;
+    let size = field('#', "size")?.parse().map_err(|_| DecodeError::InvalidField("size"))?;
+    if parts.next().is_some() {
+        return Err(DecodeError::Malformed);
+    }
+    Ok(Command::Amend { order_id, price, size })
+}
+
+const FIELDS: [&str; 5] = ["side", "kind", "price", "size", "user"];
+
+fn field_index(name: &str) -> Option<usize> {
+    FIELDS.iter().position(|&field| field == name)
+}
+
+fn build_order(values: &[Option<&str>; 5]) -> Result<IncomingOrder, DecodeError> {
+    fn get<'a>(values: &[Option<&'a str>; 5], index: usize) -> Result<&'a str, DecodeError> {
+        values[index].ok_or(DecodeError::MissingField(FIELDS[index]))
+    }
+
+    let side: OrderSide = get(values, 0)?.parse().map_err(|_| DecodeError::InvalidField("side"))?;
+    let kind: OrderKind = get(values, 1)?.parse().map_err(|_| DecodeError::InvalidField("kind"))?;
+    let price_limit: Price = get(values, 2)?.parse().map_err(|_| DecodeError::InvalidField("price"))?;
+    let size = get(values, 3)?.parse().map_err(|_| DecodeError::InvalidField("size"))?;
+    let user_id = get(values, 4)?.parse().map_err(|_| DecodeError::InvalidField("user"))?;
+    Ok(IncomingOrder {
+        price_limit,
+        size,
+        user_id,
+        kind,
+        side,
+        expires_at: None,
+        min_fill_size: None,
+        reduce_only: false,
+    })
+}
+
+/// Decoder of comma-separated records
+///
+/// Default column order is `side,kind,price,size,user`; a different order can be taken from a header line.
+/// Sides and kinds use the same names as the text format (`B`, `S`, `Lim`, `FoK`, `IoC`, `Mkt`, `Post`).
+pub struct CsvDecoder {
+    columns: Vec<Option<usize>>,
+}
+
+impl CsvDecoder {
+    /// Constructs decoder with the default column order
+    pub fn new() -> Self {
+        CsvDecoder {
+            columns: (0..FIELDS.len()).map(Some).collect(),
+        }
+    }
+
+    /// Constructs decoder with the column order given by `header`
+    ///
+    /// Unknown columns are ignored.
+    pub fn with_header(header: &str) -> Result<Self, DecodeError> {
+        let columns: Vec<_> = header.trim().split(',').map(|name| field_index(name.trim())).collect();
+        for (index, &field) in FIELDS.iter().enumerate() {
+            if !columns.contains(&Some(index)) {
+                return Err(DecodeError::MissingField(field));
+            }
+        }
+        Ok(CsvDecoder { columns })
+    }
+}
+
+impl Default for CsvDecoder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl OrderDecoder for CsvDecoder {
+    fn decode(&mut self, bytes: &[u8]) -> Result<Command, DecodeError> {
+        let line = to_str(bytes)?;
+        let mut values = [None; 5];
+        let mut count = 0;
+        for (column, value) in line.split(',').enumerate() {
+            count += 1;
+            if let Some(&Some(index)) = self.columns.get(column) {
+                values[index] = Some(value.trim());
+            }
+        }
+        if count != self.columns.len() {
+            return Err(DecodeError::Malformed);
+        }
+        build_order(&values).map(Command::New)
+    }
+}
+
+/// Decoder of flat JSON objects, e.g. `{"side":"B","kind":"Lim","price":100,"size":5,"user":1}`
+///
+/// Unknown keys are ignored.
+#[derive(Default)]
+pub struct JsonDecoder;
+
+/// Parses a flat JSON object with string and integer values into key-value pairs
+fn parse_flat_json(s: &str) -> Result<Vec<(&str, &str)>, DecodeError> {
+    let s = s.trim();
+    if !s.starts_with('{') || !s.ends_with('}') {
+        return Err(DecodeError::Malformed);
+    }
+    let mut rest = s[1..s.len() - 1].trim();
+    let mut pairs = Vec::new();
+
+    fn parse_string(s: &str) -> Result<(&str, &str), DecodeError> {
+        let s = s.strip_prefix('"').ok_or(DecodeError::Malformed)?;
+        let end = s.find('"').ok_or(DecodeError::Malformed)?;
+        if s[..end].contains('\\') {
+            return Err(DecodeError::Malformed);
+        }
+        Ok((&s[..end], s[end + 1..].trim_start()))
+    }
+
+    while !rest.is_empty() {
+        let (key, tail) = parse_string(rest)?;
+        let tail = tail.strip_prefix(':').ok_or(DecodeError::Malformed)?.trim_start();
+        let (value, tail) = if tail.starts_with('"') {
+            parse_string(tail)?
+        } else {
+            let end = tail.find(',').unwrap_or(tail.len());
+            (tail[..end].trim(), &tail[end..])
+        };
+        pairs.push((key, value));
+        rest = match tail.strip_prefix(',') {
+            Some(tail) if !tail.trim().is_empty() => tail.trim_start(),
+            Some(_) => return Err(DecodeError::Malformed),
+            None if tail.is_empty() => tail,
+            None => return Err(DecodeError::Malformed),
+        };
+    }
+    Ok(pairs)
+}
+
+impl OrderDecoder for JsonDecoder {
+    fn decode(&mut self, bytes: &[u8]) -> Result<Command, DecodeError> {
+        let mut values = [None; 5];
+        for (key, value) in parse_flat_json(to_str(bytes)?)? {
+            if let Some(index) = field_index(key) {
+                values[index] = Some(value);
+            }
+        }
+        build_order(&values).map(Command::New)
+    }
+}
+
+/// Length of a binary order record
+pub const BINARY_ORDER_LEN: usize = 26;
+
+/// Length of a binary record of a stop or iceberg order
+pub const BINARY_EXTENDED_ORDER_LEN: usize = BINARY_ORDER_LEN + 8;
+
+/// Flag of the kind byte marking records which end with an expiry time
+const EXPIRY_FLAG: u8 = 0x80;
+
+/// Flag of the kind byte marking records with a minimum fill size
+const MIN_FILL_FLAG: u8 = 0x40;
+
+/// Flag of the kind byte marking reduce-only orders
+const REDUCE_ONLY_FLAG: u8 = 0x20;
+
+/// Decoder of little-endian binary records
+///
+/// Layout: kind (`u8`: 0 - limit, 1 - fill-or-kill, 2 - immediate-or-cancel, 3 - market, 4 - stop,
+/// 5 - stop-limit, 6 - iceberg, 7 - post-only, 8 - trailing stop, 9 - pegged, 10 - hidden), side (`u8`: 0 - buy, 1 - sell), price (`i64`),
+/// size (`u64`), user id (`u64`). Records of stop kinds continue with the trigger price (`i64`), records of iceberg orders with
+/// the display size (`u64`). Records of trailing stops have the offset (`i64`) after the trigger price, records of pegged
+/// orders the offset (`i64`) and the reference (`u64`: 0 - primary, 1 - market, 2 - midpoint).
+/// Records of minimum-quantity orders have the second highest bit of the kind set and continue with the minimum fill
+/// size (`u64`). The third highest bit of the kind marks reduce-only orders. Records of good-till-date orders have the highest bit of the kind set and end with the expiry time (`u64`).
+#[derive(Default)]
+pub struct BinaryDecoder;
+
+impl BinaryDecoder {
+    /// Encodes `order` into a binary record
+    pub fn encode(order: &IncomingOrder) -> Vec<u8> {
+        let mut bytes = vec![0; BINARY_ORDER_LEN];
+        bytes[0] = match order.kind {
+            OrderKind::Limit => 0,
+            OrderKind::FillOrKill => 1,
+            OrderKind::ImmediateOrCancel => 2,
+            OrderKind::Market => 3,
+            OrderKind::Stop { .. } => 4,
+            OrderKind::StopLimit { .. } => 5,
+            OrderKind::Iceberg { .. } => 6,
+            OrderKind::PostOnly => 7,
+            OrderKind::TrailingStop { .. } => 8,
+            OrderKind::Pegged { .. } => 9,
+            OrderKind::Hidden => 10,
+        };
+        bytes[1] = match order.side {
+            OrderSide::Buy => 0,
+            OrderSide::Sell => 1,
+        };
+        bytes[2..10].copy_from_slice(&order.price_limit.to_le_bytes());
+        bytes[10..18].copy_from_slice(&order.size.to_le_bytes());
+        bytes[18..26].copy_from_slice(&order.user_id.to_le_bytes());
+        if let Some(trigger_price) = order.kind.trigger_price() {
+            bytes.extend_from_slice(&trigger_price.to_le_bytes());
+        }
+        if let OrderKind::Iceberg { display_size } = order.kind {
+            bytes.extend_from_slice(&display_size.to_le_bytes());
+        }
+        if let OrderKind::TrailingStop { offset, .. } = order.kind {
+            bytes.extend_from_slice(&offset.to_le_bytes());
+        }
+        if let OrderKind::Pegged { reference, offset } = order.kind {
+            let reference: u64 = match reference {
+                PegReference::Primary => 0,
+                PegReference::Market => 1,
+                PegReference::Midpoint => 2,
+            };
+            bytes.extend_from_slice(&offset.to_le_bytes());
+            bytes.extend_from_slice(&reference.to_le_bytes());
+        }
+        if let Some(min_fill_size) = order.min_fill_size {
+            bytes[0] |= MIN_FILL_FLAG;
+            bytes.extend_from_slice(&min_fill_size.to_le_bytes());
+        }
+        if order.reduce_only {
+            bytes[0] |= REDUCE_ONLY_FLAG;
+        }
+        if let Some(expires_at) = order.expires_at {
+            bytes[0] |= EXPIRY_FLAG;
+            bytes.extend_from_slice(&expires_at.to_le_bytes());
+        }
+        bytes
+    }
+
+    /// Returns length of the record starting with `kind` byte
+    pub fn record_len(kind: u8) -> usize {
+        let len = match kind & !(EXPIRY_FLAG | MIN_FILL_FLAG | REDUCE_ONLY_FLAG) {
+            4..=6 => BINARY_EXTENDED_ORDER_LEN,
+            8 | 9 => BINARY_EXTENDED_ORDER_LEN + 8,
+            _ => BINARY_ORDER_LEN,
+        };
+        let flags = [EXPIRY_FLAG, MIN_FILL_FLAG].iter().filter(|&&flag| kind & flag != 0).count();
+        len + 8 * flags
+    }
+}
+
+impl OrderDecoder for BinaryDecoder {
+    fn decode(&mut self, bytes: &[u8]) -> Result<Command, DecodeError> {
+        if bytes.is_empty() || bytes.len() != Self::record_len(bytes[0]) {
+            return Err(DecodeError::InvalidLength);
+        }
+        let mut word = [0; 8];
+        let extension = |index: usize| {
+            let mut word = [0; 8];
+            let start = BINARY_ORDER_LEN + 8 * index;
+            word.copy_from_slice(&bytes[start..start + 8]);
+            word
+        };
+        let trigger_price = || Price::from_le_bytes(extension(0));
+        let kind = match bytes[0] & !(EXPIRY_FLAG | MIN_FILL_FLAG | REDUCE_ONLY_FLAG) {
+            0 => OrderKind::Limit,
+            1 => OrderKind::FillOrKill,
+            2 => OrderKind::ImmediateOrCancel,
+            3 => OrderKind::Market,
+            4 => OrderKind::Stop { trigger_price: trigger_price() },
+            5 => OrderKind::StopLimit { trigger_price: trigger_price() },
+            6 => OrderKind::Iceberg { display_size: u64::from_le_bytes(extension(0)) },
+            7 => OrderKind::PostOnly,
+            10 => OrderKind::Hidden,
+            8 => OrderKind::TrailingStop { trigger_price: trigger_price(), offset: Price::from_le_bytes(extension(1)) },
+            9 => OrderKind::Pegged {
+                reference: match u64::from_le_bytes(extension(1)) {
+                    0 => PegReference::Primary,
+                    1 => PegReference::Market,
+                    2 => PegReference::Midpoint,
+                    _ => return Err(DecodeError::InvalidField("reference")),
+                },
+                offset: Price::from_le_bytes(extension(0)),
+            },
+            _ => return Err(DecodeError::InvalidField("kind")),
+        };
+        let side = match bytes[1] {
+            0 => OrderSide::Buy,
+            1 => OrderSide::Sell,
+            _ => return Err(DecodeError::InvalidField("side")),
+        };
+        word.copy_from_slice(&bytes[2..10]);
+        let price_limit = Price::from_le_bytes(word);
+        word.copy_from_slice(&bytes[10..18]);
+        let size = u64::from_le_bytes(word);
+        word.copy_from_slice(&bytes[18..26]);
+        let user_id = u64::from_le_bytes(word);
+        let mut end = bytes.len();
+        let expires_at = if bytes[0] & EXPIRY_FLAG != 0 {
+            end -= 8;
+            word.copy_from_slice(&bytes[end..end + 8]);
+            Some(u64::from_le_bytes(word))
+        } else {
+            None
+        };
+        let min_fill_size = if bytes[0] & MIN_FILL_FLAG != 0 {
+            word.copy_from_slice(&bytes[end - 8..end]);
+            Some(u64::from_le_bytes(word))
+        } else {
+            None
+        };
+        Ok(Command::New(IncomingOrder {
+            price_limit,
+            size,
+            user_id,
+            kind,
+            side,
+            expires_at,
+            min_fill_size,
+            reduce_only: bytes[0] & REDUCE_ONLY_FLAG != 0,
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn order(s: &str) -> Command {
+        Command::New(s.parse().unwrap())
+    }
+
+    #[test]
+    fn decode_text_and_csv() {
+        assert_eq!(TextDecoder.decode(b"Lim B $100 #5 u1\n"), Ok(order("Lim B $100 #5 u1")));
+        assert_eq!(TextDecoder.decode(b"Lim X $100 #5 u1"), Err(DecodeError::Malformed));
+        assert_eq!(TextDecoder.decode(b"Cxl o12\n"), Ok(Command::Cancel(12)));
+        assert_eq!(TextDecoder.decode(b"Cxl o"), Err(DecodeError::InvalidField("order_id")));
+        assert_eq!(TextDecoder.decode(b"Amd o3 $-7 #2"), Ok(Command::Amend { order_id: 3, price: -7, size: 2 }));
+        assert_eq!(TextDecoder.decode(b"Amd o3 #2"), Err(DecodeError::MissingField("price")));
+
+        let mut decoder = CsvDecoder::new();
+        assert_eq!(decoder.decode(b"S,FoK,-5,3,7"), Ok(order("FoK S $-5 #3 u7")));
+        assert_eq!(decoder.decode(b"S,FoK,-5,3"), Err(DecodeError::Malformed));
+        assert_eq!(decoder.decode(b"S,Stp,-5,3,7"), Err(DecodeError::InvalidField("kind")));
+
+        let mut decoder = CsvDecoder::with_header("user, price, size, side, kind, comment").unwrap();
+        assert_eq!(decoder.decode(b"7,100,3,B,IoC,hello"), Ok(order("IoC B $100 #3 u7")));
+        assert!(CsvDecoder::with_header("user,price,size,side").is_err());
+    }
+
+    #[test]
+    fn decode_json() {
+        let mut decoder = JsonDecoder;
+        let line = br#"{"side": "S", "kind": "Lim", "price": 101, "size": 2, "user": 9, "note": "x"}"#;
+        assert_eq!(decoder.decode(line), Ok(order("Lim S $101 #2 u9")));
+        assert_eq!(decoder.decode(br#"{"side":"S","kind":"Lim","price":101,"size":2}"#),
+                   Err(DecodeError::MissingField("user")));
+        assert_eq!(decoder.decode(br#"{"side":"S",}"#), Err(DecodeError::Malformed));
+        assert_eq!(decoder.decode(b"[]"), Err(DecodeError::Malformed));
+    }
+
+    #[test]
+    fn decode_binary() {
+        let incoming: IncomingOrder = "IoC S $-7 #12 u3".parse().unwrap();
+        let bytes = BinaryDecoder::encode(&incoming);
+        assert_eq!(BinaryDecoder.decode(&bytes), Ok(Command::New(incoming)));
+        assert_eq!(BinaryDecoder.decode(&bytes[1..]), Err(DecodeError::InvalidLength));
+
+        let stop: IncomingOrder = "StpLim B $105 #2 u3 @104".parse().unwrap();
+        let bytes = BinaryDecoder::encode(&stop);
+        assert_eq!(bytes.len(), BINARY_EXTENDED_ORDER_LEN);
+        assert_eq!(BinaryDecoder.decode(&bytes), Ok(Command::New(stop)));
+        assert_eq!(BinaryDecoder.decode(&bytes[..BINARY_ORDER_LEN]), Err(DecodeError::InvalidLength));
+
+        let iceberg: IncomingOrder = "Ice S $99 #40 u3 ^10 !500".parse().unwrap();
+        let bytes = BinaryDecoder::encode(&iceberg);
+        assert_eq!(bytes.len(), BINARY_EXTENDED_ORDER_LEN + 8);
+        assert_eq!(BinaryDecoder.decode(&bytes), Ok(Command::New(iceberg)));
+
+        let trailing: IncomingOrder = "Trl S $0 #5 u3 @95 ~5".parse().unwrap();
+        let bytes = BinaryDecoder::encode(&trailing);
+        assert_eq!(bytes.len(), BINARY_EXTENDED_ORDER_LEN + 8);
+        assert_eq!(BinaryDecoder.decode(&bytes), Ok(Command::New(trailing)));
+
+        let pegged: IncomingOrder = "Peg B $105 #5 u3 =mid-2".parse().unwrap();
+        let bytes = BinaryDecoder::encode(&pegged);
+        assert_eq!(BinaryDecoder.decode(&bytes), Ok(Command::New(pegged)));
+
+        let min_qty: IncomingOrder = "Ice B $100 #5 u3 ^2 %4 ro !9".parse().unwrap();
+        let bytes = BinaryDecoder::encode(&min_qty);
+        assert_eq!(bytes.len(), BINARY_EXTENDED_ORDER_LEN + 16);
+        assert_eq!(BinaryDecoder.decode(&bytes), Ok(Command::New(min_qty)));
+    }
+}
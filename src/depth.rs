@@ -0,0 +1,200 @@
+//! Market data views of the order book
+
+use crate::OrderQueue;
+use crate::queues::{DefaultQueue, Queue};
+use crate::math::notional;
+use crate::order::{Direction, Order, Price, Size};
+use core::iter::Peekable;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+/// Aggregated orders at one price level
+///
+/// Only displayed sizes are counted, hidden reserves of iceberg orders are excluded.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PriceLevel {
+    /// Price of the level
+    pub price: Price,
+    /// Total displayed size of the orders
+    pub size: Size,
+    /// Number of orders
+    pub orders: usize,
+}
+
+/// Aggregated (level 2) view of the order book
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DepthSnapshot {
+    /// Bid levels, best price first
+    pub bids: Vec<PriceLevel>,
+    /// Ask levels, best price first
+    pub asks: Vec<PriceLevel>,
+}
+
+/// Number of levels of each side covered by [checksum](crate::OrderBook::checksum)
+pub const CHECKSUM_LEVELS: usize = 10;
+
+impl DepthSnapshot {
+    /// Appends canonical encoding of the levels to `out`
+    ///
+    /// The encoding is the byte `A` followed by the ask levels and the byte `B` followed by the bid
+    /// levels, best price first. Every level is its price (`i64`) and size (`u64`), little-endian.
+    /// Order counts are not included.
+    pub fn write_canonical(&self, out: &mut Vec<u8>) {
+        for (tag, levels) in [(b'A', &self.asks), (b'B', &self.bids)] {
+            out.push(tag);
+            for level in levels {
+                out.extend_from_slice(&level.price.to_le_bytes());
+                out.extend_from_slice(&level.size.to_le_bytes());
+            }
+        }
+    }
+
+    /// Returns CRC-32 (IEEE) of the [canonical encoding](DepthSnapshot::write_canonical) of the levels
+    pub fn checksum(&self) -> u32 {
+        let mut bytes = Vec::with_capacity(2 + 16 * (self.asks.len() + self.bids.len()));
+        self.write_canonical(&mut bytes);
+        crc32(&bytes)
+    }
+
+    /// Formats the levels as a JSON object, e.g. `{"bids":[{"price":99,"size":5,"orders":1}],"asks":[]}`
+    pub fn to_json(&self) -> String {
+        let side = |levels: &[PriceLevel]| levels.iter()
+            .map(|level| format!("{{\"price\":{},\"size\":{},\"orders\":{}}}", level.price, level.size, level.orders))
+            .collect::<Vec<_>>()
+            .join(",");
+        format!("{{\"bids\":[{}],\"asks\":[{}]}}", side(&self.bids), side(&self.asks))
+    }
+}
+
+fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in bytes {
+        crc ^= u32::from(byte);
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 { (crc >> 1) ^ 0xEDB8_8320 } else { crc >> 1 };
+        }
+    }
+    !crc
+}
+
+/// Resting orders at one price level, see [bid_levels](crate::OrderBook::bid_levels)
+pub struct Level<'a, D> {
+    price: Price,
+    orders: Vec<&'a Order<D>>,
+}
+
+impl<'a, D: Direction> Level<'a, D> {
+    /// Returns price of the level
+    pub fn price(&self) -> Price {
+        self.price
+    }
+
+    /// Returns total displayed size of the orders
+    pub fn size(&self) -> Size {
+        self.orders.iter().fold(0, |size, order| size.saturating_add(order.size))
+    }
+
+    /// Returns total size of the orders, including hidden reserves of iceberg orders
+    pub fn total_size(&self) -> Size {
+        self.orders.iter().fold(0, |size, order| size.saturating_add(order.total_size()))
+    }
+
+    /// Returns number of the orders
+    pub fn order_count(&self) -> usize {
+        self.orders.len()
+    }
+
+    /// Returns the orders in priority order
+    pub fn orders(&self) -> impl Iterator<Item=&'a Order<D>> + '_ {
+        self.orders.iter().copied()
+    }
+
+    /// Returns the aggregated level
+    pub fn to_price_level(&self) -> PriceLevel {
+        PriceLevel { price: self.price, size: self.size(), orders: self.order_count() }
+    }
+}
+
+/// Iterator over the displayed price levels of one side of the book, best price first
+///
+/// [Hidden](crate::order::OrderKind::Hidden) orders are left out.
+pub struct Levels<'a, D: 'a+Direction, Q: 'a+Queue<Order<D>> = DefaultQueue<D>> {
+    orders: Peekable<Q::Iter<'a>>,
+}
+
+impl<'a, D: Direction, Q: Queue<Order<D>>> Levels<'a, D, Q> {
+    pub(crate) fn new(queue: &'a OrderQueue<D, Q>) -> Self {
+        Levels { orders: queue.into_iter().peekable() }
+    }
+}
+
+impl<'a, D: Direction, Q: Queue<Order<D>>> Iterator for Levels<'a, D, Q> {
+    type Item = Level<'a, D>;
+
+    fn next(&mut self) -> Option<Level<'a, D>> {
+        let first = loop {
+            let order = self.orders.next()?;
+            if !order.dark {
+                break order;
+            }
+        };
+        let mut orders = vec![first];
+        while let Some(order) = self.orders.next_if(|order| order.price_limit == first.price_limit) {
+            if !order.dark {
+                orders.push(order);
+            }
+        }
+        Some(Level { price: first.price_limit, orders })
+    }
+}
+
+/// Pre-trade statistics of the order book, see [stats](crate::OrderBook::stats)
+///
+/// Only displayed sizes are counted, hidden reserves of iceberg orders are excluded.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct BookStats {
+    /// Total displayed size of all bids
+    pub bid_volume: Size,
+    /// Total displayed size of all asks
+    pub ask_volume: Size,
+    /// Number of resting bids
+    pub bid_orders: usize,
+    /// Number of resting asks
+    pub ask_orders: usize,
+    /// Volume-weighted average price of the top bid levels, `None` if there are no bids
+    pub bid_vwap: Option<f64>,
+    /// Volume-weighted average price of the top ask levels, `None` if there are no asks
+    pub ask_vwap: Option<f64>,
+    /// `(bid size - ask size) / (bid size + ask size)` of the top levels, from -1 to 1;
+    /// `None` if both sides are empty
+    pub imbalance: Option<f64>,
+}
+
+/// Returns volume-weighted average price and total size of `levels`
+fn vwap(levels: &[PriceLevel]) -> (Option<f64>, Size) {
+    let size = levels.iter().fold(0, |total: Size, level| total.saturating_add(level.size));
+    if size == 0 {
+        return (None, 0);
+    }
+    let value = levels.iter().fold(0i128, |value, level| value.saturating_add(notional(level.price, level.size)));
+    (Some(value as f64 / size as f64), size)
+}
+
+impl BookStats {
+    /// Computes statistics from full level lists `bids` and `asks`, VWAP and imbalance are taken
+    /// over at most `levels` best levels of each side
+    pub(crate) fn from_levels(bids: &[PriceLevel], asks: &[PriceLevel], levels: usize) -> Self {
+        let total = |levels: &[PriceLevel]| levels.iter().fold((0, 0), |(size, orders): (Size, usize), level| {
+            (size.saturating_add(level.size), orders + level.orders)
+        });
+        let (bid_volume, bid_orders) = total(bids);
+        let (ask_volume, ask_orders) = total(asks);
+        let (bid_vwap, bid_size) = vwap(&bids[..levels.min(bids.len())]);
+        let (ask_vwap, ask_size) = vwap(&asks[..levels.min(asks.len())]);
+        let imbalance = match bid_size as f64 + ask_size as f64 {
+            sum if sum > 0.0 => Some((bid_size as f64 - ask_size as f64) / sum),
+            _ => None,
+        };
+        BookStats { bid_volume, ask_volume, bid_orders, ask_orders, bid_vwap, ask_vwap, imbalance }
+    }
+}
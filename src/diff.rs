@@ -0,0 +1,105 @@
+//! Differences between two states of a book
+//!
+//! [diff](OrderBook::diff) compares the resting orders of two books by their identifiers, e.g. a book
+//! and its earlier snapshot or a book and its replica. Stop orders and parked auction orders are
+//! not compared.
+
+use crate::OrderBook;
+use crate::order::{IncomingOrder, OrderId, Size};
+use alloc::collections::BTreeMap;
+use alloc::vec::Vec;
+
+/// Resting order whose size changed while it kept its price
+#[derive(Debug, Clone, PartialEq)]
+pub struct ResizedOrder {
+    #[allow(missing_docs)]
+    pub order_id: OrderId,
+    /// Order with its new total size
+    pub order: IncomingOrder,
+    /// Total size of the order in the first book
+    pub previous_size: Size,
+}
+
+/// Changes turning the resting orders of one book into those of another one
+///
+/// Orders are listed by identifier. An order which moved to another price is listed as removed
+/// and added, since it lost its time priority.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct BookDiff {
+    /// Orders resting only in the second book
+    pub added: Vec<(OrderId, IncomingOrder)>,
+    /// Orders resting only in the first book
+    pub removed: Vec<(OrderId, IncomingOrder)>,
+    /// Orders resting in both books with different sizes
+    pub resized: Vec<ResizedOrder>,
+}
+
+impl BookDiff {
+    /// Checks whether the books have the same resting orders
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.resized.is_empty()
+    }
+}
+
+impl OrderBook {
+    /// Returns changes of the resting orders from this book to `other`
+    pub fn diff(&self, other: &OrderBook) -> BookDiff {
+        let mut before = self.resting_orders();
+        let mut diff = BookDiff::default();
+        for (order_id, order) in other.resting_orders() {
+            match before.remove(&order_id) {
+                Some(previous) if previous.price_limit == order.price_limit && previous.side == order.side => {
+                    if previous.size != order.size {
+                        diff.resized.push(ResizedOrder { order_id, order, previous_size: previous.size });
+                    }
+                }
+                Some(previous) => {
+                    diff.removed.push((order_id, previous));
+                    diff.added.push((order_id, order));
+                }
+                None => diff.added.push((order_id, order)),
+            }
+        }
+        diff.removed.extend(before);
+        diff.removed.sort_by_key(|&(order_id, _)| order_id);
+        diff
+    }
+
+    fn resting_orders(&self) -> BTreeMap<OrderId, IncomingOrder> {
+        let bids = self.bid().into_iter().map(|order| (order.order_id(), order.to_incoming()));
+        let asks = self.ask().into_iter().map(|order| (order.order_id(), order.to_incoming()));
+        bids.chain(asks).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::log::DummyLogger;
+
+    #[test]
+    fn diff_of_book_states() {
+        let mut book = OrderBook::new();
+        for s in &["Lim B $99 #5 u1", "Lim B $98 #3 u2", "Lim S $101 #4 u3", "Lim S $102 #6 u4"] {
+            book.execute_order(s.parse().unwrap(), &mut DummyLogger);
+        }
+        let snapshot = book.clone();
+        assert!(snapshot.diff(&book).is_empty());
+
+        book.execute_order("IoC B $101 #1 u5".parse().unwrap(), &mut DummyLogger);
+        book.cancel_order(2, &mut DummyLogger).unwrap();
+        book.amend_order(4, 103, 6, &mut DummyLogger).unwrap();
+        book.execute_order("Lim S $105 #2 u6".parse().unwrap(), &mut DummyLogger);
+
+        let diff = snapshot.diff(&book);
+        let ids = |orders: &[(OrderId, IncomingOrder)]| orders.iter().map(|&(order_id, _)| order_id).collect::<Vec<_>>();
+        assert_eq!(ids(&diff.added), [4, 6]);
+        assert_eq!(diff.added[0].1, "Lim S $103 #6 u4".parse().unwrap());
+        assert_eq!(ids(&diff.removed), [2, 4]);
+        assert_eq!(diff.removed[1].1, "Lim S $102 #6 u4".parse().unwrap());
+        assert_eq!(diff.resized, [ResizedOrder { order_id: 3, order: "Lim S $101 #3 u3".parse().unwrap(), previous_size: 4 }]);
+
+        let reverse = book.diff(&snapshot);
+        assert_eq!((ids(&reverse.added), ids(&reverse.removed)), (vec![2, 4], vec![4, 6]));
+    }
+}
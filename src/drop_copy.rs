@@ -0,0 +1,271 @@
+//! Drop-copy export of order events and trades
+//!
+//! Drop copy is a sequenced duplicate of everything that happened to the orders, intended for
+//! surveillance and other downstream systems. Every record has the following fields:
+//!
+//! | Field                   | Description                                                           |
+//! |-------------------------|-----------------------------------------------------------------------|
+//! | `seq`                   | Record sequence number, contiguous within the stream                  |
+//! | `event`                 | `NEW`, `REJECT`, `TRADE`, `QUEUE`, `PARK`, `CANCEL` or `ADMIN_CANCEL` |
+//! | `order_id`              | Identifier of the incoming order                                      |
+//! | `user_id`               | Owner of the incoming order                                           |
+//! | `side`                  | `B` or `S`                                                            |
+//! | `price`                 | Limit price for `NEW`, execution price for `TRADE`                    |
+//! | `size`                  | Size of the order, trade, enqueued or cancelled remainder             |
+//! | `counterparty_order_id` | Passive order for `TRADE`                                             |
+//! | `counterparty_user_id`  | Owner of the passive order for `TRADE`                                |
+//! | `reason`                | Reject reason for `REJECT`                                            |
+//! | `status`                | [Status](OrderStatus) of the order after the event                    |
+//! | `cum_qty`               | Total filled size of the order after the event                        |
+//! | `avg_price`             | Average fill price of the order, empty if nothing was filled          |
+//!
+//! In CSV format empty fields are left blank and the first line is a header;
+//! in JSON format each record is an object on a separate line and empty fields are omitted.
+
+use crate::OrderBook;
+use crate::log::{LogItem, VectorLogger};
+use crate::order::{IncomingOrder, OrderId, Price, RestingOrderView, Size, UserId};
+use crate::status::{OrderState, OrderStatus, StatusStore};
+use std::io::{self, Write};
+
+const HEADER: &str = "seq,event,order_id,user_id,side,price,size,counterparty_order_id,counterparty_user_id,reason,status,cum_qty,avg_price";
+
+/// Output format of the drop copy
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DropCopyFormat {
+    /// Comma-separated values with a header line
+    Csv,
+    /// One JSON object per line
+    Json,
+}
+
+struct Record<'a> {
+    event: &'static str,
+    order_id: OrderId,
+    order: &'a IncomingOrder,
+    price: Option<Price>,
+    size: Size,
+    counterparty: Option<(OrderId, u64)>,
+    reason: Option<String>,
+    status: OrderStatus,
+    cum_qty: u64,
+    avg_price: Option<f64>,
+}
+
+/// Writer of the drop-copy stream
+pub struct DropCopy<W> {
+    writer: W,
+    format: DropCopyFormat,
+    user_filter: Option<UserId>,
+    seq: u64,
+    header_written: bool,
+    states: StatusStore,
+}
+
+impl<W: Write> DropCopy<W> {
+    /// Constructs drop copy writing to `writer`
+    pub fn new(writer: W, format: DropCopyFormat) -> Self {
+        DropCopy {
+            writer,
+            format,
+            user_filter: None,
+            seq: 0,
+            header_written: false,
+            states: StatusStore::default(),
+        }
+    }
+
+    /// Restricts the stream to the events of one user (firm)
+    ///
+    /// Trades are included if the user is on either side.
+    pub fn with_user_filter(mut self, user_id: UserId) -> Self {
+        self.user_filter = Some(user_id);
+        self
+    }
+
+    /// Returns the sequence number of the last written record
+    pub fn last_seq(&self) -> u64 {
+        self.seq
+    }
+
+    /// Returns the underlying writer
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+
+    /// Executes `order` on `book` and records the results
+    pub fn execute(&mut self, book: &mut OrderBook, order: IncomingOrder) -> io::Result<()> {
+        let mut logger = VectorLogger::new();
+        book.execute_order(order.clone(), &mut logger);
+        let order_id = book.last_order_id().expect("order id is assigned on execution");
+        self.record(order_id, &order, logger.as_slice())
+    }
+
+    /// Records the execution results `items` of the order `order_id`
+    pub fn record(&mut self, order_id: OrderId, order: &IncomingOrder, items: &[LogItem]) -> io::Result<()> {
+        let base = Record {
+            event: "NEW",
+            order_id,
+            order,
+            price: Some(order.price_limit),
+            size: order.size,
+            counterparty: None,
+            reason: None,
+            status: OrderStatus::New,
+            cum_qty: 0,
+            avg_price: None,
+        };
+        self.write(&base)?;
+        let mut state = OrderState::new(order.size);
+        for item in items {
+            state.apply(item);
+            let base = Record {
+                status: state.status(),
+                cum_qty: state.cum_qty(),
+                avg_price: state.avg_price(),
+                reason: None,
+                ..base
+            };
+            let record = match *item {
+                LogItem::Rejected { size, reason } => Record {
+                    event: "REJECT", price: None, size, reason: Some(reason.to_string()), ..base
+                },
+                LogItem::Fulfilled { size, price, user_id, order_id: maker_id } => Record {
+                    event: "TRADE", price: Some(price), size, counterparty: Some((maker_id, user_id)), reason: None, ..base
+                },
+                LogItem::Enqueued { size, price, .. } => Record { event: "QUEUE", price: Some(price), size, reason: None, ..base },
+                LogItem::Parked { size } => Record { event: "PARK", price: None, size, reason: None, ..base },
+                // Executions of triggered stop orders belong to other orders
+                LogItem::Triggered { .. } => break,
+                LogItem::Cancelled { size, price, .. } => Record { event: "CANCEL", price: Some(price), size, reason: None, ..base },
+                LogItem::ForceCancelled { size, .. } => Record {
+                    event: "ADMIN_CANCEL", price: None, size, reason: None, ..base
+                },
+                LogItem::Amended { .. } | LogItem::Refilled { .. } | LogItem::Expired { .. } | LogItem::IntegrityError { .. }
+                | LogItem::StateChanged { .. } => continue,
+            };
+            self.write(&record)?;
+        }
+        self.states.record(order_id, order.size, items);
+        Ok(())
+    }
+
+    /// Records cancellation of the resting order `view` by an administrator
+    pub fn record_force_cancel(&mut self, view: &RestingOrderView) -> io::Result<()> {
+        let (cum_qty, avg_price) = self.states.get(view.order_id)
+            .map_or((0, None), |state| (state.cum_qty(), state.avg_price()));
+        self.states.record_cancel(view.order_id);
+        self.write(&Record {
+            event: "ADMIN_CANCEL",
+            order_id: view.order_id,
+            order: &view.order,
+            price: None,
+            size: view.order.size,
+            counterparty: None,
+            reason: None,
+            status: OrderStatus::Cancelled,
+            cum_qty,
+            avg_price,
+        })
+    }
+
+    fn write(&mut self, record: &Record) -> io::Result<()> {
+        if let Some(user_id) = self.user_filter {
+            let counterparty_matches = record.counterparty.is_some_and(|(_, user)| user == user_id);
+            if record.order.user_id != user_id && !counterparty_matches {
+                return Ok(());
+            }
+        }
+        self.seq += 1;
+
+        fn opt<T: ToString>(value: Option<T>) -> String {
+            value.map(|v| v.to_string()).unwrap_or_default()
+        }
+
+        match self.format {
+            DropCopyFormat::Csv => {
+                if !self.header_written {
+                    writeln!(self.writer, "{}", HEADER)?;
+                    self.header_written = true;
+                }
+                writeln!(self.writer, "{},{},{},{},{},{},{},{},{},{},{},{},{}",
+                         self.seq, record.event, record.order_id, record.order.user_id, record.order.side.as_str(),
+                         opt(record.price), record.size, opt(record.counterparty.map(|c| c.0)),
+                         opt(record.counterparty.map(|c| c.1)), opt(record.reason.as_ref()), record.status,
+                         record.cum_qty, opt(record.avg_price))
+            }
+            DropCopyFormat::Json => {
+                write!(self.writer, "{{\"seq\":{},\"event\":\"{}\",\"order_id\":{},\"user_id\":{},\"side\":\"{}\"",
+                       self.seq, record.event, record.order_id, record.order.user_id, record.order.side.as_str())?;
+                if let Some(price) = record.price {
+                    write!(self.writer, ",\"price\":{}", price)?;
+                }
+                write!(self.writer, ",\"size\":{}", record.size)?;
+                if let Some((order_id, user_id)) = record.counterparty {
+                    write!(self.writer, ",\"counterparty_order_id\":{},\"counterparty_user_id\":{}", order_id, user_id)?;
+                }
+                if let Some(reason) = &record.reason {
+                    write!(self.writer, ",\"reason\":\"{}\"", reason)?;
+                }
+                write!(self.writer, ",\"status\":\"{}\",\"cum_qty\":{}", record.status, record.cum_qty)?;
+                if let Some(avg_price) = record.avg_price {
+                    write!(self.writer, ",\"avg_price\":{}", avg_price)?;
+                }
+                writeln!(self.writer, "}}")
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(drop_copy: &mut DropCopy<Vec<u8>>) {
+        let mut book = OrderBook::new();
+        for s in &["Lim S $100 #5 u1", "IoC B $100 #7 u2", "Lim B $0 #0 u3"] {
+            drop_copy.execute(&mut book, s.parse().unwrap()).unwrap();
+        }
+    }
+
+    #[test]
+    fn csv_drop_copy() {
+        let mut drop_copy = DropCopy::new(Vec::new(), DropCopyFormat::Csv);
+        run(&mut drop_copy);
+        let output = String::from_utf8(drop_copy.into_inner()).unwrap();
+        assert_eq!(output.lines().collect::<Vec<_>>(), [
+            HEADER,
+            "1,NEW,1,1,S,100,5,,,,NEW,0,",
+            "2,QUEUE,1,1,S,100,5,,,,NEW,0,",
+            "3,NEW,2,2,B,100,7,,,,NEW,0,",
+            "4,TRADE,2,2,B,100,5,1,1,,PARTIALLY_FILLED,5,100",
+            "5,CANCEL,2,2,B,100,2,,,,CANCELLED,5,100",
+            "6,NEW,3,3,B,0,0,,,,NEW,0,",
+        ]);
+    }
+
+    #[test]
+    fn admin_cancel_reports_fill_progress() {
+        let mut book = OrderBook::new();
+        let mut drop_copy = DropCopy::new(Vec::new(), DropCopyFormat::Csv);
+        drop_copy.execute(&mut book, "Lim S $100 #5 u1".parse().unwrap()).unwrap();
+        drop_copy.execute(&mut book, "Lim B $100 #2 u2".parse().unwrap()).unwrap();
+        let view = book.force_cancel(1, &mut VectorLogger::new()).unwrap();
+        drop_copy.record_force_cancel(&view).unwrap();
+        let output = String::from_utf8(drop_copy.into_inner()).unwrap();
+        assert_eq!(output.lines().last(), Some("5,ADMIN_CANCEL,1,1,S,,3,,,,CANCELLED,2,100"));
+    }
+
+    #[test]
+    fn json_drop_copy_filtered_by_user() {
+        let mut drop_copy = DropCopy::new(Vec::new(), DropCopyFormat::Json).with_user_filter(1);
+        run(&mut drop_copy);
+        assert_eq!(drop_copy.last_seq(), 3);
+        let output = String::from_utf8(drop_copy.into_inner()).unwrap();
+        assert_eq!(output.lines().collect::<Vec<_>>(), [
+            r#"{"seq":1,"event":"NEW","order_id":1,"user_id":1,"side":"S","price":100,"size":5,"status":"NEW","cum_qty":0}"#,
+            r#"{"seq":2,"event":"QUEUE","order_id":1,"user_id":1,"side":"S","price":100,"size":5,"status":"NEW","cum_qty":0}"#,
+            r#"{"seq":3,"event":"TRADE","order_id":2,"user_id":2,"side":"B","price":100,"size":5,"counterparty_order_id":1,"counterparty_user_id":1,"status":"PARTIALLY_FILLED","cum_qty":5,"avg_price":100}"#,
+        ]);
+    }
+}
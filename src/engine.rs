@@ -0,0 +1,155 @@
+//! Matching engine for multiple instruments
+//!
+//! [MatchingEngine](MatchingEngine) keeps an independent [OrderBook](OrderBook) with its own
+//! [configuration](BookConfig) for every symbol and routes orders to them. Order identifiers are
+//! assigned by the books, so they are only unique within a symbol.
+
+use crate::OrderBook;
+use crate::config::{BookConfig, ConfigError};
+use crate::depth::DepthSnapshot;
+use crate::log::{DummyLogger, ExecutionLogger};
+use crate::order::{IncomingOrder, OrderId};
+use alloc::collections::BTreeMap;
+use core::fmt;
+use alloc::vec::Vec;
+use alloc::string::{String, ToString};
+
+/// Error of a matching engine operation
+#[derive(Debug, Clone, PartialEq)]
+pub enum EngineError {
+    /// There is no book for the symbol
+    UnknownSymbol(String),
+    /// Book for the symbol already exists
+    DuplicateSymbol(String),
+    /// Configuration of the book cannot be updated
+    Config(ConfigError),
+}
+
+impl fmt::Display for EngineError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            EngineError::UnknownSymbol(symbol) => write!(f, "unknown symbol {}", symbol),
+            EngineError::DuplicateSymbol(symbol) => write!(f, "symbol {} already exists", symbol),
+            EngineError::Config(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+/// Order books of multiple symbols
+#[derive(Clone, Default)]
+pub struct MatchingEngine {
+    books: BTreeMap<String, OrderBook>,
+}
+
+impl MatchingEngine {
+    /// Constructs an engine without symbols
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds an empty book for `symbol` with the given configuration
+    pub fn add_symbol(&mut self, symbol: &str, config: BookConfig) -> Result<(), EngineError> {
+        if self.books.contains_key(symbol) {
+            return Err(EngineError::DuplicateSymbol(symbol.to_string()));
+        }
+        self.books.insert(symbol.to_string(), OrderBook::with_config(config));
+        Ok(())
+    }
+
+    /// Removes the book of `symbol` and returns it
+    pub fn remove_symbol(&mut self, symbol: &str) -> Option<OrderBook> {
+        self.books.remove(symbol)
+    }
+
+    /// Iterates over the symbols in alphabetical order
+    pub fn symbols(&self) -> impl Iterator<Item=&str> {
+        self.books.keys().map(String::as_str)
+    }
+
+    /// Returns the book of `symbol`
+    pub fn book(&self, symbol: &str) -> Option<&OrderBook> {
+        self.books.get(symbol)
+    }
+
+    /// Returns the book of `symbol` for direct operations (cancels, amendments, administration)
+    pub fn book_mut(&mut self, symbol: &str) -> Option<&mut OrderBook> {
+        self.books.get_mut(symbol)
+    }
+
+    fn get_mut(&mut self, symbol: &str) -> Result<&mut OrderBook, EngineError> {
+        self.books.get_mut(symbol).ok_or_else(|| EngineError::UnknownSymbol(symbol.to_string()))
+    }
+
+    /// Executes `order` in the book of `symbol` and returns the identifier assigned to it
+    ///
+    /// Execution results are logged with `logger` like in [execute_order](OrderBook::execute_order).
+    pub fn execute(&mut self, symbol: &str, order: IncomingOrder, logger: &mut impl ExecutionLogger) -> Result<OrderId, EngineError> {
+        let book = self.get_mut(symbol)?;
+        book.execute_order(order, logger);
+        Ok(book.last_order_id().expect("order id is assigned on execution"))
+    }
+
+    /// Replaces configuration of the book of `symbol`, see [update_config](OrderBook::update_config)
+    pub fn update_config(&mut self, symbol: &str, config: BookConfig) -> Result<(), EngineError> {
+        self.get_mut(symbol)?.update_config(config).map_err(EngineError::Config)?;
+        Ok(())
+    }
+
+    /// Returns up to `levels` aggregated price levels of each side of every book
+    pub fn depth(&self, levels: usize) -> BTreeMap<String, DepthSnapshot> {
+        self.books.iter().map(|(symbol, book)| (symbol.clone(), book.depth(levels))).collect()
+    }
+
+    /// Returns resting orders of every book in the [to_vec_with_hidden](OrderBook::to_vec_with_hidden) representation
+    pub fn snapshot(&self) -> BTreeMap<String, Vec<IncomingOrder>> {
+        self.books.iter().map(|(symbol, book)| (symbol.clone(), book.to_vec_with_hidden())).collect()
+    }
+
+    /// Creates an engine from per-symbol configurations and snapshots
+    pub fn from_snapshot(books: impl IntoIterator<Item=(String, BookConfig, Vec<IncomingOrder>)>) -> Self {
+        let mut engine = Self::new();
+        for (symbol, config, orders) in books {
+            let mut book = OrderBook::with_config(config);
+            for order in orders {
+                book.execute_order(order, &mut DummyLogger);
+            }
+            engine.books.insert(symbol, book);
+        }
+        engine
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::log::VectorLogger;
+    use crate::tick::TickSize;
+
+    #[test]
+    fn books_are_independent() {
+        let mut engine = MatchingEngine::new();
+        engine.add_symbol("AAA", BookConfig::default()).unwrap();
+        let config = BookConfig { tick_size: "0.5".parse::<TickSize>().unwrap(), lot_size: 10, ..BookConfig::strict() };
+        engine.add_symbol("BBB", config.clone()).unwrap();
+        assert_eq!(engine.add_symbol("AAA", config), Err(EngineError::DuplicateSymbol("AAA".to_string())));
+
+        let mut logger = VectorLogger::new();
+        assert_eq!(engine.execute("AAA", "Lim S $100 #5 u1".parse().unwrap(), &mut logger), Ok(1));
+        assert_eq!(engine.execute("BBB", "Lim S $100 #20 u1".parse().unwrap(), &mut logger), Ok(1));
+        assert_eq!(engine.execute("BBB", "Lim B $100 #10 u2".parse().unwrap(), &mut logger), Ok(2));
+        assert_eq!(engine.execute("CCC", "Lim B $100 #10 u2".parse().unwrap(), &mut logger),
+                   Err(EngineError::UnknownSymbol("CCC".to_string())));
+
+        assert_eq!(engine.symbols().collect::<Vec<_>>(), ["AAA", "BBB"]);
+        assert_eq!(engine.book("BBB").unwrap().config().lot_size, 10);
+        let depth = engine.depth(1);
+        assert_eq!((depth["AAA"].asks[0].size, depth["BBB"].asks[0].size), (5, 10));
+
+        let snapshot = engine.snapshot();
+        let restored = MatchingEngine::from_snapshot(snapshot.clone().into_iter().map(|(symbol, orders)| {
+            let config = engine.book(&symbol).unwrap().config().clone();
+            (symbol, config, orders)
+        }));
+        assert_eq!(restored.snapshot(), snapshot);
+    }
+}
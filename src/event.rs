@@ -0,0 +1,310 @@
+//! Extensible binary encoding of log items
+//!
+//! Every event is encoded as a tag (`u8`), body length (`u16`, little-endian) and a body made of
+//! fields. Each field is a field id (`u8`) followed by its value (`u64`, little-endian; prices are
+//! stored in two's complement).
+//!
+//! The encoding can evolve without breaking consumers:
+//!
+//! * new fields get new ids; decoders skip fields they do not know,
+//! * new events get new tags; decoders return [Event::Unknown](Event::Unknown) and skip the body,
+//! * tags, field ids and reason codes are never reused; a retired event keeps its tag in
+//!   [decode_event](decode_event), which maps it to the closest current [LogItem](LogItem).
+//!
+//! | Tag | Event            | Fields                         |
+//! |-----|------------------|--------------------------------|
+//! | 1   | `Enqueued`       | size, price, side, user, order |
+//! | 2   | `Fulfilled`      | size, price, user, order       |
+//! | 3   | `Cancelled`      | size, price, side, user, order |
+//! | 4   | `Rejected`       | size, reason                   |
+//! | 5   | `ForceCancelled` | size, order                    |
+//! | 6   | `IntegrityError` | expected, actual               |
+//! | 7   | `Amended`        | size, price, order             |
+//! | 8   | `Parked`         | size                           |
+//! | 9   | `Triggered`      | order                          |
+//! | 10  | `Refilled`       | size, order                    |
+//! | 11  | `Expired`        | size, order                    |
+//! | 12  | `StateChanged`   | state                          |
+
+use crate::config::{BookState, RejectReason};
+use crate::decode::DecodeError;
+use crate::log::LogItem;
+use crate::order::OrderSide;
+use alloc::vec::Vec;
+
+const TAG_ENQUEUED: u8 = 1;
+const TAG_FULFILLED: u8 = 2;
+const TAG_CANCELLED: u8 = 3;
+const TAG_REJECTED: u8 = 4;
+const TAG_FORCE_CANCELLED: u8 = 5;
+const TAG_INTEGRITY_ERROR: u8 = 6;
+const TAG_AMENDED: u8 = 7;
+const TAG_PARKED: u8 = 8;
+const TAG_TRIGGERED: u8 = 9;
+const TAG_REFILLED: u8 = 10;
+const TAG_EXPIRED: u8 = 11;
+const TAG_STATE_CHANGED: u8 = 12;
+
+const FIELD_SIZE: u8 = 1;
+const FIELD_PRICE: u8 = 2;
+const FIELD_USER_ID: u8 = 3;
+const FIELD_ORDER_ID: u8 = 4;
+const FIELD_REASON: u8 = 5;
+const FIELD_EXPECTED: u8 = 6;
+const FIELD_ACTUAL: u8 = 7;
+const FIELD_STATE: u8 = 8;
+const FIELD_SIDE: u8 = 9;
+
+const FIELD_LEN: usize = 9;
+
+/// Decoded event
+#[derive(Debug, Clone, PartialEq)]
+pub enum Event {
+    /// Event known to this version
+    Item(LogItem),
+    /// Event added by a newer version, its body was skipped
+    Unknown {
+        /// Tag of the event
+        tag: u8,
+    },
+}
+
+fn reason_code(reason: RejectReason) -> u64 {
+    match reason {
+        RejectReason::ZeroSize => 1,
+        RejectReason::ZeroPrice => 2,
+        RejectReason::NegativePrice => 3,
+        RejectReason::PriceTooHigh => 4,
+        RejectReason::SizeTooLarge => 5,
+        RejectReason::OddLot => 6,
+        RejectReason::Halted => 7,
+        RejectReason::UnknownOrder => 8,
+        RejectReason::ZeroDisplaySize => 9,
+        RejectReason::WouldCross => 10,
+        RejectReason::PriceOutOfBand => 11,
+        RejectReason::TooManyOpenOrders => 12,
+        RejectReason::SizeTooSmall => 13,
+        RejectReason::RiskLimit => 14,
+        RejectReason::Overflow => 15,
+        RejectReason::NonPositiveOffset => 16,
+        RejectReason::MinFillTooLarge => 17,
+        RejectReason::WouldIncreasePosition => 18,
+        RejectReason::NotionalLimit => 19,
+    }
+}
+
+fn side_code(side: OrderSide) -> u64 {
+    match side {
+        OrderSide::Buy => 1,
+        OrderSide::Sell => 2,
+    }
+}
+
+fn side_from_code(code: u64) -> Option<OrderSide> {
+    Some(match code {
+        1 => OrderSide::Buy,
+        2 => OrderSide::Sell,
+        _ => return None,
+    })
+}
+
+fn state_code(state: BookState) -> u64 {
+    match state {
+        BookState::Open => 1,
+        BookState::Halted => 2,
+        BookState::AuctionOnly => 3,
+    }
+}
+
+fn state_from_code(code: u64) -> Option<BookState> {
+    Some(match code {
+        1 => BookState::Open,
+        2 => BookState::Halted,
+        3 => BookState::AuctionOnly,
+        _ => return None,
+    })
+}
+
+fn reason_from_code(code: u64) -> Option<RejectReason> {
+    Some(match code {
+        1 => RejectReason::ZeroSize,
+        2 => RejectReason::ZeroPrice,
+        3 => RejectReason::NegativePrice,
+        4 => RejectReason::PriceTooHigh,
+        5 => RejectReason::SizeTooLarge,
+        6 => RejectReason::OddLot,
+        7 => RejectReason::Halted,
+        8 => RejectReason::UnknownOrder,
+        9 => RejectReason::ZeroDisplaySize,
+        10 => RejectReason::WouldCross,
+        11 => RejectReason::PriceOutOfBand,
+        12 => RejectReason::TooManyOpenOrders,
+        13 => RejectReason::SizeTooSmall,
+        14 => RejectReason::RiskLimit,
+        15 => RejectReason::Overflow,
+        16 => RejectReason::NonPositiveOffset,
+        17 => RejectReason::MinFillTooLarge,
+        18 => RejectReason::WouldIncreasePosition,
+        19 => RejectReason::NotionalLimit,
+        _ => return None,
+    })
+}
+
+/// Appends encoded `item` to `out`
+pub fn encode_event(item: &LogItem, out: &mut Vec<u8>) {
+    let (tag, fields): (u8, &[(u8, u64)]) = match *item {
+        LogItem::Enqueued { size, price, side, user_id, order_id } => (TAG_ENQUEUED, &[
+            (FIELD_SIZE, size),
+            (FIELD_PRICE, price as u64),
+            (FIELD_SIDE, side_code(side)),
+            (FIELD_USER_ID, user_id),
+            (FIELD_ORDER_ID, order_id),
+        ]),
+        LogItem::Fulfilled { size, price, user_id, order_id } => (TAG_FULFILLED, &[
+            (FIELD_SIZE, size),
+            (FIELD_PRICE, price as u64),
+            (FIELD_USER_ID, user_id),
+            (FIELD_ORDER_ID, order_id),
+        ]),
+        LogItem::Cancelled { size, price, side, user_id, order_id } => (TAG_CANCELLED, &[
+            (FIELD_SIZE, size),
+            (FIELD_PRICE, price as u64),
+            (FIELD_SIDE, side_code(side)),
+            (FIELD_USER_ID, user_id),
+            (FIELD_ORDER_ID, order_id),
+        ]),
+        LogItem::Rejected { size, reason } => (TAG_REJECTED, &[(FIELD_SIZE, size), (FIELD_REASON, reason_code(reason))]),
+        LogItem::ForceCancelled { size, order_id } => (TAG_FORCE_CANCELLED, &[(FIELD_SIZE, size), (FIELD_ORDER_ID, order_id)]),
+        LogItem::Amended { size, price, order_id } => (TAG_AMENDED, &[
+            (FIELD_SIZE, size),
+            (FIELD_PRICE, price as u64),
+            (FIELD_ORDER_ID, order_id),
+        ]),
+        LogItem::Parked { size } => (TAG_PARKED, &[(FIELD_SIZE, size)]),
+        LogItem::Triggered { order_id } => (TAG_TRIGGERED, &[(FIELD_ORDER_ID, order_id)]),
+        LogItem::Refilled { size, order_id } => (TAG_REFILLED, &[(FIELD_SIZE, size), (FIELD_ORDER_ID, order_id)]),
+        LogItem::Expired { size, order_id } => (TAG_EXPIRED, &[(FIELD_SIZE, size), (FIELD_ORDER_ID, order_id)]),
+        LogItem::IntegrityError { expected, actual } => (TAG_INTEGRITY_ERROR, &[(FIELD_EXPECTED, expected), (FIELD_ACTUAL, actual)]),
+        LogItem::StateChanged { state } => (TAG_STATE_CHANGED, &[(FIELD_STATE, state_code(state))]),
+    };
+    out.push(tag);
+    out.extend_from_slice(&((fields.len() * FIELD_LEN) as u16).to_le_bytes());
+    for &(id, value) in fields {
+        out.push(id);
+        out.extend_from_slice(&value.to_le_bytes());
+    }
+}
+
+/// Fields of one event body
+struct Fields<'a>(&'a [u8]);
+
+impl<'a> Fields<'a> {
+    fn get(&self, id: u8, name: &'static str) -> Result<u64, DecodeError> {
+        self.0.chunks(FIELD_LEN)
+            .find(|field| field[0] == id)
+            .map(|field| {
+                let mut value = [0; 8];
+                value.copy_from_slice(&field[1..]);
+                u64::from_le_bytes(value)
+            })
+            .ok_or(DecodeError::MissingField(name))
+    }
+}
+
+/// Decodes the first event of `bytes` and advances `bytes` past it
+pub fn decode_event(bytes: &mut &[u8]) -> Result<Event, DecodeError> {
+    if bytes.len() < 3 {
+        return Err(DecodeError::InvalidLength);
+    }
+    let tag = bytes[0];
+    let len = usize::from(u16::from_le_bytes([bytes[1], bytes[2]]));
+    if bytes.len() < 3 + len {
+        return Err(DecodeError::InvalidLength);
+    }
+    let body = &bytes[3..3 + len];
+    *bytes = &bytes[3 + len..];
+
+    // Bodies written by newer versions may end with a field of another layout; it is skipped too
+    let fields = Fields(&body[..len - len % FIELD_LEN]);
+    let size = || fields.get(FIELD_SIZE, "size");
+    let price = || fields.get(FIELD_PRICE, "price").map(|price| price as i64);
+    let side = || side_from_code(fields.get(FIELD_SIDE, "side")?).ok_or(DecodeError::InvalidField("side"));
+    let user_id = || fields.get(FIELD_USER_ID, "user_id");
+    let order_id = || fields.get(FIELD_ORDER_ID, "order_id");
+    let item = match tag {
+        TAG_ENQUEUED => LogItem::Enqueued { size: size()?, price: price()?, side: side()?, user_id: user_id()?, order_id: order_id()? },
+        TAG_FULFILLED => LogItem::Fulfilled {
+            size: size()?,
+            price: price()?,
+            user_id: user_id()?,
+            order_id: order_id()?,
+        },
+        TAG_CANCELLED => LogItem::Cancelled { size: size()?, price: price()?, side: side()?, user_id: user_id()?, order_id: order_id()? },
+        TAG_REJECTED => LogItem::Rejected {
+            size: size()?,
+            reason: reason_from_code(fields.get(FIELD_REASON, "reason")?).ok_or(DecodeError::InvalidField("reason"))?,
+        },
+        TAG_FORCE_CANCELLED => LogItem::ForceCancelled { size: size()?, order_id: order_id()? },
+        TAG_AMENDED => LogItem::Amended {
+            size: size()?,
+            price: price()?,
+            order_id: order_id()?,
+        },
+        TAG_PARKED => LogItem::Parked { size: size()? },
+        TAG_TRIGGERED => LogItem::Triggered { order_id: order_id()? },
+        TAG_REFILLED => LogItem::Refilled { size: size()?, order_id: order_id()? },
+        TAG_EXPIRED => LogItem::Expired { size: size()?, order_id: order_id()? },
+        TAG_INTEGRITY_ERROR => LogItem::IntegrityError {
+            expected: fields.get(FIELD_EXPECTED, "expected")?,
+            actual: fields.get(FIELD_ACTUAL, "actual")?,
+        },
+        TAG_STATE_CHANGED => LogItem::StateChanged {
+            state: state_from_code(fields.get(FIELD_STATE, "state")?).ok_or(DecodeError::InvalidField("state"))?,
+        },
+        tag => return Ok(Event::Unknown { tag }),
+    };
+    Ok(Event::Item(item))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn events_round_trip_and_tolerate_extensions() {
+        let items = vec![
+            LogItem::Fulfilled { size: 3, price: -5, user_id: 7, order_id: 2 },
+            LogItem::Rejected { size: 1, reason: RejectReason::Halted },
+            LogItem::Enqueued { size: 4, price: -3, side: OrderSide::Sell, user_id: 5, order_id: 6 },
+            LogItem::Amended { size: 2, price: 99, order_id: 1 },
+            LogItem::StateChanged { state: BookState::AuctionOnly },
+        ];
+        let mut bytes = Vec::new();
+        for item in &items {
+            encode_event(item, &mut bytes);
+        }
+        // Event from a newer version
+        bytes.extend_from_slice(&[42, 2, 0, 1, 2]);
+        // Cancellation with an extra field from a newer version
+        bytes.extend_from_slice(&[TAG_CANCELLED, 54, 0]);
+        bytes.extend_from_slice(&[99, 1, 0, 0, 0, 0, 0, 0, 0]);
+        bytes.extend_from_slice(&[FIELD_SIZE, 6, 0, 0, 0, 0, 0, 0, 0]);
+        bytes.extend_from_slice(&[FIELD_PRICE, 100, 0, 0, 0, 0, 0, 0, 0]);
+        bytes.extend_from_slice(&[FIELD_SIDE, 1, 0, 0, 0, 0, 0, 0, 0]);
+        bytes.extend_from_slice(&[FIELD_USER_ID, 2, 0, 0, 0, 0, 0, 0, 0]);
+        bytes.extend_from_slice(&[FIELD_ORDER_ID, 3, 0, 0, 0, 0, 0, 0, 0]);
+
+        let mut rest = bytes.as_slice();
+        let mut events = Vec::new();
+        while !rest.is_empty() {
+            events.push(decode_event(&mut rest).unwrap());
+        }
+        let mut expected: Vec<Event> = items.into_iter().map(Event::Item).collect();
+        expected.push(Event::Unknown { tag: 42 });
+        expected.push(Event::Item(LogItem::Cancelled { size: 6, price: 100, side: OrderSide::Buy, user_id: 2, order_id: 3 }));
+        assert_eq!(events, expected);
+
+        assert_eq!(decode_event(&mut &[TAG_ENQUEUED, 0, 0][..]), Err(DecodeError::MissingField("size")));
+        assert_eq!(decode_event(&mut &[TAG_ENQUEUED, 9, 0, 1][..]), Err(DecodeError::InvalidLength));
+    }
+}
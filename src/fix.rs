@@ -0,0 +1,538 @@
+//! FIX 4.4 adapter
+//!
+//! [FixAdapter](FixAdapter) translates application messages of a FIX session into operations on an
+//! [OrderBook](OrderBook) and the execution results into `ExecutionReport (8)` messages. Session-level
+//! concerns (logon, sequence numbers, resends, `SenderCompID`/`TargetCompID`) belong to the gateway,
+//! so the messages handled here only carry the standard header `BeginString (8)`, `BodyLength (9)`
+//! and `MsgType (35)`, and the `CheckSum (10)` trailer.
+//!
+//! Supported requests:
+//!
+//! | MsgType | Message                      | Operation                                  |
+//! |---------|------------------------------|--------------------------------------------|
+//! | `D`     | `NewOrderSingle`             | [execute_order](OrderBook::execute_order)  |
+//! | `F`     | `OrderCancelRequest`         | [cancel_order](OrderBook::cancel_order)    |
+//! | `G`     | `OrderCancelReplaceRequest`  | [amend_order](OrderBook::amend_order)      |
+//!
+//! Orders are identified by `ClOrdID (11)`, the book identifier is reported as `OrderID (37)`.
+//! The user is taken from `Account (1)`, which must be numeric. `OrdType (40)` `1`, `2`, `3` and `4`
+//! map to market, limit, stop and stop-limit orders; `TimeInForce (59)` `3` and `4` make limit orders
+//! immediate-or-cancel and fill-or-kill. `MaxFloor (111)` makes an iceberg order and `ExecInst (18)`
+//! containing `6` a post-only order; `MinQty (110)` sets the minimum fill size and `ExecInst (18)` containing
+//! `E` (do not increase) makes the order reduce-only. Prices are decimal and converted with the tick size of the book.
+//! Failed cancels and replacements are answered with `OrderCancelReject (9)`.
+
+use crate::OrderBook;
+use crate::config::RejectReason;
+use crate::log::{LogItem, VectorLogger};
+use crate::order::{AmendError, IncomingOrder, OrderId, OrderKind, OrderSide, Price};
+use crate::status::{OrderState, OrderStatus};
+use crate::tick::TickSize;
+use std::collections::HashMap;
+use std::fmt;
+
+/// Field delimiter
+pub const SOH: u8 = 0x01;
+
+const BEGIN_STRING: &str = "FIX.4.4";
+
+const TAG_ACCOUNT: u32 = 1;
+const TAG_AVG_PX: u32 = 6;
+const TAG_BEGIN_STRING: u32 = 8;
+const TAG_BODY_LENGTH: u32 = 9;
+const TAG_CHECKSUM: u32 = 10;
+const TAG_CL_ORD_ID: u32 = 11;
+const TAG_CUM_QTY: u32 = 14;
+const TAG_EXEC_ID: u32 = 17;
+const TAG_EXEC_INST: u32 = 18;
+const TAG_LAST_PX: u32 = 31;
+const TAG_LAST_QTY: u32 = 32;
+const TAG_MSG_TYPE: u32 = 35;
+const TAG_ORDER_ID: u32 = 37;
+const TAG_ORDER_QTY: u32 = 38;
+const TAG_ORD_STATUS: u32 = 39;
+const TAG_ORD_TYPE: u32 = 40;
+const TAG_ORIG_CL_ORD_ID: u32 = 41;
+const TAG_PRICE: u32 = 44;
+const TAG_SIDE: u32 = 54;
+const TAG_SYMBOL: u32 = 55;
+const TAG_TEXT: u32 = 58;
+const TAG_TIME_IN_FORCE: u32 = 59;
+const TAG_STOP_PX: u32 = 99;
+const TAG_CXL_REJ_REASON: u32 = 102;
+const TAG_MIN_QTY: u32 = 110;
+const TAG_MAX_FLOOR: u32 = 111;
+const TAG_EXEC_TYPE: u32 = 150;
+const TAG_LEAVES_QTY: u32 = 151;
+const TAG_CXL_REJ_RESPONSE_TO: u32 = 434;
+
+/// Error of parsing or handling a FIX message
+#[derive(Debug, Clone, PartialEq)]
+pub enum FixError {
+    /// Message is not a sequence of `tag=value` fields or has no FIX 4.4 header
+    Malformed,
+    /// `BodyLength (9)` does not match the message
+    BadBodyLength,
+    /// `CheckSum (10)` does not match the message
+    BadChecksum,
+    /// Required field is missing
+    MissingField(u32),
+    /// Field has an invalid or unsupported value
+    InvalidField(u32),
+    /// Message type is not supported
+    UnsupportedMsgType(String),
+    /// `ClOrdID (11)` is already used by another order
+    DuplicateClOrdId(String),
+}
+
+impl fmt::Display for FixError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FixError::Malformed => write!(f, "malformed message"),
+            FixError::BadBodyLength => write!(f, "body length mismatch"),
+            FixError::BadChecksum => write!(f, "checksum mismatch"),
+            FixError::MissingField(tag) => write!(f, "missing field {}", tag),
+            FixError::InvalidField(tag) => write!(f, "invalid value of field {}", tag),
+            FixError::UnsupportedMsgType(msg_type) => write!(f, "unsupported message type {}", msg_type),
+            FixError::DuplicateClOrdId(id) => write!(f, "duplicate ClOrdID {}", id),
+        }
+    }
+}
+
+/// FIX message without the header and trailer fields
+#[derive(Debug, Clone, PartialEq)]
+pub struct FixMessage {
+    fields: Vec<(u32, String)>,
+}
+
+impl FixMessage {
+    /// Constructs message of type `msg_type`
+    pub fn new(msg_type: &str) -> Self {
+        FixMessage { fields: vec![(TAG_MSG_TYPE, msg_type.to_string())] }
+    }
+
+    /// Appends a field
+    pub fn push(&mut self, tag: u32, value: impl ToString) -> &mut Self {
+        self.fields.push((tag, value.to_string()));
+        self
+    }
+
+    /// Returns value of the first field `tag`
+    pub fn get(&self, tag: u32) -> Option<&str> {
+        self.fields.iter().find(|&&(t, _)| t == tag).map(|(_, value)| value.as_str())
+    }
+
+    /// Returns `MsgType (35)`
+    pub fn msg_type(&self) -> &str {
+        self.get(TAG_MSG_TYPE).unwrap_or_default()
+    }
+
+    fn require(&self, tag: u32) -> Result<&str, FixError> {
+        self.get(tag).ok_or(FixError::MissingField(tag))
+    }
+
+    fn parse_field<T: std::str::FromStr>(&self, tag: u32) -> Result<T, FixError> {
+        self.require(tag)?.parse().map_err(|_| FixError::InvalidField(tag))
+    }
+
+    /// Parses a complete message, checking its header, body length and checksum
+    pub fn parse(bytes: &[u8]) -> Result<Self, FixError> {
+        let trailer = bytes.len().checked_sub(7).ok_or(FixError::Malformed)?;
+        if !bytes[trailer..].starts_with(b"10=") || bytes[bytes.len() - 1] != SOH {
+            return Err(FixError::Malformed);
+        }
+        let checksum = std::str::from_utf8(&bytes[trailer + 3..bytes.len() - 1]).ok()
+            .and_then(|s| s.parse::<u32>().ok())
+            .ok_or(FixError::Malformed)?;
+        if checksum != sum(&bytes[..trailer]) {
+            return Err(FixError::BadChecksum);
+        }
+
+        let text = std::str::from_utf8(&bytes[..trailer]).map_err(|_| FixError::Malformed)?;
+        let mut fields = Vec::new();
+        for field in text.strip_suffix('\u{1}').ok_or(FixError::Malformed)?.split('\u{1}') {
+            let (tag, value) = field.split_once('=').ok_or(FixError::Malformed)?;
+            fields.push((tag.parse::<u32>().map_err(|_| FixError::Malformed)?, value.to_string()));
+        }
+        match fields.as_slice() {
+            [(TAG_BEGIN_STRING, begin), (TAG_BODY_LENGTH, length), (TAG_MSG_TYPE, _), ..] if begin == BEGIN_STRING => {
+                let header_len = format!("8={}\u{1}9={}\u{1}", begin, length).len();
+                if length.parse::<usize>().ok() != Some(trailer - header_len) {
+                    return Err(FixError::BadBodyLength);
+                }
+            }
+            _ => return Err(FixError::Malformed),
+        }
+        fields.drain(..2);
+        Ok(FixMessage { fields })
+    }
+
+    /// Encodes the message with the header and the trailer
+    pub fn encode(&self) -> Vec<u8> {
+        let mut body = Vec::new();
+        for (tag, value) in &self.fields {
+            body.extend_from_slice(format!("{}={}", tag, value).as_bytes());
+            body.push(SOH);
+        }
+        let mut bytes = format!("8={}\u{1}9={}\u{1}", BEGIN_STRING, body.len()).into_bytes();
+        bytes.extend_from_slice(&body);
+        let checksum = sum(&bytes);
+        bytes.extend_from_slice(format!("{}={:03}\u{1}", TAG_CHECKSUM, checksum).as_bytes());
+        bytes
+    }
+}
+
+/// Formats the message with `|` as the field delimiter
+impl fmt::Display for FixMessage {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (tag, value) in &self.fields {
+            write!(f, "{}={}|", tag, value)?;
+        }
+        Ok(())
+    }
+}
+
+fn sum(bytes: &[u8]) -> u32 {
+    bytes.iter().map(|&b| u32::from(b)).sum::<u32>() % 256
+}
+
+struct FixOrder {
+    cl_ord_id: String,
+    symbol: Option<String>,
+    side: OrderSide,
+    order_qty: u64,
+    state: OrderState,
+}
+
+/// Translator between FIX messages and book operations
+#[derive(Default)]
+pub struct FixAdapter {
+    orders: HashMap<OrderId, FixOrder>,
+    ids: HashMap<String, OrderId>,
+    exec_id: u64,
+}
+
+impl FixAdapter {
+    /// Constructs adapter without known orders
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Applies the request `message` to `book` and returns the messages to send back
+    ///
+    /// Errors are returned for messages which cannot be applied at all; the gateway is expected
+    /// to answer them with a session-level or business reject.
+    pub fn handle(&mut self, book: &mut OrderBook, message: &FixMessage) -> Result<Vec<FixMessage>, FixError> {
+        match message.msg_type() {
+            "D" => self.new_order(book, message),
+            "F" => self.cancel(book, message),
+            "G" => self.replace(book, message),
+            msg_type => Err(FixError::UnsupportedMsgType(msg_type.to_string())),
+        }
+    }
+
+    /// Returns execution reports for items logged by operations not requested through the adapter,
+    /// e.g. [expire_orders](OrderBook::expire_orders)
+    pub fn report(&mut self, book: &OrderBook, items: &[LogItem]) -> Vec<FixMessage> {
+        let mut reports = Vec::new();
+        self.apply(book, None, items, None, &mut reports);
+        reports
+    }
+
+    fn new_order(&mut self, book: &mut OrderBook, message: &FixMessage) -> Result<Vec<FixMessage>, FixError> {
+        let cl_ord_id = message.require(TAG_CL_ORD_ID)?.to_string();
+        if self.ids.contains_key(&cl_ord_id) {
+            return Err(FixError::DuplicateClOrdId(cl_ord_id));
+        }
+        let order = parse_order(message, &book.config().tick_size)?;
+        let mut logger = VectorLogger::new();
+        book.execute_order(order.clone(), &mut logger);
+        let order_id = book.last_order_id().expect("order id is assigned on execution");
+        self.ids.insert(cl_ord_id.clone(), order_id);
+        self.orders.insert(order_id, FixOrder {
+            cl_ord_id,
+            symbol: message.get(TAG_SYMBOL).map(str::to_string),
+            side: order.side,
+            order_qty: order.size,
+            state: OrderState::new(order.size),
+        });
+
+        let mut reports = Vec::new();
+        if !matches!(logger.as_slice().first(), Some(LogItem::Rejected { .. })) {
+            reports.push(self.execution_report(book, order_id, "0", None));
+        }
+        self.apply(book, Some(order_id), logger.as_slice(), None, &mut reports);
+        Ok(reports)
+    }
+
+    fn cancel(&mut self, book: &mut OrderBook, message: &FixMessage) -> Result<Vec<FixMessage>, FixError> {
+        let cl_ord_id = message.require(TAG_CL_ORD_ID)?;
+        let orig_cl_ord_id = message.require(TAG_ORIG_CL_ORD_ID)?;
+        let mut logger = VectorLogger::new();
+        let cancelled = self.ids.get(orig_cl_ord_id).map(|&order_id| (order_id, book.cancel_order(order_id, &mut logger)));
+        match cancelled {
+            Some((order_id, Ok(_))) => Ok(self.replace_id(book, order_id, cl_ord_id, orig_cl_ord_id, logger.as_slice())),
+            _ => Ok(vec![cancel_reject(cl_ord_id, orig_cl_ord_id, "1", None)]),
+        }
+    }
+
+    fn replace(&mut self, book: &mut OrderBook, message: &FixMessage) -> Result<Vec<FixMessage>, FixError> {
+        let cl_ord_id = message.require(TAG_CL_ORD_ID)?;
+        let orig_cl_ord_id = message.require(TAG_ORIG_CL_ORD_ID)?;
+        let order_id = match self.ids.get(orig_cl_ord_id) {
+            Some(&order_id) => order_id,
+            None => return Ok(vec![cancel_reject(cl_ord_id, orig_cl_ord_id, "2", None)]),
+        };
+        let order_qty: u64 = message.parse_field(TAG_ORDER_QTY)?;
+        let price = parse_price(message, TAG_PRICE, &book.config().tick_size)?;
+        let cum_qty = self.orders[&order_id].state.cum_qty();
+
+        let mut logger = VectorLogger::new();
+        match book.amend_order(order_id, price, order_qty.saturating_sub(cum_qty), &mut logger) {
+            Ok(()) => {
+                self.orders.get_mut(&order_id).expect("order is known").order_qty = order_qty;
+                Ok(self.replace_id(book, order_id, cl_ord_id, orig_cl_ord_id, logger.as_slice()))
+            }
+            Err(AmendError::Rejected(reason)) => Ok(vec![cancel_reject(cl_ord_id, orig_cl_ord_id, "2", Some(reason))]),
+            Err(AmendError::UnknownOrder(_)) => Ok(vec![cancel_reject(cl_ord_id, orig_cl_ord_id, "2", None)]),
+        }
+    }
+
+    /// Moves the order to the new `ClOrdID` and reports `items` of the cancel or replace request
+    fn replace_id(&mut self, book: &OrderBook, order_id: OrderId, cl_ord_id: &str, orig_cl_ord_id: &str, items: &[LogItem]) -> Vec<FixMessage> {
+        self.ids.remove(orig_cl_ord_id);
+        self.ids.insert(cl_ord_id.to_string(), order_id);
+        self.orders.get_mut(&order_id).expect("order is known").cl_ord_id = cl_ord_id.to_string();
+        let mut reports = Vec::new();
+        self.apply(book, Some(order_id), items, Some(orig_cl_ord_id), &mut reports);
+        reports
+    }
+
+    /// Applies `items` logged for the order `current` and reports every change
+    fn apply(&mut self, book: &OrderBook, mut current: Option<OrderId>, items: &[LogItem], orig_cl_ord_id: Option<&str>,
+             reports: &mut Vec<FixMessage>) {
+        for item in items {
+            let (order_id, exec_type) = match *item {
+                LogItem::Fulfilled { order_id: maker_id, .. } => {
+                    if let Some(maker) = self.orders.get_mut(&maker_id) {
+                        maker.state.apply(item);
+                        let report = self.execution_report(book, maker_id, "F", Some(item));
+                        reports.push(report);
+                    }
+                    match current {
+                        Some(order_id) => (order_id, "F"),
+                        None => continue,
+                    }
+                }
+                LogItem::Rejected { .. } => match current {
+                    Some(order_id) => (order_id, "8"),
+                    None => continue,
+                },
+                LogItem::Cancelled { .. } => match current {
+                    Some(order_id) => (order_id, "4"),
+                    None => continue,
+                },
+                LogItem::Amended { order_id, .. } => (order_id, "5"),
+                LogItem::ForceCancelled { order_id, .. } => (order_id, "4"),
+                LogItem::Expired { order_id, .. } => (order_id, "C"),
+                LogItem::Triggered { order_id } => {
+                    current = Some(order_id);
+                    continue;
+                }
+                LogItem::Enqueued { .. } | LogItem::Parked { .. } | LogItem::Refilled { .. } | LogItem::IntegrityError { .. }
+                | LogItem::StateChanged { .. } => continue,
+            };
+            let order = match self.orders.get_mut(&order_id) {
+                Some(order) => order,
+                None => continue,
+            };
+            order.state.apply(item);
+            let mut report = self.execution_report(book, order_id, exec_type, Some(item));
+            if let (Some(orig), "4") | (Some(orig), "5") = (orig_cl_ord_id, exec_type) {
+                report.push(TAG_ORIG_CL_ORD_ID, orig);
+            }
+            reports.push(report);
+        }
+    }
+
+    fn execution_report(&mut self, book: &OrderBook, order_id: OrderId, exec_type: &str, item: Option<&LogItem>) -> FixMessage {
+        let tick_size = &book.config().tick_size;
+        let order = &self.orders[&order_id];
+        self.exec_id += 1;
+        let mut report = FixMessage::new("8");
+        report.push(TAG_ORDER_ID, order_id)
+            .push(TAG_CL_ORD_ID, &order.cl_ord_id)
+            .push(TAG_EXEC_ID, self.exec_id)
+            .push(TAG_EXEC_TYPE, exec_type)
+            .push(TAG_ORD_STATUS, ord_status(order.state.status()));
+        if let Some(symbol) = &order.symbol {
+            report.push(TAG_SYMBOL, symbol);
+        }
+        report.push(TAG_SIDE, if order.side == OrderSide::Buy { "1" } else { "2" })
+            .push(TAG_ORDER_QTY, order.order_qty);
+        if let Some(&LogItem::Fulfilled { size, price, .. }) = item {
+            report.push(TAG_LAST_QTY, size).push(TAG_LAST_PX, tick_size.to_decimal(price));
+        }
+        let avg_price = order.state.avg_price().map_or(0.0, |ticks| ticks * tick_value(tick_size));
+        // Rejected orders keep their size open in the state, but nothing is working
+        let leaves_qty = if order.state.status().is_final() { 0 } else { order.state.leaves_qty() };
+        report.push(TAG_LEAVES_QTY, leaves_qty)
+            .push(TAG_CUM_QTY, order.state.cum_qty())
+            .push(TAG_AVG_PX, avg_price);
+        if let Some(&LogItem::Rejected { reason, .. }) = item {
+            report.push(TAG_TEXT, reason);
+        }
+        report
+    }
+}
+
+fn cancel_reject(cl_ord_id: &str, orig_cl_ord_id: &str, response_to: &str, reason: Option<RejectReason>) -> FixMessage {
+    let mut reject = FixMessage::new("9");
+    reject.push(TAG_ORDER_ID, "NONE")
+        .push(TAG_CL_ORD_ID, cl_ord_id)
+        .push(TAG_ORIG_CL_ORD_ID, orig_cl_ord_id)
+        .push(TAG_ORD_STATUS, "8")
+        .push(TAG_CXL_REJ_RESPONSE_TO, response_to);
+    match reason {
+        Some(reason) => reject.push(TAG_CXL_REJ_REASON, "99").push(TAG_TEXT, reason),
+        None => reject.push(TAG_CXL_REJ_REASON, "1"),
+    };
+    reject
+}
+
+fn ord_status(status: OrderStatus) -> &'static str {
+    match status {
+        OrderStatus::New => "0",
+        OrderStatus::PartiallyFilled => "1",
+        OrderStatus::Filled => "2",
+        OrderStatus::Cancelled => "4",
+        OrderStatus::Rejected => "8",
+        OrderStatus::Expired => "C",
+    }
+}
+
+fn tick_value(tick_size: &TickSize) -> f64 {
+    tick_size.to_decimal(1).parse().expect("tick size is a decimal number")
+}
+
+fn parse_price(message: &FixMessage, tag: u32, tick_size: &TickSize) -> Result<Price, FixError> {
+    tick_size.to_ticks(message.require(tag)?).map_err(|_| FixError::InvalidField(tag))
+}
+
+fn parse_order(message: &FixMessage, tick_size: &TickSize) -> Result<IncomingOrder, FixError> {
+    let side = match message.require(TAG_SIDE)? {
+        "1" => OrderSide::Buy,
+        "2" => OrderSide::Sell,
+        _ => return Err(FixError::InvalidField(TAG_SIDE)),
+    };
+    let limit = |kind| -> Result<OrderKind, FixError> {
+        if let Some(max_floor) = message.get(TAG_MAX_FLOOR) {
+            let display_size = max_floor.parse().map_err(|_| FixError::InvalidField(TAG_MAX_FLOOR))?;
+            return Ok(OrderKind::Iceberg { display_size });
+        }
+        if message.get(TAG_EXEC_INST).is_some_and(|inst| inst.split(' ').any(|i| i == "6")) {
+            return Ok(OrderKind::PostOnly);
+        }
+        Ok(kind)
+    };
+    let kind = match (message.require(TAG_ORD_TYPE)?, message.get(TAG_TIME_IN_FORCE).unwrap_or("0")) {
+        ("1", _) => OrderKind::Market,
+        ("2", "0") | ("2", "1") => limit(OrderKind::Limit)?,
+        ("2", "3") => OrderKind::ImmediateOrCancel,
+        ("2", "4") => OrderKind::FillOrKill,
+        ("2", _) => return Err(FixError::InvalidField(TAG_TIME_IN_FORCE)),
+        ("3", _) => OrderKind::Stop { trigger_price: parse_price(message, TAG_STOP_PX, tick_size)? },
+        ("4", _) => OrderKind::StopLimit { trigger_price: parse_price(message, TAG_STOP_PX, tick_size)? },
+        _ => return Err(FixError::InvalidField(TAG_ORD_TYPE)),
+    };
+    let price_limit = match kind {
+        OrderKind::Market | OrderKind::Stop { .. } | OrderKind::TrailingStop { .. } => 0,
+        _ => parse_price(message, TAG_PRICE, tick_size)?,
+    };
+    Ok(IncomingOrder {
+        price_limit,
+        size: message.parse_field(TAG_ORDER_QTY)?,
+        user_id: message.parse_field(TAG_ACCOUNT)?,
+        kind,
+        side,
+        expires_at: None,
+        min_fill_size: message.get(TAG_MIN_QTY).map(|_| message.parse_field(TAG_MIN_QTY)).transpose()?,
+        reduce_only: message.get(TAG_EXEC_INST).is_some_and(|inst| inst.split(' ').any(|i| i == "E")),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn message(msg_type: &str, fields: &[(u32, &str)]) -> FixMessage {
+        let mut message = FixMessage::new(msg_type);
+        for &(tag, value) in fields {
+            message.push(tag, value);
+        }
+        message
+    }
+
+    fn summary(reports: &[FixMessage]) -> Vec<String> {
+        reports.iter()
+            .map(|r| format!("{} {} {} {}", r.get(TAG_CL_ORD_ID).unwrap(), r.get(TAG_EXEC_TYPE).unwrap_or("-"),
+                             r.get(TAG_ORD_STATUS).unwrap(), r.get(TAG_LEAVES_QTY).unwrap_or("-")))
+            .collect()
+    }
+
+    #[test]
+    fn codec() {
+        let bytes = b"8=FIX.4.4\x019=17\x0135=D\x0111=a\x0155=XYZ\x0110=158\x01";
+        let message = FixMessage::parse(bytes).unwrap();
+        assert_eq!(message.to_string(), "35=D|11=a|55=XYZ|");
+        assert_eq!(message.encode(), bytes.to_vec());
+
+        let mut corrupted = bytes.to_vec();
+        corrupted[16] = b'E';
+        assert_eq!(FixMessage::parse(&corrupted), Err(FixError::BadChecksum));
+        let mut bad_length = FixMessage::new("D");
+        bad_length.push(11, "a");
+        let mut bytes = bad_length.encode();
+        bytes[12] = b'8';
+        bytes.truncate(bytes.len() - 7);
+        let checksum = sum(&bytes);
+        bytes.extend_from_slice(format!("10={:03}\x01", checksum).as_bytes());
+        assert_eq!(FixMessage::parse(&bytes), Err(FixError::BadBodyLength));
+        assert_eq!(FixMessage::parse(b"10=000\x01"), Err(FixError::Malformed));
+    }
+
+    #[test]
+    fn order_lifecycle() {
+        let mut book = OrderBook::new();
+        let mut adapter = FixAdapter::new();
+        let sell = message("D", &[(11, "s1"), (1, "1"), (55, "XYZ"), (54, "2"), (40, "2"), (44, "100"), (38, "5")]);
+        assert_eq!(summary(&adapter.handle(&mut book, &sell).unwrap()), ["s1 0 0 5"]);
+        assert_eq!(adapter.handle(&mut book, &sell), Err(FixError::DuplicateClOrdId("s1".to_string())));
+
+        let buy = message("D", &[(11, "b1"), (1, "2"), (54, "1"), (40, "2"), (59, "3"), (44, "100"), (38, "2")]);
+        let reports = adapter.handle(&mut book, &buy).unwrap();
+        assert_eq!(summary(&reports), ["b1 0 0 2", "s1 F 1 3", "b1 F 2 0"]);
+        assert_eq!((reports[1].get(TAG_LAST_PX), reports[1].get(TAG_AVG_PX)), (Some("100"), Some("100")));
+
+        let replace = message("G", &[(11, "s2"), (41, "s1"), (44, "101"), (38, "4")]);
+        assert_eq!(summary(&adapter.handle(&mut book, &replace).unwrap()), ["s2 5 1 2"]);
+        assert_eq!(book.inspect_order(1).unwrap().order.size, 2);
+
+        let cancel = message("F", &[(11, "s3"), (41, "s1")]);
+        let reports = adapter.handle(&mut book, &cancel).unwrap();
+        assert_eq!(reports[0].msg_type(), "9");
+        let cancel = message("F", &[(11, "s3"), (41, "s2")]);
+        let reports = adapter.handle(&mut book, &cancel).unwrap();
+        assert_eq!(summary(&reports), ["s3 4 4 0"]);
+        assert_eq!(reports[0].get(TAG_ORIG_CL_ORD_ID), Some("s2"));
+
+        let rejected = message("D", &[(11, "b2"), (1, "2"), (54, "1"), (40, "2"), (44, "-1"), (38, "2")]);
+        let reports = adapter.handle(&mut book, &rejected).unwrap();
+        assert_eq!(summary(&reports), ["b2 8 8 0"]);
+        assert_eq!(reports[0].get(TAG_TEXT), Some("negative-price"));
+        let rejected = message("D", &[(11, "b3"), (1, "2"), (54, "1"), (40, "2"), (44, "1"), (38, "2"), (110, "3")]);
+        let reports = adapter.handle(&mut book, &rejected).unwrap();
+        assert_eq!(reports[0].get(TAG_TEXT), Some("min-fill-too-large"));
+        assert_eq!(adapter.handle(&mut book, &FixMessage::new("V")), Err(FixError::UnsupportedMsgType("V".to_string())));
+    }
+}
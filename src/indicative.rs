@@ -0,0 +1,69 @@
+//! Indicative (non-firm) quotes
+//!
+//! Indicative quotes show interest without committing to trade. They are displayed as a separate
+//! depth layer and never match; the owner has to firm a quote up, which submits it to the book
+//! as a regular order under the identifier of the quote.
+
+use crate::order::{IncomingOrder, OrderId, OrderSide, Price, UserId};
+use crate::tick::{TickError, TickSize};
+use alloc::vec::Vec;
+
+/// Indicative quotes in the order of arrival
+#[derive(Debug, Clone, Default)]
+pub struct IndicativeQuotes(Vec<(OrderId, IncomingOrder)>);
+
+impl IndicativeQuotes {
+    pub(crate) fn insert(&mut self, order_id: OrderId, order: IncomingOrder) {
+        self.0.push((order_id, order));
+    }
+
+    /// Removes the quote `order_id` if it belongs to `user_id`
+    pub(crate) fn remove(&mut self, order_id: OrderId, user_id: UserId) -> Option<IncomingOrder> {
+        let index = self.0.iter().position(|(id, order)| *id == order_id && order.user_id == user_id)?;
+        Some(self.0.remove(index).1)
+    }
+
+    /// Converts prices of all quotes from tick size `from` to tick size `to`
+    pub(crate) fn rescale(&mut self, from: &TickSize, to: &TickSize) -> Result<(), TickError> {
+        for (_, order) in &mut self.0 {
+            order.price_limit = from.rescale(order.price_limit, to)?;
+        }
+        Ok(())
+    }
+
+    /// Returns the quote `order_id`
+    pub fn get(&self, order_id: OrderId) -> Option<&IncomingOrder> {
+        self.0.iter().find(|(id, _)| *id == order_id).map(|(_, order)| order)
+    }
+
+    /// Returns number of quotes
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    /// Checks whether there are no quotes
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Iterates over quotes in the order of arrival
+    pub fn iter(&self) -> impl Iterator<Item=(OrderId, &IncomingOrder)> {
+        self.0.iter().map(|(order_id, order)| (*order_id, order))
+    }
+
+    /// Returns total indicative size per price level of `side`, best price first
+    pub fn depth(&self, side: OrderSide) -> Vec<(Price, u64)> {
+        let mut levels: Vec<(Price, u64)> = Vec::new();
+        for (_, order) in self.0.iter().filter(|(_, order)| order.side == side) {
+            match levels.iter_mut().find(|(price, _)| *price == order.price_limit) {
+                Some(level) => level.1 = level.1.saturating_add(order.size),
+                None => levels.push((order.price_limit, order.size)),
+            }
+        }
+        match side {
+            OrderSide::Buy => levels.sort_by_key(|level| core::cmp::Reverse(level.0)),
+            OrderSide::Sell => levels.sort_by_key(|level| level.0),
+        }
+        levels
+    }
+}
@@ -0,0 +1,439 @@
+//! ITCH-like binary market data feed
+//!
+//! The feed converts execution logs into order-level events for downstream book builders.
+//! Only displayed liquidity is published: hidden reserves of iceberg orders appear as new
+//! [Add Order](ItchEvent::AddOrder) messages when they are refilled, while
+//! [hidden](crate::order::OrderKind::Hidden) orders and their executions are not published at all.
+//!
+//! Every message starts with a message type byte and a sequence number, contiguous within
+//! the stream. All integers are big-endian.
+//!
+//! | Type | Message         | Fields after the sequence number                     | Length |
+//! |------|-----------------|------------------------------------------------------|--------|
+//! | `A`  | Add Order       | order id u64, side (`B` or `S`), shares u64, price i64 | 34   |
+//! | `E`  | Order Executed  | order id u64, executed shares u64, match number u64  | 33     |
+//! | `X`  | Order Cancel    | order id u64, cancelled shares u64                   | 25     |
+//! | `D`  | Order Delete    | order id u64                                         | 17     |
+//!
+//! An order which is executed or cancelled down to zero displayed shares is removed from the book
+//! without an explicit Order Delete message.
+
+use crate::OrderBook;
+use crate::decode::DecodeError;
+use crate::log::{LogItem, VectorLogger};
+use crate::order::{IncomingOrder, OrderId, OrderKind, OrderSide, Price};
+use std::collections::HashMap;
+use std::convert::TryInto;
+use std::io::{self, Write};
+
+/// Order-level event of the feed
+#[derive(Debug, Clone, PartialEq)]
+#[allow(missing_docs)]
+pub enum ItchEvent {
+    /// Displayed order was added to the book
+    AddOrder { order_id: OrderId, side: OrderSide, shares: u64, price: Price },
+    /// Displayed order was executed
+    OrderExecuted { order_id: OrderId, shares: u64, match_number: u64 },
+    /// Displayed order was partially cancelled
+    OrderCancel { order_id: OrderId, shares: u64 },
+    /// Displayed order was removed from the book
+    OrderDelete { order_id: OrderId },
+}
+
+/// Feed message with its sequence number
+#[derive(Debug, Clone, PartialEq)]
+pub struct ItchMessage {
+    #[allow(missing_docs)]
+    pub sequence: u64,
+    #[allow(missing_docs)]
+    pub event: ItchEvent,
+}
+
+impl ItchMessage {
+    /// Appends binary representation of the message to `out`
+    pub fn encode(&self, out: &mut Vec<u8>) {
+        let (kind, order_id) = match self.event {
+            ItchEvent::AddOrder { order_id, .. } => (b'A', order_id),
+            ItchEvent::OrderExecuted { order_id, .. } => (b'E', order_id),
+            ItchEvent::OrderCancel { order_id, .. } => (b'X', order_id),
+            ItchEvent::OrderDelete { order_id } => (b'D', order_id),
+        };
+        out.push(kind);
+        out.extend_from_slice(&self.sequence.to_be_bytes());
+        out.extend_from_slice(&order_id.to_be_bytes());
+        match self.event {
+            ItchEvent::AddOrder { side, shares, price, .. } => {
+                out.push(side.as_str().as_bytes()[0]);
+                out.extend_from_slice(&shares.to_be_bytes());
+                out.extend_from_slice(&price.to_be_bytes());
+            }
+            ItchEvent::OrderExecuted { shares, match_number, .. } => {
+                out.extend_from_slice(&shares.to_be_bytes());
+                out.extend_from_slice(&match_number.to_be_bytes());
+            }
+            ItchEvent::OrderCancel { shares, .. } => out.extend_from_slice(&shares.to_be_bytes()),
+            ItchEvent::OrderDelete { .. } => {}
+        }
+    }
+
+    /// Decodes a message from the beginning of `bytes`
+    ///
+    /// Returns the message and the number of bytes consumed.
+    pub fn decode(bytes: &[u8]) -> Result<(Self, usize), DecodeError> {
+        let length = match bytes.first() {
+            Some(b'A') => 34,
+            Some(b'E') => 33,
+            Some(b'X') => 25,
+            Some(b'D') => 17,
+            Some(_) => return Err(DecodeError::InvalidField("type")),
+            None => return Err(DecodeError::InvalidLength),
+        };
+        if bytes.len() < length {
+            return Err(DecodeError::InvalidLength);
+        }
+        let u64_at = |offset: usize| u64::from_be_bytes(bytes[offset..offset + 8].try_into().unwrap());
+        let sequence = u64_at(1);
+        let order_id = u64_at(9);
+        let event = match bytes[0] {
+            b'A' => {
+                let side = match bytes[17] {
+                    b'B' => OrderSide::Buy,
+                    b'S' => OrderSide::Sell,
+                    _ => return Err(DecodeError::InvalidField("side")),
+                };
+                ItchEvent::AddOrder { order_id, side, shares: u64_at(18), price: u64_at(26) as Price }
+            }
+            b'E' => ItchEvent::OrderExecuted { order_id, shares: u64_at(17), match_number: u64_at(25) },
+            b'X' => ItchEvent::OrderCancel { order_id, shares: u64_at(17) },
+            _ => ItchEvent::OrderDelete { order_id },
+        };
+        Ok((ItchMessage { sequence, event }, length))
+    }
+
+    /// Decodes all messages of `bytes`
+    pub fn decode_all(mut bytes: &[u8]) -> Result<Vec<Self>, DecodeError> {
+        let mut messages = Vec::new();
+        while !bytes.is_empty() {
+            let (message, length) = Self::decode(bytes)?;
+            messages.push(message);
+            bytes = &bytes[length..];
+        }
+        Ok(messages)
+    }
+}
+
+/// Displayed order as known to the feed subscribers
+struct VisibleOrder {
+    side: OrderSide,
+    price: Price,
+    display_size: u64,
+    shown: u64,
+    total: u64,
+}
+
+/// Order the logged items refer to
+struct Subject {
+    order_id: OrderId,
+    side: OrderSide,
+    price: Price,
+    display_size: u64,
+}
+
+/// Writer of the ITCH-like feed
+///
+/// The feed must see every operation on the book from the start, otherwise it does not know
+/// the orders referred to by the logs.
+pub struct ItchFeed<W> {
+    writer: W,
+    seq: u64,
+    match_number: u64,
+    orders: HashMap<OrderId, VisibleOrder>,
+    buffer: Vec<u8>,
+}
+
+impl<W: Write> ItchFeed<W> {
+    /// Constructs feed writing to `writer`
+    pub fn new(writer: W) -> Self {
+        ItchFeed {
+            writer,
+            seq: 0,
+            match_number: 0,
+            orders: HashMap::new(),
+            buffer: Vec::new(),
+        }
+    }
+
+    /// Returns the sequence number of the last written message
+    pub fn last_seq(&self) -> u64 {
+        self.seq
+    }
+
+    /// Returns the underlying writer
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+
+    /// Executes `order` on `book` and publishes the results
+    pub fn execute(&mut self, book: &mut OrderBook, order: IncomingOrder) -> io::Result<()> {
+        let mut logger = VectorLogger::new();
+        book.execute_order(order.clone(), &mut logger);
+        let order_id = book.last_order_id().expect("order id is assigned on execution");
+        self.record(book, order_id, &order, logger.as_slice())
+    }
+
+    /// Cancels the order `order_id` on `book` and publishes the results
+    ///
+    /// Returns `false` if there is no such order.
+    pub fn cancel(&mut self, book: &mut OrderBook, order_id: OrderId) -> io::Result<bool> {
+        if book.cancel_order(order_id, &mut VectorLogger::new()).is_err() {
+            return Ok(false);
+        }
+        self.record_cancel(order_id)?;
+        Ok(true)
+    }
+
+    /// Publishes the execution results `items` of the new order `order_id`
+    pub fn record(&mut self, book: &OrderBook, order_id: OrderId, order: &IncomingOrder, items: &[LogItem]) -> io::Result<()> {
+        let display_size = match order.kind {
+            OrderKind::Iceberg { display_size } => display_size,
+            _ => 0,
+        };
+        let subject = Subject { order_id, side: order.side, price: order.price_limit, display_size };
+        self.process(book, Some(subject), items)
+    }
+
+    /// Publishes cancellation of the order `order_id` by its owner
+    pub fn record_cancel(&mut self, order_id: OrderId) -> io::Result<()> {
+        if self.orders.remove(&order_id).is_some() {
+            self.push(ItchEvent::OrderDelete { order_id });
+        }
+        self.flush()
+    }
+
+    /// Publishes the logged `items` of other operations on `book`
+    ///
+    /// These are amendments, expirations, cancellations by an administrator and auctions,
+    /// whose logs identify the affected orders.
+    pub fn record_items(&mut self, book: &OrderBook, items: &[LogItem]) -> io::Result<()> {
+        self.process(book, None, items)
+    }
+
+    fn process(&mut self, book: &OrderBook, mut subject: Option<Subject>, items: &[LogItem]) -> io::Result<()> {
+        for item in items {
+            match *item {
+                LogItem::Enqueued { size, .. } => {
+                    let subject = match &mut subject {
+                        Some(subject) => subject,
+                        None => continue,
+                    };
+                    // Post-only orders may rest at a price other than their limit
+                    let (side, price, display_size) = match book.inspect_order(subject.order_id) {
+                        Some(view) if view.order.kind == OrderKind::Hidden => continue,
+                        Some(view) => {
+                            let display_size = match view.order.kind {
+                                OrderKind::Iceberg { display_size } => display_size,
+                                _ => 0,
+                            };
+                            (view.order.side, view.order.price_limit, display_size)
+                        }
+                        None => (subject.side, subject.price, subject.display_size),
+                    };
+                    let shown = if display_size > 0 { std::cmp::min(display_size, size) } else { size };
+                    let order_id = subject.order_id;
+                    self.orders.insert(order_id, VisibleOrder { side, price, display_size, shown, total: size });
+                    self.push(ItchEvent::AddOrder { order_id, side, shares: shown, price });
+                }
+                LogItem::Fulfilled { size, order_id, .. } => {
+                    let order = match self.orders.get_mut(&order_id) {
+                        Some(order) => order,
+                        None => continue,
+                    };
+                    order.shown = order.shown.saturating_sub(size);
+                    order.total = order.total.saturating_sub(size);
+                    if order.total == 0 {
+                        self.orders.remove(&order_id);
+                    }
+                    self.match_number += 1;
+                    let match_number = self.match_number;
+                    self.push(ItchEvent::OrderExecuted { order_id, shares: size, match_number });
+                }
+                LogItem::Refilled { size, order_id } => {
+                    let order = match self.orders.get_mut(&order_id) {
+                        Some(order) => order,
+                        None => continue,
+                    };
+                    order.shown = size;
+                    let (side, price) = (order.side, order.price);
+                    self.push(ItchEvent::AddOrder { order_id, side, shares: size, price });
+                }
+                // Cancelled remainder of a new order never rested, cancellations of resting orders
+                // are published by record_cancel
+                LogItem::Cancelled { .. } => {}
+                LogItem::ForceCancelled { order_id, .. } | LogItem::Expired { order_id, .. } => {
+                    if self.orders.remove(&order_id).is_some() {
+                        self.push(ItchEvent::OrderDelete { order_id });
+                    }
+                }
+                LogItem::Amended { size, price, order_id } => {
+                    let order = match self.orders.get_mut(&order_id) {
+                        Some(order) => order,
+                        None => continue,
+                    };
+                    if price == order.price && size <= order.total && size > 0 {
+                        // Resizing keeps the time priority and reduces the hidden reserve first
+                        let shown = std::cmp::min(order.shown, size);
+                        let cancelled = order.shown - shown;
+                        order.shown = shown;
+                        order.total = size;
+                        if cancelled > 0 {
+                            self.push(ItchEvent::OrderCancel { order_id, shares: cancelled });
+                        }
+                    } else {
+                        let order = self.orders.remove(&order_id).expect("order is visible");
+                        self.push(ItchEvent::OrderDelete { order_id });
+                        subject = Some(Subject {
+                            order_id,
+                            side: order.side,
+                            price,
+                            display_size: order.display_size,
+                        });
+                    }
+                }
+                LogItem::Triggered { order_id } => {
+                    // Side and price of the triggered order are only known if it rests
+                    subject = book.inspect_order(order_id).map(|view| Subject {
+                        order_id,
+                        side: view.order.side,
+                        price: view.order.price_limit,
+                        display_size: 0,
+                    });
+                }
+                LogItem::Rejected { .. } | LogItem::Parked { .. } | LogItem::IntegrityError { .. } | LogItem::StateChanged { .. } => {}
+            }
+        }
+        self.flush()
+    }
+
+    fn push(&mut self, event: ItchEvent) {
+        self.seq += 1;
+        ItchMessage { sequence: self.seq, event }.encode(&mut self.buffer);
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        let result = self.writer.write_all(&self.buffer);
+        self.buffer.clear();
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::depth::PriceLevel;
+
+    /// Builds displayed depth from the feed like a downstream subscriber
+    fn build_depth(messages: &[ItchMessage]) -> (Vec<PriceLevel>, Vec<PriceLevel>) {
+        let mut orders: HashMap<OrderId, (OrderSide, Price, u64)> = HashMap::new();
+        for (index, message) in messages.iter().enumerate() {
+            assert_eq!(message.sequence, index as u64 + 1);
+            match message.event {
+                ItchEvent::AddOrder { order_id, side, shares, price } => {
+                    orders.insert(order_id, (side, price, shares));
+                }
+                ItchEvent::OrderExecuted { order_id, shares, .. } | ItchEvent::OrderCancel { order_id, shares } => {
+                    let order = orders.get_mut(&order_id).expect("order is known");
+                    order.2 -= shares;
+                    if order.2 == 0 {
+                        orders.remove(&order_id);
+                    }
+                }
+                ItchEvent::OrderDelete { order_id } => {
+                    orders.remove(&order_id).expect("order is known");
+                }
+            }
+        }
+        let levels = |side: OrderSide| {
+            let mut levels: Vec<PriceLevel> = Vec::new();
+            let mut prices: Vec<_> = orders.values().filter(|o| o.0 == side).collect();
+            prices.sort_by_key(|o| if side == OrderSide::Buy { -o.1 } else { o.1 });
+            for &(_, price, shares) in prices {
+                match levels.last_mut() {
+                    Some(level) if level.price == price => {
+                        level.size += shares;
+                        level.orders += 1;
+                    }
+                    _ => levels.push(PriceLevel { price, size: shares, orders: 1 }),
+                }
+            }
+            levels
+        };
+        (levels(OrderSide::Buy), levels(OrderSide::Sell))
+    }
+
+    fn check_depth(feed: &ItchFeed<Vec<u8>>, book: &OrderBook) {
+        let messages = ItchMessage::decode_all(&feed.writer).unwrap();
+        let depth = book.depth(usize::MAX);
+        assert_eq!(build_depth(&messages), (depth.bids, depth.asks));
+    }
+
+    #[test]
+    fn codec() {
+        let messages = [
+            ItchMessage { sequence: 1, event: ItchEvent::AddOrder { order_id: 7, side: OrderSide::Sell, shares: 5, price: -3 } },
+            ItchMessage { sequence: 2, event: ItchEvent::OrderExecuted { order_id: 7, shares: 2, match_number: 1 } },
+            ItchMessage { sequence: 3, event: ItchEvent::OrderCancel { order_id: 7, shares: 1 } },
+            ItchMessage { sequence: 4, event: ItchEvent::OrderDelete { order_id: 7 } },
+        ];
+        let mut bytes = Vec::new();
+        for message in &messages {
+            message.encode(&mut bytes);
+        }
+        assert_eq!(bytes.len(), 34 + 33 + 25 + 17);
+        assert_eq!(ItchMessage::decode_all(&bytes).unwrap(), messages);
+        assert_eq!(ItchMessage::decode(&bytes[..20]), Err(DecodeError::InvalidLength));
+        assert_eq!(ItchMessage::decode(b"Q"), Err(DecodeError::InvalidField("type")));
+    }
+
+    #[test]
+    fn feed_rebuilds_displayed_depth() {
+        let mut book = OrderBook::new();
+        let mut feed = ItchFeed::new(Vec::new());
+        let orders = [
+            "Lim S $101 #5 u1",
+            "Ice S $100 #10 u2 ^4",
+            "Lim B $98 #3 u3",
+            "Lim B $99 #2 u4",
+            "Lim B $100 #6 u5",
+            "IoC S $99 #1 u6",
+            "Lim B $101 #10 u7",
+        ];
+        for s in &orders {
+            feed.execute(&mut book, s.parse().unwrap()).unwrap();
+            check_depth(&feed, &book);
+        }
+
+        let mut logger = VectorLogger::new();
+        book.amend_order(3, 98, 1, &mut logger).unwrap();
+        feed.record_items(&book, logger.as_slice()).unwrap();
+        check_depth(&feed, &book);
+
+        let mut logger = VectorLogger::new();
+        book.amend_order(4, 97, 2, &mut logger).unwrap();
+        feed.record_items(&book, logger.as_slice()).unwrap();
+        check_depth(&feed, &book);
+
+        let mut logger = VectorLogger::new();
+        book.force_cancel(3, &mut logger).unwrap();
+        feed.record_items(&book, logger.as_slice()).unwrap();
+        check_depth(&feed, &book);
+
+        assert!(feed.cancel(&mut book, 7).unwrap());
+        assert!(!feed.cancel(&mut book, 7).unwrap());
+        check_depth(&feed, &book);
+
+        let messages = ItchMessage::decode_all(&feed.writer).unwrap();
+        assert_eq!(feed.last_seq(), messages.len() as u64);
+        assert_eq!(messages[1].event, ItchEvent::AddOrder { order_id: 2, side: OrderSide::Sell, shares: 4, price: 100 });
+        assert!(messages.iter().any(|m| m.event == ItchEvent::OrderCancel { order_id: 3, shares: 2 }));
+    }
+}
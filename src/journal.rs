@@ -0,0 +1,140 @@
+//! Journal of commands applied to order books
+//!
+//! A book executing commands with [execute_journaled](OrderBook::execute_journaled) records every
+//! command before it is applied. As execution is deterministic, [replay](OrderBook::replay) rebuilds
+//! the same book state, order identifiers included, from the journal.
+
+use crate::OrderBook;
+use crate::config::BookConfig;
+use crate::decode::Command;
+use crate::log::{DummyLogger, ExecutionLogger};
+use crate::wire::{self, FormatKind, WalWriter, WireError};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufWriter};
+use std::path::{Path, PathBuf};
+
+/// Storage of applied commands
+pub trait Journal {
+    /// Appends `command`, which must be durable when the call returns
+    fn record(&mut self, command: &Command) -> io::Result<()>;
+
+    /// Returns all recorded commands in the order of recording
+    fn commands(&self) -> Result<Vec<Command>, WireError>;
+}
+
+/// In-memory journal
+impl Journal for Vec<Command> {
+    fn record(&mut self, command: &Command) -> io::Result<()> {
+        self.push(command.clone());
+        Ok(())
+    }
+
+    fn commands(&self) -> Result<Vec<Command>, WireError> {
+        Ok(self.clone())
+    }
+}
+
+/// Journal stored in a [write-ahead log](crate::wire) file
+pub struct FileJournal {
+    path: PathBuf,
+    wal: WalWriter<BufWriter<File>>,
+}
+
+impl FileJournal {
+    /// Creates an empty journal at `path`, replacing an existing file
+    pub fn create(path: impl AsRef<Path>) -> io::Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let mut wal = WalWriter::new(BufWriter::new(File::create(&path)?))?;
+        wal.flush()?;
+        Ok(FileJournal { path, wal })
+    }
+
+    /// Opens the journal at `path` to append more commands
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, WireError> {
+        let path = path.as_ref().to_path_buf();
+        wire::read_header(&fs::read(&path).map_err(|e| WireError::Io(e.kind()))?, FormatKind::Wal)?;
+        let file = OpenOptions::new().append(true).open(&path).map_err(|e| WireError::Io(e.kind()))?;
+        Ok(FileJournal { path, wal: WalWriter::resume(BufWriter::new(file)) })
+    }
+
+    /// Returns path of the journal file
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Journal for FileJournal {
+    fn record(&mut self, command: &Command) -> io::Result<()> {
+        self.wal.append(command)?;
+        self.wal.flush()?;
+        self.wal.get_ref().get_ref().sync_data()
+    }
+
+    fn commands(&self) -> Result<Vec<Command>, WireError> {
+        wire::decode_wal(&fs::read(&self.path).map_err(|e| WireError::Io(e.kind()))?)
+    }
+}
+
+impl OrderBook {
+    /// Records `command` in `journal` and executes it like [execute_command](OrderBook::execute_command)
+    ///
+    /// The command is not executed if it cannot be recorded.
+    pub fn execute_journaled(&mut self, command: Command, journal: &mut impl Journal, logger: &mut impl ExecutionLogger) -> io::Result<()> {
+        journal.record(&command)?;
+        self.execute_command(command, logger);
+        Ok(())
+    }
+
+    /// Rebuilds a book with the default configuration from `journal`
+    pub fn replay(journal: &impl Journal) -> Result<Self, WireError> {
+        Self::replay_with_config(journal, BookConfig::default())
+    }
+
+    /// Rebuilds a book with the given configuration from `journal`
+    ///
+    /// The configuration must be the one the commands were executed with, or the state may differ.
+    pub fn replay_with_config(journal: &impl Journal, config: BookConfig) -> Result<Self, WireError> {
+        let mut book = OrderBook::with_config(config);
+        for command in journal.commands()? {
+            book.execute_command(command, &mut DummyLogger);
+        }
+        Ok(book)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::log::VectorLogger;
+
+    #[test]
+    fn replay_restores_book() {
+        let path = std::env::temp_dir().join(format!("matcher-journal-{}.wal", std::process::id()));
+        let mut journal = FileJournal::create(&path).unwrap();
+        let mut book = OrderBook::new();
+        let mut logger = VectorLogger::new();
+        let commands = vec![
+            Command::New("Lim B $99 #5 u1".parse().unwrap()),
+            Command::New("Lim S $101 #3 u2".parse().unwrap()),
+            Command::Amend { order_id: 1, price: 100, size: 4 },
+            Command::New("IoC S $100 #1 u3".parse().unwrap()),
+            Command::Cancel(2),
+        ];
+        let (first, rest) = commands.split_at(2);
+        for command in first {
+            book.execute_journaled(command.clone(), &mut journal, &mut logger).unwrap();
+        }
+        drop(journal);
+        let mut journal = FileJournal::open(&path).unwrap();
+        for command in rest {
+            book.execute_journaled(command.clone(), &mut journal, &mut logger).unwrap();
+        }
+
+        assert_eq!(journal.commands(), Ok(commands.clone()));
+        let replayed = OrderBook::replay(&journal).unwrap();
+        assert_eq!(replayed.to_vec(), book.to_vec());
+        assert_eq!(replayed.last_order_id(), book.last_order_id());
+        assert_eq!(OrderBook::replay(&commands).unwrap().to_vec(), book.to_vec());
+        fs::remove_file(journal.path()).unwrap();
+    }
+}
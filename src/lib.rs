@@ -1,735 +1,4221 @@
 //! This crate implements order matching for [IncomingOrders](order::IncomingOrder) against an [OrderBook](OrderBook).
+//!
+//! The crate is `no_std` compatible with the default `std` feature disabled; the book then only needs
+//! `alloc`, while file formats, I/O, threads and the shared hooks of the book are left out.
 
-use crate::queues::{ReversedVec, Queue};
-use crate::order::{OrderSide, Order, OrderKind, IncomingOrder, Direction, Buy, Sell, TaggedOrder};
-use crate::log::{ExecutionLogger, LogItem, DummyLogger};
-use std::fmt;
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
 
+#[macro_use]
+extern crate alloc;
+
+use crate::queues::{DefaultQueue, Queue};
+use crate::order::{OrderSide, Order, OrderKind, OrderId, Price, Size, UserId, IncomingOrder, Direction, Buy, Sell, TaggedOrder, RestingOrderView, QueuePosition, CancelledOrder, CancelError, AmendError};
+use crate::log::{ExecutionLogger, TransactionLogger, LogItem, DummyLogger, FillLogger, RecordingLogger, SequencingLogger, Trade};
+use crate::config::{BookConfig, BookState, CollarReference, ConfigChange, ConfigError, HaltPolicy, MatchingAlgorithm, PostOnlyPolicy, RejectReason};
+use crate::tick::{TickError, TickSize};
+use crate::indicative::IndicativeQuotes;
+use crate::depth::{BookStats, DepthSnapshot, Levels, PriceLevel, CHECKSUM_LEVELS};
+use crate::stop::StopQueue;
+use crate::peg::{Peg, PegStore};
+use crate::quote::{Quote, QuoteAction};
+use crate::summary::{ExecutionSummary, SummaryLogger};
+use crate::decode::Command;
+use crate::command_queue::{Prioritized, Priority};
+use crate::trail::{TrailStore, OrderTrail, TrailEvent};
+use crate::metrics::{MetricsTracker, UserMetrics};
+use crate::status::{StatusStore, OrderState, OrderStatus};
+#[cfg(feature = "std")]
+use crate::wire::WireError;
+use crate::clock::Clock;
+use crate::math::notional;
+use crate::risk::PositionProvider;
+#[cfg(feature = "std")]
+use crate::risk::RiskChecker;
+use crate::market_data::Bbo;
+#[cfg(feature = "std")]
+use crate::market_data::{BboUpdate, MarketDataListener};
+use crate::tape::TradeTape;
+use crate::auction::AuctionOrder;
+use alloc::collections::VecDeque;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::fmt;
+#[cfg(feature = "std")]
+use std::io::{self, Read, Write};
+#[cfg(feature = "std")]
+use std::sync::Mutex;
+
+#[cfg(feature = "std")]
+pub(crate) use std::collections::HashMap;
+// Without std there is no hasher with random keys
+#[cfg(not(feature = "std"))]
+pub(crate) use alloc::collections::BTreeMap as HashMap;
+
+#[cfg(feature = "async")]
+pub mod actor;
+pub mod auction;
+#[cfg(feature = "std")]
+pub mod bench;
+pub mod clock;
+pub mod command_queue;
+pub mod config;
+pub mod decode;
+pub mod depth;
+pub mod diff;
+#[cfg(feature = "std")]
+pub mod drop_copy;
+pub mod engine;
+pub mod event;
+#[cfg(feature = "fix")]
+pub mod fix;
+pub mod indicative;
+#[cfg(feature = "std")]
+pub mod itch;
+#[cfg(feature = "std")]
+pub mod journal;
 pub mod log;
+pub mod market_data;
+pub mod math;
+pub mod metrics;
+#[cfg(feature = "net")]
+pub mod net;
 pub mod order;
-mod queues;
+pub mod peg;
+#[cfg(feature = "std")]
+pub mod pipeline;
+pub mod quote;
+pub mod rfq;
+pub mod risk;
+#[cfg(feature = "std")]
+pub mod shared;
+pub mod surveillance;
+pub mod status;
+pub mod stop;
+pub mod summary;
+pub mod tape;
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
+pub mod tick;
+pub mod trail;
+mod audit;
+pub mod queues;
+pub mod verify;
+#[cfg(feature = "std")]
+pub mod wire;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+#[cfg(test)]
+mod model_check;
 
+#[cfg(feature = "std")]
+#[doc(hidden)]
+pub use crate::bench::create_orders;
 
 /// Represents underlying order queue
+///
+/// Orders are stored in a [DefaultQueue](queues::DefaultQueue) unless the book is constructed with
+/// [with_queues](OrderBook::with_queues).
 #[derive(Clone)]
-pub struct OrderQueue<D>(ReversedVec<D>);
+pub struct OrderQueue<D, Q = DefaultQueue<D>> {
+    orders: Q,
+    /// Price of the first order, kept up to date by every mutation
+    best: Option<Price>,
+    /// Prices of the orders, so that orders are found without scanning the whole queue
+    index: HashMap<OrderId, Price>,
+    /// Orders of every user, so that the limits per user are checked without scanning the whole queue
+    users: HashMap<UserId, Vec<OrderId>>,
+    /// Order which set the best price, as long as it is the first order
+    top: Option<OrderId>,
+    /// Scratch buffers of the matching, kept to reuse their allocations
+    retained: Vec<Order<D>>,
+    refilled: Vec<Order<D>>,
+    /// Whether a reduce-only order was ever inserted, so that queues without them are not searched for them
+    reduce_only: bool,
+}
+
+/// Adds the order `order_id` of the user `user_id` to the per-user index `users`
+fn index_user(users: &mut HashMap<UserId, Vec<OrderId>>, user_id: UserId, order_id: OrderId) {
+    users.entry(user_id).or_default().push(order_id);
+}
+
+/// Removes the order `order_id` of the user `user_id` from the per-user index `users`
+fn unindex_user(users: &mut HashMap<UserId, Vec<OrderId>>, user_id: UserId, order_id: OrderId) {
+    if let Some(orders) = users.get_mut(&user_id) {
+        orders.retain(|&id| id != order_id);
+        if orders.is_empty() {
+            users.remove(&user_id);
+        }
+    }
+}
+
+/// Logs the fill of `size` units of `passive_order` by `order`
+fn log_fill<D: Direction>(passive_order: &Order<D>, order: &Order<D::Other>, size: Size, trade_sequence: &mut u64,
+                          logger: &mut impl ExecutionLogger) {
+    logger.log(LogItem::Fulfilled {
+        size,
+        price: passive_order.price_limit,
+        user_id: passive_order.user_id,
+        order_id: passive_order.order_id,
+    });
+    *trade_sequence += 1;
+    logger.trade(Trade {
+        sequence: *trade_sequence,
+        maker_order_id: passive_order.order_id,
+        taker_order_id: order.order_id,
+        maker_user_id: passive_order.user_id,
+        taker_user_id: order.user_id,
+        taker_side: D::Other::SIDE,
+        price: passive_order.price_limit,
+        size,
+    });
+}
+
+/// Splits `size` among resting orders with `sizes` in proportion to them
+///
+/// Allocations smaller than `min_fill` are dropped; the rest of `size` is allocated in the order of `sizes`.
+fn allocate_pro_rata(sizes: &[Size], size: Size, min_fill: Size) -> Vec<Size> {
+    let total: u128 = sizes.iter().map(|&size| u128::from(size)).sum();
+    if u128::from(size) >= total {
+        return sizes.to_vec();
+    }
+    let mut allocations: Vec<Size> = sizes.iter().map(|&available| {
+        // Smaller than `available` because `size` is smaller than `total`
+        let share = (u128::from(available) * u128::from(size) / total) as u64;
+        if share < min_fill { 0 } else { share }
+    }).collect();
+    let mut remainder = size - allocations.iter().sum::<u64>();
+    for (allocation, &available) in allocations.iter_mut().zip(sizes) {
+        let extra = core::cmp::min(remainder, available - *allocation);
+        *allocation += extra;
+        remainder -= extra;
+    }
+    allocations
+}
 
-impl<D: Direction> OrderQueue<D> {
+impl<D: Direction, Q: Queue<Order<D>>> OrderQueue<D, Q> {
     fn new() -> Self {
-        Self(ReversedVec::new())
+        Self {
+            orders: Q::new(),
+            best: None,
+            index: HashMap::new(),
+            users: HashMap::new(),
+            top: None,
+            retained: Vec::new(),
+            refilled: Vec::new(),
+            reduce_only: false,
+        }
     }
 
-    fn match_order(&mut self, order: &mut Order<D::Other>, kind: OrderKind, logger: &mut impl ExecutionLogger) {
-        let initial_size = order.size;
-        let mut retained = Vec::new();
-        let mut drop_first = 0;
+    /// Creates a queue from `orders` given in priority order
+    #[cfg(feature = "std")]
+    fn restore(orders: Vec<Order<D>>) -> Self {
+        let mut queue = Self::new();
+        // Orders are pushed from the last one, which is cheap for the reversed vector
+        for order in orders.into_iter().rev() {
+            queue.index.insert(order.order_id, order.price_limit);
+            index_user(&mut queue.users, order.user_id, order.order_id);
+            queue.reduce_only |= order.reduce_only;
+            queue.orders.push_front(order);
+        }
+        queue.refresh_best();
+        queue
+    }
 
-        self.0.iterate(|passive_order, index| {
-            if !passive_order.price_matches(order) {
-                return false;
-            }
+    fn refresh_best(&mut self) {
+        let first = self.orders.iter().next();
+        self.best = first.map(|order| order.price_limit);
+        self.top = self.top.filter(|&top| first.is_some_and(|order| order.order_id == top));
+    }
 
+    /// Checks whether an order of the opposite side with `price_limit` can match the first order
+    fn crosses(&self, price_limit: Price) -> bool {
+        match (self.best, D::SIDE) {
+            (None, _) => false,
+            (Some(best), OrderSide::Buy) => price_limit <= best,
+            (Some(best), OrderSide::Sell) => price_limit >= best,
+        }
+    }
+
+    /// Returns the reduce-only orders `order` may reach which exceed the positions of their users,
+    /// with the sizes they may keep
+    ///
+    /// The position of a user is allowed to their orders in the order of the queue, up to the end of
+    /// the price level at which `order` would be filled.
+    fn reduce_only_excess(&self, order: &IncomingOrder, position: impl Fn(UserId) -> i128) -> Vec<(OrderId, Size)> {
+        let mut excess = Vec::new();
+        if !self.reduce_only {
+            return excess;
+        }
+        // Sizes of the reduce-only orders of every user already allowed
+        let mut allowed: Vec<(UserId, Size)> = Vec::new();
+        let (mut available, mut level): (Size, _) = (0, None);
+        for passive_order in self.orders.iter() {
+            if order.kind != OrderKind::Market && !self.crosses_at(passive_order.price_limit, order.price_limit) {
+                break;
+            }
+            if available >= order.size && level != Some(passive_order.price_limit) {
+                break;
+            }
+            level = Some(passive_order.price_limit);
             if passive_order.user_id == order.user_id {
-                retained.push(passive_order.clone());
-                drop_first = index + 1;
-                return true;
+                continue;
             }
+            let mut size = passive_order.total_size();
+            if passive_order.reduce_only {
+                let index = match allowed.iter().position(|&(user_id, _)| user_id == passive_order.user_id) {
+                    Some(index) => index,
+                    None => {
+                        allowed.push((passive_order.user_id, 0));
+                        allowed.len() - 1
+                    }
+                };
+                let used = allowed[index].1;
+                let total = risk::reduce_only_size(D::SIDE, used.saturating_add(size), position(passive_order.user_id)).unwrap_or(0);
+                if total.saturating_sub(used) < size {
+                    size = total.saturating_sub(used);
+                    excess.push((passive_order.order_id, size));
+                }
+                allowed[index].1 += size;
+            }
+            available = available.saturating_add(size);
+        }
+        excess
+    }
+
+    /// Checks whether an order of the opposite side with `price_limit` can match an order at `price`
+    fn crosses_at(&self, price: Price, price_limit: Price) -> bool {
+        match D::SIDE {
+            OrderSide::Buy => price_limit <= price,
+            OrderSide::Sell => price_limit >= price,
+        }
+    }
+
+    /// Matches `order` against the queue and returns the price of the last fill
+    ///
+    /// Iceberg orders whose displayed part is filled are refilled and moved to the end of their price level,
+    /// where `order` can reach them again in the next pass. [Hidden](OrderKind::Hidden) orders are only
+    /// matched once the displayed orders of their price level are filled. Nothing is matched unless at least
+    /// `min_fill_size` can be filled.
+    fn match_order(&mut self, order: &mut Order<D::Other>, kind: OrderKind, min_fill_size: Size, config: &BookConfig,
+                   trade_sequence: &mut u64, logger: &mut impl ExecutionLogger) -> Option<Price> {
+        // Refilled orders are moved during matching, so fill-or-kill and minimum-quantity orders are checked up front
+        let required = if kind == OrderKind::FillOrKill { order.size } else { min_fill_size };
+        if required > 0 && self.fillable_size(order, kind) < required {
+            return None;
+        }
+
+        let last_price = match config.matching {
+            MatchingAlgorithm::Fifo => self.match_fifo(order, kind, trade_sequence, logger),
+            MatchingAlgorithm::ProRata { min_fill } => {
+                self.match_pro_rata(order, kind, min_fill, config.top_priority, trade_sequence, logger)
+            }
+        };
+        self.refresh_best();
+        last_price
+    }
 
-            let size = std::cmp::min(order.size, passive_order.size);
-            order.size -= size;
+    fn match_fifo(&mut self, order: &mut Order<D::Other>, kind: OrderKind, trade_sequence: &mut u64, logger: &mut impl ExecutionLogger) -> Option<Price> {
+        let mut last_price = None;
+        // Price level whose displayed orders are filled, so that its hidden orders are matched
+        let mut dark_level = None;
+        loop {
+            let retained = &mut self.retained;
+            let refilled = &mut self.refilled;
+            let mut drop_first = 0;
+            let order_index = &mut self.index;
+            let user_index = &mut self.users;
+            // Price level of the skipped hidden orders, the pass ends with the level
+            let mut skipped = None;
 
-            logger.log(LogItem::Fulfilled {
-                size,
-                price: passive_order.price_limit,
-                user_id: passive_order.user_id,
+            self.orders.iterate(|passive_order, index| {
+                if kind != OrderKind::Market && !passive_order.price_matches(order) {
+                    return false;
+                }
+                if skipped.is_some_and(|price| price != passive_order.price_limit) {
+                    return false;
+                }
+
+                if passive_order.user_id == order.user_id {
+                    retained.push(passive_order.clone());
+                    drop_first = index + 1;
+                    return true;
+                }
+                if passive_order.dark && dark_level != Some(passive_order.price_limit) {
+                    retained.push(passive_order.clone());
+                    drop_first = index + 1;
+                    skipped = Some(passive_order.price_limit);
+                    return true;
+                }
+
+                let size = core::cmp::min(order.size, passive_order.size);
+                order.size -= size;
+                last_price = Some(passive_order.price_limit);
+                log_fill(passive_order, order, size, trade_sequence, logger);
+
+                if passive_order.size == size {
+                    drop_first = index + 1;
+                    order_index.remove(&passive_order.order_id);
+                    unindex_user(user_index, passive_order.user_id, passive_order.order_id);
+                    if passive_order.hidden > 0 {
+                        let mut refill = passive_order.clone();
+                        refill.size = 0;
+                        refill.refill();
+                        refilled.push(refill);
+                    }
+                } else {
+                    drop_first = index;
+                }
+
+                if order.size == 0 {
+                    passive_order.size -= size;
+                    return false;
+                }
+                true
             });
 
-            if passive_order.size == size {
-                drop_first = index + 1;
-            } else {
-                drop_first = index;
+            if drop_first > 0 {
+                self.orders.drop_first_n(drop_first);
+            }
+            for order in self.retained.drain(..).rev() {
+                self.orders.push_front(order);
+            }
+            let done = order.size == 0 || (self.refilled.is_empty() && skipped.is_none());
+            // Refilled orders are displayed, so they are matched before the hidden ones
+            if self.refilled.is_empty() {
+                dark_level = skipped;
             }
+            let mut refilled = core::mem::take(&mut self.refilled);
+            for refill in refilled.drain(..) {
+                logger.log(LogItem::Refilled { size: refill.size, order_id: refill.order_id });
+                self.insert(refill);
+            }
+            self.refilled = refilled;
+            if done {
+                break;
+            }
+        }
+        last_price
+    }
 
-            if order.size == 0 {
-                passive_order.size -= size;
-                return false;
+    /// Matches `order` level by level, allocating every level in proportion to the displayed sizes
+    ///
+    /// Orders of the user of `order` are skipped, levels made only of them are passed through.
+    /// With `top_priority` the top order is filled before the rest of its level is allocated.
+    fn match_pro_rata(&mut self, order: &mut Order<D::Other>, kind: OrderKind, min_fill: Size, top_priority: bool,
+                      trade_sequence: &mut u64, logger: &mut impl ExecutionLogger) -> Option<Price> {
+        let mut last_price = None;
+        loop {
+            // Sizes available to `order` at the first level where it can trade, starting at `start`
+            let mut level = None;
+            let mut start = 0;
+            let mut sizes = Vec::new();
+            // Sizes of the hidden orders of the level, allocated once the displayed orders are filled
+            let mut dark_sizes = Vec::new();
+            for (index, passive_order) in self.orders.iter().enumerate() {
+                if kind != OrderKind::Market && !passive_order.price_matches(order) {
+                    break;
+                }
+                if level != Some(passive_order.price_limit) {
+                    if sizes.iter().chain(&dark_sizes).any(|&size| size > 0) {
+                        break;
+                    }
+                    level = Some(passive_order.price_limit);
+                    start = index;
+                    sizes.clear();
+                    dark_sizes.clear();
+                }
+                let size = if passive_order.user_id == order.user_id { 0 } else { passive_order.size };
+                let (displayed, dark) = if passive_order.dark { (0, size) } else { (size, 0) };
+                sizes.push(displayed);
+                dark_sizes.push(dark);
+            }
+            if !sizes.iter().any(|&size| size > 0) {
+                sizes = dark_sizes;
+            }
+            if order.size == 0 || !sizes.iter().any(|&size| size > 0) {
+                break;
             }
-            true
-        });
 
-        if kind == OrderKind::FillOrKill && order.size != 0 {
-            // Cancel order
-            logger.cancel();
-            order.size = initial_size;
-            return;
-        }
+            // The top order is always the first one
+            let top_fill = if top_priority && start == 0 && self.top.is_some() { core::cmp::min(sizes[0], order.size) } else { 0 };
+            sizes[0] -= top_fill;
+            let mut allocations = allocate_pro_rata(&sizes, order.size - top_fill, min_fill);
+            allocations[0] += top_fill;
+            let mut filled = Vec::new();
+            self.orders.iterate(|passive_order, index| {
+                let allocation = match index.checked_sub(start).and_then(|i| allocations.get(i)) {
+                    Some(&allocation) => allocation,
+                    None => return index < start,
+                };
+                if allocation > 0 {
+                    passive_order.size -= allocation;
+                    order.size -= allocation;
+                    last_price = Some(passive_order.price_limit);
+                    log_fill(passive_order, order, allocation, trade_sequence, logger);
+                    if passive_order.size == 0 {
+                        filled.push(passive_order.clone());
+                    }
+                }
+                true
+            });
 
-        if drop_first > 0 {
-            self.0.drop_first_n(drop_first);
+            for passive_order in filled {
+                self.remove(passive_order.order_id);
+                if passive_order.hidden > 0 {
+                    let mut refill = passive_order;
+                    refill.refill();
+                    logger.log(LogItem::Refilled { size: refill.size, order_id: refill.order_id });
+                    self.insert(refill);
+                }
+            }
         }
-        for order in retained.into_iter().rev() {
-            self.0.push_front(order);
+        last_price
+    }
+
+    /// Returns the size available to `order` including hidden reserves, up to the size of `order`
+    fn fillable_size(&self, order: &Order<D::Other>, kind: OrderKind) -> Size {
+        let mut available = 0u64;
+        for passive_order in self.orders.iter() {
+            if available >= order.size || (kind != OrderKind::Market && !passive_order.price_matches(order)) {
+                break;
+            }
+            if passive_order.user_id != order.user_id {
+                available = available.saturating_add(passive_order.total_size());
+            }
         }
+        available
     }
 
-    fn insert(&mut self, order: Order<D>) {
+    fn insert(&mut self, mut order: Order<D>) {
+        order.hide_reserve();
+        // The order goes to the end of its price level
+        let price = order.price_limit;
+        let improves = match (self.best, D::SIDE) {
+            (None, _) => true,
+            (Some(best), OrderSide::Buy) => price > best,
+            (Some(best), OrderSide::Sell) => price < best,
+        };
+        if improves {
+            self.top = Some(order.order_id);
+        }
+        self.index.insert(order.order_id, price);
+        index_user(&mut self.users, order.user_id, order.order_id);
+        self.reduce_only |= order.reduce_only;
         match D::SIDE {
-            OrderSide::Buy => {
-                let index = self.0.insert_position(|o| o.price_limit < order.price_limit);
-                if let Some(index) = index {
-                    self.0.insert_at(index, order);
-                } else {
-                    self.0.push_back(order);
+            OrderSide::Buy => self.orders.insert_before(order, |o| o.price_limit < price),
+            OrderSide::Sell => self.orders.insert_before(order, |o| o.price_limit > price),
+        }
+        self.refresh_best();
+    }
+
+    fn find(&self, order_id: OrderId) -> Option<RestingOrderView> {
+        let price = *self.index.get(&order_id)?;
+        let position = self.orders.position_at_price(price, |o| o.order_id == order_id)?;
+        let order = self.orders.iter().nth(position)?;
+        Some(RestingOrderView {
+            order_id,
+            order: order.to_incoming(),
+            position,
+            entered_at: order.entered_at,
+        })
+    }
+
+    fn queue_position(&self, order_id: OrderId) -> Option<QueuePosition> {
+        let target = self.get(order_id)?;
+        let (price, dark) = (target.price_limit, target.dark);
+        let mut position = QueuePosition::default();
+        let mut behind = false;
+        for order in self.orders.iter() {
+            if order.order_id == order_id {
+                behind = true;
+            } else if order.price_limit != price {
+                if behind {
+                    break;
                 }
-            }
-            OrderSide::Sell => {
-                let index = self.0.insert_position(|o| o.price_limit > order.price_limit);
-                if let Some(index) = index {
-                    self.0.insert_at(index, order);
-                } else {
-                    self.0.push_back(order);
+                position.better_size = position.better_size.saturating_add(order.total_size());
+                position.orders_ahead += 1;
+            } else {
+                let ahead = if order.dark { dark && !behind } else { dark || !behind };
+                if ahead {
+                    position.level_size = position.level_size.saturating_add(order.size);
+                    position.orders_ahead += 1;
                 }
             }
         }
+        Some(position)
+    }
+
+    /// Returns the order `order_id` without computing its position
+    fn get(&self, order_id: OrderId) -> Option<&Order<D>> {
+        let price = *self.index.get(&order_id)?;
+        self.orders.find_order(price, order_id)
+    }
+
+    fn remove(&mut self, order_id: OrderId) -> Option<IncomingOrder> {
+        let price = *self.index.get(&order_id)?;
+        let order = self.orders.remove_order(price, order_id)?;
+        self.index.remove(&order_id);
+        unindex_user(&mut self.users, order.user_id, order_id);
+        if self.best == Some(price) {
+            self.refresh_best();
+        }
+        Some(order.to_incoming())
+    }
+
+    /// Returns identifiers of the orders which expire by `now`
+    fn expired(&self, now: u64) -> Vec<OrderId> {
+        self.orders.iter()
+            .filter(|order| order.expires_at.is_some_and(|expires_at| expires_at <= now))
+            .map(|order| order.order_id)
+            .collect()
+    }
+
+    /// Sets total size of the order `order_id` keeping its position, removes the order if `size` is zero
+    fn resize(&mut self, order_id: OrderId, size: Size) {
+        if size == 0 {
+            self.remove(order_id);
+            return;
+        }
+        if let Some(&price) = self.index.get(&order_id) {
+            self.orders.update_order(price, order_id, |order| order.set_total_size(size));
+        }
+    }
+
+    /// Removes `size` units from the orders at the front of the queue
+    ///
+    /// Hidden reserves of iceberg orders are consumed too, the orders keep their positions.
+    fn consume_front(&mut self, mut size: Size) {
+        let mut drop_first = 0;
+        let order_index = &mut self.index;
+        let user_index = &mut self.users;
+        self.orders.iterate(|order, index| {
+            let fill = core::cmp::min(size, order.total_size());
+            order.consume(fill);
+            size -= fill;
+            if order.size == 0 {
+                drop_first = index + 1;
+                order_index.remove(&order.order_id);
+                unindex_user(user_index, order.user_id, order.order_id);
+            }
+            size > 0
+        });
+        if drop_first > 0 {
+            self.orders.drop_first_n(drop_first);
+        }
+        self.refresh_best();
+    }
+
+    /// Converts prices of all orders from tick size `from` to tick size `to`
+    ///
+    /// The queue is left unchanged if any price cannot be converted exactly.
+    fn rescale(&mut self, from: &TickSize, to: &TickSize) -> Result<(), TickError> {
+        let prices = self.orders.iter()
+            .map(|order| from.rescale(order.price_limit, to))
+            .collect::<Result<Vec<_>, _>>()?;
+        let mut prices = prices.into_iter();
+        self.orders.iterate(|order, _| {
+            order.price_limit = prices.next().expect("price is converted for every order");
+            true
+        });
+        self.index = self.orders.iter().map(|order| (order.order_id, order.price_limit)).collect();
+        self.refresh_best();
+        Ok(())
+    }
+
+    /// Returns identifiers of the resting orders of the user `user_id`, in the order of insertion
+    pub fn user_orders(&self, user_id: UserId) -> &[OrderId] {
+        self.users.get(&user_id).map_or(&[], Vec::as_slice)
+    }
+
+    /// Returns total notional of the resting orders of the user `user_id`, including hidden reserves
+    ///
+    /// Notional of orders at negative prices is counted by its absolute value.
+    pub fn user_notional(&self, user_id: UserId) -> u128 {
+        self.user_orders(user_id).iter()
+            .filter_map(|&order_id| self.get(order_id))
+            .map(|order| notional(order.price_limit, order.total_size()).unsigned_abs())
+            .fold(0, u128::saturating_add)
+    }
+
+    /// Returns price of the first (best) order
+    pub fn best_price(&self) -> Option<Price> {
+        self.best
+    }
+
+    /// Returns the top order, which set the best price by improving on the previous one
+    ///
+    /// The order loses the status once it is no longer the first order, e.g. when it is filled or
+    /// cancelled. Levels reached after the better ones are consumed have no top order.
+    pub fn top_order(&self) -> Option<OrderId> {
+        self.top
+    }
+
+    /// Returns the first (best) displayed price level
+    pub fn best_level(&self) -> Option<PriceLevel> {
+        self.levels(1).pop()
+    }
+
+    /// Returns up to `max_levels` displayed price levels, best price first
+    ///
+    /// [Hidden](OrderKind::Hidden) orders are left out.
+    pub fn levels(&self, max_levels: usize) -> Vec<PriceLevel> {
+        let mut levels: Vec<PriceLevel> = Vec::new();
+        for order in self.orders.iter().filter(|order| !order.dark) {
+            if let Some(level) = levels.last_mut().filter(|level| level.price == order.price_limit) {
+                level.size = level.size.saturating_add(order.size);
+                level.orders += 1;
+            } else if levels.len() == max_levels {
+                break;
+            } else {
+                levels.push(PriceLevel { price: order.price_limit, size: order.size, orders: 1 });
+            }
+        }
+        levels
     }
 
     /// Returns queue length
     pub fn len(&self) -> usize {
-        self.0.len()
+        self.orders.len()
+    }
+
+    /// Checks whether the queue is empty
+    pub fn is_empty(&self) -> bool {
+        self.orders.len() == 0
+    }
+
+    /// Returns total size of the orders in the queue, including hidden reserves of iceberg orders
+    pub fn total_size(&self) -> Size {
+        self.orders.iter().fold(0, |total, order| total.saturating_add(order.total_size()))
     }
 }
 
-impl<'a, D: 'a+Direction> IntoIterator for &'a OrderQueue<D> {
-    type Item = <&'a ReversedVec<D> as IntoIterator>::Item;
-    type IntoIter = <&'a ReversedVec<D> as IntoIterator>::IntoIter;
+impl<'a, D: 'a+Direction, Q: Queue<Order<D>>> IntoIterator for &'a OrderQueue<D, Q> {
+    type Item = &'a Order<D>;
+    type IntoIter = Q::Iter<'a>;
 
     fn into_iter(self) -> Self::IntoIter {
-        self.0.into_iter()
+        self.orders.iter()
     }
 }
 
 
+/// Options of [execute_batch](OrderBook::execute_batch)
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct BatchOptions {
+    /// Execute cancels and amends before new orders
+    pub cancels_first: bool,
+    /// Execute either all commands or none of them
+    pub atomic: bool,
+}
+
+/// Rejection which rolled back an [atomic](BatchOptions::atomic) batch or a [group](OrderBook::execute_group)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BatchError {
+    /// Position of the rejected command or order in the execution order
+    pub index: usize,
+    #[allow(missing_docs)]
+    pub reason: RejectReason,
+}
+
+impl fmt::Display for BatchError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "command {} rejected: {}", self.index, self.reason)
+    }
+}
+
 /// Represents order book
+///
+/// The orders of each side are kept in a [Queue](queues::Queue), see [with_queues](OrderBook::with_queues).
 #[derive(Clone)]
-pub struct OrderBook {
-    bid: OrderQueue<Buy>,
-    ask: OrderQueue<Sell>,
+pub struct OrderBook<QB = DefaultQueue<Buy>, QA = DefaultQueue<Sell>> {
+    bid: OrderQueue<Buy, QB>,
+    ask: OrderQueue<Sell, QA>,
+    config: BookConfig,
+    next_order_id: OrderId,
+    audit: bool,
+    trail: Option<TrailStore>,
+    metrics: Option<MetricsTracker>,
+    statuses: Option<StatusStore>,
+    indicative: IndicativeQuotes,
+    stops: StopQueue,
+    pegs: PegStore,
+    last_trade_price: Option<Price>,
+    /// Sequence number of the last trade
+    trade_sequence: u64,
+    /// Sequence number of the last log item
+    log_sequence: u64,
+    /// Whether an operation is being executed with a sequencing logger
+    sequencing: bool,
+    clock: Option<Arc<dyn Clock + Send + Sync>>,
+    #[cfg(feature = "std")]
+    risk: Option<Arc<Mutex<dyn RiskChecker + Send>>>,
+    /// Whether fills are held from the risk checker until the transaction is committed
+    #[cfg(feature = "std")]
+    holding_fills: bool,
+    positions: Option<Arc<dyn PositionProvider + Send + Sync>>,
+    tape: Option<TradeTape>,
+    #[cfg(feature = "std")]
+    listener: Option<Arc<Mutex<dyn MarketDataListener + Send>>>,
+    /// Best bid and ask the listener was last notified of
+    #[cfg(feature = "std")]
+    bbo: Bbo,
+    config_changes: Vec<ConfigChange>,
+    recorded: Vec<LogItem>,
+    state: BookState,
+    /// Orders parked while trading is not open, uncrossed when it reopens
+    auction_orders: Vec<AuctionOrder>,
+    /// Times and prices of the trades observed by the circuit breaker
+    price_window: VecDeque<(u64, Price)>,
 }
 
 impl OrderBook {
     /// Constructs an empty `OrderBook`
     pub fn new() -> Self {
+        Self::with_config(BookConfig::default())
+    }
+
+    /// Constructs an empty `OrderBook` with the given configuration
+    pub fn with_config(config: BookConfig) -> Self {
+        Self::with_queues_and_config(config)
+    }
+
+    /// Constructs an empty `OrderBook` keeping the bids in `QB` and the asks in `QA`
+    ///
+    /// Snapshots, journals and the serde support are only available for the [default
+    /// queues](queues::DefaultQueue).
+    pub fn with_queues<QB: Queue<Order<Buy>>, QA: Queue<Order<Sell>>>() -> OrderBook<QB, QA> {
+        Self::with_queues_and_config(BookConfig::default())
+    }
+
+    /// Constructs an empty `OrderBook` with the given queues and configuration
+    pub fn with_queues_and_config<QB: Queue<Order<Buy>>, QA: Queue<Order<Sell>>>(config: BookConfig) -> OrderBook<QB, QA> {
         OrderBook {
             bid: OrderQueue::new(),
             ask: OrderQueue::new(),
+            config,
+            next_order_id: 1,
+            audit: false,
+            trail: None,
+            metrics: None,
+            statuses: None,
+            indicative: IndicativeQuotes::default(),
+            stops: StopQueue::default(),
+            pegs: PegStore::default(),
+            last_trade_price: None,
+            trade_sequence: 0,
+            log_sequence: 0,
+            sequencing: false,
+            clock: None,
+            #[cfg(feature = "std")]
+            risk: None,
+            #[cfg(feature = "std")]
+            holding_fills: false,
+            positions: None,
+            tape: None,
+            #[cfg(feature = "std")]
+            listener: None,
+            #[cfg(feature = "std")]
+            bbo: Bbo::default(),
+            config_changes: Vec::new(),
+            recorded: Vec::new(),
+            state: BookState::Open,
+            auction_orders: Vec::new(),
+            price_window: VecDeque::new(),
         }
     }
+}
 
-    /// Returns a reference to the `bid` queue
-    pub fn bid(&self) -> &OrderQueue<Buy> {
-        &self.bid
-    }
-
-    /// Returns a reference to the `ask` queue
-    pub fn ask(&self) -> &OrderQueue<Sell> {
-        &self.ask
+impl<QB: Queue<Order<Buy>>, QA: Queue<Order<Sell>>> OrderBook<QB, QA> {
+    /// Returns book configuration
+    pub fn config(&self) -> &BookConfig {
+        &self.config
     }
 
-    /// Executes `order`
+    /// Replaces book configuration without touching the resting orders
     ///
-    /// Execution results will be logged with `logger`. Previous state of the logger may be lost.
-    pub fn execute_order(&mut self, order: IncomingOrder, logger: &mut impl ExecutionLogger) {
-        let kind = order.kind;
-        let mut order = TaggedOrder::from(order);
-
-        match order {
-            TaggedOrder::Buy(ref mut order) => self.ask.match_order(order, kind, logger),
-            TaggedOrder::Sell(ref mut order) => self.bid.match_order(order, kind, logger),
+    /// If the tick size changes (e.g. on a re-denomination), prices of the resting orders, stop orders, pegs and
+    /// indicative quotes are converted to the new tick size; the update fails and nothing is changed if any of
+    /// them is not a multiple of the new tick size. The size unit can only change while the book has
+    /// no orders. Other parameters only apply to orders submitted after the change. Returns the recorded
+    /// change event.
+    pub fn update_config(&mut self, config: BookConfig) -> Result<&ConfigChange, ConfigError> {
+        let has_orders = !self.bid.is_empty() || !self.ask.is_empty() || !self.stops.is_empty()
+            || !self.indicative.is_empty() || !self.auction_orders.is_empty();
+        if config.size_unit != self.config.size_unit && has_orders {
+            return Err(ConfigError::SizeUnit);
         }
-
-        let size = order.size();
-        if size > 0 {
-            match kind {
-                OrderKind::Limit => {
-                    logger.log(LogItem::Enqueued {
-                        size
-                    });
-                    match order {
-                        TaggedOrder::Buy(order) => self.bid.insert(order),
-                        TaggedOrder::Sell(order) => self.ask.insert(order),
-                    }
-                },
-                OrderKind::FillOrKill => {
-                    logger.log(LogItem::Cancelled {
-                        size
-                    });
-                },
-                OrderKind::ImmediateOrCancel => {
-                    logger.log(LogItem::Cancelled {
-                        size
-                    });
-                },
-            }
+        let (from, to) = (self.config.tick_size, config.tick_size);
+        if from != to {
+            let mut bid = self.bid.clone();
+            let mut ask = self.ask.clone();
+            let mut indicative = self.indicative.clone();
+            let mut stops = self.stops.clone();
+            let mut pegs = self.pegs.clone();
+            bid.rescale(&from, &to).map_err(ConfigError::TickSize)?;
+            ask.rescale(&from, &to).map_err(ConfigError::TickSize)?;
+            indicative.rescale(&from, &to).map_err(ConfigError::TickSize)?;
+            stops.rescale(&from, &to).map_err(ConfigError::TickSize)?;
+            pegs.rescale(&from, &to).map_err(ConfigError::TickSize)?;
+            let last_trade_price = self.last_trade_price.map(|price| from.rescale(price, &to)).transpose()
+                .map_err(ConfigError::TickSize)?;
+            self.bid = bid;
+            self.ask = ask;
+            self.indicative = indicative;
+            self.stops = stops;
+            self.pegs = pegs;
+            self.last_trade_price = last_trade_price;
         }
+        let previous = core::mem::replace(&mut self.config, config);
+        self.config_changes.push(ConfigChange {
+            version: self.config_changes.len() as u64 + 1,
+            previous,
+            current: self.config.clone(),
+        });
+        Ok(self.config_changes.last().expect("change was just recorded"))
     }
 
-    /// Returns a vector of [IncomingOrders](IncomingOrder) reflecting the current state of `OrderBook`
-    pub fn to_vec(&self) -> Vec<IncomingOrder> {
-        let mut orders = Vec::new();
-        for order in (&self.bid).into_iter().rev() {
-            orders.push(order.to_incoming());
-        }
-        for order in &self.ask {
-            orders.push(order.to_incoming());
-        }
-        orders
+    /// Returns configuration changes applied since construction, oldest first
+    pub fn config_changes(&self) -> &[ConfigChange] {
+        &self.config_changes
     }
 
-    /// Creates an `OrderBook` from vector of [IncomingOrders](IncomingOrder)
-    pub fn from_vec(orders: Vec<IncomingOrder>) -> Self {
-        let mut book = Self::new();
-        let mut logger = DummyLogger;
-        for order in orders {
-            book.execute_order(order, &mut logger);
-        }
-        book
+    /// Enables or disables size conservation audit
+    ///
+    /// In audit mode every executed order is checked for size conservation and
+    /// [IntegrityErrors](LogItem::IntegrityError) are logged for any mismatch.
+    /// Each check walks both queues, so audit mode is intended for testing and debugging.
+    pub fn set_audit(&mut self, enabled: bool) {
+        self.audit = enabled;
     }
-}
 
-impl fmt::Debug for OrderBook {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        writeln!(f, "== ORDER BOOK START")?;
-        for order in (&self.ask).into_iter().rev() {
-            writeln!(f, "{}", order.to_incoming())?;
-        }
-        writeln!(f, "-----")?;
-        for order in &self.bid {
-            writeln!(f, "{}", order.to_incoming())?;
+    /// Enables or disables per-order audit trail
+    ///
+    /// Trails are kept for all orders submitted while the trail is enabled.
+    /// Disabling the trail drops the collected records.
+    pub fn set_order_trail(&mut self, enabled: bool) {
+        if !enabled {
+            self.trail = None;
+        } else if self.trail.is_none() {
+            self.trail = Some(TrailStore::default());
         }
-        writeln!(f, "== ORDER BOOK END")?;
-        Ok(())
     }
-}
 
-/// Returns a vector representing order book contents for benchmark
-#[doc(hidden)]
-#[allow(unused)]
-pub fn create_orders() -> Vec<IncomingOrder> {
-    let price = 10000;
-    let mut orders = Vec::new();
-
-    let mut user_id = 10;
-    for i in 0..3500 {
-        user_id += 1;
-        let order = IncomingOrder {
-            price_limit: price + i + 1,
-            size: 10,
-            user_id,
-            kind: OrderKind::Limit,
-            side: OrderSide::Sell
-        };
-        orders.push(order);
-        user_id += 1;
-        let order = IncomingOrder {
-            price_limit: price - i,
-            size: 10,
-            user_id,
-            kind: OrderKind::Limit,
-            side: OrderSide::Buy
-        };
-        orders.push(order);
+    /// Returns the audit trail of the order `order_id`
+    pub fn order_trail(&self, order_id: OrderId) -> Option<&OrderTrail> {
+        self.trail.as_ref().and_then(|trail| trail.get(order_id))
     }
-    orders
-}
 
-#[cfg(test)]
-pub mod tests {
-    use crate::order::*;
-    use crate::log::{DummyLogger, VectorLogger, LogItem};
-    use crate::{OrderBook, OrderQueue};
-    use super::create_orders;
+    /// Enables or disables per-user conduct metrics
+    ///
+    /// Disabling the metrics drops the collected values.
+    pub fn set_user_metrics(&mut self, enabled: bool) {
+        if !enabled {
+            self.metrics = None;
+        } else if self.metrics.is_none() {
+            self.metrics = Some(MetricsTracker::default());
+        }
+    }
 
-    fn get_order<'a, D: 'a+Direction>(queue: impl IntoIterator<Item=&'a Order<D>>, index: usize) -> IncomingOrder {
-        queue.into_iter().nth(index).expect("invalid order index").to_incoming()
+    /// Returns conduct metrics of the user `user_id`
+    pub fn user_metrics(&self, user_id: UserId) -> Option<&UserMetrics> {
+        self.metrics.as_ref().and_then(|metrics| metrics.get(user_id))
     }
 
-    fn check_order<'a, D: 'a+Direction>(queue: impl IntoIterator<Item=&'a Order<D>>, side: &str, index: usize, s: &str) {
-        let order = get_order(queue, index);
-        if order.to_string() != s {
-            panic!("Invalid {} order at index {}: {}, should be {}", side, index, order, s);
-        }
-        assert_eq!(order.to_string(), s);
+    /// Returns conduct metrics of all users seen since the metrics were enabled
+    pub fn all_user_metrics(&self) -> impl Iterator<Item=(UserId, &UserMetrics)> {
+        self.metrics.iter().flat_map(|metrics| metrics.iter())
     }
 
-    fn check_len<D: Direction>(queue: &OrderQueue<D>, side: &str, n: usize) {
-        if queue.len() != n {
-            panic!("Invalid {} queue length: {}, should be {}", side, queue.len(), n);
+    /// Enables or disables tracking of [order statuses](OrderStatus)
+    ///
+    /// Statuses are kept for all orders submitted while tracking is enabled.
+    /// Disabling tracking drops the collected statuses.
+    pub fn set_status_tracking(&mut self, enabled: bool) {
+        if !enabled {
+            self.statuses = None;
+        } else if self.statuses.is_none() {
+            self.statuses = Some(StatusStore::default());
         }
     }
 
-    fn check_log(log: &[LogItem], expected: &[&str]) {
-        if log.len() != expected.len() {
-            panic!("Incorrect log length: {}, expected {}", log.len(), expected.len());
-        }
-        for (i, log_item) in log.iter().enumerate() {
-            if log_item.to_string() != expected[i] {
-                panic!("Incorrect log item {}: '{}', expected '{}'", i, log_item.to_string(), expected[i]);
-            }
-        }
+    /// Returns current status of the order `order_id`
+    pub fn order_status(&self, order_id: OrderId) -> Option<OrderStatus> {
+        self.order_state(order_id).map(OrderState::status)
     }
 
-    pub trait OrderBookExt {
-        fn check_bid(&self, index: usize, s: &str);
+    /// Returns current status and fill progress of the order `order_id`
+    pub fn order_state(&self, order_id: OrderId) -> Option<&OrderState> {
+        self.statuses.as_ref().and_then(|statuses| statuses.get(order_id))
+    }
+
+    /// Sets the clock which timestamps [sequenced log items](crate::log::SequencedLogItem), or removes it
+    ///
+    /// The clock is read once per operation, so all items of the operation have the same timestamp.
+    pub fn set_clock(&mut self, clock: Option<Arc<dyn Clock + Send + Sync>>) {
+        self.clock = clock;
+    }
+
+    /// Sets the hook approving incoming orders and following their fills, or removes it
+    ///
+    /// The checker is shared by the clones of the book.
+    #[cfg(feature = "std")]
+    pub fn set_risk_checker(&mut self, checker: Option<Arc<Mutex<dyn RiskChecker + Send>>>) {
+        self.risk = checker;
+    }
+
+    /// Sets the source of the positions which limit [reduce-only](IncomingOrder::reduce_only) orders, or removes it
+    ///
+    /// The provider is shared by the clones of the book.
+    pub fn set_position_provider(&mut self, provider: Option<Arc<dyn PositionProvider + Send + Sync>>) {
+        self.positions = provider;
+    }
+
+    /// Starts keeping the last `capacity` trades, or stops keeping them with `None`
+    ///
+    /// Trades of auction uncrosses are kept too, their takers being the orders they are [logged](crate::auction) on.
+    pub fn set_trade_tape(&mut self, capacity: Option<usize>) {
+        self.tape = capacity.map(TradeTape::new);
+    }
+
+    /// Returns the kept trades
+    pub fn trade_tape(&self) -> Option<&TradeTape> {
+        self.tape.as_ref()
+    }
+
+    /// Sets the listener notified of the changes of the best bid and ask, or removes it
+    ///
+    /// The listener is shared by the clones of the book.
+    #[cfg(feature = "std")]
+    pub fn set_market_data_listener(&mut self, listener: Option<Arc<Mutex<dyn MarketDataListener + Send>>>) {
+        self.bbo = self.bbo();
+        self.listener = listener;
+    }
+
+    /// Returns the best bid and ask
+    pub fn bbo(&self) -> Bbo {
+        Bbo { bid: self.best_bid(), ask: self.best_ask() }
+    }
+
+    /// Returns the last kept trade
+    pub fn last_trade(&self) -> Option<&Trade> {
+        self.tape.as_ref().and_then(TradeTape::last)
+    }
+
+    /// Returns the kept trades with sequence numbers greater than `sequence`
+    pub fn trades_since(&self, sequence: u64) -> impl Iterator<Item=&Trade> {
+        self.tape.iter().flat_map(move |tape| tape.since(sequence))
+    }
+
+    /// Returns sequence number of the last logged item
+    pub fn log_sequence(&self) -> u64 {
+        self.log_sequence
+    }
+
+    /// Runs the operation `f` with a logger which numbers the logged items
+    pub(crate) fn sequenced<L: ExecutionLogger, R>(&mut self, logger: &mut L, f: impl FnOnce(&mut Self, &mut SequencingLogger<L>) -> R) -> R {
+        if self.sequencing {
+            return f(self, &mut SequencingLogger::new(logger, None, None));
+        }
+        let timestamp = self.clock.as_ref().map(|clock| clock.now());
+        let mut logger = SequencingLogger::new(logger, Some(self.log_sequence), timestamp);
+        self.sequencing = true;
+        let result = f(self, &mut logger);
+        self.repeg(timestamp, &mut logger);
+        self.sequencing = false;
+        self.log_sequence = logger.sequence().expect("outermost logger is sequencing");
+        #[cfg(feature = "std")]
+        self.notify_bbo(timestamp);
+        result
+    }
+
+    #[cfg(feature = "std")]
+    fn notify_bbo(&mut self, timestamp: Option<u64>) {
+        let listener = match &self.listener {
+            Some(listener) => listener,
+            None => return,
+        };
+        let bbo = self.bbo();
+        if bbo.changed(&self.bbo) {
+            self.bbo = bbo;
+            let update = BboUpdate { bbo, sequence: self.log_sequence, timestamp };
+            listener.lock().expect("market data listener lock is poisoned").on_bbo(&update);
+        }
+    }
+
+    /// Returns identifier assigned to the last submitted order
+    pub fn last_order_id(&self) -> Option<OrderId> {
+        if self.next_order_id > 1 {
+            Some(self.next_order_id - 1)
+        } else {
+            None
+        }
+    }
+
+    /// Returns a reference to the `bid` queue
+    pub fn bid(&self) -> &OrderQueue<Buy, QB> {
+        &self.bid
+    }
+
+    /// Returns a reference to the `ask` queue
+    pub fn ask(&self) -> &OrderQueue<Sell, QA> {
+        &self.ask
+    }
+
+    /// Returns the bid price levels, best price first
+    pub fn bid_levels(&self) -> Levels<'_, Buy, QB> {
+        Levels::new(&self.bid)
+    }
+
+    /// Returns the ask price levels, best price first
+    pub fn ask_levels(&self) -> Levels<'_, Sell, QA> {
+        Levels::new(&self.ask)
+    }
+
+    /// Returns the best bid price level
+    pub fn best_bid(&self) -> Option<PriceLevel> {
+        self.bid.best_level()
+    }
+
+    /// Returns the best ask price level
+    pub fn best_ask(&self) -> Option<PriceLevel> {
+        self.ask.best_level()
+    }
+
+    /// Returns up to `levels` aggregated price levels of each side
+    pub fn depth(&self, levels: usize) -> DepthSnapshot {
+        DepthSnapshot {
+            bids: self.bid.levels(levels),
+            asks: self.ask.levels(levels),
+        }
+    }
+
+    /// Returns checksum of the [CHECKSUM_LEVELS](crate::depth::CHECKSUM_LEVELS) best levels of each side
+    ///
+    /// Clients replicating the book from the execution log compute [DepthSnapshot::checksum]
+    /// of their copy to verify that it is in sync.
+    pub fn checksum(&self) -> u32 {
+        self.depth(CHECKSUM_LEVELS).checksum()
+    }
+
+    /// Returns volumes and order counts of both sides, with VWAP and imbalance of at most `levels` best levels
+    pub fn stats(&self, levels: usize) -> BookStats {
+        BookStats::from_levels(&self.bid.levels(usize::MAX), &self.ask.levels(usize::MAX), levels)
+    }
+
+    /// Returns difference between the best ask and the best bid prices
+    ///
+    /// The spread can be zero or negative, as orders of the same user are allowed to cross.
+    pub fn spread(&self) -> Option<Price> {
+        let (bid, ask) = self.displayed_touch()?;
+        ask.checked_sub(bid)
+    }
+
+    /// Returns average of the best bid and the best ask prices
+    pub fn mid_price(&self) -> Option<f64> {
+        let (bid, ask) = self.displayed_touch()?;
+        Some((i128::from(bid) + i128::from(ask)) as f64 / 2.0)
+    }
+
+    /// Returns the best displayed bid and ask prices, leaving out [Hidden](OrderKind::Hidden) orders
+    fn displayed_touch(&self) -> Option<(Price, Price)> {
+        Some((self.bid.best_level()?.price, self.ask.best_level()?.price))
+    }
+
+    /// Returns detailed view of the resting order `order_id`
+    pub fn inspect_order(&self, order_id: OrderId) -> Option<RestingOrderView> {
+        self.bid.find(order_id).or_else(|| self.ask.find(order_id))
+    }
+
+    /// Returns the estimated position of the resting order `order_id` in its queue
+    pub fn queue_position(&self, order_id: OrderId) -> Option<QueuePosition> {
+        self.bid.queue_position(order_id).or_else(|| self.ask.queue_position(order_id))
+    }
+
+    /// Returns the resting order `order_id` without computing its position
+    fn resting_order(&self, order_id: OrderId) -> Option<IncomingOrder> {
+        self.bid.get(order_id).map(Order::to_incoming).or_else(|| self.ask.get(order_id).map(Order::to_incoming))
+    }
+
+    /// Cancels the resting order `order_id` on behalf of an administrator
+    ///
+    /// Cancellation is logged as [ForceCancelled](LogItem::ForceCancelled), distinct from cancellations
+    /// initiated by users. Returns the removed order or `None` if there is no such resting order.
+    pub fn force_cancel(&mut self, order_id: OrderId, logger: &mut impl ExecutionLogger) -> Option<RestingOrderView> {
+        self.sequenced(logger, |book, logger| {
+            let view = book.inspect_order(order_id)?;
+            book.remove_resting(order_id, true);
+            logger.log(LogItem::ForceCancelled { size: view.order.size, order_id });
+            logger.finish();
+            Some(view)
+        })
+    }
+
+    /// Cancels all resting, stop and parked orders on behalf of an administrator
+    ///
+    /// Every removed order is logged as [ForceCancelled](LogItem::ForceCancelled), in the order of
+    /// submission. Returns the number of removed orders.
+    pub fn cancel_all(&mut self, logger: &mut impl ExecutionLogger) -> usize {
+        self.force_cancel_where(|_| true, logger)
+    }
+
+    /// Cancels all resting, stop and parked orders of the side `side` like [cancel_all](Self::cancel_all)
+    pub fn cancel_all_side(&mut self, side: OrderSide, logger: &mut impl ExecutionLogger) -> usize {
+        self.force_cancel_where(|order| order.side == side, logger)
+    }
+
+    /// Cancels all resting, stop and parked orders of the user `user_id` like [cancel_all](Self::cancel_all)
+    pub fn cancel_all_for_user(&mut self, user_id: UserId, logger: &mut impl ExecutionLogger) -> usize {
+        self.force_cancel_where(|order| order.user_id == user_id, logger)
+    }
+
+    fn force_cancel_where<P>(&mut self, predicate: P, logger: &mut impl ExecutionLogger) -> usize
+        where P: Fn(&IncomingOrder) -> bool
+    {
+        self.sequenced(logger, |book, logger| {
+            let bids = book.bid.into_iter().filter(|order| predicate(&order.to_incoming())).map(|order| order.order_id);
+            let asks = book.ask.into_iter().filter(|order| predicate(&order.to_incoming())).map(|order| order.order_id);
+            let resting: Vec<OrderId> = bids.chain(asks).collect();
+            let mut cancelled: Vec<_> = resting.into_iter()
+                .filter_map(|order_id| book.remove_resting(order_id, true).map(|order| (order_id, order)))
+                .collect();
+            let mut removed = book.stops.remove_where(&predicate);
+            let (parked, waiting) = core::mem::take(&mut book.auction_orders).into_iter()
+                .partition(|parked| predicate(&parked.order));
+            book.auction_orders = waiting;
+            removed.extend(parked.into_iter().map(|parked: AuctionOrder| {
+                (parked.order_id, IncomingOrder { size: parked.order.size + parked.odd_lot, ..parked.order })
+            }));
+            for (order_id, order) in &removed {
+                let item = LogItem::ForceCancelled { size: order.size, order_id: *order_id };
+                book.record_observer_updates(*order_id, order.user_id, core::slice::from_ref(&item));
+            }
+            cancelled.append(&mut removed);
+            cancelled.sort_by_key(|&(order_id, _)| order_id);
+            for (order_id, order) in &cancelled {
+                logger.log(LogItem::ForceCancelled { size: order.size, order_id: *order_id });
+            }
+            logger.finish();
+            cancelled.len()
+        })
+    }
+
+    /// Removes resting and stop orders which expire by `now`
+    ///
+    /// Every removed order is logged as [Expired](LogItem::Expired), in the order of submission.
+    /// Returns the number of removed orders.
+    pub fn expire_orders(&mut self, now: u64, logger: &mut impl ExecutionLogger) -> usize {
+        self.sequenced(logger, |book, logger| {
+            let mut expired = book.stops.remove_expired(now);
+            for order_id in book.bid.expired(now) {
+                expired.extend(book.bid.remove(order_id).map(|order| (order_id, order)));
+            }
+            for order_id in book.ask.expired(now) {
+                expired.extend(book.ask.remove(order_id).map(|order| (order_id, order)));
+            }
+            expired.sort_by_key(|&(order_id, _)| order_id);
+            for (order_id, order) in &expired {
+                book.pegs.remove(*order_id);
+                let item = LogItem::Expired { size: order.size, order_id: *order_id };
+                book.record_observer_updates(*order_id, order.user_id, core::slice::from_ref(&item));
+                logger.log(item);
+            }
+            logger.finish();
+            expired.len()
+        })
+    }
+
+    /// Cancels the resting or stop order `order_id`
+    ///
+    /// Cancellation is logged as [Cancelled](LogItem::Cancelled) with the remaining size of the order.
+    pub fn cancel_order(&mut self, order_id: OrderId, logger: &mut impl ExecutionLogger) -> Result<CancelledOrder, CancelError> {
+        self.sequenced(logger, |book, logger| {
+            let order = match book.remove_resting(order_id, false) {
+                Some(order) => order,
+                None => {
+                    let order = match book.auction_orders.iter().position(|o| o.order_id == order_id) {
+                        Some(index) => {
+                            let parked = book.auction_orders.remove(index);
+                            IncomingOrder { size: parked.order.size + parked.odd_lot, ..parked.order }
+                        }
+                        None => book.stops.remove(order_id).ok_or(CancelError::UnknownOrder(order_id))?,
+                    };
+                    book.record_observer_updates(order_id, order.user_id, &[LogItem::cancelled(&order, order_id, order.size)]);
+                    order
+                }
+            };
+            logger.log(LogItem::cancelled(&order, order_id, order.size));
+            logger.finish();
+            Ok(CancelledOrder { order_id, order })
+        })
+    }
+
+    /// Amends price and remaining size of the resting order `order_id`
+    ///
+    /// Decreasing the size keeps the time priority of the order. Changing the price or increasing
+    /// the size moves the order to the end of its new price level, and an order amended to
+    /// a crossing price is matched like a new order. Amending the size to zero removes the order.
+    /// The price of a [pegged](crate::peg) order is its new price limit, which caps the pegged price.
+    /// A [post-only](OrderKind::PostOnly) order amended to a crossing price is rejected or repriced
+    /// according to the [post-only policy](BookConfig::post_only). A new price is checked against the
+    /// price band and the price collar like the price of a new order. Amendments raising the notional of
+    /// the order are checked against the [resting notional limit](BookConfig::max_resting_notional) and
+    /// by the [risk checker](crate::risk), if any. A reduce-only order may not be enlarged beyond the
+    /// position of its user.
+    ///
+    /// The amendment is logged as [Amended](LogItem::Amended) followed by the execution results
+    /// of the re-matched order, if any. Amendments which do not pass validation are
+    /// [rejected](LogItem::Rejected) and leave the order unchanged.
+    pub fn amend_order(&mut self, order_id: OrderId, price: Price, size: Size, logger: &mut impl ExecutionLogger) -> Result<(), AmendError> {
+        self.sequenced(logger, |book, logger| {
+            let current = book.resting_order(order_id).ok_or(AmendError::UnknownOrder(order_id))?;
+            let mut amended = IncomingOrder { price_limit: price, size, ..current.clone() };
+            let repriced = price != current.price_limit;
+            let accepted = book.config.validate(&amended)
+                .and_then(|_| if book.state == BookState::Open { Ok(()) } else { Err(RejectReason::Halted) })
+                // A new price is checked like the price of a new order, the unchanged one may have drifted away
+                .and_then(|_| if repriced { book.config.check_band(&amended, book.last_trade_price) } else { Ok(()) })
+                .and_then(|_| if repriced { book.config.apply_collar(&amended, book.collar_reference()) } else { Ok(price) })
+                .map(|price| amended.price_limit = price)
+                .and_then(|_| book.config.round_to_lot(size))
+                .and_then(|rounded| if rounded == size { Ok(()) } else { Err(RejectReason::OddLot) })
+                .and_then(|_| book.check_amendment_limits(&current, &amended))
+                // A larger reduce-only order must still not increase the position
+                .and_then(|_| if amended.reduce_only && size > current.size {
+                    risk::reduce_only_size(current.side, size, book.position(current.user_id))
+                        .and_then(|allowed| if allowed == size { Ok(()) } else { Err(RejectReason::WouldIncreasePosition) })
+                } else {
+                    Ok(())
+                });
+            // Post-only orders are amended under their policy, so that they never take liquidity
+            let accepted = accepted.and_then(|_| if current.kind == OrderKind::PostOnly {
+                book.post_only_price(&amended).map(|price| amended.price_limit = price)
+            } else {
+                Ok(())
+            });
+            if let Err(reason) = accepted {
+                logger.log(LogItem::Rejected { size, reason });
+                logger.finish();
+                return Err(AmendError::Rejected(reason));
+            }
+            if let Some(peg) = book.pegs.get_mut(order_id) {
+                // The price limit caps the pegged price, which is updated once the amendment is done
+                let price = amended.price_limit;
+                peg.price_limit = price;
+                amended.price_limit = match current.side {
+                    OrderSide::Buy => current.price_limit.min(price),
+                    OrderSide::Sell => current.price_limit.max(price),
+                };
+            }
+
+            if amended.price_limit != current.price_limit || amended.size > current.size {
+                book.trim_reduce_only(&amended, logger);
+            }
+            book.amend_observed(order_id, &current, amended, logger);
+            logger.finish();
+            Ok(())
+        })
+    }
+
+    /// Returns the position of `user_id` reported by the position provider, zero without one
+    fn position(&self, user_id: UserId) -> i128 {
+        self.positions.as_ref().map_or(0, |positions| positions.position(user_id))
+    }
+
+    /// Shrinks or cancels the resting reduce-only orders `order` may be matched with, which would
+    /// increase the positions of their users as these changed since the orders were placed
+    ///
+    /// The orders are [amended](LogItem::Amended) before `order` is executed.
+    fn trim_reduce_only(&mut self, order: &IncomingOrder, logger: &mut impl ExecutionLogger) {
+        let excess = match order.side {
+            OrderSide::Buy => self.ask.reduce_only_excess(order, |user_id| self.position(user_id)),
+            OrderSide::Sell => self.bid.reduce_only_excess(order, |user_id| self.position(user_id)),
+        };
+        for &(order_id, size) in &excess {
+            let current = self.resting_order(order_id).expect("reduce-only order is resting");
+            self.amend_observed(order_id, &current, IncomingOrder { size, ..current.clone() }, logger);
+            if size == 0 {
+                self.pegs.remove(order_id);
+            }
+        }
+        if !excess.is_empty() {
+            logger.finish();
+        }
+    }
+
+    fn amend_observed(&mut self, order_id: OrderId, current: &IncomingOrder, amended: IncomingOrder, logger: &mut impl ExecutionLogger) {
+        if self.trail.is_some() || self.metrics.is_some() || self.statuses.is_some() {
+            let mut recorded = core::mem::take(&mut self.recorded);
+            self.amend(order_id, current, amended, &mut RecordingLogger::new(logger, &mut recorded));
+            self.record_observer_updates(order_id, current.user_id, &recorded);
+            self.recorded = recorded;
+        } else {
+            self.amend(order_id, current, amended, logger);
+        }
+    }
+
+    fn amend(&mut self, order_id: OrderId, current: &IncomingOrder, amended: IncomingOrder, logger: &mut impl ExecutionLogger) {
+        logger.log(LogItem::Amended { size: amended.size, price: amended.price_limit, order_id });
+        if amended.price_limit == current.price_limit && amended.size <= current.size {
+            match current.side {
+                OrderSide::Buy => self.bid.resize(order_id, amended.size),
+                OrderSide::Sell => self.ask.resize(order_id, amended.size),
+            }
+        } else {
+            match current.side {
+                OrderSide::Buy => self.bid.remove(order_id),
+                OrderSide::Sell => self.ask.remove(order_id),
+            };
+            if amended.size > 0 {
+                self.match_and_enqueue(amended, order_id, logger);
+            }
+        }
+    }
+
+    /// Removes the resting order `order_id` and records the cancellation in the observers
+    fn remove_resting(&mut self, order_id: OrderId, forced: bool) -> Option<IncomingOrder> {
+        let order = self.bid.remove(order_id).or_else(|| self.ask.remove(order_id))?;
+        self.pegs.remove(order_id);
+        let size = order.size;
+        if let Some(trail) = &mut self.trail {
+            let event = if forced { TrailEvent::ForceCancelled { size } } else { TrailEvent::Cancelled { size } };
+            trail.append(order_id, event);
+        }
+        if let Some(metrics) = &mut self.metrics {
+            metrics.record_cancel(order_id, order.user_id);
+        }
+        if let Some(statuses) = &mut self.statuses {
+            statuses.record_cancel(order_id);
+        }
+        Some(order)
+    }
+
+    /// Returns stop orders waiting for their triggers
+    pub fn stops(&self) -> &StopQueue {
+        &self.stops
+    }
+
+    /// Returns pegs of the resting pegged orders
+    pub fn pegs(&self) -> &PegStore {
+        &self.pegs
+    }
+
+    /// Checks the pegged price of `order` like the price of a new order, returning the accepted price
+    fn check_pegged_price(&self, order: &IncomingOrder) -> Result<Price, RejectReason> {
+        self.config.validate(order).and_then(|_| self.config.apply_collar(order, self.collar_reference()))
+    }
+
+    /// Returns the best bid and ask prices of the displayed resting orders which are not pegged
+    fn peg_references(&self) -> (Option<Price>, Option<Price>) {
+        let bid = self.bid.orders.iter().find(|order| !order.dark && !self.pegs.contains(order.order_id)).map(|order| order.price_limit);
+        let ask = self.ask.orders.iter().find(|order| !order.dark && !self.pegs.contains(order.order_id)).map(|order| order.price_limit);
+        (bid, ask)
+    }
+
+    /// Reprices the pegged orders whose references moved, in the order of arrival
+    ///
+    /// Repricings are logged as amendments, and orders whose new prices are invalid as cancellations,
+    /// followed by [finish](ExecutionLogger::finish).
+    fn repeg(&mut self, now: Option<u64>, logger: &mut impl ExecutionLogger) {
+        if self.pegs.is_empty() || self.state != BookState::Open {
+            return;
+        }
+        let trade_sequence = self.trade_sequence;
+        let mut repriced = false;
+        for order_id in self.pegs.order_ids() {
+            let current = match self.resting_order(order_id) {
+                Some(current) => current,
+                None => {
+                    // Filled since the last repricing
+                    self.pegs.remove(order_id);
+                    continue;
+                }
+            };
+            let (bid, ask) = self.peg_references();
+            let peg = self.pegs.get_mut(order_id).expect("peg of a resting order");
+            if !peg.may_reprice(now, self.config.repeg_interval) {
+                continue;
+            }
+            let price = match peg.price(bid, ask) {
+                Some(price) if price != current.price_limit => price,
+                _ => continue,
+            };
+            peg.priced_at = now;
+            let amended = IncomingOrder { price_limit: price, ..current.clone() };
+            match self.check_pegged_price(&amended) {
+                Ok(price) if price == current.price_limit => continue,
+                Ok(price) => self.amend_observed(order_id, &current, IncomingOrder { price_limit: price, ..amended }, logger),
+                Err(_) => {
+                    // Pegs are not left at prices which new orders could not have
+                    self.remove_resting(order_id, false);
+                    logger.log(LogItem::cancelled(&current, order_id, current.size));
+                }
+            }
+            if self.resting_order(order_id).is_none() {
+                self.pegs.remove(order_id);
+            }
+            repriced = true;
+        }
+        if self.trade_sequence != trade_sequence {
+            self.trigger_stops(logger);
+            self.check_circuit_breaker(logger);
+        }
+        if repriced {
+            logger.finish();
+        }
+    }
+
+    /// Returns price of the last trade, which stop orders are triggered by
+    pub fn last_trade_price(&self) -> Option<Price> {
+        self.last_trade_price
+    }
+
+    /// Returns indicative (non-firm) quotes
+    pub fn indicative(&self) -> &IndicativeQuotes {
+        &self.indicative
+    }
+
+    /// Adds an indicative quote which is displayed but never matched
+    ///
+    /// The quote is validated and checked against the limits of the book like a regular order, and is
+    /// assigned the next sequential [OrderId](OrderId).
+    pub fn add_indicative(&mut self, order: IncomingOrder) -> Result<OrderId, RejectReason> {
+        self.config.validate(&order)?;
+        self.check_limits(&order)?;
+        let order_id = self.take_order_id();
+        self.indicative.insert(order_id, order);
+        Ok(order_id)
+    }
+
+    /// Withdraws the indicative quote `order_id` of the user `user_id`
+    pub fn withdraw_indicative(&mut self, order_id: OrderId, user_id: UserId) -> Option<IncomingOrder> {
+        self.indicative.remove(order_id, user_id)
+    }
+
+    /// Converts the indicative quote `order_id` of the user `user_id` into a firm order and executes it
+    ///
+    /// The firm order keeps the identifier of the quote, which is returned, or `None` if the user has no such quote.
+    pub fn firm_up(&mut self, order_id: OrderId, user_id: UserId, logger: &mut impl ExecutionLogger) -> Option<OrderId> {
+        let order = self.indicative.remove(order_id, user_id)?;
+        self.sequenced(logger, |book, logger| book.submit(order, order_id, logger));
+        Some(order_id)
+    }
+
+    /// Executes `order`
+    ///
+    /// Execution results will be logged with `logger`. Previous state of the logger may be lost.
+    /// The order is assigned the next sequential [OrderId](OrderId).
+    ///
+    /// Stop orders triggered by the trades of `order` are executed afterwards; the execution results
+    /// of each of them follow a [Triggered](LogItem::Triggered) item. [Pegged](crate::peg) orders whose
+    /// references moved are repriced after the operation.
+    ///
+    /// An order with a [minimum fill size](IncomingOrder::min_fill_size) is only matched if at least that
+    /// size can be filled on arrival; otherwise it rests or is cancelled like an unmatched order of its kind.
+    /// An unmatched order which would rest across the opposite touch is cancelled instead, so that the book
+    /// is never left crossed.
+    ///
+    /// While trading is not [open](BookState::Open) orders are not matched but
+    /// [parked](LogItem::Parked) until the book reopens, or rejected with [Halted](RejectReason::Halted),
+    /// see [set_state](OrderBook::set_state). If the trades of `order` trip the
+    /// [circuit breaker](crate::config::CircuitBreaker), the state change is logged after them.
+    pub fn execute_order(&mut self, order: IncomingOrder, logger: &mut impl ExecutionLogger) {
+        self.sequenced(logger, |book, logger| {
+            let order_id = book.take_order_id();
+            book.submit(order, order_id, logger);
+        })
+    }
+
+    /// Parks or executes `order` under `order_id`, followed by the triggered stop orders
+    fn submit(&mut self, order: IncomingOrder, order_id: OrderId, logger: &mut impl ExecutionLogger) {
+        if self.parks_orders() && order.kind.trigger_price().is_none() {
+            self.park(order, order_id, logger);
+        } else {
+            let trade_sequence = self.trade_sequence;
+            self.execute_observed(order, order_id, false, logger);
+            self.trigger_stops(logger);
+            if self.trade_sequence != trade_sequence {
+                self.check_circuit_breaker(logger);
+            }
+        }
+        logger.finish();
+    }
+
+    /// Returns the trading state of the book
+    pub fn state(&self) -> BookState {
+        self.state
+    }
+
+    /// Changes the trading state of the book
+    ///
+    /// The change is logged as [StateChanged](LogItem::StateChanged) followed by
+    /// [finish](ExecutionLogger::finish). When trading reopens, orders parked in the meantime are
+    /// uncrossed together with the resting orders in a [call auction](crate::auction) and their
+    /// execution results are logged afterwards.
+    pub fn set_state(&mut self, state: BookState, logger: &mut impl ExecutionLogger) {
+        self.sequenced(logger, |book, logger| {
+            book.change_state(state, logger);
+            logger.finish();
+            if state == BookState::Open {
+                book.uncross(logger);
+            }
+        })
+    }
+
+    fn change_state(&mut self, state: BookState, logger: &mut impl ExecutionLogger) {
+        self.state = state;
+        self.price_window.clear();
+        logger.log(LogItem::StateChanged { state });
+    }
+
+    /// Returns whether incoming orders are parked instead of matched
+    fn parks_orders(&self) -> bool {
+        match self.state {
+            BookState::Open => false,
+            BookState::Halted => self.config.halt_policy == HaltPolicy::Park,
+            BookState::AuctionOnly => true,
+        }
+    }
+
+    /// Validates `order` and parks it for the auction held when trading reopens
+    ///
+    /// Observers record the order once it is uncrossed.
+    fn park(&mut self, order: IncomingOrder, order_id: OrderId, logger: &mut impl ExecutionLogger) {
+        let accepted = self.config.validate(&order)
+            .and_then(|_| self.check_limits(&order))
+            .and_then(|_| if order.kind == OrderKind::PostOnly || order.reduce_only || matches!(order.kind, OrderKind::Pegged { .. }) { Err(RejectReason::Halted) } else { Ok(()) })
+            .and_then(|_| self.config.round_to_lot(order.size));
+        match accepted {
+            Ok(size) => {
+                logger.log(LogItem::Parked { size: order.size });
+                let odd_lot = order.size - size;
+                self.auction_orders.push(AuctionOrder { order_id, order: IncomingOrder { size, ..order }, odd_lot });
+            }
+            Err(reason) => {
+                let item = LogItem::Rejected { size: order.size, reason };
+                self.record_observers(order_id, &order, core::slice::from_ref(&item));
+                logger.log(item);
+            }
+        }
+    }
+
+    /// Records the last trade in the circuit breaker window and changes the state if the price moved too far
+    fn check_circuit_breaker(&mut self, logger: &mut impl ExecutionLogger) {
+        let (breaker, price) = match (self.config.circuit_breaker, self.last_trade_price) {
+            (Some(breaker), Some(price)) if self.state == BookState::Open => (breaker, price),
+            _ => return,
+        };
+        let now = match &self.clock {
+            Some(clock) => clock.now(),
+            None => return,
+        };
+        while self.price_window.front().is_some_and(|&(time, _)| now.saturating_sub(time) > breaker.window) {
+            self.price_window.pop_front();
+        }
+        let tripped = self.price_window.iter().any(|&(_, earlier)| {
+            let change = (i128::from(price) - i128::from(earlier)).unsigned_abs();
+            change * 10_000 > i128::from(earlier).unsigned_abs() * u128::from(breaker.max_move_bps)
+        });
+        if tripped {
+            self.change_state(breaker.state, logger);
+        } else {
+            self.price_window.push_back((now, price));
+        }
+    }
+
+    /// Executes `order` like [execute_order](OrderBook::execute_order) and summarizes its execution
+    ///
+    /// Returns the reason if the order is rejected; the rejection is logged as well.
+    pub fn try_execute_order(&mut self, order: IncomingOrder, logger: &mut impl ExecutionLogger) -> Result<ExecutionSummary, RejectReason> {
+        let mut logger = SummaryLogger::new(logger, self.next_order_id);
+        self.execute_order(order, &mut logger);
+        logger.summary()
+    }
+
+    /// Executes `order` like [try_execute_order](OrderBook::try_execute_order) unless it could
+    /// overflow the totals of the book
+    ///
+    /// An order which may rest is rejected with [Overflow](RejectReason::Overflow) if its size together
+    /// with the resting, stop and parked orders exceeds the range of [Size](Size). Sequences of orders executed
+    /// this way never overflow, so the method is suited for untrusted input and fuzzing.
+    pub fn execute_order_checked(&mut self, order: IncomingOrder, logger: &mut impl ExecutionLogger) -> Result<ExecutionSummary, RejectReason> {
+        let may_rest = !matches!(order.kind, OrderKind::Market | OrderKind::ImmediateOrCancel | OrderKind::FillOrKill);
+        if !may_rest || self.open_size().checked_add(order.size).is_some() {
+            return self.try_execute_order(order, logger);
+        }
+        let mut logger = SummaryLogger::new(logger, self.next_order_id);
+        self.sequenced(&mut logger, |book, logger| {
+            let order_id = book.take_order_id();
+            let item = LogItem::Rejected { size: order.size, reason: RejectReason::Overflow };
+            book.record_observers(order_id, &order, core::slice::from_ref(&item));
+            logger.log(item);
+            logger.finish();
+        });
+        logger.summary()
+    }
+
+    /// Checks limits of the book configuration which depend on the book state
+    fn check_limits(&self, order: &IncomingOrder) -> Result<(), RejectReason> {
+        if self.state == BookState::Halted && self.config.halt_policy == HaltPolicy::Reject {
+            return Err(RejectReason::Halted);
+        }
+        self.config.check_band(order, self.last_trade_price)?;
+        let may_rest = !matches!(order.kind, OrderKind::Market | OrderKind::ImmediateOrCancel | OrderKind::FillOrKill);
+        if let Some(max_open_orders) = self.config.max_open_orders {
+            if may_rest && self.open_orders(order.user_id) >= max_open_orders {
+                return Err(RejectReason::TooManyOpenOrders);
+            }
+        }
+        if let Some(max_resting_notional) = self.config.max_resting_notional {
+            let added = notional(order.price_limit, order.size).unsigned_abs();
+            if may_rest && self.resting_notional(order.user_id).saturating_add(added) > max_resting_notional {
+                return Err(RejectReason::NotionalLimit);
+            }
+        }
+        self.check_risk(order)
+    }
+
+    /// Checks limits of the book configuration for the amendment of the resting order `current` to `amended`
+    ///
+    /// Only amendments which raise the notional of the order are checked, against the resting notional
+    /// without the order and with the risk checker.
+    fn check_amendment_limits(&self, current: &IncomingOrder, amended: &IncomingOrder) -> Result<(), RejectReason> {
+        let before = notional(current.price_limit, current.size).unsigned_abs();
+        let after = notional(amended.price_limit, amended.size).unsigned_abs();
+        if after <= before {
+            return Ok(());
+        }
+        if let Some(max_resting_notional) = self.config.max_resting_notional {
+            if self.resting_notional(amended.user_id).saturating_sub(before).saturating_add(after) > max_resting_notional {
+                return Err(RejectReason::NotionalLimit);
+            }
+        }
+        self.check_risk(amended)
+    }
+
+    /// Asks the risk checker, if any, to approve `order`
+    fn check_risk(&self, _order: &IncomingOrder) -> Result<(), RejectReason> {
+        #[cfg(feature = "std")]
+        if let Some(checker) = &self.risk {
+            checker.lock().expect("risk checker lock is poisoned").check(_order)?;
+        }
+        Ok(())
+    }
+
+    /// Returns the reference price of the configured price collar
+    fn collar_reference(&self) -> Option<Price> {
+        match self.config.price_collar?.reference {
+            CollarReference::LastTrade => self.last_trade_price,
+            CollarReference::MidPrice => {
+                let (bid, ask) = self.displayed_touch()?;
+                Some((i128::from(bid) + i128::from(ask)).div_euclid(2) as Price)
+            }
+        }
+    }
+
+    /// Returns the number of resting and stop orders of the user `user_id`
+    fn open_orders(&self, user_id: UserId) -> usize {
+        let resting = self.bid.user_orders(user_id).len() + self.ask.user_orders(user_id).len();
+        resting + self.stops.iter().filter(|(_, order)| order.user_id == user_id).count()
+    }
+
+    /// Returns total notional of the resting orders of the user `user_id`, see [user_notional](OrderQueue::user_notional)
+    pub fn resting_notional(&self, user_id: UserId) -> u128 {
+        self.bid.user_notional(user_id).saturating_add(self.ask.user_notional(user_id))
+    }
+
+    /// Executes stop orders triggered by the last trade price, including those triggered by their own trades
+    fn trigger_stops(&mut self, logger: &mut impl ExecutionLogger) {
+        if self.state != BookState::Open {
+            return;
+        }
+        while let Some(price) = self.last_trade_price {
+            // Trailing triggers follow every trade, including the trades of the triggered orders
+            self.stops.trail(price);
+            match self.stops.take_triggered(price) {
+                Some((order_id, order)) => self.execute_observed(stop::triggered(&order), order_id, true, logger),
+                None => break,
+            }
+        }
+    }
+
+    fn execute_observed(&mut self, order: IncomingOrder, order_id: OrderId, triggered: bool, logger: &mut impl ExecutionLogger) {
+        if order.kind != OrderKind::PostOnly && order.kind.trigger_price().is_none() {
+            self.trim_reduce_only(&order, logger);
+        }
+        if self.audit || self.trail.is_some() || self.metrics.is_some() || self.statuses.is_some() {
+            let submitted = order.clone();
+            let resting_before = if self.audit { self.resting_size() } else { 0 };
+            let mut recorded = core::mem::take(&mut self.recorded);
+            {
+                let mut logger = RecordingLogger::new(logger, &mut recorded);
+                if triggered {
+                    logger.log(LogItem::Triggered { order_id });
+                }
+                self.execute(order, order_id, &mut logger);
+            }
+
+            if self.audit {
+                audit::check(&recorded, submitted.size, resting_before, self.resting_size(), logger);
+            }
+            if triggered {
+                self.record_observer_updates(order_id, submitted.user_id, &recorded);
+            } else {
+                self.record_observers(order_id, &submitted, &recorded);
+            }
+            self.recorded = recorded;
+        } else {
+            if triggered {
+                logger.log(LogItem::Triggered { order_id });
+            }
+            self.execute(order, order_id, logger);
+        }
+    }
+
+    /// Executes `commands` one by one
+    ///
+    /// Execution results of every command are logged with `logger` followed by
+    /// [finish](ExecutionLogger::finish).
+    ///
+    /// With [cancels_first](BatchOptions::cancels_first) all [high-priority](Priority::High) commands
+    /// (cancels and amends) are executed before the rest. The relative order of commands within each
+    /// priority class is preserved, so the execution order depends only on the batch contents and
+    /// is the same in every run.
+    ///
+    /// Rejected commands of a batch are logged and the rest of the batch is executed, unless the batch
+    /// is [atomic](BatchOptions::atomic): then the batch is executed as a [transaction](OrderBook::transaction),
+    /// which the first rejection rolls back.
+    pub fn execute_batch(&mut self, commands: impl IntoIterator<Item=impl Into<Command>>, options: BatchOptions,
+                         logger: &mut impl ExecutionLogger) -> Result<(), BatchError> {
+        let mut commands: Vec<Command> = commands.into_iter().map(Into::into).collect();
+        if options.cancels_first {
+            // Stable sort keeps the input order within each priority
+            commands.sort_by_key(|command| command.priority() != Priority::High);
+        }
+        if !options.atomic {
+            for command in commands {
+                self.execute_command(command, logger);
+            }
+            return Ok(());
+        }
+
+        self.transaction(logger, |book, logger| {
+            for (index, command) in commands.into_iter().enumerate() {
+                let start = logger.len();
+                book.execute_command(command, logger);
+                if let Some(reason) = logger.rejection_since(start) {
+                    return Err(BatchError { index, reason });
+                }
+            }
+            Ok(())
+        })
+    }
+
+    /// Executes `orders` as one [transaction](OrderBook::transaction), e.g. a basket or both legs of a spread
+    ///
+    /// Returns summaries of the executed orders. If any order is rejected, none of them is executed
+    /// and the first rejection is returned.
+    pub fn execute_group(&mut self, orders: impl IntoIterator<Item=IncomingOrder>, logger: &mut impl ExecutionLogger)
+                         -> Result<Vec<ExecutionSummary>, BatchError> {
+        self.transaction(logger, |book, logger| {
+            orders.into_iter().enumerate()
+                .map(|(index, order)| book.try_execute_order(order, logger).map_err(|reason| BatchError { index, reason }))
+                .collect()
+        })
+    }
+
+    /// Replaces the resting orders of the user `user_id` with limit orders of `quotes` as one [transaction](OrderBook::transaction)
+    ///
+    /// Resting orders quoted again at their side and price keep their identifiers: orders of the quoted size
+    /// are left as they are and log nothing, the others are [amended](OrderBook::amend_order) to it. The rest
+    /// of the resting orders are cancelled before the new quotes are inserted, so the old and the new quotes
+    /// never cross. Stop orders of the user are not affected.
+    ///
+    /// Returns identifiers of the orders of `quotes`. If any quote is rejected, the book is left unchanged
+    /// and the position of the quote is returned with the rejection. Like every transaction, it copies the
+    /// whole book, so large books are better quoted with [amend_order](OrderBook::amend_order) directly.
+    pub fn apply_quote_set(&mut self, user_id: UserId, quotes: &[Quote], logger: &mut impl ExecutionLogger)
+                           -> Result<Vec<OrderId>, BatchError> {
+        self.transaction(logger, |book, logger| {
+            let resting: Vec<_> = book.bid.user_orders(user_id).iter().chain(book.ask.user_orders(user_id))
+                .filter_map(|&order_id| book.resting_order(order_id).map(|order| (order_id, order)))
+                .collect();
+            let (cancels, actions) = quote::plan(&resting, quotes);
+            for order_id in cancels {
+                book.cancel_order(order_id, logger).expect("order of the user rests");
+            }
+            let mut order_ids = vec![0; quotes.len()];
+            for (index, (quote, action)) in quotes.iter().zip(&actions).enumerate() {
+                match *action {
+                    QuoteAction::Keep(order_id) => order_ids[index] = order_id,
+                    QuoteAction::Resize(order_id) => {
+                        book.amend_order(order_id, quote.price, quote.size, logger).map_err(|error| match error {
+                            AmendError::Rejected(reason) => BatchError { index, reason },
+                            AmendError::UnknownOrder(_) => unreachable!("order of the user rests"),
+                        })?;
+                        order_ids[index] = order_id;
+                    }
+                    QuoteAction::Insert => {}
+                }
+            }
+            // New quotes go last, after the orders they replace are gone
+            for (index, (quote, action)) in quotes.iter().zip(&actions).enumerate() {
+                if *action == QuoteAction::Insert {
+                    let summary = book.try_execute_order(quote.to_incoming(user_id), logger)
+                        .map_err(|reason| BatchError { index, reason })?;
+                    order_ids[index] = summary.order_id;
+                }
+            }
+            Ok(order_ids)
+        })
+    }
+
+    /// Executes `f` as a transaction: either all of its operations take effect or none of them
+    ///
+    /// Operations of `f` log with the [TransactionLogger], which holds the items until `f` returns.
+    /// If `f` returns an error, the book is restored to its state before the transaction and nothing
+    /// is logged; otherwise the items are passed to `logger` and the market data listener is notified
+    /// once of the changed best bid and ask. Fills are reported to the risk checker on commit too, so
+    /// its checks within the transaction do not see them; only fills of auctions uncrossed by `f` are
+    /// reported as they happen.
+    ///
+    /// The whole book is copied before `f` is called, which costs time proportional to its size.
+    pub fn transaction<R, E>(&mut self, logger: &mut impl ExecutionLogger,
+                             f: impl FnOnce(&mut Self, &mut TransactionLogger) -> Result<R, E>) -> Result<R, E> {
+        let snapshot = self.clone();
+        #[cfg(feature = "std")]
+        let listener = self.listener.take();
+        // Fills of nested transactions are reported by the outermost one
+        #[cfg(feature = "std")]
+        let holding = core::mem::replace(&mut self.holding_fills, true);
+        let mut held = TransactionLogger::new();
+        let result = f(self, &mut held);
+        if result.is_err() {
+            *self = snapshot;
+            return result;
+        }
+        #[cfg(feature = "std")]
+        {
+            self.holding_fills = holding;
+            let risk = if holding { None } else { self.risk.clone() };
+            held.replay(&mut FillLogger::new(logger, None, risk.as_deref()));
+        }
+        #[cfg(not(feature = "std"))]
+        held.replay(logger);
+        #[cfg(feature = "std")]
+        {
+            self.listener = listener;
+            let timestamp = self.clock.as_ref().map(|clock| clock.now());
+            self.notify_bbo(timestamp);
+        }
+        result
+    }
+
+    /// Executes one command
+    ///
+    /// Cancels and amendments of orders which are not resting in the book are logged as
+    /// [rejected](LogItem::Rejected) with [UnknownOrder](RejectReason::UnknownOrder).
+    pub fn execute_command(&mut self, command: Command, logger: &mut impl ExecutionLogger) {
+        self.sequenced(logger, |book, logger| {
+            match command {
+                Command::New(order) => book.execute_order(order, logger),
+                Command::Cancel(order_id) => {
+                    if book.cancel_order(order_id, logger).is_err() {
+                        logger.log(LogItem::Rejected { size: 0, reason: RejectReason::UnknownOrder });
+                        logger.finish();
+                    }
+                }
+                Command::Amend { order_id, price, size } => {
+                    if let Err(AmendError::UnknownOrder(_)) = book.amend_order(order_id, price, size, logger) {
+                        logger.log(LogItem::Rejected { size, reason: RejectReason::UnknownOrder });
+                        logger.finish();
+                    }
+                }
+            }
+        })
+    }
+
+    fn take_order_id(&mut self) -> OrderId {
+        let order_id = self.next_order_id;
+        self.next_order_id += 1;
+        order_id
+    }
+
+    fn record_observers(&mut self, order_id: OrderId, submitted: &IncomingOrder, items: &[LogItem]) {
+        if let Some(trail) = &mut self.trail {
+            trail.record(order_id, submitted, items);
+        }
+        if let Some(metrics) = &mut self.metrics {
+            metrics.record(order_id, submitted, items);
+        }
+        if let Some(statuses) = &mut self.statuses {
+            statuses.record(order_id, submitted.size, items);
+        }
+    }
+
+    /// Records further items logged for the existing order `order_id` of the user `user_id`
+    fn record_observer_updates(&mut self, order_id: OrderId, user_id: UserId, items: &[LogItem]) {
+        if let Some(trail) = &mut self.trail {
+            trail.record_update(order_id, items);
+        }
+        if let Some(metrics) = &mut self.metrics {
+            metrics.record_update(order_id, user_id, items);
+        }
+        if let Some(statuses) = &mut self.statuses {
+            statuses.record_update(order_id, items);
+        }
+    }
+
+    fn resting_size(&self) -> Size {
+        self.bid.total_size().saturating_add(self.ask.total_size())
+    }
+
+    /// Returns total size of the resting, stop and parked orders, saturating at the maximum size
+    fn open_size(&self) -> Size {
+        let stops = self.stops.iter().map(|(_, order)| order.size);
+        let parked = self.auction_orders.iter().map(|parked| parked.order.size.saturating_add(parked.odd_lot));
+        stops.chain(parked).fold(self.resting_size(), Size::saturating_add)
+    }
+
+    fn execute(&mut self, mut order: IncomingOrder, order_id: OrderId, logger: &mut impl ExecutionLogger) {
+        let submitted = order.size;
+        let accepted = self.config.validate(&order)
+            .and_then(|_| self.check_limits(&order))
+            .and_then(|_| self.config.apply_collar(&order, self.collar_reference()))
+            .and_then(|price| self.config.round_to_lot(order.size).map(|size| (price, size)))
+            .and_then(|(price, size)| if order.reduce_only {
+                risk::reduce_only_size(order.side, size, self.position(order.user_id)).map(|size| (price, size))
+            } else {
+                Ok((price, size))
+            });
+        match accepted {
+            Ok((price, size)) => {
+                order.price_limit = price;
+                order.size = size;
+            }
+            Err(reason) => {
+                logger.log(LogItem::Rejected {
+                    size: submitted,
+                    reason,
+                });
+                return;
+            }
+        }
+        let odd_lot = submitted - order.size;
+
+        if order.kind == OrderKind::PostOnly {
+            match self.post_only_price(&order) {
+                Ok(price) => order.price_limit = price,
+                Err(reason) => {
+                    logger.log(LogItem::Rejected {
+                        size: submitted,
+                        reason,
+                    });
+                    return;
+                }
+            }
+        }
+
+        let now = self.clock.as_ref().map(|clock| clock.now());
+        let peg = Peg::from_order(&order, now);
+        if let Some(peg) = &peg {
+            let (bid, ask) = self.peg_references();
+            order.price_limit = peg.price(bid, ask).unwrap_or(peg.price_limit);
+            order.kind = OrderKind::Limit;
+            match self.check_pegged_price(&order) {
+                Ok(price) => order.price_limit = price,
+                Err(reason) => {
+                    logger.log(LogItem::Rejected { size: submitted, reason });
+                    return;
+                }
+            }
+        }
+
+        // The odd lot part and the excess of a reduce-only order are logged after the execution results of the accepted size
+        let odd_lot = if odd_lot > 0 { Some(LogItem::cancelled(&order, order_id, odd_lot)) } else { None };
+        if order.kind.trigger_price().is_some() {
+            logger.log(LogItem::Parked { size: order.size });
+            self.stops.insert(order_id, order);
+        } else {
+            self.match_and_enqueue(order, order_id, logger);
+            if let Some(peg) = peg.filter(|_| self.resting_order(order_id).is_some()) {
+                self.pegs.insert(order_id, peg);
+            }
+        }
+        if let Some(item) = odd_lot {
+            logger.log(item);
+        }
+    }
+
+    /// Returns the price at which the post-only `order` can rest without matching on arrival
+    fn post_only_price(&self, order: &IncomingOrder) -> Result<Price, RejectReason> {
+        let touch = match order.side {
+            OrderSide::Buy => self.ask.best_price().filter(|&best| order.price_limit >= best),
+            OrderSide::Sell => self.bid.best_price().filter(|&best| order.price_limit <= best),
+        };
+        let best = match (touch, self.config.post_only) {
+            (None, _) => return Ok(order.price_limit),
+            (Some(_), PostOnlyPolicy::Reject) => return Err(RejectReason::WouldCross),
+            (Some(best), PostOnlyPolicy::Reprice) => best,
+        };
+        let price = match order.side {
+            OrderSide::Buy => best.checked_sub(1),
+            OrderSide::Sell => best.checked_add(1),
+        }.ok_or(RejectReason::WouldCross)?;
+        // The repriced order must still pass validation, e.g. stay non-negative
+        self.config.validate(&IncomingOrder { price_limit: price, ..order.clone() })?;
+        Ok(price)
+    }
+
+    fn match_and_enqueue(&mut self, order: IncomingOrder, order_id: OrderId, logger: &mut impl ExecutionLogger) {
+        #[cfg(feature = "std")]
+        let observed = self.tape.is_some() || (self.risk.is_some() && !self.holding_fills);
+        #[cfg(not(feature = "std"))]
+        let observed = self.tape.is_some();
+        if !observed {
+            return self.match_and_rest(order, order_id, logger);
+        }
+        // Fills reach the tape and the risk checker through the trades
+        let mut tape = self.tape.take();
+        #[cfg(feature = "std")]
+        let risk = if self.holding_fills { None } else { self.risk.clone() };
+        #[cfg(feature = "std")]
+        let mut logger = FillLogger::new(logger, tape.as_mut(), risk.as_deref());
+        #[cfg(not(feature = "std"))]
+        let mut logger = FillLogger::new(logger, tape.as_mut());
+        self.match_and_rest(order, order_id, &mut logger);
+        self.tape = tape;
+    }
+
+    fn match_and_rest(&mut self, order: IncomingOrder, order_id: OrderId, logger: &mut impl ExecutionLogger) {
+        let (kind, min_fill_size) = (order.kind, order.min_fill_size.unwrap_or(0));
+        let (price, side, user_id) = (order.price_limit, order.side, order.user_id);
+        let mut order = TaggedOrder::new(order, order_id);
+
+        // Orders which cannot cross the opposite touch skip the matching pass
+        let last_price = match order {
+            TaggedOrder::Buy(ref mut order) if kind == OrderKind::Market || self.ask.crosses(order.price_limit) => {
+                self.ask.match_order(order, kind, min_fill_size, &self.config, &mut self.trade_sequence, logger)
+            }
+            TaggedOrder::Sell(ref mut order) if kind == OrderKind::Market || self.bid.crosses(order.price_limit) => {
+                self.bid.match_order(order, kind, min_fill_size, &self.config, &mut self.trade_sequence, logger)
+            }
+            _ => None,
+        };
+        if last_price.is_some() {
+            self.last_trade_price = last_price;
+        }
+
+        let size = order.size();
+        // An order not matched for its minimum fill size may still cross the opposite touch
+        let crossing = min_fill_size > 0 && match side {
+            OrderSide::Buy => self.ask.crosses(price),
+            OrderSide::Sell => self.bid.crosses(price),
+        };
+        if size > 0 {
+            match kind {
+                _ if crossing => {
+                    logger.log(LogItem::Cancelled { size, price, side, user_id, order_id });
+                },
+                OrderKind::Limit | OrderKind::Iceberg { .. } | OrderKind::PostOnly | OrderKind::Hidden => {
+                    logger.log(LogItem::Enqueued { size, price, side, user_id, order_id });
+                    self.enqueue(order);
+                },
+                OrderKind::FillOrKill => {
+                    logger.log(LogItem::Cancelled { size, price, side, user_id, order_id });
+                },
+                OrderKind::ImmediateOrCancel | OrderKind::Market => {
+                    logger.log(LogItem::Cancelled { size, price, side, user_id, order_id });
+                },
+                OrderKind::Stop { .. } | OrderKind::StopLimit { .. } | OrderKind::TrailingStop { .. } => unreachable!("stop orders are parked before matching"),
+                OrderKind::Pegged { .. } => unreachable!("pegged orders are priced before matching"),
+            }
+        }
+    }
+
+    /// Inserts `order` into its queue, stamped with the current time
+    fn enqueue(&mut self, order: TaggedOrder) {
+        let now = self.clock.as_ref().map(|clock| clock.now());
+        match order {
+            TaggedOrder::Buy(mut order) => {
+                order.entered_at = now;
+                self.bid.insert(order)
+            }
+            TaggedOrder::Sell(mut order) => {
+                order.entered_at = now;
+                self.ask.insert(order)
+            }
+        }
+    }
+
+    /// Returns the resting `order` as submitted, with the peg of a pegged order
+    fn listed<D: Direction>(&self, order: &Order<D>) -> IncomingOrder {
+        match self.pegs.get(order.order_id) {
+            Some(peg) => IncomingOrder {
+                reduce_only: order.reduce_only,
+                ..peg.to_incoming(order.total_size(), order.user_id, order.expires_at)
+            },
+            None => order.to_incoming(),
+        }
+    }
+
+    /// Returns a vector of [IncomingOrders](IncomingOrder) reflecting the current state of `OrderBook`
+    ///
+    /// [Hidden](OrderKind::Hidden) orders are left out, see [to_vec_with_hidden](OrderBook::to_vec_with_hidden).
+    pub fn to_vec(&self) -> Vec<IncomingOrder> {
+        self.list_orders(false)
+    }
+
+    /// Returns the orders of [to_vec](OrderBook::to_vec) together with the hidden orders, e.g. for snapshots
+    pub fn to_vec_with_hidden(&self) -> Vec<IncomingOrder> {
+        self.list_orders(true)
+    }
+
+    fn list_orders(&self, with_hidden: bool) -> Vec<IncomingOrder> {
+        let mut orders = Vec::new();
+        // Bids are listed from the worst price to the best one, but orders within a price level
+        // keep their arrival order, so that `from_vec` restores the same time priority.
+        let bids: Vec<_> = self.bid.into_iter().collect();
+        let mut end = bids.len();
+        while end > 0 {
+            let price = bids[end - 1].price_limit;
+            let mut start = end - 1;
+            while start > 0 && bids[start - 1].price_limit == price {
+                start -= 1;
+            }
+            for order in bids[start..end].iter().filter(|order| with_hidden || !order.dark) {
+                orders.push(self.listed(order));
+            }
+            end = start;
+        }
+        for order in self.ask.into_iter().filter(|order| with_hidden || !order.dark) {
+            orders.push(self.listed(order));
+        }
+        // Stop orders come last, so that `from_vec` parks them again without triggering
+        orders.extend(self.stops.iter().map(|(_, order)| order.clone()));
+        orders
+    }
+}
+
+impl OrderBook {
+    /// Writes state of the book in the binary [book state](wire::encode_book_state) format
+    #[cfg(feature = "std")]
+    pub fn write_snapshot(&self, mut writer: impl Write) -> io::Result<()> {
+        writer.write_all(&wire::encode_book_state(self))
+    }
+
+    /// Reads a book written by [write_snapshot](OrderBook::write_snapshot) with the default configuration
+    ///
+    /// Orders are put into the queues with their identifiers and positions instead of being replayed,
+    /// which makes loading much faster than [from_vec](OrderBook::from_vec).
+    #[cfg(feature = "std")]
+    pub fn read_snapshot(reader: impl Read) -> Result<Self, WireError> {
+        Self::read_snapshot_with_config(reader, BookConfig::default())
+    }
+
+    /// Reads a book written by [write_snapshot](OrderBook::write_snapshot) with the given configuration
+    #[cfg(feature = "std")]
+    pub fn read_snapshot_with_config(mut reader: impl Read, config: BookConfig) -> Result<Self, WireError> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes).map_err(|e| WireError::Io(e.kind()))?;
+        wire::decode_book_state(&bytes, config)
+    }
+
+    /// Creates an `OrderBook` from vector of [IncomingOrders](IncomingOrder)
+    pub fn from_vec(orders: Vec<IncomingOrder>) -> Self {
+        let mut book = Self::new();
+        let mut logger = DummyLogger;
+        for order in orders {
+            book.execute_order(order, &mut logger);
+        }
+        book
+    }
+}
+
+impl Default for OrderBook {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Serialized as the ordered list of resting orders returned by [to_vec_with_hidden](OrderBook::to_vec_with_hidden)
+#[cfg(feature = "serde")]
+impl serde::Serialize for OrderBook {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(self.to_vec_with_hidden())
+    }
+}
+
+/// Deserialized with [from_vec](OrderBook::from_vec), so the book gets the default configuration
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for OrderBook {
+    fn deserialize<De: serde::Deserializer<'de>>(deserializer: De) -> Result<Self, De::Error> {
+        Vec::<IncomingOrder>::deserialize(deserializer).map(Self::from_vec)
+    }
+}
+
+impl<QB: Queue<Order<Buy>>, QA: Queue<Order<Sell>>> fmt::Debug for OrderBook<QB, QA> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "== ORDER BOOK START")?;
+        for order in (&self.ask).into_iter().rev() {
+            writeln!(f, "{}", order.to_incoming())?;
+        }
+        writeln!(f, "-----")?;
+        for order in &self.bid {
+            writeln!(f, "{}", order.to_incoming())?;
+        }
+        if !self.indicative.is_empty() {
+            writeln!(f, "== INDICATIVE")?;
+            for (_, order) in self.indicative.iter() {
+                writeln!(f, "{}", order)?;
+            }
+        }
+        if !self.stops.is_empty() {
+            writeln!(f, "== STOPS")?;
+            for (_, order) in self.stops.iter() {
+                writeln!(f, "{}", order)?;
+            }
+        }
+        writeln!(f, "== ORDER BOOK END")?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use crate::order::*;
+    use crate::log::{DummyLogger, VectorLogger, CountingLogger, EventCount, LogItem, SequencedLogItem, SequencedVectorLogger, Trade, TradeLogger, FnLogger, TeeLogger, ExecutionLogger};
+    use crate::config::{BookConfig, BookState, CircuitBreaker, CollarPolicy, CollarReference, ConfigError, HaltPolicy, MatchingAlgorithm,
+                        OddLotPolicy, PostOnlyPolicy, PriceCollar, RejectReason};
+    use crate::trail::TrailEvent;
+    use crate::status::OrderStatus;
+    use crate::tick::TickSize;
+    use crate::decode::Command;
+    use crate::depth::{BookStats, DepthSnapshot, PriceLevel};
+    use crate::{OrderBook, OrderQueue, BatchError, BatchOptions};
+    use crate::queues::Queue;
+    use crate::summary::ExecutionSummary;
+
+    fn get_order<'a, D: 'a+Direction>(queue: impl IntoIterator<Item=&'a Order<D>>, index: usize) -> IncomingOrder {
+        queue.into_iter().nth(index).expect("invalid order index").to_incoming()
+    }
+
+    fn check_order<'a, D: 'a+Direction>(queue: impl IntoIterator<Item=&'a Order<D>>, side: &str, index: usize, s: &str) {
+        let order = get_order(queue, index);
+        if order.to_string() != s {
+            panic!("Invalid {} order at index {}: {}, should be {}", side, index, order, s);
+        }
+        assert_eq!(order.to_string(), s);
+    }
+
+    fn check_len<D: Direction>(queue: &OrderQueue<D>, side: &str, n: usize) {
+        if queue.len() != n {
+            panic!("Invalid {} queue length: {}, should be {}", side, queue.len(), n);
+        }
+    }
+
+    pub(crate) fn check_log(log: &[LogItem], expected: &[&str]) {
+        if log.len() != expected.len() {
+            panic!("Incorrect log length: {}, expected {}", log.len(), expected.len());
+        }
+        for (i, log_item) in log.iter().enumerate() {
+            if log_item.to_string() != expected[i] {
+                panic!("Incorrect log item {}: '{}', expected '{}'", i, log_item, expected[i]);
+            }
+        }
+    }
+
+    pub trait OrderBookExt {
+        fn check_bid(&self, index: usize, s: &str);
 
         fn check_bid_list(&self, list: &[&str]);
 
-        fn check_ask(&self, index: usize, s: &str);
+        fn check_ask(&self, index: usize, s: &str);
+
+        fn check_ask_list(&self, list: &[&str]);
+
+        fn check_bid_len(&self, n: usize);
+
+        fn check_ask_len(&self, n: usize);
+
+        fn from_orders(list: &[&str]) -> Self;
+    }
+
+    impl OrderBookExt for OrderBook {
+        fn check_bid(&self, index: usize, s: &str) {
+            check_order(&self.bid, "bid", index, s);
+        }
+
+        fn check_bid_list(&self, list: &[&str]) {
+            check_len(&self.bid, "bid", list.len());
+            for (index, s) in list.iter().enumerate() {
+                check_order(&self.bid, "bid", index, s);
+            }
+        }
+
+        fn check_ask(&self, index: usize, s: &str) {
+            check_order(&self.ask, "ask", index, s);
+        }
+
+        fn check_ask_list(&self, list: &[&str]) {
+            check_len(&self.ask, "ask", list.len());
+            for (index, s) in list.iter().enumerate() {
+                check_order(&self.ask, "ask", index, s);
+            }
+        }
+
+        fn check_bid_len(&self, n: usize) {
+            check_len(&self.bid, "bid", n);
+        }
+
+        fn check_ask_len(&self, n: usize) {
+            check_len(&self.ask, "ask", n);
+        }
+
+        fn from_orders(list: &[&str]) -> Self {
+            let mut logger = DummyLogger;
+
+            let mut book = OrderBook::new();
+            for s in list {
+                book.execute_order(s.parse().unwrap(), &mut logger);
+            }
+            book
+        }
+    }
+
+    #[test]
+    fn new_book_is_empty() {
+        let book = OrderBook::new();
+        book.check_bid_len(0);
+        book.check_ask_len(0);
+    }
+
+    #[test]
+    fn book_insert_correct_queue() {
+        let mut logger = DummyLogger;
+
+        let mut book = OrderBook::new();
+        let order = "Lim B $100 #200 u42".parse().unwrap();
+        book.execute_order(order, &mut logger);
+        book.check_bid_len(1);
+        book.check_ask_len(0);
+
+        let mut book = OrderBook::new();
+        let order = "Lim S $100 #200 u42".parse().unwrap();
+        book.execute_order(order, &mut logger);
+        book.check_bid_len(0);
+        book.check_ask_len(1);
+    }
+
+    #[test]
+    fn book_insert_correct_ordering_by_price() {
+        let orders = [
+            "Lim B $110 #100 u42",
+            "Lim B $130 #100 u42",
+            "Lim B $120 #100 u42",
+            "Lim B $100 #100 u42",
+        ];
+        let book = OrderBook::from_orders(&orders);
+        book.check_bid_list(&[
+            orders[1],
+            orders[2],
+            orders[0],
+            orders[3],
+        ]);
+
+        let orders = [
+            "Lim S $110 #100 u42",
+            "Lim S $130 #100 u42",
+            "Lim S $120 #100 u42",
+            "Lim S $100 #100 u42",
+        ];
+        let book = OrderBook::from_orders(&orders);
+        book.check_ask_list(&[
+            orders[3],
+            orders[0],
+            orders[2],
+            orders[1],
+        ]);
+    }
+
+    #[test]
+    fn book_insert_correct_ordering_by_arrival() {
+        let orders = [
+            "Lim B $100 #100 u41",
+            "Lim B $101 #100 u42",
+            "Lim B $102 #100 u43",
+            "Lim B $101 #100 u44",
+            "Lim B $101 #100 u45",
+        ];
+        let book = OrderBook::from_orders(&orders);
+        book.check_bid_list(&[
+            orders[2],
+            orders[1],
+            orders[3],
+            orders[4],
+            orders[0],
+        ]);
+
+        let orders = [
+            "Lim S $100 #100 u41",
+            "Lim S $101 #100 u42",
+            "Lim S $102 #100 u43",
+            "Lim S $101 #100 u44",
+            "Lim S $101 #100 u45",
+        ];
+        let book = OrderBook::from_orders(&orders);
+        book.check_ask_list(&[
+            orders[0],
+            orders[1],
+            orders[3],
+            orders[4],
+            orders[2],
+        ]);
+    }
+
+    #[test]
+    fn match_ignores_orders_with_own_user_id() {
+        let orders = [
+            "Lim B $103 #1 u3",
+            "Lim B $102 #1 u0",
+            "Lim B $102 #1 u2",
+            "Lim B $101 #1 u1",
+            "Lim B $100 #1 u0",
+            "Lim S $90 #5 u0",
+        ];
+        let book = OrderBook::from_orders(&orders);
+        book.check_bid_list(&[
+            orders[1],
+            orders[4],
+        ]);
+        book.check_ask_list(&[
+            "Lim S $90 #2 u0"
+        ]);
+
+        let orders = [
+            "Lim S $103 #1 u3",
+            "Lim S $102 #1 u0",
+            "Lim S $102 #1 u2",
+            "Lim S $101 #1 u1",
+            "Lim S $100 #1 u0",
+            "Lim B $110 #5 u0",
+        ];
+        let book = OrderBook::from_orders(&orders);
+        book.check_ask_list(&[
+            orders[4],
+            orders[1],
+        ]);
+        book.check_bid_list(&[
+            "Lim B $110 #2 u0"
+        ]);
+    }
+
+    #[test]
+    fn test_fill_or_kill() {
+        /* Selling */
+        let orders = [
+            "Lim B $103 #1 u1",
+            "Lim B $102 #1 u2",
+            "Lim B $102 #1 u3",
+            "Lim B $101 #1 u4",
+            "Lim B $100 #1 u5",
+        ];
+
+        // No orders matched incoming order
+        let mut book = OrderBook::from_orders(&orders);
+        let mut logger = VectorLogger::new();
+        book.execute_order("FoK S $110 #5 u0".parse().unwrap(), &mut logger);
+        check_log(logger.as_slice(), &["C S #5 $110 u0 o6"]);
+        book.check_bid_list(&orders);
+        book.check_ask_len(0);
+
+        // Some orders matched incoming order, order was not fulfilled
+        let mut book = OrderBook::from_orders(&orders);
+        let mut logger = VectorLogger::new();
+        book.execute_order("FoK S $101 #5 u0".parse().unwrap(), &mut logger);
+        check_log(logger.as_slice(), &["C S #5 $101 u0 o6"]);
+        book.check_bid_list(&orders);
+        book.check_ask_len(0);
+
+        // Incoming order was fulfilled
+        let mut book = OrderBook::from_orders(&orders);
+        let mut logger = VectorLogger::new();
+        book.execute_order("FoK S $100 #4 u0".parse().unwrap(), &mut logger);
+        check_log(logger.as_slice(), &[
+            "F #1 $103 u1 o1",
+            "F #1 $102 u2 o2",
+            "F #1 $102 u3 o3",
+            "F #1 $101 u4 o4",
+        ]);
+        book.check_bid_list(&[orders[4]]);
+        book.check_ask_len(0);
+
+        /* Buying */
+        let orders = [
+            "Lim S $100 #1 u1",
+            "Lim S $101 #1 u2",
+            "Lim S $102 #1 u3",
+            "Lim S $102 #1 u4",
+            "Lim S $103 #1 u5",
+        ];
+
+        // No orders matched incoming order
+        let mut book = OrderBook::from_orders(&orders);
+        let mut logger = VectorLogger::new();
+        book.execute_order("FoK B $90 #5 u0".parse().unwrap(), &mut logger);
+        check_log(logger.as_slice(), &["C B #5 $90 u0 o6"]);
+        book.check_ask_list(&orders);
+        book.check_bid_len(0);
+
+        // Some orders matched incoming order, order was not fulfilled
+        let mut book = OrderBook::from_orders(&orders);
+        let mut logger = VectorLogger::new();
+        book.execute_order("FoK B $102 #5 u0".parse().unwrap(), &mut logger);
+        check_log(logger.as_slice(), &["C B #5 $102 u0 o6"]);
+        book.check_ask_list(&orders);
+        book.check_bid_len(0);
+
+        // Incoming order was fulfilled
+        let mut book = OrderBook::from_orders(&orders);
+        let mut logger = VectorLogger::new();
+        book.execute_order("FoK B $110 #4 u0".parse().unwrap(), &mut logger);
+        check_log(logger.as_slice(), &[
+            "F #1 $100 u1 o1",
+            "F #1 $101 u2 o2",
+            "F #1 $102 u3 o3",
+            "F #1 $102 u4 o4",
+        ]);
+        book.check_ask_list(&[orders[4]]);
+        book.check_bid_len(0);
+    }
+
+    #[test]
+    fn test_immediate_or_cancel() {
+        /* Selling */
+        let orders = [
+            "Lim B $103 #1 u1",
+            "Lim B $102 #1 u2",
+            "Lim B $102 #1 u3",
+            "Lim B $101 #1 u4",
+            "Lim B $100 #1 u5",
+        ];
+
+        // No orders matched incoming order
+        let mut book = OrderBook::from_orders(&orders);
+        let mut logger = VectorLogger::new();
+        book.execute_order("IoC S $110 #5 u0".parse().unwrap(), &mut logger);
+        check_log(logger.as_slice(), &["C S #5 $110 u0 o6"]);
+        book.check_bid_list(&orders);
+        book.check_ask_len(0);
+
+        // Some orders matched incoming order, order was partially fulfilled
+        let mut book = OrderBook::from_orders(&orders);
+        let mut logger = VectorLogger::new();
+        book.execute_order("IoC S $101 #5 u0".parse().unwrap(), &mut logger);
+        check_log(logger.as_slice(), &[
+            "F #1 $103 u1 o1",
+            "F #1 $102 u2 o2",
+            "F #1 $102 u3 o3",
+            "F #1 $101 u4 o4",
+            "C S #1 $101 u0 o6",
+        ]);
+        book.check_bid_list(&[orders[4]]);
+        book.check_ask_len(0);
+
+        // Incoming order was fully fulfilled
+        let mut book = OrderBook::from_orders(&orders);
+        let mut logger = VectorLogger::new();
+        book.execute_order("IoC S $100 #4 u0".parse().unwrap(), &mut logger);
+        check_log(logger.as_slice(), &[
+            "F #1 $103 u1 o1",
+            "F #1 $102 u2 o2",
+            "F #1 $102 u3 o3",
+            "F #1 $101 u4 o4",
+        ]);
+        book.check_bid_list(&[orders[4]]);
+        book.check_ask_len(0);
+
+        /* Buying */
+        let orders = [
+            "Lim S $100 #1 u1",
+            "Lim S $101 #1 u2",
+            "Lim S $102 #1 u3",
+            "Lim S $102 #1 u4",
+            "Lim S $103 #1 u5",
+        ];
+
+        // No orders matched incoming order
+        let mut book = OrderBook::from_orders(&orders);
+        let mut logger = VectorLogger::new();
+        book.execute_order("IoC B $90 #5 u0".parse().unwrap(), &mut logger);
+        check_log(logger.as_slice(), &["C B #5 $90 u0 o6"]);
+        book.check_ask_list(&orders);
+        book.check_bid_len(0);
+
+        // Some orders matched incoming order, order was partially fulfilled
+        let mut book = OrderBook::from_orders(&orders);
+        let mut logger = VectorLogger::new();
+        book.execute_order("IoC B $102 #5 u0".parse().unwrap(), &mut logger);
+        check_log(logger.as_slice(), &[
+            "F #1 $100 u1 o1",
+            "F #1 $101 u2 o2",
+            "F #1 $102 u3 o3",
+            "F #1 $102 u4 o4",
+            "C B #1 $102 u0 o6",
+        ]);
+        book.check_ask_list(&[orders[4]]);
+        book.check_bid_len(0);
+
+        // Incoming order was fully fulfilled
+        let mut book = OrderBook::from_orders(&orders);
+        let mut logger = VectorLogger::new();
+        book.execute_order("IoC B $110 #4 u0".parse().unwrap(), &mut logger);
+        check_log(logger.as_slice(), &[
+            "F #1 $100 u1 o1",
+            "F #1 $101 u2 o2",
+            "F #1 $102 u3 o3",
+            "F #1 $102 u4 o4",
+        ]);
+        book.check_ask_list(&[orders[4]]);
+        book.check_bid_len(0);
+    }
+
+    #[test]
+    fn test_matching1() {
+        // Source: _MessageBook1.txt
+        let orders = [
+            "Lim S $110 #6 u1",
+            "Lim S $120 #3 u2",
+            "Lim S $115 #4 u3",
+            "Lim S $105 #5 u4",
+            "Lim S $110 #2 u5",
+            "Lim S $105 #3 u6",
+            "Lim B $130 #23 u7",
+        ];
+
+        let expected_log = [
+            "Q S #6 $110 u1 o1",
+            "Q S #3 $120 u2 o2",
+            "Q S #4 $115 u3 o3",
+            "Q S #5 $105 u4 o4",
+            "Q S #2 $110 u5 o5",
+            "Q S #3 $105 u6 o6",
+            "F #5 $105 u4 o4",
+            "F #3 $105 u6 o6",
+            "F #6 $110 u1 o1",
+            "F #2 $110 u5 o5",
+            "F #4 $115 u3 o3",
+            "F #3 $120 u2 o2",
+        ];
+
+        let mut logger = VectorLogger::new();
+        let mut book = OrderBook::new();
+        for s in &orders {
+            book.execute_order(s.parse().unwrap(), &mut logger);
+        }
+        book.check_bid_len(0);
+        book.check_ask_len(0);
+
+        check_log(logger.as_slice(), &expected_log);
+    }
+
+    #[test]
+    fn test_matching2() {
+        // Source: _MessageBook2.txt
+        let orders = [
+            "Lim S $120 #1 u1",
+            "Lim S $115 #4 u2",
+            "Lim B $108 #3 u3",
+            "Lim S $105 #5 u4",
+            "Lim S $105 #6 u5",
+            "Lim B $110 #5 u6",
+            "Lim B $113 #2 u7",
+            "Lim B $118 #6 u8",
+        ];
+
+        let expected_log = [
+            "Q S #1 $120 u1 o1",
+            "Q S #4 $115 u2 o2",
+            "Q B #3 $108 u3 o3",
+
+            // Lim S $105 #5 u4
+            "F #3 $108 u3 o3",
+            "Q S #2 $105 u4 o4",
+
+            // Lim S $105 #6 u5
+            "Q S #6 $105 u5 o5",
+
+            // Lim B $110 #5 u6
+            "F #2 $105 u4 o4",
+            "F #3 $105 u5 o5",
+
+            // Lim B $113 #2 u7
+            "F #2 $105 u5 o5",
+
+            // Lim B $118 #6 u8
+            "F #1 $105 u5 o5",
+            "F #4 $115 u2 o2",
+            "Q B #1 $118 u8 o8",
+        ];
+
+        let mut logger = VectorLogger::new();
+        let mut book = OrderBook::new();
+        for s in &orders {
+            book.execute_order(s.parse().unwrap(), &mut logger);
+        }
+
+        book.check_bid_list(&[
+            "Lim B $118 #1 u8",
+        ]);
+        book.check_ask_list(&[
+            "Lim S $120 #1 u1",
+        ]);
+
+        check_log(logger.as_slice(), &expected_log);
+    }
+
+    #[test]
+    fn strict_book_rejects_degenerate_orders() {
+        let mut book = OrderBook::with_config(BookConfig::strict());
+        let mut logger = VectorLogger::new();
+        book.execute_order("Lim B $100 #0 u1".parse().unwrap(), &mut logger);
+        book.execute_order("Lim B $0 #5 u1".parse().unwrap(), &mut logger);
+        check_log(logger.as_slice(), &["R #0 zero-size", "R #5 zero-price"]);
+        book.check_bid_len(0);
+
+        let mut book = OrderBook::new();
+        let mut logger = VectorLogger::new();
+        book.execute_order("Lim B $0 #5 u1".parse().unwrap(), &mut logger);
+        check_log(logger.as_slice(), &["Q B #5 $0 u1 o1"]);
+    }
+
+    #[test]
+    fn negative_prices_match_when_allowed() {
+        let mut book = OrderBook::with_config(BookConfig {
+            allow_negative_prices: true,
+            ..BookConfig::default()
+        });
+        let mut logger = VectorLogger::new();
+        for s in &["Lim S $-3 #1 u1", "Lim S $-5 #1 u2", "Lim S $0 #1 u3", "Lim B $-4 #2 u4"] {
+            book.execute_order(s.parse().unwrap(), &mut logger);
+        }
+        check_log(logger.as_slice(), &["Q S #1 $-3 u1 o1", "Q S #1 $-5 u2 o2", "Q S #1 $0 u3 o3", "F #1 $-5 u2 o2", "Q B #1 $-4 u4 o4"]);
+        book.check_bid_list(&["Lim B $-4 #1 u4"]);
+        book.check_ask_list(&["Lim S $-3 #1 u1", "Lim S $0 #1 u3"]);
+
+        let mut book = OrderBook::new();
+        let mut logger = VectorLogger::new();
+        book.execute_order("Lim S $-3 #1 u1".parse().unwrap(), &mut logger);
+        check_log(logger.as_slice(), &["R #1 negative-price"]);
+    }
+
+    #[test]
+    fn counting_logger_keeps_totals_across_orders() {
+        let orders = [
+            "Lim B $103 #1 u1",
+            "Lim B $102 #1 u2",
+            "Lim B $101 #1 u3",
+        ];
+        let mut book = OrderBook::from_orders(&orders);
+        let mut logger = CountingLogger::new();
+        book.execute_order("IoC S $102 #3 u0".parse().unwrap(), &mut logger);
+        book.execute_order("FoK S $101 #2 u0".parse().unwrap(), &mut logger);
+        book.execute_order("Lim S $110 #4 u0".parse().unwrap(), &mut logger);
+
+        let counts = logger.counts();
+        assert_eq!(counts.fulfilled, EventCount { count: 2, size: 2 });
+        assert_eq!(counts.cancelled, EventCount { count: 2, size: 3 });
+        assert_eq!(counts.enqueued, EventCount { count: 1, size: 4 });
+        assert_eq!(counts.rejected, EventCount::default());
+    }
+
+    #[test]
+    fn odd_lots_are_rounded_down() {
+        let mut book = OrderBook::with_config(BookConfig {
+            lot_size: 10,
+            odd_lot: OddLotPolicy::RoundDown,
+            ..BookConfig::default()
+        });
+        book.set_audit(true);
+        let expected_logs: [(&str, &[&str]); 4] = [
+            ("Lim S $100 #20 u1", &["Q S #20 $100 u1 o1"]),
+            ("Lim B $100 #35 u2", &["F #20 $100 u1 o1", "Q B #10 $100 u2 o2", "C B #5 $100 u2 o2"]),
+            ("FoK B $100 #15 u3", &["C B #10 $100 u3 o3", "C B #5 $100 u3 o3"]),
+            ("Lim S $100 #5 u4", &["R #5 odd-lot"]),
+        ];
+        for (order, expected_log) in expected_logs.iter() {
+            let mut logger = VectorLogger::new();
+            book.execute_order(order.parse().unwrap(), &mut logger);
+            check_log(logger.as_slice(), expected_log);
+        }
+        book.check_bid_list(&["Lim B $100 #10 u2"]);
+    }
+
+    #[test]
+    fn size_increments_and_bounds_are_enforced() {
+        let mut book = OrderBook::with_config(BookConfig {
+            lot_size: 10,
+            odd_lot: OddLotPolicy::Reject,
+            min_size: Some(20),
+            max_size: Some(1000),
+            ..BookConfig::default()
+        });
+        let expected_logs: [(&str, &[&str]); 4] = [
+            ("Lim S $100 #20 u1", &["Q S #20 $100 u1 o1"]),
+            ("Lim B $100 #25 u2", &["R #25 odd-lot"]),
+            ("Lim B $100 #10 u2", &["R #10 size-too-small"]),
+            ("Lim B $100 #1010 u2", &["R #1010 size-too-large"]),
+        ];
+        for (order, expected_log) in expected_logs.iter() {
+            let mut logger = VectorLogger::new();
+            book.execute_order(order.parse().unwrap(), &mut logger);
+            check_log(logger.as_slice(), expected_log);
+        }
+        assert_eq!(book.amend_order(1, 100, 10, &mut DummyLogger), Err(AmendError::Rejected(RejectReason::SizeTooSmall)));
+        book.check_ask_list(&["Lim S $100 #20 u1"]);
+    }
+
+    #[test]
+    fn audit_mode_is_silent_for_correct_execution() {
+        let orders = [
+            "Lim B $103 #1 u1",
+            "Lim B $102 #1 u0",
+            "Lim B $101 #1 u2",
+        ];
+        let mut book = OrderBook::from_orders(&orders);
+        book.set_audit(true);
+
+        let mut logger = VectorLogger::new();
+        book.execute_order("FoK S $101 #3 u0".parse().unwrap(), &mut logger);
+        check_log(logger.as_slice(), &["C S #3 $101 u0 o4"]);
+
+        let mut logger = VectorLogger::new();
+        book.execute_order("Lim S $101 #3 u0".parse().unwrap(), &mut logger);
+        check_log(logger.as_slice(), &["F #1 $103 u1 o1", "F #1 $101 u2 o3", "Q S #1 $101 u0 o5"]);
+    }
+
+    #[test]
+    fn order_trail_records_lifecycle() {
+        let mut book = OrderBook::new();
+        book.set_order_trail(true);
+        let mut logger = DummyLogger;
+        book.execute_order("Lim S $100 #5 u1".parse().unwrap(), &mut logger);
+        let maker_id = book.last_order_id().unwrap();
+        book.execute_order("IoC B $100 #7 u2".parse().unwrap(), &mut logger);
+        let taker_id = book.last_order_id().unwrap();
+        assert_eq!(taker_id, maker_id + 1);
+
+        let maker = book.order_trail(maker_id).unwrap();
+        assert_eq!(maker.events(), &[
+            TrailEvent::Submitted,
+            TrailEvent::Accepted,
+            TrailEvent::Enqueued { size: 5 },
+            TrailEvent::Filled { size: 5, price: 100, counterparty: taker_id, passive: true },
+        ]);
+        assert!(maker.is_closed());
+
+        let taker = book.order_trail(taker_id).unwrap();
+        assert_eq!(taker.events(), &[
+            TrailEvent::Submitted,
+            TrailEvent::Accepted,
+            TrailEvent::Filled { size: 5, price: 100, counterparty: maker_id, passive: false },
+            TrailEvent::Cancelled { size: 2 },
+        ]);
+        assert_eq!(taker.filled_size(), 5);
+        assert_eq!(taker.average_price(), Some(100.0));
+        assert!(taker.is_closed());
+    }
+
+    #[test]
+    fn user_metrics_are_collected() {
+        let mut book = OrderBook::new();
+        book.set_user_metrics(true);
+        let orders = [
+            "Lim S $100 #5 u1",
+            "Lim S $101 #5 u1",
+            "Lim B $90 #1 u3",
+            "IoC B $100 #3 u2",
+            "IoC B $100 #3 u2",
+            "Lim B $100 #0 u2",
+        ];
+        let mut logger = DummyLogger;
+        for s in &orders {
+            book.execute_order(s.parse().unwrap(), &mut logger);
+        }
+
+        let maker = book.user_metrics(1).unwrap();
+        assert_eq!((maker.orders, maker.trades, maker.cancels), (2, 2, 0));
+        assert_eq!(maker.order_to_trade_ratio(), Some(1.0));
+        assert_eq!(maker.average_resting_time(), Some(4.0));
+
+        let taker = book.user_metrics(2).unwrap();
+        assert_eq!((taker.orders, taker.trades, taker.cancels), (3, 2, 1));
+        assert_eq!(taker.order_to_trade_ratio(), Some(1.5));
+        assert_eq!(taker.cancel_rate(), Some(1.0 / 3.0));
+        assert_eq!(taker.average_resting_time(), None);
+
+        assert_eq!(book.user_metrics(3).unwrap().order_to_trade_ratio(), None);
+        assert_eq!(book.all_user_metrics().count(), 3);
+
+        // Odd lots and remainders of one order are counted as one cancel
+        let mut book = OrderBook::with_config(BookConfig { lot_size: 10, odd_lot: OddLotPolicy::RoundDown, ..BookConfig::default() });
+        book.set_user_metrics(true);
+        for s in &["Lim S $100 #10 u1", "IoC B $100 #25 u2", "Lim B $90 #15 u2"] {
+            book.execute_order(s.parse().unwrap(), &mut logger);
+        }
+        book.cancel_order(3, &mut logger).unwrap();
+        let taker = book.user_metrics(2).unwrap();
+        assert_eq!((taker.orders, taker.trades, taker.cancels), (2, 1, 2));
+        assert_eq!(taker.cancel_rate(), Some(1.0));
+    }
+
+    #[test]
+    fn orders_are_found_by_index() {
+        let orders = [
+            "Lim S $102 #1 u1",
+            "Ice S $101 #4 u2 ^2",
+            "Lim S $101 #3 u3",
+            "Lim S $100 #1 u4",
+            "Lim S $101 #5 u5",
+        ];
+        let mut book = OrderBook::from_orders(&orders);
+        assert_eq!((1..=5).map(|id| book.inspect_order(id).unwrap().position).collect::<Vec<_>>(), [4, 1, 2, 0, 3]);
+
+        let mut logger = VectorLogger::new();
+        book.execute_order("IoC B $101 #4 u6".parse().unwrap(), &mut logger);
+        assert_eq!(book.inspect_order(4), None);
+        assert_eq!(book.inspect_order(2).unwrap().position, 2);
+        assert_eq!(book.inspect_order(3).unwrap().position, 0);
+        assert_eq!(book.cancel_order(4, &mut logger), Err(CancelError::UnknownOrder(4)));
+        assert_eq!(book.cancel_order(5, &mut logger).unwrap().order.to_string(), orders[4]);
+        assert_eq!(book.inspect_order(1).unwrap().position, 2);
+    }
+
+    #[test]
+    fn book_stats() {
+        assert_eq!(OrderBook::new().stats(5), BookStats::default());
+
+        let book = OrderBook::from_orders(&[
+            "Lim B $100 #3 u1",
+            "Lim B $100 #1 u2",
+            "Lim B $98 #4 u3",
+            "Ice S $102 #10 u4 ^2",
+            "Lim S $103 #2 u5",
+        ]);
+        let stats = book.stats(1);
+        assert_eq!((stats.bid_volume, stats.ask_volume, stats.bid_orders, stats.ask_orders), (8, 4, 3, 2));
+        assert_eq!((stats.bid_vwap, stats.ask_vwap), (Some(100.0), Some(102.0)));
+        assert_eq!(stats.imbalance, Some(2.0 / 6.0));
+
+        let stats = book.stats(2);
+        assert_eq!((stats.bid_vwap, stats.ask_vwap), (Some(99.0), Some(102.5)));
+        assert_eq!(stats.imbalance, Some(4.0 / 12.0));
+    }
+
+    #[test]
+    fn inspect_and_force_cancel() {
+        let orders = [
+            "Lim B $100 #1 u1",
+            "Lim B $101 #2 u2",
+            "Lim B $100 #3 u3",
+            "Lim S $110 #4 u4",
+        ];
+        let mut book = OrderBook::from_orders(&orders);
+        book.set_order_trail(true);
+
+        let view = book.inspect_order(3).unwrap();
+        assert_eq!(view.order.to_string(), orders[2]);
+        assert_eq!(view.position, 2);
+        assert_eq!(book.inspect_order(4).unwrap().position, 0);
+        assert_eq!(book.inspect_order(5), None);
+
+        let mut logger = VectorLogger::new();
+        assert_eq!(book.force_cancel(1, &mut logger).unwrap().order.to_string(), orders[0]);
+        assert_eq!(book.force_cancel(1, &mut logger), None);
+        check_log(logger.as_slice(), &["A #1 o1"]);
+        book.check_bid_list(&[orders[1], orders[2]]);
+        assert_eq!(book.inspect_order(3).unwrap().position, 1);
+
+        book.execute_order("Lim S $120 #1 u5".parse().unwrap(), &mut logger);
+        let order_id = book.last_order_id().unwrap();
+        book.force_cancel(order_id, &mut logger);
+        assert_eq!(book.order_trail(order_id).unwrap().events().last(), Some(&TrailEvent::ForceCancelled { size: 1 }));
+        book.check_ask_list(&[orders[3]]);
+    }
+
+    #[test]
+    fn resting_orders_record_entry_times() {
+        use std::sync::Arc;
+        use std::sync::atomic::{AtomicU64, Ordering};
+
+        let mut book = OrderBook::new();
+        book.execute_order("Lim B $100 #5 u1".parse().unwrap(), &mut DummyLogger);
+        assert_eq!(book.inspect_order(1).unwrap().entered_at, None);
+
+        let time = Arc::new(AtomicU64::new(10));
+        let clock_time = time.clone();
+        book.set_clock(Some(Arc::new(move || clock_time.load(Ordering::Relaxed))));
+        book.execute_order("Lim B $100 #5 u2".parse().unwrap(), &mut DummyLogger);
+        time.store(20, Ordering::Relaxed);
+        book.execute_order("Lim B $100 #5 u3".parse().unwrap(), &mut DummyLogger);
+        let view = book.inspect_order(2).unwrap();
+        assert_eq!((view.position, view.entered_at, view.age(25)), (1, Some(10), Some(15)));
+
+        // Reducing the size keeps the entry time, increasing it moves the order to the end of the level
+        time.store(30, Ordering::Relaxed);
+        book.amend_order(2, 100, 4, &mut DummyLogger).unwrap();
+        assert_eq!(book.inspect_order(2).unwrap().entered_at, Some(10));
+        book.amend_order(2, 100, 6, &mut DummyLogger).unwrap();
+        let view = book.inspect_order(2).unwrap();
+        assert_eq!((view.position, view.entered_at), (2, Some(30)));
+        assert_eq!(book.bid().into_iter().map(|order| order.entered_at()).collect::<Vec<_>>(), [None, Some(20), Some(30)]);
+    }
+
+    #[test]
+    fn queue_positions_count_size_ahead() {
+        let book = OrderBook::from_orders(&[
+            "Lim S $101 #2 u1",
+            "Ice S $102 #10 u2 ^3",
+            "Hid S $102 #4 u3",
+            "Lim S $102 #5 u4",
+            "Lim S $103 #1 u5",
+            "Hid S $102 #6 u6",
+        ]);
+        let position = |order_id| book.queue_position(order_id).unwrap();
+        assert_eq!(position(1), QueuePosition::default());
+        // Hidden orders and the iceberg reserve at the same price are matched later
+        assert_eq!(position(4), QueuePosition { orders_ahead: 2, better_size: 2, level_size: 3 });
+        assert_eq!(position(4).size_ahead(), 5);
+        // Displayed orders are ahead of hidden orders, which keep their time priority
+        assert_eq!(position(6), QueuePosition { orders_ahead: 4, better_size: 2, level_size: 12 });
+        assert_eq!(position(5), QueuePosition { orders_ahead: 5, better_size: 27, level_size: 0 });
+        assert_eq!(book.queue_position(7), None);
+    }
+
+    #[test]
+    fn kill_switch_cancels_all_orders() {
+        let orders = [
+            "Lim B $100 #1 u1",
+            "Lim S $110 #2 u2",
+            "Lim B $99 #3 u2",
+            "Stp B $0 #4 u1 @120",
+            "Lim S $111 #5 u1",
+        ];
+        let mut book = OrderBook::from_orders(&orders);
+
+        let mut logger = VectorLogger::new();
+        assert_eq!(book.cancel_all_for_user(1, &mut logger), 3);
+        check_log(logger.as_slice(), &["A #1 o1", "A #4 o4", "A #5 o5"]);
+        book.check_bid_list(&[orders[2]]);
+        book.check_ask_list(&[orders[1]]);
+        assert!(book.stops().is_empty());
+
+        let mut logger = VectorLogger::new();
+        assert_eq!(book.cancel_all_side(OrderSide::Sell, &mut logger), 1);
+        check_log(logger.as_slice(), &["A #2 o2"]);
+        book.check_ask_list(&[]);
+
+        let mut logger = VectorLogger::new();
+        assert_eq!(book.cancel_all(&mut logger), 1);
+        check_log(logger.as_slice(), &["A #3 o3"]);
+        book.check_bid_list(&[]);
+        assert_eq!(book.cancel_all(&mut logger), 0);
+    }
+
+    #[test]
+    fn order_statuses_are_tracked() {
+        let mut book = OrderBook::with_config(BookConfig {
+            lot_size: 2,
+            odd_lot: OddLotPolicy::RoundDown,
+            ..BookConfig::default()
+        });
+        book.set_status_tracking(true);
+        let mut logger = DummyLogger;
+        for s in &["Lim S $100 #4 u1", "Lim S $101 #4 u2", "Lim B $100 #2 u3", "Lim B $-1 #2 u4",
+                   "IoC B $101 #5 u5", "Lim B $99 #2 u6"] {
+            book.execute_order(s.parse().unwrap(), &mut logger);
+        }
+        let statuses: Vec<_> = (1..=6).map(|order_id| book.order_status(order_id).unwrap()).collect();
+        assert_eq!(statuses, [
+            OrderStatus::Filled,
+            OrderStatus::PartiallyFilled,
+            OrderStatus::Filled,
+            OrderStatus::Rejected,
+            OrderStatus::Cancelled,
+            OrderStatus::New,
+        ]);
+        let state = book.order_state(2).unwrap();
+        assert_eq!((state.cum_qty(), state.leaves_qty(), state.avg_price()), (2, 2, Some(101.0)));
+        let state = book.order_state(5).unwrap();
+        assert_eq!((state.cum_qty(), state.cum_notional()), (4, 402));
+        book.force_cancel(2, &mut logger);
+        assert_eq!(book.order_status(2), Some(OrderStatus::Cancelled));
+        assert_eq!(book.order_status(7), None);
+
+        // The rounded part of an order with a cancelled odd lot stays open
+        book.execute_order("Lim S $110 #5 u7".parse().unwrap(), &mut logger);
+        assert_eq!(book.order_status(7), Some(OrderStatus::New));
+        book.execute_order("Lim B $110 #4 u8".parse().unwrap(), &mut logger);
+        assert_eq!(book.order_status(7), Some(OrderStatus::Filled));
+    }
+
+    #[test]
+    fn orders_are_generic_over_numeric_types() {
+        let order: IncomingOrder<i32, u128, u32> = IncomingOrder {
+            price_limit: 100,
+            size: u128::from(u64::MAX) + 1,
+            user_id: 7,
+            kind: OrderKind::Iceberg { display_size: 5 },
+            side: OrderSide::Buy,
+            expires_at: None,
+            min_fill_size: None,
+            reduce_only: false,
+        };
+        assert_eq!(order.clone(), order);
+        let item: LogItem<i32, u128, u32> = LogItem::Fulfilled { size: order.size, price: order.price_limit, user_id: order.user_id, order_id: 1 };
+        assert_ne!(item, LogItem::Fulfilled { size: 1, price: 100, user_id: 7, order_id: 1 });
+    }
+
+    #[test]
+    fn indicative_quotes_do_not_match_until_firm() {
+        let mut book = OrderBook::from_orders(&["Lim S $101 #5 u1"]);
+        let first = book.add_indicative("Lim B $101 #3 u2".parse().unwrap()).unwrap();
+        let second = book.add_indicative("Lim B $101 #4 u3".parse().unwrap()).unwrap();
+        let third = book.add_indicative("Lim B $99 #1 u3".parse().unwrap()).unwrap();
+        assert!(book.add_indicative("Lim B $-1 #1 u3".parse().unwrap()).is_err());
+        book.check_ask_list(&["Lim S $101 #5 u1"]);
+        assert_eq!(book.indicative().depth(OrderSide::Buy), [(101, 7), (99, 1)]);
+        assert_eq!(book.indicative().depth(OrderSide::Sell), []);
+
+        let mut logger = VectorLogger::new();
+        assert_eq!(book.firm_up(first, 3, &mut logger), None);
+        assert_eq!(book.firm_up(first, 2, &mut logger), Some(first));
+        check_log(logger.as_slice(), &["F #3 $101 u1 o1"]);
+        assert_eq!(book.withdraw_indicative(second, 3).unwrap().size, 4);
+        assert_eq!(book.indicative().len(), 1);
+        book.check_ask_list(&["Lim S $101 #2 u1"]);
+        // Firm orders rest under the identifiers of their quotes
+        assert_eq!(book.firm_up(third, 3, &mut DummyLogger), Some(third));
+        assert_eq!(book.inspect_order(third).unwrap().order.to_string(), "Lim B $99 #1 u3");
+
+        // Quotes are checked against the limits of the book
+        book.update_config(BookConfig { max_open_orders: Some(1), ..BookConfig::default() }).unwrap();
+        assert_eq!(book.add_indicative("Lim B $91 #1 u3".parse().unwrap()), Err(RejectReason::TooManyOpenOrders));
+        let mut logger = VectorLogger::new();
+        book.set_state(BookState::Halted, &mut logger);
+        assert_eq!(book.add_indicative("Lim B $91 #1 u5".parse().unwrap()), Err(RejectReason::Halted));
+    }
+
+    #[test]
+    fn config_update_keeps_book() {
+        let mut book = OrderBook::from_orders(&["Lim S $120 #5 u1", "Lim B $100 #5 u2"]);
+        book.add_indicative("Lim B $110 #1 u3".parse().unwrap()).unwrap();
+
+        let config = BookConfig {
+            tick_size: "0.1".parse().unwrap(),
+            ..BookConfig::default()
+        };
+        let change = book.update_config(config.clone()).unwrap();
+        assert_eq!((change.version, change.previous.tick_size), (1, TickSize::ONE));
+        book.check_ask_list(&["Lim S $1200 #5 u1"]);
+        book.check_bid_list(&["Lim B $1000 #5 u2"]);
+        assert_eq!(book.indicative().depth(OrderSide::Buy), [(1100, 1)]);
+
+        let config = BookConfig {
+            tick_size: "25".parse().unwrap(),
+            ..config
+        };
+        assert!(book.update_config(config).is_err());
+        book.check_ask_list(&["Lim S $1200 #5 u1"]);
+
+        let mut logger = VectorLogger::new();
+        book.update_config(BookConfig { min_size: Some(2), ..book.config().clone() }).unwrap();
+        book.execute_order("Lim B $1200 #1 u4".parse().unwrap(), &mut logger);
+        check_log(logger.as_slice(), &["R #1 size-too-small"]);
+        assert_eq!(book.config_changes().len(), 2);
+    }
+
+    #[test]
+    fn cancel_resting_order() {
+        let mut book = OrderBook::new();
+        book.set_status_tracking(true);
+        book.execute_order("Lim S $120 #5 u1".parse().unwrap(), &mut DummyLogger);
+        let order_id = book.last_order_id().unwrap();
+        book.execute_order("Lim B $120 #2 u2".parse().unwrap(), &mut DummyLogger);
+
+        let mut logger = VectorLogger::new();
+        let cancelled = book.cancel_order(order_id, &mut logger).unwrap();
+        assert_eq!(cancelled.order.to_string(), "Lim S $120 #3 u1");
+        check_log(logger.as_slice(), &["C S #3 $120 u1 o1"]);
+        assert!(book.ask().is_empty());
+        assert_eq!(book.order_status(order_id), Some(OrderStatus::Cancelled));
+
+        assert_eq!(book.cancel_order(order_id, &mut logger), Err(CancelError::UnknownOrder(order_id)));
+        let mut logger = VectorLogger::new();
+        book.execute_command(Command::Cancel(order_id), &mut logger);
+        check_log(logger.as_slice(), &["R #0 unknown-order"]);
+    }
+
+    #[test]
+    fn amend_keeps_priority_only_when_reducing_size() {
+        let mut book = OrderBook::new();
+        book.set_status_tracking(true);
+        book.execute_order("Lim S $110 #5 u1".parse().unwrap(), &mut DummyLogger);
+        let first = book.last_order_id().unwrap();
+        book.execute_order("Lim S $110 #5 u2".parse().unwrap(), &mut DummyLogger);
 
-        fn check_ask_list(&self, list: &[&str]);
+        let mut logger = VectorLogger::new();
+        book.amend_order(first, 110, 3, &mut logger).unwrap();
+        check_log(logger.as_slice(), &[&format!("M #3 $110 o{}", first)]);
+        book.check_ask_list(&["Lim S $110 #3 u1", "Lim S $110 #5 u2"]);
 
-        fn check_bid_len(&self, n: usize);
+        book.amend_order(first, 110, 4, &mut DummyLogger).unwrap();
+        book.check_ask_list(&["Lim S $110 #5 u2", "Lim S $110 #4 u1"]);
 
-        fn check_ask_len(&self, n: usize);
+        book.execute_order("Lim B $100 #2 u3".parse().unwrap(), &mut DummyLogger);
+        let mut logger = VectorLogger::new();
+        book.amend_order(first, 100, 4, &mut logger).unwrap();
+        check_log(logger.as_slice(), &[&format!("M #4 $100 o{}", first), "F #2 $100 u3 o3", "Q S #2 $100 u1 o1"]);
+        book.check_ask_list(&["Lim S $100 #2 u1", "Lim S $110 #5 u2"]);
+        assert_eq!(book.order_status(first), Some(OrderStatus::PartiallyFilled));
 
-        fn from_orders(list: &[&str]) -> Self;
+        let mut logger = VectorLogger::new();
+        assert_eq!(book.amend_order(first, -1, 4, &mut logger), Err(AmendError::Rejected(RejectReason::NegativePrice)));
+        check_log(logger.as_slice(), &["R #4 negative-price"]);
+        assert_eq!(book.amend_order(99, 100, 4, &mut logger), Err(AmendError::UnknownOrder(99)));
+
+        book.execute_command(Command::Amend { order_id: first, price: 100, size: 0 }, &mut DummyLogger);
+        book.check_ask_list(&["Lim S $110 #5 u2"]);
+        assert_eq!(book.order_status(first), Some(OrderStatus::Cancelled));
     }
 
-    impl OrderBookExt for OrderBook {
-        fn check_bid(&self, index: usize, s: &str) {
-            check_order(&self.bid, "bid", index, s);
-        }
+    #[test]
+    fn stop_orders_trigger_on_last_trade_price() {
+        let mut book = OrderBook::new();
+        book.execute_order("Lim S $100 #2 u1".parse().unwrap(), &mut DummyLogger);
+        book.execute_order("Lim S $105 #4 u2".parse().unwrap(), &mut DummyLogger);
 
-        fn check_bid_list(&self, list: &[&str]) {
-            check_len(&self.bid, "bid", list.len());
-            for (index, s) in list.iter().enumerate() {
-                check_order(&self.bid, "bid", index, s);
-            }
-        }
+        let stop: IncomingOrder = "Stp B $0 #3 u3 @101".parse().unwrap();
+        assert_eq!(stop.to_string(), "Stp B $0 #3 u3 @101");
+        let mut logger = VectorLogger::new();
+        book.execute_order(stop, &mut logger);
+        let stop_id = book.last_order_id().unwrap();
+        check_log(logger.as_slice(), &["P #3"]);
+        book.execute_order("StpLim S $90 #1 u4 @95".parse().unwrap(), &mut DummyLogger);
+        assert_eq!(book.stops().len(), 2);
 
-        fn check_ask(&self, index: usize, s: &str) {
-            check_order(&self.ask, "ask", index, s);
+        let mut logger = VectorLogger::new();
+        book.execute_order("Lim B $100 #2 u5".parse().unwrap(), &mut logger);
+        check_log(logger.as_slice(), &["F #2 $100 u1 o1"]);
+        assert_eq!(book.last_trade_price(), Some(100));
+
+        let mut logger = VectorLogger::new();
+        book.execute_order("Lim B $105 #1 u5".parse().unwrap(), &mut logger);
+        check_log(logger.as_slice(), &["F #1 $105 u2 o2", &format!("T o{}", stop_id), "F #3 $105 u2 o2"]);
+        book.check_ask_list(&[]);
+        assert_eq!(book.stops().len(), 1);
+
+        assert_eq!(book.cancel_order(stop_id + 1, &mut DummyLogger).map(|c| c.order.size), Ok(1));
+        assert!(book.stops().is_empty());
+    }
+
+    #[test]
+    fn trailing_stop_follows_trades() {
+        let mut book = OrderBook::new();
+        book.execute_order("Lim B $90 #10 u1".parse().unwrap(), &mut DummyLogger);
+
+        let stop: IncomingOrder = "Trl S $0 #3 u3 @95 ~5".parse().unwrap();
+        assert_eq!(stop.to_string(), "Trl S $0 #3 u3 @95 ~5");
+        book.execute_order(stop, &mut DummyLogger);
+        let stop_id = book.last_order_id().unwrap();
+        let trigger_price = |book: &OrderBook| book.stops().get(stop_id).and_then(|order| order.kind.trigger_price());
+
+        let trade = |book: &mut OrderBook, price: Price| {
+            let mut logger = VectorLogger::new();
+            book.execute_order(IncomingOrder { price_limit: price, .."Lim S $0 #1 u2".parse().unwrap() }, &mut logger);
+            book.execute_order(IncomingOrder { price_limit: price, .."IoC B $0 #1 u4".parse().unwrap() }, &mut logger);
+            logger
+        };
+        trade(&mut book, 102);
+        assert_eq!(trigger_price(&book), Some(97));
+        // The trigger price does not follow a falling price
+        trade(&mut book, 98);
+        assert_eq!(trigger_price(&book), Some(97));
+
+        let logger = trade(&mut book, 97);
+        check_log(logger.as_slice(), &["Q S #1 $97 u2 o7", "F #1 $97 u2 o7", &format!("T o{}", stop_id), "F #3 $90 u1 o1"]);
+        assert!(book.stops().is_empty());
+
+        let mut logger = VectorLogger::new();
+        book.execute_order("Trl B $0 #3 u3 @95 ~0".parse().unwrap(), &mut logger);
+        check_log(logger.as_slice(), &["R #3 non-positive-offset"]);
+    }
+
+    #[test]
+    fn pegged_orders_follow_references() {
+        let mut book = OrderBook::from_orders(&["Lim B $100 #5 u1", "Lim S $104 #5 u2"]);
+        let pegged: IncomingOrder = "Peg B $103 #3 u3 =pri+1".parse().unwrap();
+        assert_eq!(pegged.to_string(), "Peg B $103 #3 u3 =pri+1");
+        book.execute_order(pegged, &mut DummyLogger);
+        book.check_bid_list(&["Lim B $101 #3 u3", "Lim B $100 #5 u1"]);
+
+        // The price limit caps the pegged price
+        let mut logger = VectorLogger::new();
+        book.execute_order("Lim B $102 #1 u4".parse().unwrap(), &mut logger);
+        check_log(logger.as_slice(), &["Q B #1 $102 u4 o4", "M #3 $103 o3", "Q B #3 $103 u3 o3"]);
+        book.cancel_order(4, &mut DummyLogger).unwrap();
+        book.check_bid_list(&["Lim B $101 #3 u3", "Lim B $100 #5 u1"]);
+
+        book.execute_order("Peg S $90 #2 u5 =mid".parse().unwrap(), &mut DummyLogger);
+        book.check_ask_list(&["Lim S $102 #2 u5", "Lim S $104 #5 u2"]);
+        let listed: Vec<_> = book.to_vec().iter().map(|order| order.to_string()).collect();
+        assert_eq!(listed, ["Lim B $100 #5 u1", "Peg B $103 #3 u3 =pri+1", "Peg S $90 #2 u5 =mid", "Lim S $104 #5 u2"]);
+
+        // Filled pegs are dropped, pegs without a reference keep their prices
+        let mut logger = VectorLogger::new();
+        book.execute_order("IoC S $100 #8 u6".parse().unwrap(), &mut logger);
+        check_log(logger.as_slice(), &["F #3 $101 u3 o3", "F #5 $100 u1 o1"]);
+        book.check_ask_list(&["Lim S $102 #2 u5", "Lim S $104 #5 u2"]);
+        assert_eq!(book.pegs().len(), 1);
+    }
+
+    #[test]
+    fn repricing_of_pegged_orders_is_throttled() {
+        use std::sync::Arc;
+        use std::sync::atomic::{AtomicU64, Ordering};
+
+        let mut book = OrderBook::with_config(BookConfig { repeg_interval: Some(10), ..BookConfig::default() });
+        let time = Arc::new(AtomicU64::new(0));
+        let clock_time = time.clone();
+        book.set_clock(Some(Arc::new(move || clock_time.load(Ordering::Relaxed))));
+        for s in &["Lim B $100 #5 u1", "Lim S $110 #5 u2", "Peg S $90 #1 u3 =mkt+2"] {
+            book.execute_order(s.parse().unwrap(), &mut DummyLogger);
         }
+        book.check_ask(0, "Lim S $102 #1 u3");
 
-        fn check_ask_list(&self, list: &[&str]) {
-            check_len(&self.ask, "ask", list.len());
-            for (index, s) in list.iter().enumerate() {
-                check_order(&self.ask, "ask", index, s);
-            }
+        time.store(5, Ordering::Relaxed);
+        book.execute_order("Lim B $101 #1 u4".parse().unwrap(), &mut DummyLogger);
+        book.check_ask(0, "Lim S $102 #1 u3");
+
+        time.store(10, Ordering::Relaxed);
+        let mut logger = VectorLogger::new();
+        book.execute_order("Lim B $90 #1 u5".parse().unwrap(), &mut logger);
+        check_log(logger.as_slice(), &["Q B #1 $90 u5 o5", "M #1 $103 o3", "Q S #1 $103 u3 o3"]);
+    }
+
+    #[test]
+    fn pegged_prices_are_checked_like_new_prices() {
+        let collar = PriceCollar { max_deviation_bps: 1000, reference: CollarReference::LastTrade, policy: CollarPolicy::Reject };
+        let mut book = OrderBook::with_config(BookConfig { price_collar: Some(collar), ..BookConfig::default() });
+        for s in &["Lim S $100 #1 u1", "Lim B $100 #1 u2", "Lim B $95 #5 u3", "Lim S $105 #5 u4", "Peg B $100 #2 u5 =pri-3"] {
+            book.execute_order(s.parse().unwrap(), &mut DummyLogger);
         }
+        book.check_bid_list(&["Lim B $95 #5 u3", "Lim B $92 #2 u5"]);
+        let mut logger = VectorLogger::new();
+        book.execute_order("Peg B $100 #2 u6 =pri-20".parse().unwrap(), &mut logger);
+        check_log(logger.as_slice(), &["R #2 price-out-of-band"]);
 
-        fn check_bid_len(&self, n: usize) {
-            check_len(&self.bid, "bid", n);
+        // The collar moved with the last trade, so the repriced peg is cancelled
+        book.execute_order("Lim B $105 #1 u7".parse().unwrap(), &mut DummyLogger);
+        let mut logger = VectorLogger::new();
+        book.execute_order("Lim B $96 #1 u8".parse().unwrap(), &mut logger);
+        check_log(logger.as_slice(), &["Q B #1 $96 u8 o8", "C B #2 $92 u5 o5"]);
+        assert!(book.pegs().is_empty());
+    }
+
+    #[test]
+    fn minimum_fill_size_is_checked_on_arrival() {
+        let mut book = OrderBook::from_orders(&["Lim S $100 #2 u1", "Lim S $101 #2 u2", "Lim S $102 #3 u2"]);
+        let mut logger = VectorLogger::new();
+        book.execute_order("IoC B $101 #5 u3 %5".parse().unwrap(), &mut logger);
+        check_log(logger.as_slice(), &["C B #5 $101 u3 o4"]);
+
+        let mut logger = VectorLogger::new();
+        book.execute_order("IoC B $101 #5 u3 %4".parse().unwrap(), &mut logger);
+        check_log(logger.as_slice(), &["F #2 $100 u1 o1", "F #2 $101 u2 o2", "C B #1 $101 u3 o5"]);
+
+        let mut logger = VectorLogger::new();
+        book.execute_order("Mkt B $0 #5 u4 %4".parse().unwrap(), &mut logger);
+        check_log(logger.as_slice(), &["C B #5 $0 u4 o6"]);
+        book.check_ask_list(&["Lim S $102 #3 u2"]);
+
+        // Limit orders rest only if they do not cross the opposite touch
+        let mut logger = VectorLogger::new();
+        book.execute_order("Lim B $102 #5 u5 %4".parse().unwrap(), &mut logger);
+        book.execute_order("Lim B $101 #5 u5 %4".parse().unwrap(), &mut logger);
+        check_log(logger.as_slice(), &["C B #5 $102 u5 o7", "Q B #5 $101 u5 o8"]);
+
+        let rejected = "Lim B $100 #2 u5 %3".parse().unwrap();
+        assert_eq!(book.try_execute_order(rejected, &mut DummyLogger), Err(RejectReason::MinFillTooLarge));
+    }
+
+    #[test]
+    fn hidden_orders_lose_priority() {
+        let mut book = OrderBook::from_orders(&["Hid S $100 #5 u1", "Lim S $100 #3 u2", "Lim S $101 #4 u3"]);
+        assert_eq!(book.depth(2).asks, [PriceLevel { price: 100, size: 3, orders: 1 }, PriceLevel { price: 101, size: 4, orders: 1 }]);
+        assert_eq!(book.ask_levels().next().map(|level| level.order_count()), Some(1));
+        let listed: Vec<_> = book.to_vec().iter().map(|order| order.to_string()).collect();
+        assert_eq!(listed, ["Lim S $100 #3 u2", "Lim S $101 #4 u3"]);
+        assert_eq!(book.to_vec_with_hidden().len(), 3);
+
+        // The hidden order is matched once the displayed order at its price is filled
+        let mut logger = VectorLogger::new();
+        book.execute_order("IoC B $101 #10 u4".parse().unwrap(), &mut logger);
+        check_log(logger.as_slice(), &["F #3 $100 u2 o2", "F #5 $100 u1 o1", "F #2 $101 u3 o3"]);
+        book.check_ask_list(&["Lim S $101 #2 u3"]);
+
+        // Hidden prices stay out of the spread and the mid price
+        let book = OrderBook::from_orders(&["Lim B $100 #1 u1", "Hid B $108 #1 u2", "Lim S $110 #1 u3"]);
+        assert_eq!(book.best_bid().map(|level| level.price), Some(100));
+        assert_eq!((book.spread(), book.mid_price()), (Some(10), Some(105.0)));
+
+        let mut book = OrderBook::with_config(BookConfig {
+            matching: MatchingAlgorithm::ProRata { min_fill: 0 },
+            ..BookConfig::default()
+        });
+        for s in &["Hid S $100 #10 u1", "Lim S $100 #10 u2", "Lim S $100 #10 u3"] {
+            book.execute_order(s.parse().unwrap(), &mut DummyLogger);
         }
+        book.execute_order("Lim B $100 #24 u4".parse().unwrap(), &mut DummyLogger);
+        book.check_ask_list(&["Hid S $100 #6 u1"]);
+    }
 
-        fn check_ask_len(&self, n: usize) {
-            check_len(&self.ask, "ask", n);
+    #[test]
+    fn pro_rata_matching() {
+        let mut book = OrderBook::with_config(BookConfig {
+            matching: MatchingAlgorithm::ProRata { min_fill: 3 },
+            ..BookConfig::default()
+        });
+        for s in &["Lim S $100 #10 u1", "Lim S $100 #30 u2", "Lim S $100 #5 u3", "Lim S $101 #20 u4"] {
+            book.execute_order(s.parse().unwrap(), &mut DummyLogger);
         }
 
-        fn from_orders(list: &[&str]) -> Self {
-            let mut logger = DummyLogger;
+        let mut logger = VectorLogger::new();
+        book.execute_order("Lim B $101 #20 u5".parse().unwrap(), &mut logger);
+        // Shares are 4, 13 and 2; the last one is below the minimum and the rest goes to the first order
+        check_log(logger.as_slice(), &["F #7 $100 u1 o1", "F #13 $100 u2 o2"]);
+        book.check_ask_list(&["Lim S $100 #3 u1", "Lim S $100 #17 u2", "Lim S $100 #5 u3", "Lim S $101 #20 u4"]);
 
-            let mut book = OrderBook::new();
-            for s in list {
-                book.execute_order(s.parse().unwrap(), &mut logger);
-            }
-            book
+        let mut logger = VectorLogger::new();
+        book.execute_order("Lim B $101 #30 u2".parse().unwrap(), &mut logger);
+        check_log(logger.as_slice(), &["F #3 $100 u1 o1", "F #5 $100 u3 o3", "F #20 $101 u4 o4", "Q B #2 $101 u2 o6"]);
+        book.check_ask_list(&["Lim S $100 #17 u2"]);
+        book.check_bid_list(&["Lim B $101 #2 u2"]);
+    }
+
+    #[test]
+    fn top_order_is_filled_first() {
+        let mut book = OrderBook::with_config(BookConfig {
+            matching: MatchingAlgorithm::ProRata { min_fill: 0 },
+            top_priority: true,
+            ..BookConfig::default()
+        });
+        for s in &["Lim S $101 #10 u1", "Lim S $100 #4 u2", "Lim S $100 #12 u3"] {
+            book.execute_order(s.parse().unwrap(), &mut DummyLogger);
         }
+        assert_eq!(book.ask().top_order(), Some(2));
+
+        let mut logger = VectorLogger::new();
+        book.execute_order("IoC B $100 #8 u4".parse().unwrap(), &mut logger);
+        // Pro-rata alone would allocate 2 and 6
+        check_log(logger.as_slice(), &["F #4 $100 u2 o2", "F #4 $100 u3 o3"]);
+        assert_eq!(book.ask().top_order(), None);
+
+        book.execute_order("Lim S $99 #1 u5".parse().unwrap(), &mut DummyLogger);
+        assert_eq!(book.ask().top_order(), Some(5));
+        book.cancel_order(5, &mut DummyLogger).unwrap();
+        assert_eq!(book.ask().top_order(), None);
     }
 
     #[test]
-    fn new_book_is_empty() {
-        let book = OrderBook::new();
-        book.check_bid_len(0);
-        book.check_ask_len(0);
+    fn iceberg_order_is_refilled_at_the_end_of_its_level() {
+        let mut book = OrderBook::new();
+        book.set_audit(true);
+        book.execute_order("Ice S $100 #5 u1 ^2".parse().unwrap(), &mut DummyLogger);
+        let iceberg = book.last_order_id().unwrap();
+        book.execute_order("Lim S $100 #3 u2".parse().unwrap(), &mut DummyLogger);
+        assert_eq!((book.ask().len(), book.ask().total_size()), (2, 8));
+
+        let mut logger = VectorLogger::new();
+        book.execute_order("Lim B $100 #6 u3".parse().unwrap(), &mut logger);
+        check_log(logger.as_slice(), &["F #2 $100 u1 o1", "F #3 $100 u2 o2", &format!("H #2 o{}", iceberg), "F #1 $100 u1 o1"]);
+        book.check_ask_list(&["Ice S $100 #2 u1 ^2"]);
+        let order = book.ask().into_iter().next().unwrap();
+        assert_eq!((order.size, order.hidden_size()), (1, 1));
+
+        let mut logger = VectorLogger::new();
+        book.execute_order("FoK B $100 #3 u3".parse().unwrap(), &mut logger);
+        check_log(logger.as_slice(), &["C B #3 $100 u3 o4"]);
+        let mut logger = VectorLogger::new();
+        book.execute_order("FoK B $100 #2 u3".parse().unwrap(), &mut logger);
+        check_log(logger.as_slice(), &["F #1 $100 u1 o1", &format!("H #1 o{}", iceberg), "F #1 $100 u1 o1"]);
+        book.check_ask_list(&[]);
     }
 
     #[test]
-    fn book_insert_correct_queue() {
-        let mut logger = DummyLogger;
+    fn orders_of_the_same_user_keep_their_positions() {
+        let mut book = OrderBook::from_orders(&["Lim S $100 #2 u1", "Lim S $100 #3 u2", "Lim S $101 #2 u1", "Lim S $101 #4 u3"]);
+        let steps: [(&str, &[&str], &[&str]); 2] = [
+            ("Lim B $101 #5 u1", &["F #3 $100 u2 o2", "F #2 $101 u3 o4"], &["Lim S $100 #2 u1", "Lim S $101 #2 u1", "Lim S $101 #2 u3"]),
+            ("IoC B $101 #3 u1", &["F #2 $101 u3 o4", "C B #1 $101 u1 o6"], &["Lim S $100 #2 u1", "Lim S $101 #2 u1"]),
+        ];
+        for (s, log, asks) in &steps {
+            let mut logger = VectorLogger::new();
+            book.execute_order(s.parse().unwrap(), &mut logger);
+            check_log(logger.as_slice(), log);
+            book.check_ask_list(asks);
+        }
+    }
 
+    #[test]
+    fn post_only_order_is_rejected_or_repriced() {
         let mut book = OrderBook::new();
-        let order = "Lim B $100 #200 u42".parse().unwrap();
-        book.execute_order(order, &mut logger);
-        book.check_bid_len(1);
-        book.check_ask_len(0);
+        book.execute_order("Lim S $100 #5 u1".parse().unwrap(), &mut DummyLogger);
+        book.execute_order("Lim B $90 #5 u2".parse().unwrap(), &mut DummyLogger);
+
+        let mut logger = VectorLogger::new();
+        book.execute_order("Post B $100 #2 u3".parse().unwrap(), &mut logger);
+        check_log(logger.as_slice(), &["R #2 would-cross"]);
+        book.execute_order("Post B $99 #2 u3".parse().unwrap(), &mut DummyLogger);
+        book.check_bid_list(&["Post B $99 #2 u3", "Lim B $90 #5 u2"]);
+
+        // Amendments of post-only orders do not take liquidity either
+        let mut logger = VectorLogger::new();
+        assert_eq!(book.amend_order(4, 100, 2, &mut logger), Err(AmendError::Rejected(RejectReason::WouldCross)));
+        check_log(logger.as_slice(), &["R #2 would-cross"]);
+
+        book.update_config(BookConfig { post_only: PostOnlyPolicy::Reprice, ..BookConfig::default() }).unwrap();
+        let mut logger = VectorLogger::new();
+        book.execute_order("Post S $95 #3 u4".parse().unwrap(), &mut logger);
+        check_log(logger.as_slice(), &["Q S #3 $100 u4 o5"]);
+        book.check_ask_list(&["Lim S $100 #5 u1", "Post S $100 #3 u4"]);
+
+        let mut logger = VectorLogger::new();
+        book.amend_order(4, 100, 3, &mut logger).unwrap();
+        check_log(logger.as_slice(), &["M #3 $99 o4", "Q B #3 $99 u3 o4"]);
+        book.check_bid_list(&["Post B $99 #3 u3", "Lim B $90 #5 u2"]);
+
+        let mut book = OrderBook::with_config(BookConfig { post_only: PostOnlyPolicy::Reprice, ..BookConfig::default() });
+        book.execute_order("Lim S $0 #1 u5".parse().unwrap(), &mut DummyLogger);
+        let mut logger = VectorLogger::new();
+        book.execute_order("Post B $0 #1 u6".parse().unwrap(), &mut logger);
+        check_log(logger.as_slice(), &["R #1 negative-price"]);
+    }
 
+    #[test]
+    fn expired_orders_are_removed() {
         let mut book = OrderBook::new();
-        let order = "Lim S $100 #200 u42".parse().unwrap();
-        book.execute_order(order, &mut logger);
-        book.check_bid_len(0);
-        book.check_ask_len(1);
+        book.set_status_tracking(true);
+        book.execute_order("Lim S $100 #5 u1 !200".parse().unwrap(), &mut DummyLogger);
+        let first = book.last_order_id().unwrap();
+        book.execute_order("Lim S $101 #5 u2".parse().unwrap(), &mut DummyLogger);
+        book.execute_order("Lim B $90 #3 u3 !100".parse().unwrap(), &mut DummyLogger);
+        book.execute_order("Stp B $0 #1 u4 @120 !150".parse().unwrap(), &mut DummyLogger);
+        book.execute_order("Lim B $100 #2 u5".parse().unwrap(), &mut DummyLogger);
+
+        let mut logger = VectorLogger::new();
+        assert_eq!(book.expire_orders(99, &mut logger), 0);
+        assert_eq!(book.expire_orders(150, &mut logger), 2);
+        check_log(logger.as_slice(), &[&format!("X #3 o{}", first + 2), &format!("X #1 o{}", first + 3)]);
+        book.check_bid_list(&[]);
+        assert!(book.stops().is_empty());
+
+        assert_eq!(book.expire_orders(200, &mut logger), 1);
+        book.check_ask_list(&["Lim S $101 #5 u2"]);
+        assert_eq!(book.order_status(first), Some(OrderStatus::Expired));
+        assert_eq!(book.order_state(first).unwrap().cum_qty(), 2);
     }
 
     #[test]
-    fn book_insert_correct_ordering_by_price() {
-        let orders = [
-            "Lim B $110 #100 u42",
-            "Lim B $130 #100 u42",
-            "Lim B $120 #100 u42",
-            "Lim B $100 #100 u42",
-        ];
-        let book = OrderBook::from_orders(&orders);
-        book.check_bid_list(&[
-            orders[1],
-            orders[2],
-            orders[0],
-            orders[3],
+    fn log_items_as_json() {
+        let mut book = OrderBook::new();
+        let mut logger = VectorLogger::new();
+        book.execute_order("Lim S $100 #5 u1".parse().unwrap(), &mut logger);
+        book.execute_order("IoC B $100 #7 u2".parse().unwrap(), &mut logger);
+        logger.log(LogItem::Rejected { size: 1, reason: RejectReason::ZeroPrice });
+        let json: Vec<_> = logger.as_slice().iter().map(LogItem::to_json).collect();
+        assert_eq!(json, [
+            r#"{"type":"enqueued","size":5,"price":100,"side":"S","user_id":1,"order_id":1}"#,
+            r#"{"type":"fulfilled","size":5,"price":100,"user_id":1,"order_id":1}"#,
+            r#"{"type":"cancelled","size":2,"price":100,"side":"B","user_id":2,"order_id":2}"#,
+            r#"{"type":"rejected","size":1,"reason":"zero-price"}"#,
         ]);
+    }
 
-        let orders = [
-            "Lim S $110 #100 u42",
-            "Lim S $130 #100 u42",
-            "Lim S $120 #100 u42",
-            "Lim S $100 #100 u42",
+    #[test]
+    fn log_items_round_trip_through_text() {
+        let items = [
+            LogItem::Enqueued { size: 5, price: -100, side: OrderSide::Sell, user_id: 1, order_id: 2 },
+            LogItem::Fulfilled { size: 5, price: 100, user_id: 1, order_id: 2 },
+            LogItem::Cancelled { size: 2, price: 100, side: OrderSide::Buy, user_id: 3, order_id: 4 },
+            LogItem::Rejected { size: 1, reason: RejectReason::WouldIncreasePosition },
+            LogItem::ForceCancelled { size: 3, order_id: 5 },
+            LogItem::Amended { size: 4, price: 99, order_id: 6 },
+            LogItem::Parked { size: 7 },
+            LogItem::Triggered { order_id: 8 },
+            LogItem::Refilled { size: 2, order_id: 9 },
+            LogItem::Expired { size: 1, order_id: 10 },
+            LogItem::IntegrityError { expected: 11, actual: 12 },
+            LogItem::StateChanged { state: BookState::AuctionOnly },
         ];
-        let book = OrderBook::from_orders(&orders);
-        book.check_ask_list(&[
-            orders[3],
-            orders[0],
-            orders[2],
-            orders[1],
-        ]);
+        for item in &items {
+            assert_eq!(item.to_string().parse::<LogItem>().as_ref(), Ok(item));
+        }
+        let mut book = OrderBook::new();
+        let mut logger = VectorLogger::new();
+        book.execute_order("Lim S $100 #5 u1".parse().unwrap(), &mut logger);
+        book.execute_order("IoC B $100 #7 u2".parse().unwrap(), &mut logger);
+        let parsed: Vec<LogItem> = ["Q S #5 $100 u1 o1", "F #5 $100 u1 o1", "C B #2 $100 u2 o2"].iter()
+            .map(|s| s.parse().unwrap())
+            .collect();
+        assert_eq!(logger.as_slice(), parsed.as_slice());
+
+        for s in &["", "F #5 $100 u1", "Q X #5 $100 u1 o1", "R #1 unknown-reason", "S closed", "P 5", "T o1 o2"] {
+            assert!(s.parse::<LogItem>().is_err(), "{}", s);
+        }
     }
 
     #[test]
-    fn book_insert_correct_ordering_by_arrival() {
-        let orders = [
-            "Lim B $100 #100 u41",
-            "Lim B $101 #100 u42",
-            "Lim B $102 #100 u43",
-            "Lim B $101 #100 u44",
-            "Lim B $101 #100 u45",
-        ];
-        let book = OrderBook::from_orders(&orders);
-        book.check_bid_list(&[
-            orders[2],
-            orders[1],
-            orders[3],
-            orders[4],
-            orders[0],
-        ]);
+    fn fractional_prices_and_sizes() {
+        let config = BookConfig {
+            tick_size: "0.01".parse().unwrap(),
+            size_unit: "0.001".parse().unwrap(),
+            ..BookConfig::default()
+        };
+        let mut book = OrderBook::with_config(config.clone());
+        let mut logger = VectorLogger::new();
+        for s in &["Lim S $1.25 #0.5 u1", "IoC B $1.30 #0.75 u2"] {
+            book.execute_order(IncomingOrder::parse_with_config(s, &config).unwrap(), &mut logger);
+        }
+        let log: Vec<_> = logger.as_slice().iter().map(|item| item.format_with_config(&config)).collect();
+        assert_eq!(log, ["Q S #0.500 $1.25 u1 o1", "F #0.500 $1.25 u1 o1", "C B #0.250 $1.30 u2 o2"]);
 
-        let orders = [
-            "Lim S $100 #100 u41",
-            "Lim S $101 #100 u42",
-            "Lim S $102 #100 u43",
-            "Lim S $101 #100 u44",
-            "Lim S $101 #100 u45",
-        ];
-        let book = OrderBook::from_orders(&orders);
-        book.check_ask_list(&[
-            orders[0],
-            orders[1],
-            orders[3],
-            orders[4],
-            orders[2],
-        ]);
+        book.execute_order("Lim S $125 #500 u1".parse().unwrap(), &mut DummyLogger);
+        let size_unit = TickSize::new(1, 2).unwrap();
+        assert_eq!(book.update_config(BookConfig { size_unit, ..config }).err(), Some(ConfigError::SizeUnit));
     }
 
     #[test]
-    fn match_ignores_orders_with_own_user_id() {
-        let orders = [
-            "Lim B $103 #1 u3",
-            "Lim B $102 #1 u0",
-            "Lim B $102 #1 u2",
-            "Lim B $101 #1 u1",
-            "Lim B $100 #1 u0",
-            "Lim S $90 #5 u0",
-        ];
-        let book = OrderBook::from_orders(&orders);
-        book.check_bid_list(&[
-            orders[1],
-            orders[4],
+    fn trades_identify_maker_and_taker() {
+        let mut book = OrderBook::from_orders(&[
+            "Lim S $101 #3 u1",
+            "Lim S $102 #5 u2",
         ]);
-        book.check_ask_list(&[
-            "Lim S $90 #2 u0"
+        let mut logger = TradeLogger::new();
+        book.execute_order("Lim B $102 #4 u3".parse().unwrap(), &mut logger);
+        book.execute_order("Mkt B $0 #1 u4".parse().unwrap(), &mut logger);
+        let trade = |sequence, maker_order_id, taker_order_id, maker_user_id, taker_user_id, price, size| Trade {
+            sequence, maker_order_id, taker_order_id, maker_user_id, taker_user_id, taker_side: OrderSide::Buy, price, size,
+        };
+        assert_eq!(logger.trades(), [
+            trade(1, 1, 3, 1, 3, 101, 3),
+            trade(2, 2, 3, 2, 3, 102, 1),
+            trade(3, 2, 4, 2, 4, 102, 1),
         ]);
+    }
 
-        let orders = [
-            "Lim S $103 #1 u3",
-            "Lim S $102 #1 u0",
-            "Lim S $102 #1 u2",
-            "Lim S $101 #1 u1",
-            "Lim S $100 #1 u0",
-            "Lim B $110 #5 u0",
-        ];
-        let book = OrderBook::from_orders(&orders);
-        book.check_ask_list(&[
-            orders[4],
-            orders[1],
+    #[test]
+    fn log_items_are_sequenced_and_timestamped() {
+        use std::sync::Arc;
+        use std::sync::atomic::{AtomicU64, Ordering};
+
+        let mut book = OrderBook::new();
+        let mut logger = SequencedVectorLogger::new();
+        book.execute_order("Lim S $101 #3 u1".parse().unwrap(), &mut logger);
+        let time = Arc::new(AtomicU64::new(50));
+        let clock_time = time.clone();
+        book.set_clock(Some(Arc::new(move || clock_time.load(Ordering::Relaxed))));
+        time.store(60, Ordering::Relaxed);
+        book.execute_batch(vec![
+            Command::New("Lim B $101 #5 u2".parse().unwrap()),
+            Command::Cancel(9),
+        ], BatchOptions::default(), &mut logger).unwrap();
+
+        let item = |sequence, timestamp, item| SequencedLogItem { sequence, timestamp, item };
+        assert_eq!(logger.as_slice(), [
+            item(1, None, LogItem::Enqueued { size: 3, price: 101, side: OrderSide::Sell, user_id: 1, order_id: 1 }),
+            item(2, Some(60), LogItem::Fulfilled { size: 3, price: 101, user_id: 1, order_id: 1 }),
+            item(3, Some(60), LogItem::Enqueued { size: 2, price: 101, side: OrderSide::Buy, user_id: 2, order_id: 2 }),
+            item(4, Some(60), LogItem::Rejected { size: 0, reason: RejectReason::UnknownOrder }),
         ]);
-        book.check_bid_list(&[
-            "Lim B $110 #2 u0"
+        assert_eq!(book.log_sequence(), 4);
+    }
+
+    #[test]
+    fn callback_and_tee_loggers() {
+        let mut items = Vec::new();
+        {
+            let mut logger = TeeLogger(FnLogger::new(|item| items.push(item)), TradeLogger::new());
+            let mut book = OrderBook::from_orders(&["Lim S $101 #3 u1"]);
+            book.execute_order("IoC B $101 #5 u2".parse().unwrap(), &mut logger);
+            assert_eq!(logger.1.trades().len(), 1);
+
+            logger.log(LogItem::Enqueued { size: 1, price: 101, side: OrderSide::Sell, user_id: 1, order_id: 3 });
+            logger.cancel();
+            logger.finish();
+        }
+        assert_eq!(items, [
+            LogItem::Fulfilled { size: 3, price: 101, user_id: 1, order_id: 1 },
+            LogItem::Cancelled { size: 2, price: 101, side: OrderSide::Buy, user_id: 2, order_id: 2 },
         ]);
     }
 
+    #[cfg(feature = "std")]
     #[test]
-    fn test_fill_or_kill() {
-        /* Selling */
-        let orders = [
-            "Lim B $103 #1 u1",
-            "Lim B $102 #1 u2",
-            "Lim B $102 #1 u3",
-            "Lim B $101 #1 u4",
-            "Lim B $100 #1 u5",
-        ];
+    fn channel_logger_sends_finished_orders() {
+        use crate::log::ChannelLogger;
+        let (sender, receiver) = std::sync::mpsc::channel();
+        let mut logger = ChannelLogger::new(sender);
+        let mut book = OrderBook::new();
+        book.execute_order("Lim S $101 #3 u1".parse().unwrap(), &mut logger);
+        logger.log(LogItem::Enqueued { size: 1, price: 101, side: OrderSide::Sell, user_id: 1, order_id: 2 });
+        assert_eq!(receiver.try_recv().ok(), None);
 
-        // No orders matched incoming order
-        let mut book = OrderBook::from_orders(&orders);
+        logger.flush().unwrap();
+        assert_eq!(receiver.try_iter().collect::<Vec<_>>(), [LogItem::Enqueued { size: 3, price: 101, side: OrderSide::Sell, user_id: 1, order_id: 1 }]);
+        logger.cancel();
+        book.execute_order("Lim B $101 #1 u2".parse().unwrap(), &mut logger);
+        drop(logger);
+        assert_eq!(receiver.iter().collect::<Vec<_>>(), [LogItem::Fulfilled { size: 1, price: 101, user_id: 1, order_id: 1 }]);
+    }
+
+    #[test]
+    fn price_collar_around_mid_price() {
+        let collar = PriceCollar { max_deviation_bps: 1000, reference: CollarReference::MidPrice, policy: CollarPolicy::Reject };
+        let mut book = OrderBook::with_config(BookConfig { price_collar: Some(collar), ..BookConfig::default() });
         let mut logger = VectorLogger::new();
-        book.execute_order("FoK S $110 #5 u0".parse().unwrap(), &mut logger);
-        check_log(logger.as_slice(), &["C #5"]);
-        book.check_bid_list(&orders);
-        book.check_ask_len(0);
+        // Without both sides there is no mid price to check against
+        book.execute_order("Lim S $150 #5 u1".parse().unwrap(), &mut logger);
+        book.execute_order("Lim B $50 #5 u2".parse().unwrap(), &mut logger);
+        check_log(logger.as_slice(), &["Q S #5 $150 u1 o1", "Q B #5 $50 u2 o2"]);
 
-        // Some orders matched incoming order, order was not fulfilled
-        let mut book = OrderBook::from_orders(&orders);
         let mut logger = VectorLogger::new();
-        book.execute_order("FoK S $101 #5 u0".parse().unwrap(), &mut logger);
-        check_log(logger.as_slice(), &["C #5"]);
-        book.check_bid_list(&orders);
-        book.check_ask_len(0);
+        book.execute_order("Lim B $111 #1 u3".parse().unwrap(), &mut logger);
+        check_log(logger.as_slice(), &["R #1 price-out-of-band"]);
 
-        // Incoming order was fulfilled
-        let mut book = OrderBook::from_orders(&orders);
+        // Amended prices are collared too, while orders keeping their prices are not
         let mut logger = VectorLogger::new();
-        book.execute_order("FoK S $100 #4 u0".parse().unwrap(), &mut logger);
-        check_log(logger.as_slice(), &[
-            "F #1 $103 u1",
-            "F #1 $102 u2",
-            "F #1 $102 u3",
-            "F #1 $101 u4",
-        ]);
-        book.check_bid_list(&[orders[4]]);
-        book.check_ask_len(0);
+        assert_eq!(book.amend_order(2, 80, 5, &mut logger), Err(AmendError::Rejected(RejectReason::PriceOutOfBand)));
+        book.amend_order(2, 50, 4, &mut logger).unwrap();
+        check_log(logger.as_slice(), &["R #5 price-out-of-band", "M #4 $50 o2"]);
 
-        /* Buying */
-        let orders = [
-            "Lim S $100 #1 u1",
-            "Lim S $101 #1 u2",
-            "Lim S $102 #1 u3",
-            "Lim S $102 #1 u4",
-            "Lim S $103 #1 u5",
-        ];
+        book.update_config(BookConfig { price_collar: Some(PriceCollar { policy: CollarPolicy::Clamp, ..collar }), ..BookConfig::default() }).unwrap();
+        let mut logger = VectorLogger::new();
+        book.execute_order("Lim S $10 #1 u3".parse().unwrap(), &mut logger);
+        check_log(logger.as_slice(), &["Q S #1 $90 u3 o4"]);
+        assert_eq!(book.inspect_order(4).unwrap().order.price_limit, 90);
 
-        // No orders matched incoming order
-        let mut book = OrderBook::from_orders(&orders);
+        // Hidden orders do not move the mid price
+        let mut book = OrderBook::with_config(BookConfig { price_collar: Some(collar), ..BookConfig::default() });
+        for s in &["Lim S $150 #5 u1", "Lim B $50 #5 u2", "Hid B $105 #5 u3"] {
+            book.execute_order(s.parse().unwrap(), &mut DummyLogger);
+        }
         let mut logger = VectorLogger::new();
-        book.execute_order("FoK B $90 #5 u0".parse().unwrap(), &mut logger);
-        check_log(logger.as_slice(), &["C #5"]);
-        book.check_ask_list(&orders);
-        book.check_bid_len(0);
+        book.execute_order("Lim B $125 #1 u4".parse().unwrap(), &mut logger);
+        check_log(logger.as_slice(), &["R #1 price-out-of-band"]);
+    }
 
-        // Some orders matched incoming order, order was not fulfilled
-        let mut book = OrderBook::from_orders(&orders);
+    #[test]
+    fn halted_book_rejects_or_parks_orders() {
+        let mut book = OrderBook::from_orders(&["Lim S $101 #5 u1", "Lim B $99 #5 u2"]);
+        book.set_status_tracking(true);
         let mut logger = VectorLogger::new();
-        book.execute_order("FoK B $102 #5 u0".parse().unwrap(), &mut logger);
-        check_log(logger.as_slice(), &["C #5"]);
-        book.check_ask_list(&orders);
-        book.check_bid_len(0);
+        book.set_state(BookState::Halted, &mut logger);
+        book.execute_order("Lim B $101 #2 u3".parse().unwrap(), &mut logger);
+        check_log(logger.as_slice(), &["S halted", "R #2 halted"]);
 
-        // Incoming order was fulfilled
-        let mut book = OrderBook::from_orders(&orders);
+        book.update_config(BookConfig { halt_policy: HaltPolicy::Park, ..book.config().clone() }).unwrap();
         let mut logger = VectorLogger::new();
-        book.execute_order("FoK B $110 #4 u0".parse().unwrap(), &mut logger);
-        check_log(logger.as_slice(), &[
-            "F #1 $100 u1",
-            "F #1 $101 u2",
-            "F #1 $102 u3",
-            "F #1 $102 u4",
-        ]);
-        book.check_ask_list(&[orders[4]]);
-        book.check_bid_len(0);
+        for s in &["Lim B $102 #4 u3", "Lim S $100 #3 u4", "IoC B $103 #9 u5", "Post B $98 #1 u6"] {
+            book.execute_order(s.parse().unwrap(), &mut logger);
+        }
+        check_log(logger.as_slice(), &["P #4", "P #3", "P #9", "R #1 halted"]);
+        assert_eq!(book.cancel_order(6, &mut DummyLogger).map(|c| c.order.size), Ok(9));
+        book.check_ask_list(&["Lim S $101 #5 u1"]);
+
+        let mut logger = VectorLogger::new();
+        book.set_state(BookState::Open, &mut logger);
+        // Uncrossed at 101, the rest of the parked buy is filled by the later parked sell
+        check_log(logger.as_slice(), &["S open", "F #1 $101 u1 o1", "Q B #3 $102 u3 o4", "F #3 $101 u3 o4"]);
+        book.check_ask_list(&["Lim S $101 #4 u1"]);
+        book.check_bid_list(&["Lim B $99 #5 u2"]);
+        assert_eq!(book.order_status(4), Some(OrderStatus::Filled));
+        assert_eq!(book.state(), BookState::Open);
     }
 
     #[test]
-    fn test_immediate_or_cancel() {
-        /* Selling */
-        let orders = [
-            "Lim B $103 #1 u1",
-            "Lim B $102 #1 u2",
-            "Lim B $102 #1 u3",
-            "Lim B $101 #1 u4",
-            "Lim B $100 #1 u5",
-        ];
+    fn circuit_breaker_switches_to_auction() {
+        use std::sync::Arc;
+        use std::sync::atomic::{AtomicU64, Ordering};
+
+        let mut book = OrderBook::with_config(BookConfig {
+            circuit_breaker: Some(CircuitBreaker { max_move_bps: 500, window: 100, state: BookState::AuctionOnly }),
+            ..BookConfig::default()
+        });
+        let time = Arc::new(AtomicU64::new(0));
+        let clock_time = time.clone();
+        book.set_clock(Some(Arc::new(move || clock_time.load(Ordering::Relaxed))));
+        for s in &["Lim S $100 #1 u1", "Lim S $104 #1 u1", "Lim S $106 #1 u1", "Lim S $112 #1 u1"] {
+            book.execute_order(s.parse().unwrap(), &mut DummyLogger);
+        }
 
-        // No orders matched incoming order
-        let mut book = OrderBook::from_orders(&orders);
         let mut logger = VectorLogger::new();
-        book.execute_order("IoC S $110 #5 u0".parse().unwrap(), &mut logger);
-        check_log(logger.as_slice(), &["C #5"]);
-        book.check_bid_list(&orders);
-        book.check_ask_len(0);
+        book.execute_order("IoC B $100 #1 u2".parse().unwrap(), &mut logger);
+        time.store(50, Ordering::Relaxed);
+        book.execute_order("IoC B $104 #1 u2".parse().unwrap(), &mut logger);
+        // The move from 100 is outside the window
+        time.store(120, Ordering::Relaxed);
+        book.execute_order("IoC B $106 #1 u2".parse().unwrap(), &mut logger);
+        check_log(logger.as_slice(), &["F #1 $100 u1 o1", "F #1 $104 u1 o2", "F #1 $106 u1 o3"]);
 
-        // Some orders matched incoming order, order was partially fulfilled
-        let mut book = OrderBook::from_orders(&orders);
         let mut logger = VectorLogger::new();
-        book.execute_order("IoC S $101 #5 u0".parse().unwrap(), &mut logger);
-        check_log(logger.as_slice(), &[
-            "F #1 $103 u1",
-            "F #1 $102 u2",
-            "F #1 $102 u3",
-            "F #1 $101 u4",
-            "C #1",
-        ]);
-        book.check_bid_list(&[orders[4]]);
-        book.check_ask_len(0);
+        book.execute_order("IoC B $112 #1 u2".parse().unwrap(), &mut logger);
+        check_log(logger.as_slice(), &["F #1 $112 u1 o4", "S auction-only"]);
+        assert_eq!(book.state(), BookState::AuctionOnly);
 
-        // Incoming order was fully fulfilled
-        let mut book = OrderBook::from_orders(&orders);
         let mut logger = VectorLogger::new();
-        book.execute_order("IoC S $100 #4 u0".parse().unwrap(), &mut logger);
-        check_log(logger.as_slice(), &[
-            "F #1 $103 u1",
-            "F #1 $102 u2",
-            "F #1 $102 u3",
-            "F #1 $101 u4",
-        ]);
-        book.check_bid_list(&[orders[4]]);
-        book.check_ask_len(0);
+        book.execute_order("Lim S $110 #2 u1".parse().unwrap(), &mut logger);
+        book.execute_order("Mkt B $0 #1 u2".parse().unwrap(), &mut logger);
+        check_log(logger.as_slice(), &["P #2", "P #1"]);
+        book.check_ask_list(&[]);
+    }
 
-        /* Buying */
-        let orders = [
-            "Lim S $100 #1 u1",
-            "Lim S $101 #1 u2",
-            "Lim S $102 #1 u3",
-            "Lim S $102 #1 u4",
-            "Lim S $103 #1 u5",
-        ];
+    #[test]
+    fn try_execute_order_checks_limits() {
+        let config = BookConfig { price_band: Some(5), max_open_orders: Some(2), ..BookConfig::strict() };
+        let mut book = OrderBook::with_config(config);
+        let mut logger = VectorLogger::new();
+        assert_eq!(book.try_execute_order("Lim S $100 #0 u1".parse().unwrap(), &mut logger), Err(RejectReason::ZeroSize));
+        assert_eq!(book.try_execute_order("Lim S $100 #3 u1".parse().unwrap(), &mut logger).map(|s| s.order_id), Ok(2));
+        assert_eq!(book.try_execute_order("Lim S $110 #3 u1".parse().unwrap(), &mut logger).map(|s| s.order_id), Ok(3));
+        assert_eq!(book.try_execute_order("Lim S $101 #3 u1".parse().unwrap(), &mut logger), Err(RejectReason::TooManyOpenOrders));
+        assert_eq!(logger.as_slice().last(), Some(&LogItem::Rejected { size: 3, reason: RejectReason::TooManyOpenOrders }));
+        // Orders which never rest are not limited
+        let summary = book.try_execute_order("IoC B $100 #2 u1".parse().unwrap(), &mut logger).unwrap();
+        assert_eq!(summary.filled_size, 0);
+
+        let summary = book.try_execute_order("IoC B $100 #2 u2".parse().unwrap(), &mut logger).unwrap();
+        assert_eq!((summary.order_id, summary.filled_size), (6, 2));
+        assert_eq!(summary.average_price, Some(100.0));
+        assert_eq!(book.try_execute_order("Lim B $94 #1 u2".parse().unwrap(), &mut logger), Err(RejectReason::PriceOutOfBand));
+        assert!(book.try_execute_order("Lim B $95 #1 u2".parse().unwrap(), &mut logger).is_ok());
+        assert_eq!(book.amend_order(3, 106, 3, &mut logger), Err(AmendError::Rejected(RejectReason::PriceOutOfBand)));
+        assert!(book.amend_order(3, 105, 3, &mut logger).is_ok());
+    }
+
+    #[test]
+    fn resting_notional_is_limited_per_user() {
+        let mut book = OrderBook::with_config(BookConfig { max_resting_notional: Some(1000), ..BookConfig::default() });
+        for s in &["Lim S $100 #4 u1", "Ice B $90 #5 u1 ^1", "Lim B $95 #20 u2"] {
+            book.execute_order(s.parse().unwrap(), &mut DummyLogger);
+        }
+        assert_eq!(book.resting_notional(1), 850);
+        assert_eq!(book.bid().user_orders(1), [2]);
 
-        // No orders matched incoming order
-        let mut book = OrderBook::from_orders(&orders);
         let mut logger = VectorLogger::new();
-        book.execute_order("IoC B $90 #5 u0".parse().unwrap(), &mut logger);
-        check_log(logger.as_slice(), &["C #5"]);
-        book.check_ask_list(&orders);
-        book.check_bid_len(0);
+        book.execute_order("Lim S $101 #2 u1".parse().unwrap(), &mut logger);
+        check_log(logger.as_slice(), &["R #2 notional-limit"]);
+        // Orders which never rest are not limited, fills release the notional
+        book.execute_order("IoC B $100 #3 u3".parse().unwrap(), &mut DummyLogger);
+        book.execute_order("Lim S $101 #1 u1".parse().unwrap(), &mut DummyLogger);
+        assert_eq!(book.resting_notional(1), 651);
+        assert_eq!(book.ask().user_orders(1), [1, 6]);
 
-        // Some orders matched incoming order, order was partially fulfilled
-        let mut book = OrderBook::from_orders(&orders);
+        // Amendments count the new notional of the order instead of the old one
         let mut logger = VectorLogger::new();
-        book.execute_order("IoC B $102 #5 u0".parse().unwrap(), &mut logger);
-        check_log(logger.as_slice(), &[
-            "F #1 $100 u1",
-            "F #1 $101 u2",
-            "F #1 $102 u3",
-            "F #1 $102 u4",
-            "C #1",
-        ]);
-        book.check_ask_list(&[orders[4]]);
-        book.check_bid_len(0);
+        assert_eq!(book.amend_order(6, 101, 5, &mut logger), Err(AmendError::Rejected(RejectReason::NotionalLimit)));
+        book.amend_order(6, 101, 4, &mut logger).unwrap();
+        check_log(logger.as_slice(), &["R #5 notional-limit", "M #4 $101 o6", "Q S #4 $101 u1 o6"]);
+        assert_eq!(book.resting_notional(1), 954);
+    }
 
-        // Incoming order was fully fulfilled
-        let mut book = OrderBook::from_orders(&orders);
+    #[test]
+    fn quote_sets_replace_resting_orders() {
+        use crate::quote::Quote;
+
+        let mut book = OrderBook::from_orders(&["Lim S $103 #5 u2", "Lim B $97 #5 u2"]);
+        let quote = |side, price, size| Quote { side, price, size };
         let mut logger = VectorLogger::new();
-        book.execute_order("IoC B $110 #4 u0".parse().unwrap(), &mut logger);
-        check_log(logger.as_slice(), &[
-            "F #1 $100 u1",
-            "F #1 $101 u2",
-            "F #1 $102 u3",
-            "F #1 $102 u4",
+        let quotes = [quote(OrderSide::Buy, 99, 5), quote(OrderSide::Sell, 101, 5)];
+        assert_eq!(book.apply_quote_set(1, &quotes, &mut logger), Ok(vec![3, 4]));
+        check_log(logger.as_slice(), &["Q B #5 $99 u1 o3", "Q S #5 $101 u1 o4"]);
+
+        // The old ask is cancelled before the lower one is inserted, the bid keeps its priority
+        let mut logger = VectorLogger::new();
+        let quotes = [quote(OrderSide::Buy, 99, 5), quote(OrderSide::Sell, 100, 3), quote(OrderSide::Buy, 98, 2)];
+        assert_eq!(book.apply_quote_set(1, &quotes, &mut logger), Ok(vec![3, 5, 6]));
+        check_log(logger.as_slice(), &["C S #5 $101 u1 o4", "Q S #3 $100 u1 o5", "Q B #2 $98 u1 o6"]);
+
+        let mut logger = VectorLogger::new();
+        let quotes = [quote(OrderSide::Buy, 99, 2), quote(OrderSide::Sell, 100, 3)];
+        assert_eq!(book.apply_quote_set(1, &quotes, &mut logger), Ok(vec![3, 5]));
+        check_log(logger.as_slice(), &["C B #2 $98 u1 o6", "M #2 $99 o3"]);
+
+        let mut logger = VectorLogger::new();
+        let quotes = [quote(OrderSide::Buy, 99, 1), quote(OrderSide::Sell, -1, 1)];
+        assert_eq!(book.apply_quote_set(1, &quotes, &mut logger), Err(BatchError { index: 1, reason: RejectReason::NegativePrice }));
+        assert!(logger.as_slice().is_empty());
+        book.check_bid_list(&["Lim B $99 #2 u1", "Lim B $97 #5 u2"]);
+        book.check_ask_list(&["Lim S $100 #3 u1", "Lim S $103 #5 u2"]);
+    }
+
+    #[test]
+    fn execute_order_checked_prevents_overflow() {
+        let mut book = OrderBook::new();
+        let mut logger = VectorLogger::new();
+        let large = "Lim S $9223372036854775806 #18446744073709551615 u1";
+        assert!(book.execute_order_checked(large.parse().unwrap(), &mut logger).is_ok());
+        for s in &["Lim S $9223372036854775807 #1 u2", "Stp B $0 #1 u2 @1"] {
+            assert_eq!(book.execute_order_checked(s.parse().unwrap(), &mut logger), Err(RejectReason::Overflow));
+        }
+        assert_eq!(logger.as_slice().last(), Some(&LogItem::Rejected { size: 1, reason: RejectReason::Overflow }));
+        let summary = book.execute_order_checked("IoC B $9223372036854775806 #5 u2".parse().unwrap(), &mut logger).unwrap();
+        assert_eq!((summary.order_id, summary.filled_size), (4, 5));
+        assert!(book.execute_order_checked("Lim S $9223372036854775807 #5 u2".parse().unwrap(), &mut logger).is_ok());
+
+        // Unchecked orders saturate the totals
+        book.execute_order("Lim S $9223372036854775807 #18446744073709551615 u2".parse().unwrap(), &mut logger);
+        assert_eq!(book.stats(5).ask_volume, u64::MAX);
+    }
+
+    #[test]
+    fn execution_summary() {
+        let mut book = OrderBook::from_orders(&[
+            "Lim S $100 #2 u1",
+            "Lim S $103 #2 u1",
+            "Stp B $0 #1 u2 @100",
         ]);
-        book.check_ask_list(&[orders[4]]);
-        book.check_bid_len(0);
+        let mut logger = DummyLogger;
+        let summary = book.try_execute_order("Lim B $103 #6 u3".parse().unwrap(), &mut logger).unwrap();
+        // The triggered stop order finds nothing to fill and does not belong to the summary
+        assert_eq!(summary, ExecutionSummary {
+            order_id: 4,
+            filled_size: 4,
+            average_price: Some(101.5),
+            resting_size: 2,
+            cancelled_size: 0,
+        });
+
+        let summary = book.try_execute_order("Stp S $0 #3 u4 @103".parse().unwrap(), &mut logger).unwrap();
+        assert_eq!((summary.filled_size, summary.resting_size, summary.average_price), (2, 0, Some(103.0)));
+        assert_eq!((summary.cancelled_size, summary.remaining_size()), (1, 1));
     }
 
     #[test]
-    fn test_matching1() {
-        // Source: _MessageBook1.txt
-        let orders = [
-            "Lim S $110 #6 u1",
-            "Lim S $120 #3 u2",
-            "Lim S $115 #4 u3",
-            "Lim S $105 #5 u4",
-            "Lim S $110 #2 u5",
-            "Lim S $105 #3 u6",
-            "Lim B $130 #23 u7",
-        ];
+    fn top_of_book_and_depth() {
+        let mut book = OrderBook::new();
+        assert_eq!((book.best_bid(), book.spread(), book.mid_price()), (None, None, None));
+        book.execute_order("Lim B $99 #5 u1".parse().unwrap(), &mut DummyLogger);
+        book.execute_order("Lim B $99 #2 u2".parse().unwrap(), &mut DummyLogger);
+        book.execute_order("Lim B $98 #7 u3".parse().unwrap(), &mut DummyLogger);
+        book.execute_order("Ice S $102 #9 u4 ^3".parse().unwrap(), &mut DummyLogger);
+        assert_eq!(book.best_bid(), Some(PriceLevel { price: 99, size: 7, orders: 2 }));
+        assert_eq!(book.best_ask(), Some(PriceLevel { price: 102, size: 3, orders: 1 }));
+        assert_eq!(book.spread(), Some(3));
+        assert_eq!(book.mid_price(), Some(100.5));
 
-        let expected_log = [
-            "Q #6",
-            "Q #3",
-            "Q #4",
-            "Q #5",
-            "Q #2",
-            "Q #3",
-            "F #5 $105 u4",
-            "F #3 $105 u6",
-            "F #6 $110 u1",
-            "F #2 $110 u5",
-            "F #4 $115 u3",
-            "F #3 $120 u2",
-        ];
+        book.execute_order("Lim S $104 #1 u5".parse().unwrap(), &mut DummyLogger);
+        let depth = book.depth(2);
+        assert_eq!(depth.bids, [PriceLevel { price: 99, size: 7, orders: 2 }, PriceLevel { price: 98, size: 7, orders: 1 }]);
+        assert_eq!(depth.asks, [PriceLevel { price: 102, size: 3, orders: 1 }, PriceLevel { price: 104, size: 1, orders: 1 }]);
+        assert_eq!(book.depth(1).bids.len(), 1);
+        assert_eq!(book.depth(0), DepthSnapshot::default());
 
-        let mut logger = VectorLogger::new();
+        let levels: Vec<_> = book.ask_levels().map(|level| (level.price(), level.total_size(), level.order_count())).collect();
+        assert_eq!(levels, [(102, 9, 1), (104, 1, 1)]);
+        let level = book.bid_levels().next().unwrap();
+        assert_eq!(level.to_price_level(), PriceLevel { price: 99, size: 7, orders: 2 });
+        assert_eq!(level.orders().map(|order| order.order_id()).collect::<Vec<_>>(), [1, 2]);
+        assert_eq!(book.bid_levels().map(|level| level.to_price_level()).collect::<Vec<_>>(), book.depth(usize::MAX).bids);
+        assert_eq!(OrderBook::new().ask_levels().count(), 0);
+    }
+
+    #[test]
+    fn checksum_of_replicated_book() {
+        assert_eq!(OrderBook::new().checksum(), 0x3069_4c07);
         let mut book = OrderBook::new();
-        for s in &orders {
+        let mut logger = VectorLogger::new();
+        for s in &["Lim B $99 #5 u1", "Lim B $99 #2 u2", "Lim B $98 #7 u3", "Lim S $102 #4 u4", "Lim S $104 #1 u5", "IoC B $102 #1 u6"] {
             book.execute_order(s.parse().unwrap(), &mut logger);
         }
-        book.check_bid_len(0);
-        book.check_ask_len(0);
+        assert_eq!(book.checksum(), 0x8419_076a);
 
-        check_log(logger.as_slice(), &expected_log);
+        // Replica built from the log alone
+        let mut replica = OrderBook::new();
+        for item in logger.as_slice() {
+            match *item {
+                LogItem::Enqueued { size, price, side, user_id, .. } => {
+                    replica.execute_order(IncomingOrder { price_limit: price, size, user_id, kind: OrderKind::Limit, side, expires_at: None, min_fill_size: None, reduce_only: false }, &mut DummyLogger);
+                }
+                LogItem::Fulfilled { size, order_id, .. } => {
+                    let view = replica.inspect_order(order_id).unwrap();
+                    replica.amend_order(order_id, view.order.price_limit, view.order.size - size, &mut DummyLogger).unwrap();
+                }
+                _ => {}
+            }
+        }
+        assert_eq!(replica.checksum(), book.checksum());
+        replica.cancel_order(1, &mut DummyLogger).unwrap();
+        assert_ne!(replica.checksum(), book.checksum());
     }
 
     #[test]
-    fn test_matching2() {
-        // Source: _MessageBook2.txt
-        let orders = [
-            "Lim S $120 #1 u1",
-            "Lim S $115 #4 u2",
-            "Lim B $108 #3 u3",
-            "Lim S $105 #5 u4",
-            "Lim S $105 #6 u5",
-            "Lim B $110 #5 u6",
-            "Lim B $113 #2 u7",
-            "Lim B $118 #6 u8",
-        ];
+    fn market_order_ignores_price_limit() {
+        let mut book = OrderBook::with_config(BookConfig::strict());
+        book.execute_order("Lim S $120 #5 u1".parse().unwrap(), &mut DummyLogger);
+        book.execute_order("Lim S $110 #5 u2".parse().unwrap(), &mut DummyLogger);
 
-        let expected_log = [
-            "Q #1",
-            "Q #4",
-            "Q #3",
+        let mut logger = VectorLogger::new();
+        book.execute_order("Mkt B $0 #7 u3".parse().unwrap(), &mut logger);
+        check_log(logger.as_slice(), &["F #5 $110 u2 o2", "F #2 $120 u1 o1"]);
+        book.check_ask_list(&["Lim S $120 #3 u1"]);
 
-            // Lim S $105 #5 u4
-            "F #3 $108 u3",
-            "Q #2",
+        let mut logger = VectorLogger::new();
+        book.execute_order("Mkt B $0 #4 u3".parse().unwrap(), &mut logger);
+        check_log(logger.as_slice(), &["F #3 $120 u1 o1", "C B #1 $0 u3 o4"]);
+        assert!(book.ask().is_empty());
 
-            // Lim S $105 #6 u5
-            "Q #6",
+        let mut logger = VectorLogger::new();
+        book.execute_order("Mkt S $0 #2 u3".parse().unwrap(), &mut logger);
+        check_log(logger.as_slice(), &["C S #2 $0 u3 o5"]);
+        assert!(book.bid().is_empty());
+    }
 
-            // Lim B $110 #5 u6
-            "F #2 $105 u4",
-            "F #3 $105 u5",
+    #[test]
+    fn best_prices_are_cached() {
+        let mut book = OrderBook::from_orders(&["Lim S $120 #5 u1", "Lim S $110 #5 u2", "Lim B $100 #5 u3"]);
+        assert_eq!((book.bid().best_price(), book.ask().best_price()), (Some(100), Some(110)));
 
-            // Lim B $113 #2 u7
-            "F #2 $105 u5",
+        let mut logger = VectorLogger::new();
+        book.execute_order("Lim B $105 #2 u4".parse().unwrap(), &mut logger);
+        check_log(logger.as_slice(), &["Q B #2 $105 u4 o4"]);
+        let improved = book.last_order_id().unwrap();
+        assert_eq!(book.bid().best_price(), Some(105));
 
-            // Lim B $118 #6 u8
-            "F #1 $105 u5",
-            "F #4 $115 u2",
-            "Q #1",
+        let mut logger = VectorLogger::new();
+        book.execute_order("Lim B $110 #5 u4".parse().unwrap(), &mut logger);
+        check_log(logger.as_slice(), &["F #5 $110 u2 o2"]);
+        assert_eq!(book.ask().best_price(), Some(120));
+
+        book.force_cancel(improved, &mut DummyLogger).unwrap();
+        assert_eq!(book.bid().best_price(), Some(100));
+    }
+
+    #[test]
+    fn batch_execution_keeps_order_within_class() {
+        let commands: Vec<_> = ["Lim S $100 #5 u1", "Lim B $100 #2 u2", "Lim B $100 #2 u3"].iter()
+            .map(|s| Command::New(s.parse().unwrap()))
+            .collect();
+        for &cancels_first in &[false, true] {
+            let mut book = OrderBook::new();
+            let mut logger = CountingLogger::new();
+            book.execute_batch(commands.clone(), BatchOptions { cancels_first, ..BatchOptions::default() }, &mut logger).unwrap();
+            book.check_ask_list(&["Lim S $100 #1 u1"]);
+            assert_eq!(logger.counts().fulfilled, EventCount { count: 2, size: 4 });
+        }
+
+        // Cancels and amends run before the new orders, each class in the order of input
+        let mut book = OrderBook::from_orders(&["Lim S $100 #5 u1", "Lim S $101 #5 u2"]);
+        let commands = vec![
+            Command::New("Lim B $101 #6 u3".parse().unwrap()),
+            Command::Cancel(1),
+            Command::New("Lim B $101 #2 u4".parse().unwrap()),
+            Command::Amend { order_id: 2, price: 101, size: 4 },
         ];
+        let mut logger = VectorLogger::new();
+        book.execute_batch(commands, BatchOptions { cancels_first: true, ..BatchOptions::default() }, &mut logger).unwrap();
+        check_log(logger.as_slice(), &["C S #5 $100 u1 o1", "M #4 $101 o2", "F #4 $101 u2 o2", "Q B #2 $101 u3 o3", "Q B #2 $101 u4 o4"]);
+        book.check_bid_list(&["Lim B $101 #2 u3", "Lim B $101 #2 u4"]);
+        assert!(book.best_ask().is_none());
+    }
+
+    #[test]
+    fn atomic_batch_is_rolled_back() {
+        let mut book = OrderBook::from_orders(&["Lim S $101 #5 u1"]);
+        let order: IncomingOrder = "Lim B $101 #2 u2".parse().unwrap();
+        let commands = vec![Command::from(order.clone()), Command::Cancel(9)];
+        let atomic = BatchOptions { atomic: true, ..BatchOptions::default() };
 
         let mut logger = VectorLogger::new();
-        let mut book = OrderBook::new();
-        for s in &orders {
-            book.execute_order(s.parse().unwrap(), &mut logger);
-        }
+        assert_eq!(book.execute_batch(commands.clone(), atomic, &mut logger), Err(BatchError { index: 1, reason: RejectReason::UnknownOrder }));
+        assert!(logger.as_slice().is_empty());
+        book.check_ask_list(&["Lim S $101 #5 u1"]);
+        assert_eq!((book.last_order_id(), book.log_sequence()), (Some(1), 1));
 
-        book.check_bid_list(&[
-            "Lim B $118 #1 u8",
-        ]);
-        book.check_ask_list(&[
-            "Lim S $120 #1 u1",
-        ]);
+        book.execute_batch(vec![order], atomic, &mut logger).unwrap();
+        check_log(logger.as_slice(), &["F #2 $101 u1 o1"]);
+        book.check_ask_list(&["Lim S $101 #3 u1"]);
 
-        check_log(logger.as_slice(), &expected_log);
+        let mut logger = VectorLogger::new();
+        book.execute_batch(commands, BatchOptions::default(), &mut logger).unwrap();
+        check_log(logger.as_slice(), &["F #2 $101 u1 o1", "R #0 unknown-order"]);
+    }
+
+    #[test]
+    fn order_group_is_executed_as_transaction() {
+        let mut book = OrderBook::from_orders(&["Lim S $101 #5 u1", "Lim B $99 #5 u2"]);
+        let group = |list: &[&str]| list.iter().map(|s| s.parse().unwrap()).collect::<Vec<IncomingOrder>>();
+
+        let mut logger = VectorLogger::new();
+        let rejected = book.execute_group(group(&["IoC B $101 #2 u3", "Ice S $102 #4 u3 ^0"]), &mut logger);
+        assert_eq!(rejected, Err(BatchError { index: 1, reason: RejectReason::ZeroDisplaySize }));
+        assert!(logger.as_slice().is_empty());
+        book.check_ask_list(&["Lim S $101 #5 u1"]);
+        assert_eq!(book.last_order_id(), Some(2));
+
+        let summaries = book.execute_group(group(&["IoC B $101 #2 u3", "Lim S $102 #4 u3"]), &mut logger).unwrap();
+        assert_eq!(summaries.iter().map(|summary| summary.order_id).collect::<Vec<_>>(), [3, 4]);
+        check_log(logger.as_slice(), &["F #2 $101 u1 o1", "Q S #4 $102 u3 o4"]);
+        book.check_ask_list(&["Lim S $101 #3 u1", "Lim S $102 #4 u3"]);
+
+        // Any error of the closure rolls the transaction back
+        let mut logger = VectorLogger::new();
+        let result: Result<(), CancelError> = book.transaction(&mut logger, |book, logger| {
+            book.cancel_order(1, logger)?;
+            book.cancel_order(1, logger)?;
+            Ok(())
+        });
+        assert_eq!(result, Err(CancelError::UnknownOrder(1)));
+        assert!(logger.as_slice().is_empty());
+        book.check_ask_list(&["Lim S $101 #3 u1", "Lim S $102 #4 u3"]);
+    }
+
+    #[test]
+    fn to_vec_round_trip_keeps_priority() {
+        let orders = [
+            "Lim B $100 #1 u1",
+            "Lim B $101 #1 u2",
+            "Lim B $100 #1 u3",
+            "Lim S $110 #1 u4",
+            "Lim S $110 #1 u5",
+            "Lim S $105 #1 u6",
+        ];
+        let book = OrderBook::from_orders(&orders);
+        let restored = OrderBook::from_vec(book.to_vec());
+        restored.check_bid_list(&[orders[1], orders[0], orders[2]]);
+        restored.check_ask_list(&[orders[5], orders[3], orders[4]]);
+    }
+
+    #[cfg(all(feature = "serde", feature = "std"))]
+    #[test]
+    fn serde_round_trip() {
+        let orders = [
+            "Lim B $100 #1 u1",
+            "Ice S $110 #10 u2 ^2 !50",
+            "StpLim S $90 #1 u3 @95",
+        ];
+        let book = OrderBook::from_orders(&orders);
+        let json = serde_json::to_string(&book).unwrap();
+        let restored: OrderBook = serde_json::from_str(&json).unwrap();
+        assert_eq!(restored.to_vec(), book.to_vec());
+
+        let item = LogItem::Rejected { size: 0, reason: RejectReason::ZeroSize };
+        assert_eq!(serde_json::from_str::<LogItem>(&serde_json::to_string(&item).unwrap()).unwrap(), item);
     }
 
+    #[cfg(feature = "std")]
     #[test]
     fn matching_with_20_orders() {
-        let orders = create_orders();
+        let orders = crate::create_orders();
         let mut book = OrderBook::from_vec(orders);
         let mut logger = DummyLogger;
         book.check_bid_len(3500);
@@ -740,10 +4226,42 @@ pub mod tests {
             size: 200,
             user_id: 0,
             kind: OrderKind::Limit,
-            side: OrderSide::Buy
+            side: OrderSide::Buy,
+            expires_at: None,
+            min_fill_size: None,
+            reduce_only: false,
         };
         book.execute_order(order, &mut logger);
         book.check_bid_len(3500);
         book.check_ask_len(3500-20);
     }
+
+    fn check_book_with_queues<QB: Queue<Order<Buy>>, QA: Queue<Order<Sell>>>() {
+        let mut book = OrderBook::new();
+        let mut other = OrderBook::with_queues::<QB, QA>();
+        let (mut log, mut other_log) = (VectorLogger::new(), VectorLogger::new());
+        for s in &["Lim B $99 #5 u1", "Lim B $100 #3 u2", "Lim B $99 #2 u3", "Lim S $102 #4 u4", "Lim S $101 #2 u5",
+                   "IoC S $99 #6 u6", "Lim B $102 #3 u7", "Lim S $103 #4 u8", "Lim S $103 #1 u9"] {
+            book.execute_order(s.parse().unwrap(), &mut log);
+            other.execute_order(s.parse().unwrap(), &mut other_log);
+        }
+        book.cancel_order(1, &mut log).unwrap();
+        other.cancel_order(1, &mut other_log).unwrap();
+        book.amend_order(8, 103, 2, &mut log).unwrap();
+        other.amend_order(8, 103, 2, &mut other_log).unwrap();
+        book.amend_order(4, 104, 4, &mut log).unwrap();
+        other.amend_order(4, 104, 4, &mut other_log).unwrap();
+        assert_eq!(other_log.as_slice(), log.as_slice());
+        assert_eq!(other.to_vec(), book.to_vec());
+        assert_eq!(other.depth(10), book.depth(10));
+        assert_eq!(format!("{:?}", other), format!("{:?}", book));
+    }
+
+    #[test]
+    fn book_with_other_queues() {
+        use crate::queues::{LinkedQueue, SimpleVecQueue, VecDequeQueue};
+
+        check_book_with_queues::<SimpleVecQueue<Buy>, VecDequeQueue<Sell>>();
+        check_book_with_queues::<LinkedQueue<Buy>, LinkedQueue<Sell>>();
+    }
 }
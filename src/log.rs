@@ -1,45 +1,515 @@
 //! Logger implementations
+#[cfg(feature = "std")]
 use smallvec::SmallVec;
+use crate::config::{BookConfig, BookState, RejectReason};
+use crate::order::{IncomingOrder, Price, OrderId, OrderSide, Size, UserId};
+#[cfg(feature = "std")]
+use crate::risk::RiskChecker;
+use crate::tape::TradeTape;
+use core::fmt;
+use core::str::FromStr;
+#[cfg(feature = "std")]
+use std::sync::{mpsc, Mutex};
+use alloc::vec::Vec;
+use alloc::string::{String, ToString};
 
 /// Order execution result presented to logger
+///
+/// The numeric types default to those of the [OrderBook](crate::OrderBook), like in [IncomingOrder](IncomingOrder).
 #[allow(missing_docs)]
-#[derive(PartialEq)]
-pub enum LogItem {
-    /// Order was added to the corresponding order queue
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum LogItem<P = Price, S = Size, U = UserId> {
+    /// Order `order_id` of the user `user_id` was added to the `side` order queue at `price`
     Enqueued {
-        size: u64,
+        size: S,
+        price: P,
+        side: OrderSide,
+        user_id: U,
+        order_id: OrderId,
     },
     /// Order was fulfilled with another passive order
+    ///
+    /// `user_id` and `order_id` identify the passive order.
     Fulfilled {
-        size: u64,
-        price: u64,
-        user_id: u64,
+        size: S,
+        price: P,
+        user_id: U,
+        order_id: OrderId,
     },
-    /// Order was cancelled
+    /// Order `order_id` of the user `user_id` with the limit `price` was cancelled
+    ///
+    /// Market orders carry their (ignored) price limit.
     Cancelled {
-        size: u64,
+        size: S,
+        price: P,
+        side: OrderSide,
+        user_id: U,
+        order_id: OrderId,
+    },
+    /// Order was rejected by validation
+    Rejected {
+        size: S,
+        reason: RejectReason,
+    },
+    /// Resting order was cancelled by an administrator
+    ForceCancelled {
+        size: S,
+        order_id: OrderId,
+    },
+    /// Resting order `order_id` was amended to `price` and remaining `size`
+    ///
+    /// Items logged after it (fills, enqueueing) refer to the amended order.
+    Amended {
+        size: S,
+        price: P,
+        order_id: OrderId,
+    },
+    /// Stop order was added to the trigger queue, or the order was set aside for the auction held
+    /// when trading reopens
+    Parked {
+        size: S,
+    },
+    /// Stop order `order_id` was triggered
+    ///
+    /// Items logged after it are execution results of the triggered order.
+    Triggered {
+        order_id: OrderId,
+    },
+    /// Displayed part of the passive iceberg order `order_id` was refilled with `size` units from its hidden reserve
+    ///
+    /// Fills of the order logged after it are executions against the former hidden reserve.
+    Refilled {
+        size: S,
+        order_id: OrderId,
+    },
+    /// Resting or stop order `order_id` expired with remaining `size`
+    Expired {
+        size: S,
+        order_id: OrderId,
+    },
+    /// Size accounting mismatch detected in audit mode
+    IntegrityError {
+        expected: S,
+        actual: S,
+    },
+    /// Trading state of the book changed to `state`
+    StateChanged {
+        state: BookState,
     },
 }
 
-impl ToString for LogItem {
-    fn to_string(&self) -> String {
+impl fmt::Display for LogItem {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LogItem::Enqueued { size, price, side, user_id, order_id } => {
+                write!(f, "Q {} #{} ${} u{} o{}", side.as_str(), size, price, user_id, order_id)
+            }
+            LogItem::Fulfilled { size, price, user_id, order_id } => write!(f, "F #{} ${} u{} o{}", size, price, user_id, order_id),
+            LogItem::Cancelled { size, price, side, user_id, order_id } => {
+                write!(f, "C {} #{} ${} u{} o{}", side.as_str(), size, price, user_id, order_id)
+            }
+            LogItem::Rejected { size, reason } => write!(f, "R #{} {}", size, reason),
+            LogItem::ForceCancelled { size, order_id } => write!(f, "A #{} o{}", size, order_id),
+            LogItem::Amended { size, price, order_id } => write!(f, "M #{} ${} o{}", size, price, order_id),
+            LogItem::Parked { size } => write!(f, "P #{}", size),
+            LogItem::Triggered { order_id } => write!(f, "T o{}", order_id),
+            LogItem::Refilled { size, order_id } => write!(f, "H #{} o{}", size, order_id),
+            LogItem::Expired { size, order_id } => write!(f, "X #{} o{}", size, order_id),
+            LogItem::IntegrityError { expected, actual } => write!(f, "E #{} #{}", expected, actual),
+            LogItem::StateChanged { state } => write!(f, "S {}", state),
+        }
+    }
+}
+
+/// Error of parsing a [LogItem](LogItem) from its text form
+#[allow(missing_docs)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LogItemParseError;
+
+fn parse_field<T: FromStr>(s: &str, prefix: char) -> Result<T, LogItemParseError> {
+    s.strip_prefix(prefix).ok_or(LogItemParseError)?.parse().map_err(|_| LogItemParseError)
+}
+
+/// Parses the text form written by [Display](fmt::Display), e.g. `F #5 $100 u1 o2`
+impl FromStr for LogItem {
+    type Err = LogItemParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parts: Vec<_> = s.split_whitespace().collect();
+        let side = |s: &str| s.parse::<OrderSide>().map_err(|_| LogItemParseError);
+        Ok(match parts.as_slice() {
+            ["Q", side_, size, price, user_id, order_id] => LogItem::Enqueued {
+                size: parse_field(size, '#')?,
+                price: parse_field(price, '$')?,
+                side: side(side_)?,
+                user_id: parse_field(user_id, 'u')?,
+                order_id: parse_field(order_id, 'o')?,
+            },
+            ["F", size, price, user_id, order_id] => LogItem::Fulfilled {
+                size: parse_field(size, '#')?,
+                price: parse_field(price, '$')?,
+                user_id: parse_field(user_id, 'u')?,
+                order_id: parse_field(order_id, 'o')?,
+            },
+            ["C", side_, size, price, user_id, order_id] => LogItem::Cancelled {
+                size: parse_field(size, '#')?,
+                price: parse_field(price, '$')?,
+                side: side(side_)?,
+                user_id: parse_field(user_id, 'u')?,
+                order_id: parse_field(order_id, 'o')?,
+            },
+            ["R", size, reason] => LogItem::Rejected { size: parse_field(size, '#')?, reason: reason.parse()? },
+            ["A", size, order_id] => LogItem::ForceCancelled { size: parse_field(size, '#')?, order_id: parse_field(order_id, 'o')? },
+            ["M", size, price, order_id] => LogItem::Amended {
+                size: parse_field(size, '#')?,
+                price: parse_field(price, '$')?,
+                order_id: parse_field(order_id, 'o')?,
+            },
+            ["P", size] => LogItem::Parked { size: parse_field(size, '#')? },
+            ["T", order_id] => LogItem::Triggered { order_id: parse_field(order_id, 'o')? },
+            ["H", size, order_id] => LogItem::Refilled { size: parse_field(size, '#')?, order_id: parse_field(order_id, 'o')? },
+            ["X", size, order_id] => LogItem::Expired { size: parse_field(size, '#')?, order_id: parse_field(order_id, 'o')? },
+            ["E", expected, actual] => LogItem::IntegrityError { expected: parse_field(expected, '#')?, actual: parse_field(actual, '#')? },
+            ["S", state] => LogItem::StateChanged { state: state.parse()? },
+            _ => return Err(LogItemParseError),
+        })
+    }
+}
+
+impl LogItem {
+    /// Constructs [Enqueued](LogItem::Enqueued) item of `size` units of the order `order_id`
+    pub fn enqueued(order: &IncomingOrder, order_id: OrderId, size: Size) -> Self {
+        LogItem::Enqueued { size, price: order.price_limit, side: order.side, user_id: order.user_id, order_id }
+    }
+
+    /// Constructs [Cancelled](LogItem::Cancelled) item of `size` units of the order `order_id`
+    pub fn cancelled(order: &IncomingOrder, order_id: OrderId, size: Size) -> Self {
+        LogItem::Cancelled { size, price: order.price_limit, side: order.side, user_id: order.user_id, order_id }
+    }
+
+    /// Formats the item like [Display](fmt::Display) with decimal prices and sizes converted from
+    /// ticks and size units of `config`
+    pub fn format_with_config(&self, config: &BookConfig) -> String {
+        let price = |price: Price| config.tick_size.to_decimal(price);
+        let size = |size: u64| config.size_unit.units_to_decimal(size);
+        match self {
+            LogItem::Enqueued { size: s, price: p, side, user_id, order_id } => {
+                format!("Q {} #{} ${} u{} o{}", side.as_str(), size(*s), price(*p), user_id, order_id)
+            }
+            LogItem::Fulfilled { size: s, price: p, user_id, order_id } => {
+                format!("F #{} ${} u{} o{}", size(*s), price(*p), user_id, order_id)
+            }
+            LogItem::Cancelled { size: s, price: p, side, user_id, order_id } => {
+                format!("C {} #{} ${} u{} o{}", side.as_str(), size(*s), price(*p), user_id, order_id)
+            }
+            LogItem::Rejected { size: s, reason } => format!("R #{} {}", size(*s), reason),
+            LogItem::ForceCancelled { size: s, order_id } => format!("A #{} o{}", size(*s), order_id),
+            LogItem::Amended { size: s, price: p, order_id } => format!("M #{} ${} o{}", size(*s), price(*p), order_id),
+            LogItem::Parked { size: s } => format!("P #{}", size(*s)),
+            LogItem::Refilled { size: s, order_id } => format!("H #{} o{}", size(*s), order_id),
+            LogItem::Expired { size: s, order_id } => format!("X #{} o{}", size(*s), order_id),
+            LogItem::IntegrityError { expected, actual } => format!("E #{} #{}", size(*expected), size(*actual)),
+            LogItem::Triggered { .. } | LogItem::StateChanged { .. } => self.to_string(),
+        }
+    }
+
+    /// Formats the item as a flat JSON object with the item type in the `type` field,
+    /// e.g. `{"type":"fulfilled","size":5,"price":100,"user_id":1,"order_id":2}`
+    pub fn to_json(&self) -> String {
         match self {
-            LogItem::Enqueued { size } => format!("Q #{}", size),
-            LogItem::Fulfilled { size, price, user_id } => format!("F #{} ${} u{}", size, price, user_id),
-            LogItem::Cancelled { size } => format!("C #{}", size),
+            LogItem::Enqueued { size, price, side, user_id, order_id } => format!(
+                "{{\"type\":\"enqueued\",\"size\":{},\"price\":{},\"side\":\"{}\",\"user_id\":{},\"order_id\":{}}}",
+                size, price, side.as_str(), user_id, order_id),
+            LogItem::Fulfilled { size, price, user_id, order_id } => format!(
+                "{{\"type\":\"fulfilled\",\"size\":{},\"price\":{},\"user_id\":{},\"order_id\":{}}}",
+                size, price, user_id, order_id),
+            LogItem::Cancelled { size, price, side, user_id, order_id } => format!(
+                "{{\"type\":\"cancelled\",\"size\":{},\"price\":{},\"side\":\"{}\",\"user_id\":{},\"order_id\":{}}}",
+                size, price, side.as_str(), user_id, order_id),
+            LogItem::Rejected { size, reason } => format!("{{\"type\":\"rejected\",\"size\":{},\"reason\":\"{}\"}}", size, reason),
+            LogItem::ForceCancelled { size, order_id } => format!(
+                "{{\"type\":\"force_cancelled\",\"size\":{},\"order_id\":{}}}", size, order_id),
+            LogItem::Amended { size, price, order_id } => format!(
+                "{{\"type\":\"amended\",\"size\":{},\"price\":{},\"order_id\":{}}}", size, price, order_id),
+            LogItem::Parked { size } => format!("{{\"type\":\"parked\",\"size\":{}}}", size),
+            LogItem::Triggered { order_id } => format!("{{\"type\":\"triggered\",\"order_id\":{}}}", order_id),
+            LogItem::Refilled { size, order_id } => format!("{{\"type\":\"refilled\",\"size\":{},\"order_id\":{}}}", size, order_id),
+            LogItem::Expired { size, order_id } => format!("{{\"type\":\"expired\",\"size\":{},\"order_id\":{}}}", size, order_id),
+            LogItem::IntegrityError { expected, actual } => format!(
+                "{{\"type\":\"integrity_error\",\"expected\":{},\"actual\":{}}}", expected, actual),
+            LogItem::StateChanged { state } => format!("{{\"type\":\"state_changed\",\"state\":\"{}\"}}", state),
         }
     }
 }
 
+/// Formats `items` as a JSON array of [flat objects](LogItem::to_json)
+pub fn log_to_json(items: &[LogItem]) -> String {
+    format!("[{}]", items.iter().map(LogItem::to_json).collect::<Vec<_>>().join(","))
+}
+
+/// Log item with its position in the execution log of the book
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SequencedLogItem {
+    /// Sequence number, increasing by one with every item logged by the book
+    pub sequence: u64,
+    /// Time of the operation which produced the item, if the book has a [clock](crate::clock::Clock)
+    pub timestamp: Option<u64>,
+    #[allow(missing_docs)]
+    pub item: LogItem,
+}
+
+/// Fill of a resting (maker) order by an incoming (taker) order
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Trade {
+    /// Trade identifier, sequential within the book starting from 1
+    pub sequence: u64,
+    #[allow(missing_docs)]
+    pub maker_order_id: OrderId,
+    #[allow(missing_docs)]
+    pub taker_order_id: OrderId,
+    #[allow(missing_docs)]
+    pub maker_user_id: UserId,
+    #[allow(missing_docs)]
+    pub taker_user_id: UserId,
+    /// Side of the taker order
+    pub taker_side: OrderSide,
+    #[allow(missing_docs)]
+    pub price: Price,
+    #[allow(missing_docs)]
+    pub size: Size,
+}
+
 /// Represents abstract logger for order execution results
 pub trait ExecutionLogger {
     /// Logs execution result
     fn log(&mut self, item: LogItem);
 
+    /// Logs execution result with its sequence number and timestamp
+    ///
+    /// Books log every item through this method; the default implementation drops the sequencing
+    /// and calls [log](ExecutionLogger::log).
+    fn log_sequenced(&mut self, item: SequencedLogItem) {
+        self.log(item.item)
+    }
+
+    /// Receives the trade of a fill, right after its [Fulfilled](LogItem::Fulfilled) item
+    ///
+    /// Fills of auctions are reported as [AuctionTrades](crate::auction::AuctionTrade) instead.
+    fn trade(&mut self, _trade: Trade) { }
+
     /// Removes previously logged items
     ///
     /// Used for matching transactions that can be cancelled.
     fn cancel(&mut self);
+
+    /// Marks the end of order execution
+    ///
+    /// Items logged before this call can no longer be removed by [cancel](ExecutionLogger::cancel).
+    fn finish(&mut self) { }
+}
+
+/// Logger wrapper which keeps a copy of the items logged for the current order
+pub(crate) struct RecordingLogger<'a, L> {
+    inner: &'a mut L,
+    items: &'a mut Vec<LogItem>,
+}
+
+impl<'a, L: ExecutionLogger> RecordingLogger<'a, L> {
+    pub fn new(inner: &'a mut L, items: &'a mut Vec<LogItem>) -> Self {
+        items.clear();
+        Self { inner, items }
+    }
+}
+
+impl<'a, L: ExecutionLogger> ExecutionLogger for RecordingLogger<'a, L> {
+    fn log(&mut self, item: LogItem) {
+        self.items.push(item.clone());
+        self.inner.log(item);
+    }
+
+    fn log_sequenced(&mut self, item: SequencedLogItem) {
+        self.items.push(item.item.clone());
+        self.inner.log_sequenced(item);
+    }
+
+    fn trade(&mut self, trade: Trade) {
+        self.inner.trade(trade);
+    }
+
+    fn cancel(&mut self) {
+        self.items.clear();
+        self.inner.cancel();
+    }
+
+    fn finish(&mut self) {
+        self.inner.finish();
+    }
+}
+
+/// Logger wrapper which numbers the items logged during one operation of the book
+///
+/// A wrapper created inside another one passes the items through, so nested operations
+/// (e.g. commands of a batch) are numbered once.
+pub(crate) struct SequencingLogger<'a, L> {
+    inner: &'a mut L,
+    /// Sequence number of the last item, `None` for a pass-through wrapper
+    sequence: Option<u64>,
+    timestamp: Option<u64>,
+}
+
+impl<'a, L: ExecutionLogger> SequencingLogger<'a, L> {
+    pub fn new(inner: &'a mut L, sequence: Option<u64>, timestamp: Option<u64>) -> Self {
+        Self { inner, sequence, timestamp }
+    }
+
+    pub fn sequence(&self) -> Option<u64> {
+        self.sequence
+    }
+}
+
+impl<'a, L: ExecutionLogger> ExecutionLogger for SequencingLogger<'a, L> {
+    fn log(&mut self, item: LogItem) {
+        match &mut self.sequence {
+            Some(sequence) => {
+                *sequence += 1;
+                self.inner.log_sequenced(SequencedLogItem { sequence: *sequence, timestamp: self.timestamp, item });
+            }
+            None => self.inner.log(item),
+        }
+    }
+
+    fn log_sequenced(&mut self, item: SequencedLogItem) {
+        self.inner.log_sequenced(item);
+    }
+
+    fn trade(&mut self, trade: Trade) {
+        self.inner.trade(trade);
+    }
+
+    fn cancel(&mut self) {
+        self.inner.cancel();
+    }
+
+    fn finish(&mut self) {
+        self.inner.finish();
+    }
+}
+
+/// Logger wrapper passing the trades to the trade tape and the risk checker of the book
+pub(crate) struct FillLogger<'a, L> {
+    inner: &'a mut L,
+    tape: Option<&'a mut TradeTape>,
+    #[cfg(feature = "std")]
+    risk: Option<&'a Mutex<dyn RiskChecker + Send>>,
+}
+
+impl<'a, L: ExecutionLogger> FillLogger<'a, L> {
+    #[cfg(feature = "std")]
+    pub fn new(inner: &'a mut L, tape: Option<&'a mut TradeTape>, risk: Option<&'a Mutex<dyn RiskChecker + Send>>) -> Self {
+        Self { inner, tape, risk }
+    }
+
+    #[cfg(not(feature = "std"))]
+    pub fn new(inner: &'a mut L, tape: Option<&'a mut TradeTape>) -> Self {
+        Self { inner, tape }
+    }
+}
+
+impl<'a, L: ExecutionLogger> ExecutionLogger for FillLogger<'a, L> {
+    fn log(&mut self, item: LogItem) {
+        self.inner.log(item);
+    }
+
+    fn log_sequenced(&mut self, item: SequencedLogItem) {
+        self.inner.log_sequenced(item);
+    }
+
+    fn trade(&mut self, trade: Trade) {
+        #[cfg(feature = "std")]
+        if let Some(risk) = self.risk {
+            risk.lock().expect("risk checker lock is poisoned").on_fill(&trade);
+        }
+        if let Some(tape) = self.tape.as_mut() {
+            tape.push(trade.clone());
+        }
+        self.inner.trade(trade);
+    }
+
+    fn cancel(&mut self) {
+        self.inner.cancel();
+    }
+
+    fn finish(&mut self) {
+        self.inner.finish();
+    }
+}
+
+enum BufferedEvent {
+    Item(LogItem),
+    Sequenced(SequencedLogItem),
+    Trade(Trade),
+    Finish,
+}
+
+/// Logger of a [transaction](crate::OrderBook::transaction), holding everything logged until the
+/// transaction is committed
+pub struct TransactionLogger(Vec<BufferedEvent>);
+
+impl TransactionLogger {
+    pub(crate) fn new() -> Self {
+        TransactionLogger(Vec::new())
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    /// Returns reason of the first rejection logged after the first `start` events
+    pub(crate) fn rejection_since(&self, start: usize) -> Option<RejectReason> {
+        self.0[start..].iter().find_map(|event| match event {
+            BufferedEvent::Item(LogItem::Rejected { reason, .. }) => Some(*reason),
+            BufferedEvent::Sequenced(SequencedLogItem { item: LogItem::Rejected { reason, .. }, .. }) => Some(*reason),
+            _ => None,
+        })
+    }
+
+    /// Passes the held events to `logger` in the order they were logged
+    pub(crate) fn replay(self, logger: &mut impl ExecutionLogger) {
+        for event in self.0 {
+            match event {
+                BufferedEvent::Item(item) => logger.log(item),
+                BufferedEvent::Sequenced(item) => logger.log_sequenced(item),
+                BufferedEvent::Trade(trade) => logger.trade(trade),
+                BufferedEvent::Finish => logger.finish(),
+            }
+        }
+    }
+}
+
+impl ExecutionLogger for TransactionLogger {
+    fn log(&mut self, item: LogItem) {
+        self.0.push(BufferedEvent::Item(item));
+    }
+
+    fn log_sequenced(&mut self, item: SequencedLogItem) {
+        self.0.push(BufferedEvent::Sequenced(item));
+    }
+
+    fn trade(&mut self, trade: Trade) {
+        self.0.push(BufferedEvent::Trade(trade));
+    }
+
+    fn cancel(&mut self) {
+        let finished = self.0.iter().rposition(|event| matches!(event, BufferedEvent::Finish)).map_or(0, |index| index + 1);
+        self.0.truncate(finished);
+    }
+
+    fn finish(&mut self) {
+        self.0.push(BufferedEvent::Finish);
+    }
 }
 
 /// Dummy logger which logs everything into the void
@@ -51,13 +521,18 @@ impl ExecutionLogger for DummyLogger {
     fn cancel(&mut self) { }
 }
 
+#[cfg(feature = "std")]
+type LogItems = SmallVec<[LogItem; 32]>;
+#[cfg(not(feature = "std"))]
+type LogItems = Vec<LogItem>;
+
 /// Logger which uses vector as storage
-pub struct VectorLogger(SmallVec<[LogItem; 32]>);
+pub struct VectorLogger(LogItems);
 
 impl VectorLogger {
     /// Constructs `VectorLogger`
     pub fn new() -> Self {
-        Self(SmallVec::new())
+        Self(LogItems::new())
     }
 
     /// Returns slice representation of logged items
@@ -66,6 +541,12 @@ impl VectorLogger {
     }
 }
 
+impl Default for VectorLogger {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl ExecutionLogger for VectorLogger {
     fn log(&mut self, item: LogItem) {
         self.0.push(item);
@@ -74,4 +555,311 @@ impl ExecutionLogger for VectorLogger {
     fn cancel(&mut self) {
         self.0.clear();
     }
-}
\ No newline at end of file
+}
+/// Logger which keeps sequenced items in a vector
+#[derive(Default)]
+pub struct SequencedVectorLogger(Vec<SequencedLogItem>);
+
+impl SequencedVectorLogger {
+    /// Constructs `SequencedVectorLogger`
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns slice of logged items
+    pub fn as_slice(&self) -> &[SequencedLogItem] {
+        &self.0
+    }
+}
+
+impl ExecutionLogger for SequencedVectorLogger {
+    fn log(&mut self, item: LogItem) {
+        let sequence = self.0.last().map_or(1, |last| last.sequence + 1);
+        self.0.push(SequencedLogItem { sequence, timestamp: None, item });
+    }
+
+    fn log_sequenced(&mut self, item: SequencedLogItem) {
+        self.0.push(item);
+    }
+
+    fn cancel(&mut self) {
+        self.0.clear();
+    }
+}
+
+/// Logger which only collects trades
+#[derive(Default)]
+pub struct TradeLogger(Vec<Trade>);
+
+impl TradeLogger {
+    /// Constructs `TradeLogger`
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns slice of collected trades
+    pub fn trades(&self) -> &[Trade] {
+        &self.0
+    }
+}
+
+impl ExecutionLogger for TradeLogger {
+    fn log(&mut self, _item: LogItem) { }
+
+    fn trade(&mut self, trade: Trade) {
+        self.0.push(trade);
+    }
+
+    fn cancel(&mut self) { }
+}
+
+/// Logger which passes items to a callback
+///
+/// Items are buffered until the end of order execution, so the callback never receives items
+/// removed by [cancel](ExecutionLogger::cancel).
+pub struct FnLogger<F> {
+    callback: F,
+    pending: Vec<LogItem>,
+}
+
+impl<F: FnMut(LogItem)> FnLogger<F> {
+    /// Constructs `FnLogger` calling `callback` for every item
+    pub fn new(callback: F) -> Self {
+        Self { callback, pending: Vec::new() }
+    }
+}
+
+impl<F: FnMut(LogItem)> ExecutionLogger for FnLogger<F> {
+    fn log(&mut self, item: LogItem) {
+        self.pending.push(item);
+    }
+
+    fn cancel(&mut self) {
+        self.pending.clear();
+    }
+
+    fn finish(&mut self) {
+        for item in self.pending.drain(..) {
+            (self.callback)(item);
+        }
+    }
+}
+
+/// Logger which passes everything to two loggers
+pub struct TeeLogger<A, B>(pub A, pub B);
+
+impl<A: ExecutionLogger, B: ExecutionLogger> ExecutionLogger for TeeLogger<A, B> {
+    fn log(&mut self, item: LogItem) {
+        self.0.log(item.clone());
+        self.1.log(item);
+    }
+
+    fn log_sequenced(&mut self, item: SequencedLogItem) {
+        self.0.log_sequenced(item.clone());
+        self.1.log_sequenced(item);
+    }
+
+    fn trade(&mut self, trade: Trade) {
+        self.0.trade(trade.clone());
+        self.1.trade(trade);
+    }
+
+    fn cancel(&mut self) {
+        self.0.cancel();
+        self.1.cancel();
+    }
+
+    fn finish(&mut self) {
+        self.0.finish();
+        self.1.finish();
+    }
+}
+
+/// Sending half of a channel used by [ChannelLogger](ChannelLogger)
+///
+/// Implemented for the standard channels; other channels can be used by implementing it.
+#[cfg(feature = "std")]
+pub trait LogSender {
+    /// Sends `item`, returning it back if the receiver is gone
+    fn send_item(&self, item: LogItem) -> Result<(), LogItem>;
+}
+
+#[cfg(feature = "std")]
+impl LogSender for mpsc::Sender<LogItem> {
+    fn send_item(&self, item: LogItem) -> Result<(), LogItem> {
+        self.send(item).map_err(|e| e.0)
+    }
+}
+
+#[cfg(feature = "std")]
+impl LogSender for mpsc::SyncSender<LogItem> {
+    fn send_item(&self, item: LogItem) -> Result<(), LogItem> {
+        self.send(item).map_err(|e| e.0)
+    }
+}
+
+/// Logger which sends items of completed orders over a channel
+///
+/// Items are buffered until [flush](ChannelLogger::flush) or drop; items of an order whose
+/// execution has not finished are never sent, so [cancel](ExecutionLogger::cancel) only has to discard them.
+#[cfg(feature = "std")]
+pub struct ChannelLogger<S: LogSender = mpsc::Sender<LogItem>> {
+    sender: S,
+    pending: Vec<LogItem>,
+    finished: Vec<LogItem>,
+}
+
+#[cfg(feature = "std")]
+impl<S: LogSender> ChannelLogger<S> {
+    /// Constructs `ChannelLogger` sending with `sender`
+    pub fn new(sender: S) -> Self {
+        Self { sender, pending: Vec::new(), finished: Vec::new() }
+    }
+
+    /// Sends items of the finished orders
+    ///
+    /// Fails with the first item which cannot be sent; the items after it stay buffered.
+    pub fn flush(&mut self) -> Result<(), LogItem> {
+        let mut items = core::mem::take(&mut self.finished).into_iter();
+        while let Some(item) = items.next() {
+            if let Err(item) = self.sender.send_item(item) {
+                self.finished = items.collect();
+                return Err(item);
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "std")]
+impl<S: LogSender> ExecutionLogger for ChannelLogger<S> {
+    fn log(&mut self, item: LogItem) {
+        self.pending.push(item);
+    }
+
+    fn cancel(&mut self) {
+        self.pending.clear();
+    }
+
+    fn finish(&mut self) {
+        self.finished.append(&mut self.pending);
+    }
+}
+
+#[cfg(feature = "std")]
+impl<S: LogSender> Drop for ChannelLogger<S> {
+    fn drop(&mut self) {
+        // Nobody can receive the rest if the receiver is gone
+        let _ = self.flush();
+    }
+}
+
+/// Number of log items of one type and their total size
+#[allow(missing_docs)]
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct EventCount {
+    pub count: u64,
+    pub size: Size,
+}
+
+impl EventCount {
+    fn add(&mut self, size: Size) {
+        self.count += 1;
+        self.size = self.size.saturating_add(size);
+    }
+
+    fn merge(&mut self, other: &EventCount) {
+        self.count += other.count;
+        self.size = self.size.saturating_add(other.size);
+    }
+}
+
+/// Aggregated counts of log items by type
+#[allow(missing_docs)]
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct LogCounts {
+    pub enqueued: EventCount,
+    pub fulfilled: EventCount,
+    pub cancelled: EventCount,
+    pub rejected: EventCount,
+    pub force_cancelled: EventCount,
+    pub amended: EventCount,
+    pub parked: EventCount,
+    pub triggered: EventCount,
+    pub refilled: EventCount,
+    pub expired: EventCount,
+    pub integrity_errors: EventCount,
+    pub state_changes: EventCount,
+}
+
+impl LogCounts {
+    fn add(&mut self, item: &LogItem) {
+        match *item {
+            LogItem::Enqueued { size, .. } => self.enqueued.add(size),
+            LogItem::Fulfilled { size, .. } => self.fulfilled.add(size),
+            LogItem::Cancelled { size, .. } => self.cancelled.add(size),
+            LogItem::Rejected { size, .. } => self.rejected.add(size),
+            LogItem::ForceCancelled { size, .. } => self.force_cancelled.add(size),
+            LogItem::Amended { size, .. } => self.amended.add(size),
+            LogItem::Parked { size } => self.parked.add(size),
+            LogItem::Triggered { .. } => self.triggered.add(0),
+            LogItem::Refilled { size, .. } => self.refilled.add(size),
+            LogItem::Expired { size, .. } => self.expired.add(size),
+            LogItem::IntegrityError { .. } => self.integrity_errors.add(0),
+            LogItem::StateChanged { .. } => self.state_changes.add(0),
+        }
+    }
+
+    fn merge(&mut self, other: &LogCounts) {
+        self.enqueued.merge(&other.enqueued);
+        self.fulfilled.merge(&other.fulfilled);
+        self.cancelled.merge(&other.cancelled);
+        self.rejected.merge(&other.rejected);
+        self.force_cancelled.merge(&other.force_cancelled);
+        self.amended.merge(&other.amended);
+        self.parked.merge(&other.parked);
+        self.triggered.merge(&other.triggered);
+        self.refilled.merge(&other.refilled);
+        self.expired.merge(&other.expired);
+        self.integrity_errors.merge(&other.integrity_errors);
+        self.state_changes.merge(&other.state_changes);
+    }
+}
+
+/// Logger which only counts log items and their sizes
+///
+/// Unlike [VectorLogger](VectorLogger), cancelling only discards items of the order being executed.
+#[derive(Default)]
+pub struct CountingLogger {
+    committed: LogCounts,
+    pending: LogCounts,
+}
+
+impl CountingLogger {
+    /// Constructs `CountingLogger`
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns counts of the items logged so far
+    pub fn counts(&self) -> LogCounts {
+        let mut counts = self.committed.clone();
+        counts.merge(&self.pending);
+        counts
+    }
+}
+
+impl ExecutionLogger for CountingLogger {
+    fn log(&mut self, item: LogItem) {
+        self.pending.add(&item);
+    }
+
+    fn cancel(&mut self) {
+        self.pending = LogCounts::default();
+    }
+
+    fn finish(&mut self) {
+        let pending = core::mem::take(&mut self.pending);
+        self.committed.merge(&pending);
+    }
+}
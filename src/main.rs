@@ -1,29 +1,317 @@
 use std::env;
+use std::process;
+use std::ffi::OsString;
+use std::path::PathBuf;
 use matcher::OrderBook;
 use matcher::log::VectorLogger;
-use std::fs::File;
-use std::io::{BufReader, BufRead};
+use matcher::order::IncomingOrder;
+use matcher::pipeline;
+use matcher::verify;
+use matcher::decode::{OrderDecoder, TextDecoder, CsvDecoder, JsonDecoder, Command};
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufRead, BufWriter, Read, Write};
 
-fn main() {
-    let mut args = env::args_os();
-    if args.len() < 2 {
-        eprintln!("Usage: matcher <filename>");
-        return;
+fn usage() -> ! {
+    eprintln!("Usage: matcher [--format <text|csv|json>] [--load-snapshot <snapshot>] [--sequenced] [--parse-threads <n>] <file or directory>...");
+    eprintln!("       matcher [--format <text|csv|json>] [--load-snapshot <snapshot>] [--save-snapshot <snapshot>] stream");
+    eprintln!("       matcher verify <snapshot> <commands> <expected snapshot>");
+    process::exit(2);
+}
+
+#[cfg(unix)]
+mod signal {
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    static SHUTDOWN: AtomicBool = AtomicBool::new(false);
+
+    extern "C" fn handle(_signal: libc::c_int) {
+        SHUTDOWN.store(true, Ordering::SeqCst);
+    }
+
+    /// Installs SIGINT and SIGTERM handlers
+    ///
+    /// Handlers are installed without `SA_RESTART`, so a blocking read returns as soon as a signal arrives.
+    pub fn install() {
+        unsafe {
+            let mut action: libc::sigaction = std::mem::zeroed();
+            action.sa_sigaction = handle as extern "C" fn(libc::c_int) as libc::sighandler_t;
+            libc::sigemptyset(&mut action.sa_mask);
+            libc::sigaction(libc::SIGINT, &action, std::ptr::null_mut());
+            libc::sigaction(libc::SIGTERM, &action, std::ptr::null_mut());
+        }
+    }
+
+    pub fn requested() -> bool {
+        SHUTDOWN.load(Ordering::SeqCst)
+    }
+}
+
+#[cfg(not(unix))]
+mod signal {
+    pub fn install() { }
+
+    pub fn requested() -> bool {
+        false
+    }
+}
+
+/// Format of the input orders
+#[derive(Clone, Copy, PartialEq, Default)]
+enum Format {
+    /// Terse text format, e.g. `Lim B $100 #5 u1`
+    #[default]
+    Text,
+    /// Comma-separated records, each file (or the standard input) starting with a header line
+    Csv,
+    /// One JSON object per line, log items are printed as JSON objects too
+    Json,
+}
+
+/// Decoder of the input lines in the selected format
+enum LineDecoder {
+    Text(TextDecoder),
+    Csv(Option<CsvDecoder>),
+    Json(JsonDecoder),
+}
+
+impl LineDecoder {
+    fn new(format: Format) -> Self {
+        match format {
+            Format::Text => LineDecoder::Text(TextDecoder),
+            Format::Csv => LineDecoder::Csv(None),
+            Format::Json => LineDecoder::Json(JsonDecoder),
+        }
+    }
+
+    /// Decodes `line`, returns `None` for header and empty CSV lines
+    fn decode(&mut self, line: &[u8]) -> Option<Command> {
+        match self {
+            LineDecoder::Text(decoder) => Some(decoder.decode(line).expect("can't parse order")),
+            LineDecoder::Csv(_) if line.iter().all(u8::is_ascii_whitespace) => None,
+            LineDecoder::Csv(Some(decoder)) => Some(decoder.decode(line).expect("can't parse order")),
+            LineDecoder::Json(decoder) => Some(decoder.decode(line).expect("can't parse order")),
+            LineDecoder::Csv(header) => {
+                let line = String::from_utf8_lossy(line);
+                *header = Some(CsvDecoder::with_header(&line).expect("invalid CSV header"));
+                None
+            }
+        }
     }
-    let filename = args.nth(1).unwrap();
+}
+
+#[derive(Default)]
+struct Options {
+    format: Format,
+    load_snapshot: Option<OsString>,
+    save_snapshot: Option<OsString>,
+    sequenced: bool,
+    parse_threads: usize,
+}
 
+fn read_orders(filename: &OsString) -> Vec<IncomingOrder> {
     let f = File::open(filename).expect("invalid filename");
     let f = BufReader::new(f);
 
-    let mut book = OrderBook::new();
+    let mut orders = Vec::new();
     for line in f.lines() {
         let line = line.unwrap();
-        //println!("{}", line);
-        let order = line.parse().expect("can't parse order");
-        let mut logger = VectorLogger::new();
-        book.execute_order(order, &mut logger);
-        for log_item in logger.as_slice() {
-            println!("{}", log_item.to_string());
+        orders.push(line.parse().expect("can't parse order"));
+    }
+    orders
+}
+
+fn write_orders(filename: &OsString, orders: &[IncomingOrder]) -> io::Result<()> {
+    let mut f = BufWriter::new(File::create(filename)?);
+    for order in orders {
+        writeln!(f, "{}", order)?;
+    }
+    f.flush()
+}
+
+fn load_book(options: &Options) -> OrderBook {
+    match &options.load_snapshot {
+        Some(snapshot) => OrderBook::from_vec(read_orders(snapshot)),
+        None => OrderBook::new(),
+    }
+}
+
+fn execute_line(book: &mut OrderBook, decoder: &mut LineDecoder, line: &[u8], options: &Options, out: &mut impl Write) -> io::Result<()> {
+    match decoder.decode(line) {
+        Some(command) => execute_command(book, command, options, out),
+        None => Ok(()),
+    }
+}
+
+fn execute_command(book: &mut OrderBook, command: Command, options: &Options, out: &mut impl Write) -> io::Result<()> {
+    let order_id = match command {
+        Command::Cancel(order_id) | Command::Amend { order_id, .. } => Some(order_id),
+        Command::New(_) => None,
+    };
+    let mut logger = VectorLogger::new();
+    book.execute_command(command, &mut logger);
+    for log_item in logger.as_slice() {
+        let incoming_id = || order_id.or_else(|| book.last_order_id()).expect("order id is assigned on execution");
+        if options.format == Format::Json {
+            let json = log_item.to_json();
+            if options.sequenced {
+                writeln!(out, "{{\"order\":{},{}", incoming_id(), &json[1..])?;
+            } else {
+                writeln!(out, "{}", json)?;
+            }
+            continue;
+        }
+        if options.sequenced {
+            write!(out, "{} ", incoming_id())?;
+        }
+        writeln!(out, "{}", log_item)?;
+    }
+    Ok(())
+}
+
+/// Expands directories in `paths` into the files they contain, sorted by name
+fn input_files(paths: &[OsString]) -> Vec<PathBuf> {
+    let mut files = Vec::new();
+    for path in paths {
+        let path = PathBuf::from(path);
+        if path.is_dir() {
+            let mut entries: Vec<PathBuf> = fs::read_dir(&path).expect("can't read directory")
+                .map(|entry| entry.expect("can't read directory").path())
+                .filter(|path| path.is_file())
+                .collect();
+            entries.sort();
+            files.extend(entries);
+        } else {
+            files.push(path);
         }
     }
+    files
+}
+
+/// Executes orders from `paths` one after another as a single session
+fn run(paths: &[OsString], options: &Options) {
+    let mut book = load_book(options);
+
+    let stdout = io::stdout();
+    let mut out = BufWriter::new(stdout.lock());
+    if options.parse_threads > 0 {
+        let lines = input_files(paths).into_iter().flat_map(|filename| {
+            let f = File::open(&filename).unwrap_or_else(|_| panic!("can't open {}", filename.display()));
+            BufReader::new(f).split(b'\n').map(|line| line.unwrap())
+        });
+        pipeline::decode_pipelined(lines, options.parse_threads, || TextDecoder, |_, command| {
+            let command = command.expect("can't parse order");
+            execute_command(&mut book, command, options, &mut out).expect("can't write output");
+        });
+        return;
+    }
+
+    for filename in input_files(paths) {
+        let mut decoder = LineDecoder::new(options.format);
+        let f = File::open(&filename).unwrap_or_else(|_| panic!("can't open {}", filename.display()));
+        let f = BufReader::new(f);
+        for line in f.lines() {
+            let line = line.unwrap();
+            execute_line(&mut book, &mut decoder, line.as_bytes(), options, &mut out).expect("can't write output");
+        }
+    }
+}
+
+/// Executes orders from the standard input until it is closed or a shutdown signal is received
+///
+/// On shutdown the order being executed is finished, the remaining input is discarded,
+/// the output is flushed and the final state of the book is saved as a snapshot.
+fn stream(options: &Options) {
+    signal::install();
+    let mut book = load_book(options);
+
+    let mut decoder = LineDecoder::new(options.format);
+    let stdin = io::stdin();
+    let mut input = stdin.lock();
+    let stdout = io::stdout();
+    let mut out = BufWriter::new(stdout.lock());
+    let mut pending = Vec::new();
+    let mut buf = [0; 4096];
+    'input: while !signal::requested() {
+        let n = match input.read(&mut buf) {
+            Ok(0) => {
+                if !pending.is_empty() {
+                    execute_line(&mut book, &mut decoder, &pending, options, &mut out).expect("can't write output");
+                }
+                break;
+            }
+            Ok(n) => n,
+            Err(ref e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => panic!("can't read input: {}", e),
+        };
+        pending.extend_from_slice(&buf[..n]);
+        while let Some(end) = pending.iter().position(|&b| b == b'\n') {
+            let line: Vec<u8> = pending.drain(..=end).collect();
+            execute_line(&mut book, &mut decoder, &line, options, &mut out).expect("can't write output");
+            if signal::requested() {
+                break 'input;
+            }
+        }
+        out.flush().expect("can't write output");
+    }
+    out.flush().expect("can't write output");
+
+    if signal::requested() {
+        eprintln!("shutting down");
+    }
+    if let Some(snapshot) = &options.save_snapshot {
+        write_orders(snapshot, &book.to_vec_with_hidden()).expect("can't write snapshot");
+    }
+}
+
+fn verify(snapshot: &OsString, commands: &OsString, expected: &OsString) {
+    let snapshot = read_orders(snapshot);
+    let commands = read_orders(commands);
+    let expected = read_orders(expected);
+    match verify::verify_replay(snapshot, commands, &expected) {
+        Ok(()) => println!("OK"),
+        Err(divergence) => {
+            println!("{}", divergence);
+            process::exit(1);
+        }
+    }
+}
+
+fn main() {
+    let mut options = Options::default();
+    let mut args: Vec<OsString> = Vec::new();
+    let mut iter = env::args_os().skip(1);
+    while let Some(arg) = iter.next() {
+        match arg.to_str() {
+            Some("--load-snapshot") => options.load_snapshot = Some(iter.next().unwrap_or_else(|| usage())),
+            Some("--save-snapshot") => options.save_snapshot = Some(iter.next().unwrap_or_else(|| usage())),
+            Some("--format") => {
+                options.format = match iter.next().as_ref().and_then(|format| format.to_str()) {
+                    Some("text") => Format::Text,
+                    Some("csv") => Format::Csv,
+                    Some("json") => Format::Json,
+                    _ => usage(),
+                };
+            }
+            Some("--sequenced") => options.sequenced = true,
+            Some("--parse-threads") => {
+                options.parse_threads = iter.next()
+                    .and_then(|n| n.to_str().and_then(|n| n.parse().ok()))
+                    .unwrap_or_else(|| usage());
+            }
+            _ => args.push(arg),
+        }
+    }
+
+    // Parsing threads decode lines independently of the file headers
+    if options.parse_threads > 0 && options.format != Format::Text {
+        usage();
+    }
+
+    match args.first().and_then(|arg| arg.to_str()) {
+        Some("verify") if args.len() == 4 => verify(&args[1], &args[2], &args[3]),
+        Some("verify") => usage(),
+        Some("stream") if args.len() == 1 => stream(&options),
+        Some(_) if options.save_snapshot.is_none() => run(&args, &options),
+        _ => usage(),
+    }
 }
@@ -0,0 +1,264 @@
+//! Market data aggregated from the execution log
+//!
+//! [CandleAggregator](CandleAggregator) turns fills into OHLCV candles of a fixed time interval.
+//! It can be used directly as a logger of a book with a [clock](crate::clock::Clock), since the fills
+//! are bucketed by the timestamps of their [sequenced items](SequencedLogItem).
+//!
+//! [MarketDataListener](MarketDataListener) installed with
+//! [set_market_data_listener](crate::OrderBook::set_market_data_listener) is notified of the changes
+//! of the best bid and ask.
+
+use crate::depth::PriceLevel;
+use crate::log::{ExecutionLogger, LogItem, SequencedLogItem};
+use crate::order::{Price, Size};
+use alloc::vec::Vec;
+
+/// Best bid and ask of a book
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Bbo {
+    #[allow(missing_docs)]
+    pub bid: Option<PriceLevel>,
+    #[allow(missing_docs)]
+    pub ask: Option<PriceLevel>,
+}
+
+impl Bbo {
+    /// Checks whether price or size of either side differs from `other`
+    pub fn changed(&self, other: &Bbo) -> bool {
+        let quote = |level: &Option<PriceLevel>| level.map(|level| (level.price, level.size));
+        quote(&self.bid) != quote(&other.bid) || quote(&self.ask) != quote(&other.ask)
+    }
+}
+
+/// Change of the best bid or ask caused by an operation of the book
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BboUpdate {
+    /// Best bid and ask after the operation
+    pub bbo: Bbo,
+    /// Sequence number of the last item logged by the operation
+    pub sequence: u64,
+    /// Time of the operation, if the book has a [clock](crate::clock::Clock)
+    pub timestamp: Option<u64>,
+}
+
+/// Receiver of the market data of a book
+pub trait MarketDataListener {
+    /// Receives the best bid and ask after every operation which changed them
+    fn on_bbo(&mut self, update: &BboUpdate);
+}
+
+/// Open, high, low and close prices and the volume of the fills within one interval
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Candle {
+    /// Start of the interval, a multiple of the interval length
+    pub start: u64,
+    #[allow(missing_docs)]
+    pub open: Price,
+    #[allow(missing_docs)]
+    pub high: Price,
+    #[allow(missing_docs)]
+    pub low: Price,
+    #[allow(missing_docs)]
+    pub close: Price,
+    /// Total size of the fills
+    pub volume: Size,
+    /// Number of the fills
+    pub fills: u64,
+}
+
+impl Candle {
+    fn new(start: u64, price: Price, size: Size) -> Self {
+        Candle { start, open: price, high: price, low: price, close: price, volume: size, fills: 1 }
+    }
+
+    fn add(&mut self, price: Price, size: Size) {
+        self.high = self.high.max(price);
+        self.low = self.low.min(price);
+        self.close = price;
+        self.volume = self.volume.saturating_add(size);
+        self.fills += 1;
+    }
+}
+
+/// Builds candles of fixed-length intervals from fills
+///
+/// Intervals without fills produce no candles. Fills older than the current candle are added to it.
+#[derive(Debug, Clone)]
+pub struct CandleAggregator {
+    interval: u64,
+    closed: Vec<Candle>,
+    current: Option<Candle>,
+}
+
+impl CandleAggregator {
+    /// Constructs aggregator of intervals of `interval` time units
+    ///
+    /// # Panics
+    ///
+    /// Panics if `interval` is zero.
+    pub fn new(interval: u64) -> Self {
+        assert!(interval > 0, "candle interval must be positive");
+        CandleAggregator {
+            interval,
+            closed: Vec::new(),
+            current: None,
+        }
+    }
+
+    /// Returns the interval length
+    pub fn interval(&self) -> u64 {
+        self.interval
+    }
+
+    /// Adds fill of `size` at `price` which happened at `timestamp`
+    pub fn add_fill(&mut self, timestamp: u64, price: Price, size: Size) {
+        let start = timestamp - timestamp % self.interval;
+        match &mut self.current {
+            Some(current) if start <= current.start => current.add(price, size),
+            current => {
+                if let Some(candle) = current.replace(Candle::new(start, price, size)) {
+                    self.closed.push(candle);
+                }
+            }
+        }
+    }
+
+    /// Adds the fill of `item`, items without a timestamp are ignored
+    pub fn add_item(&mut self, item: &SequencedLogItem) {
+        if let (LogItem::Fulfilled { size, price, .. }, Some(timestamp)) = (&item.item, item.timestamp) {
+            self.add_fill(timestamp, *price, *size);
+        }
+    }
+
+    /// Returns the candle of the last interval with fills, which may still change
+    pub fn current(&self) -> Option<&Candle> {
+        self.current.as_ref()
+    }
+
+    /// Returns the completed candles, oldest first
+    pub fn closed(&self) -> &[Candle] {
+        &self.closed
+    }
+
+    /// Removes and returns the completed candles
+    pub fn take_closed(&mut self) -> Vec<Candle> {
+        core::mem::take(&mut self.closed)
+    }
+
+    /// Completes the current candle, e.g. at the end of a session, and returns all completed candles
+    pub fn finish(mut self) -> Vec<Candle> {
+        self.closed.extend(self.current.take());
+        self.closed
+    }
+}
+
+impl ExecutionLogger for CandleAggregator {
+    fn log(&mut self, _item: LogItem) { }
+
+    fn log_sequenced(&mut self, item: SequencedLogItem) {
+        self.add_item(&item);
+    }
+
+    fn cancel(&mut self) { }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::OrderBook;
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicU64, Ordering};
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn bbo_changes_are_notified() {
+        use crate::log::DummyLogger;
+        use std::sync::Mutex;
+
+        #[derive(Default)]
+        struct BboCollector(Vec<BboUpdate>);
+
+        impl MarketDataListener for BboCollector {
+            fn on_bbo(&mut self, update: &BboUpdate) {
+                self.0.push(*update);
+            }
+        }
+
+        let collector = Arc::new(Mutex::new(BboCollector::default()));
+        let mut book = OrderBook::new();
+        book.execute_order("Lim S $101 #5 u1".parse().unwrap(), &mut DummyLogger);
+        book.set_market_data_listener(Some(collector.clone()));
+
+        for s in &["Lim S $102 #5 u1", "Lim B $99 #3 u2", "Lim S $101 #2 u3", "IoC B $101 #7 u4"] {
+            book.execute_order(s.parse().unwrap(), &mut DummyLogger);
+        }
+        book.cancel_order(2, &mut DummyLogger).unwrap();
+
+        let quotes: Vec<_> = collector.lock().unwrap().0.iter()
+            .map(|update| {
+                let quote = |level: Option<PriceLevel>| level.map(|level| (level.price, level.size));
+                (update.sequence, quote(update.bbo.bid), quote(update.bbo.ask))
+            })
+            .collect();
+        // Order at a worse price leaves the best bid and ask unchanged
+        assert_eq!(quotes, [
+            (3, Some((99, 3)), Some((101, 5))),
+            (4, Some((99, 3)), Some((101, 7))),
+            (6, Some((99, 3)), Some((102, 5))),
+            (7, Some((99, 3)), None),
+        ]);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn bbo_is_notified_once_per_transaction() {
+        use crate::log::DummyLogger;
+        use std::sync::Mutex;
+
+        struct BboCounter(usize);
+
+        impl MarketDataListener for BboCounter {
+            fn on_bbo(&mut self, _update: &BboUpdate) {
+                self.0 += 1;
+            }
+        }
+
+        let counter = Arc::new(Mutex::new(BboCounter(0)));
+        let mut book = OrderBook::new();
+        book.set_market_data_listener(Some(counter.clone()));
+        let orders = ["Lim S $101 #5 u1", "Lim B $99 #3 u2", "Lim S $100 #1 u1"].iter().map(|s| s.parse().unwrap());
+        book.execute_group(orders, &mut DummyLogger).unwrap();
+        assert_eq!(counter.lock().unwrap().0, 1);
+
+        let rejected = ["Lim B $100 #3 u2", "Ice B $99 #3 u2 ^0"].iter().map(|s| s.parse().unwrap());
+        assert!(book.execute_group(rejected, &mut DummyLogger).is_err());
+        assert_eq!(counter.lock().unwrap().0, 1);
+        assert_eq!(book.bbo().ask.map(|level| level.price), Some(100));
+    }
+
+    #[test]
+    fn candles_from_book_fills() {
+        let time = Arc::new(AtomicU64::new(0));
+        let mut book = OrderBook::new();
+        let clock = time.clone();
+        book.set_clock(Some(Arc::new(move || clock.load(Ordering::SeqCst))));
+
+        let mut candles = CandleAggregator::new(60);
+        for (t, s) in &[
+            (0, "Lim S $100 #10 u1"), (5, "Lim S $102 #10 u1"), (10, "IoC B $102 #12 u2"),
+            (59, "IoC B $102 #3 u2"), (130, "IoC B $102 #4 u3"), (170, "Lim B $90 #1 u3"),
+        ] {
+            time.store(*t, Ordering::SeqCst);
+            book.execute_order(s.parse().unwrap(), &mut candles);
+        }
+        assert_eq!(candles.closed(), &[Candle { start: 0, open: 100, high: 102, low: 100, close: 102, volume: 15, fills: 3 }]);
+        assert_eq!(candles.current(), Some(&Candle { start: 120, open: 102, high: 102, low: 102, close: 102, volume: 4, fills: 1 }));
+
+        // Unsequenced items carry no time
+        candles.log(LogItem::Fulfilled { size: 1, price: 1, user_id: 1, order_id: 1 });
+        let candles = candles.finish();
+        assert_eq!(candles.len(), 2);
+        assert_eq!(candles[1].volume, 4);
+    }
+}
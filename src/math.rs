@@ -0,0 +1,81 @@
+//! Overflow-safe notional and fee arithmetic
+
+use crate::order::{Price, Size};
+use core::fmt;
+
+/// Number of basis points in one
+pub const BPS_SCALE: u128 = 10_000;
+
+/// Arithmetic error
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MathError {
+    /// Result does not fit into the target type
+    Overflow,
+}
+
+impl fmt::Display for MathError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MathError::Overflow => f.write_str("arithmetic overflow"),
+        }
+    }
+}
+
+/// Returns notional value of `size` units traded at `price`
+///
+/// The product of `i64` and `u64` values always fits into `i128`, so this function never fails.
+/// Notional is negative for negative prices.
+pub fn notional(price: Price, size: Size) -> i128 {
+    i128::from(price) * i128::from(size)
+}
+
+/// Returns sum of two notional values
+pub fn add_notional(a: i128, b: i128) -> Result<i128, MathError> {
+    a.checked_add(b).ok_or(MathError::Overflow)
+}
+
+/// Returns average price of `size` units with the total value of `notional`, or `None` if `size` is zero
+pub fn average_price(notional: i128, size: Size) -> Option<f64> {
+    if size == 0 {
+        None
+    } else {
+        Some(notional as f64 / size as f64)
+    }
+}
+
+/// Returns fee for `notional` at the rate of `rate_bps` basis points
+///
+/// Fee is computed on the absolute value of `notional`.
+/// Fractional fees are rounded up, so a non-zero rate never yields a zero fee for a non-zero notional.
+pub fn fee(notional: i128, rate_bps: u64) -> Result<u128, MathError> {
+    let scaled = notional.unsigned_abs().checked_mul(u128::from(rate_bps)).ok_or(MathError::Overflow)?;
+    let fee = scaled / BPS_SCALE;
+    if scaled % BPS_SCALE != 0 {
+        Ok(fee + 1)
+    } else {
+        Ok(fee)
+    }
+}
+
+/// Converts `value` to `u64`
+pub fn to_u64(value: u128) -> Result<u64, MathError> {
+    if value > u128::from(u64::MAX) {
+        Err(MathError::Overflow)
+    } else {
+        Ok(value as u64)
+    }
+}
+
+#[test]
+fn test_notional_and_fee() {
+    assert_eq!(notional(i64::MIN, u64::MAX), i128::from(i64::MIN) * i128::from(u64::MAX));
+    assert_eq!(notional(-5, 3), -15);
+    assert_eq!(fee(notional(100, 10), 25), Ok(3));
+    assert_eq!(fee(notional(100, 100), 25), Ok(25));
+    assert_eq!(fee(notional(-100, 100), 25), Ok(25));
+    assert_eq!(fee(notional(100, 10), 0), Ok(0));
+    assert_eq!(fee(i128::MAX, 4), Err(MathError::Overflow));
+    assert_eq!(add_notional(i128::MAX, 1), Err(MathError::Overflow));
+    assert_eq!(to_u64(u128::from(u64::MAX)), Ok(u64::MAX));
+    assert_eq!(to_u64(u128::from(u64::MAX) + 1), Err(MathError::Overflow));
+}
@@ -0,0 +1,160 @@
+//! Per-user conduct metrics
+//!
+//! Metrics are derived from the execution results and can be used to enforce conduct rules,
+//! e.g. to detect agents submitting many orders which never trade.
+//! Time is measured in the number of orders submitted to the book.
+
+use crate::log::LogItem;
+use crate::order::{IncomingOrder, OrderId, Size, UserId};
+use crate::HashMap;
+
+/// Conduct metrics of one user
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct UserMetrics {
+    /// Number of submitted orders
+    pub orders: u64,
+    /// Number of fills in which the user took part, either as an aggressor or as a passive side
+    pub trades: u64,
+    /// Number of orders which were (partially) cancelled, each counted once
+    pub cancels: u64,
+    /// Number of rejected orders
+    pub rejects: u64,
+    /// Number of orders which were completely removed from the book by fills
+    pub rested_orders: u64,
+    /// Total time the completely filled orders were resting in the book
+    pub total_resting_time: u64,
+}
+
+impl UserMetrics {
+    /// Returns the number of orders per trade, or `None` if the user has not traded yet
+    pub fn order_to_trade_ratio(&self) -> Option<f64> {
+        if self.trades == 0 {
+            None
+        } else {
+            Some(self.orders as f64 / self.trades as f64)
+        }
+    }
+
+    /// Returns the share of orders which were (partially) cancelled
+    pub fn cancel_rate(&self) -> Option<f64> {
+        if self.orders == 0 {
+            None
+        } else {
+            Some(self.cancels as f64 / self.orders as f64)
+        }
+    }
+
+    /// Returns average time completely filled orders were resting in the book
+    pub fn average_resting_time(&self) -> Option<f64> {
+        if self.rested_orders == 0 {
+            None
+        } else {
+            Some(self.total_resting_time as f64 / self.rested_orders as f64)
+        }
+    }
+}
+
+#[derive(Clone)]
+struct RestingOrder {
+    size: Size,
+    since: u64,
+    /// Whether a part of the order was cancelled already, e.g. an odd lot
+    cancelled: bool,
+}
+
+/// Collector of per-user metrics
+#[derive(Clone, Default)]
+pub(crate) struct MetricsTracker {
+    users: HashMap<UserId, UserMetrics>,
+    resting: HashMap<OrderId, RestingOrder>,
+    clock: u64,
+}
+
+impl MetricsTracker {
+    pub fn get(&self, user_id: UserId) -> Option<&UserMetrics> {
+        self.users.get(&user_id)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item=(UserId, &UserMetrics)> {
+        self.users.iter().map(|(&user_id, metrics)| (user_id, metrics))
+    }
+
+    /// Records removal of the resting order `order_id` of the user `user_id`
+    pub fn record_cancel(&mut self, order_id: OrderId, user_id: UserId) {
+        let cancelled = self.resting.remove(&order_id).is_some_and(|resting| resting.cancelled);
+        if !cancelled {
+            self.users.entry(user_id).or_default().cancels += 1;
+        }
+    }
+
+    /// Records the items logged for the order `order_id`
+    pub fn record(&mut self, order_id: OrderId, order: &IncomingOrder, items: &[LogItem]) {
+        self.clock += 1;
+        let mut user = self.users.remove(&order.user_id).unwrap_or_default();
+        user.orders += 1;
+        self.apply(order_id, &mut user, items, false);
+        self.users.insert(order.user_id, user);
+    }
+
+    /// Records further items logged for the order `order_id` of the user `user_id` (amendment, stop trigger, expiry)
+    pub fn record_update(&mut self, order_id: OrderId, user_id: UserId, items: &[LogItem]) {
+        self.clock += 1;
+        let mut user = self.users.remove(&user_id).unwrap_or_default();
+        let cancelled = self.resting.get(&order_id).is_some_and(|resting| resting.cancelled);
+        if items.len() > 1 {
+            // The order was matched again (amended with priority loss, triggered), it rests anew if enqueued
+            self.resting.remove(&order_id);
+        }
+        self.apply(order_id, &mut user, items, cancelled);
+        self.users.insert(user_id, user);
+    }
+
+    /// Applies `items` of the order `order_id`, whose cancels are counted once, unless it was `cancelled` already
+    fn apply(&mut self, order_id: OrderId, user: &mut UserMetrics, items: &[LogItem], mut cancelled: bool) {
+        for item in items {
+            match *item {
+                LogItem::Fulfilled { size, user_id, order_id: maker_id, .. } => {
+                    user.trades += 1;
+                    let maker = self.users.entry(user_id).or_default();
+                    maker.trades += 1;
+                    if let Some(resting) = self.resting.get_mut(&maker_id) {
+                        resting.size = resting.size.saturating_sub(size);
+                        if resting.size == 0 {
+                            maker.rested_orders += 1;
+                            maker.total_resting_time += self.clock - resting.since;
+                            self.resting.remove(&maker_id);
+                        }
+                    }
+                }
+                LogItem::Enqueued { size, .. } => {
+                    self.resting.insert(order_id, RestingOrder { size, since: self.clock, cancelled });
+                }
+                LogItem::Cancelled { .. } => {
+                    if !cancelled {
+                        user.cancels += 1;
+                        cancelled = true;
+                    }
+                }
+                LogItem::Rejected { .. } => user.rejects += 1,
+                LogItem::Expired { .. } => {
+                    self.resting.remove(&order_id);
+                }
+                LogItem::Amended { size, .. } => {
+                    if size == 0 {
+                        if !cancelled {
+                            user.cancels += 1;
+                        }
+                        self.resting.remove(&order_id);
+                    } else if let Some(resting) = self.resting.get_mut(&order_id) {
+                        resting.size = size;
+                    }
+                }
+                LogItem::ForceCancelled { .. } | LogItem::Parked { .. } | LogItem::Triggered { .. } | LogItem::Refilled { .. }
+                | LogItem::IntegrityError { .. } | LogItem::StateChanged { .. } => {}
+            }
+        }
+        if let Some(resting) = self.resting.get_mut(&order_id) {
+            resting.cancelled = cancelled;
+        }
+    }
+}
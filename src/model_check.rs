@@ -0,0 +1,68 @@
+//! Exhaustive model checking of small order sequences
+//!
+//! Every sequence of orders built from a small alphabet (few users, prices, sizes, kinds and sides)
+//! is executed up to a fixed depth and the book and the log are checked after each step.
+
+use crate::OrderBook;
+use crate::order::{IncomingOrder, OrderKind, OrderSide, Price};
+use crate::log::VectorLogger;
+use crate::testing::{check_book, check_execution, resting_size};
+
+const USERS: [u64; 2] = [1, 2];
+const PRICES: [Price; 2] = [100, 101];
+const SIZES: [u64; 2] = [1, 2];
+const KINDS: [OrderKind; 6] = [OrderKind::Limit, OrderKind::FillOrKill, OrderKind::ImmediateOrCancel, OrderKind::Market,
+                               OrderKind::Iceberg { display_size: 1 }, OrderKind::PostOnly];
+const SIDES: [OrderSide; 2] = [OrderSide::Buy, OrderSide::Sell];
+const DEPTH: usize = 3;
+
+fn alphabet() -> Vec<IncomingOrder> {
+    let mut orders = Vec::new();
+    for &user_id in &USERS {
+        for &price_limit in &PRICES {
+            for &size in &SIZES {
+                for &kind in &KINDS {
+                    for &side in &SIDES {
+                        orders.push(IncomingOrder { price_limit, size, user_id, kind, side, expires_at: None, min_fill_size: None, reduce_only: false });
+                    }
+                }
+            }
+        }
+    }
+    orders
+}
+
+fn explore(book: &OrderBook, alphabet: &[IncomingOrder], path: &mut Vec<IncomingOrder>, checked: &mut usize) {
+    if path.len() == DEPTH {
+        return;
+    }
+    for order in alphabet {
+        let mut book = book.clone();
+        let mut logger = VectorLogger::new();
+        let before = resting_size(&book);
+        book.execute_order(order.clone(), &mut logger);
+        path.push(order.clone());
+        *checked += 1;
+
+        let result = check_execution(order, logger.as_slice(), before, resting_size(&book))
+            .and_then(|_| check_book(&book));
+        if let Err(e) = result {
+            let path: Vec<_> = path.iter().map(|o| o.to_string()).collect();
+            panic!("invariant violated after {:?}: {}", path, e);
+        }
+
+        explore(&book, alphabet, path, checked);
+        path.pop();
+    }
+}
+
+#[test]
+fn exhaustive_small_scope() {
+    let alphabet = alphabet();
+    let mut checked = 0;
+    let mut book = OrderBook::new();
+    book.set_audit(true);
+    explore(&book, &alphabet, &mut Vec::new(), &mut checked);
+    let n = alphabet.len();
+    assert_eq!(checked, n + n * n + n * n * n);
+}
@@ -0,0 +1,210 @@
+//! Line-protocol TCP order gateway
+//!
+//! [Gateway](Gateway) accepts TCP connections and runs the received commands through an
+//! [OrderBook](OrderBook) owned by a single matching thread. Every connection is served by a
+//! reader thread decoding its lines and a writer thread, so slow clients never block matching.
+//!
+//! Clients send commands in the [text format](crate::decode::TextDecoder), one per line.
+//! For every command the gateway replies with the execution results as
+//! `<order id> <log item>` lines followed by `OK <order id>`; lines which can't be decoded are
+//! answered with `ERR <error>`. After sending `Sub` a client also receives the results of
+//! all commands, including those of other clients, as `<order id> <log item>` lines.
+
+use crate::OrderBook;
+use crate::decode::{Command, OrderDecoder, TextDecoder};
+use crate::log::VectorLogger;
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread::{self, JoinHandle};
+
+/// Request to the matching thread
+enum Request {
+    /// Execute the command and send the results to the connection
+    Execute(Command, Sender<String>),
+    /// Send the results of all commands to the connection
+    Subscribe(Sender<String>),
+}
+
+/// TCP gateway bound to an address
+pub struct Gateway {
+    listener: TcpListener,
+    book: OrderBook,
+}
+
+impl Gateway {
+    /// Binds the gateway to `addr`, commands are executed on `book`
+    pub fn bind(addr: impl ToSocketAddrs, book: OrderBook) -> io::Result<Self> {
+        Ok(Gateway {
+            listener: TcpListener::bind(addr)?,
+            book,
+        })
+    }
+
+    /// Returns the address the gateway is bound to
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    /// Starts accepting connections and matching on background threads
+    pub fn spawn(self) -> io::Result<GatewayHandle> {
+        let addr = self.listener.local_addr()?;
+        let shutdown = Arc::new(AtomicBool::new(false));
+        let (request_tx, request_rx) = mpsc::channel();
+
+        let mut book = self.book;
+        let matcher = thread::spawn(move || {
+            run_matcher(&mut book, request_rx);
+            book
+        });
+
+        let listener = self.listener;
+        let stop = shutdown.clone();
+        let acceptor = thread::spawn(move || {
+            for stream in listener.incoming() {
+                if stop.load(Ordering::SeqCst) {
+                    break;
+                }
+                // Failed handshakes only affect the connecting client
+                if let Ok(stream) = stream {
+                    let _ = serve(stream, request_tx.clone());
+                }
+            }
+        });
+
+        Ok(GatewayHandle { addr, shutdown, acceptor, matcher })
+    }
+}
+
+/// Running gateway
+pub struct GatewayHandle {
+    addr: SocketAddr,
+    shutdown: Arc<AtomicBool>,
+    acceptor: JoinHandle<()>,
+    matcher: JoinHandle<OrderBook>,
+}
+
+impl GatewayHandle {
+    /// Returns the address the gateway accepts connections on
+    pub fn local_addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    /// Stops accepting connections and returns the book once all connections are closed
+    pub fn shutdown(self) -> OrderBook {
+        self.shutdown.store(true, Ordering::SeqCst);
+        // Wake up the acceptor blocked in accept()
+        let _ = TcpStream::connect(self.addr);
+        self.acceptor.join().expect("acceptor thread panicked");
+        self.matcher.join().expect("matching thread panicked")
+    }
+}
+
+fn run_matcher(book: &mut OrderBook, requests: Receiver<Request>) {
+    let mut subscribers: Vec<Sender<String>> = Vec::new();
+    for request in requests {
+        let (command, reply) = match request {
+            Request::Execute(command, reply) => (command, reply),
+            Request::Subscribe(subscriber) => {
+                subscribers.push(subscriber);
+                continue;
+            }
+        };
+        let order_id = match command {
+            Command::Cancel(order_id) | Command::Amend { order_id, .. } => Some(order_id),
+            Command::New(_) => None,
+        };
+        let mut logger = VectorLogger::new();
+        book.execute_command(command, &mut logger);
+        let order_id = order_id.or_else(|| book.last_order_id()).expect("order id is assigned on execution");
+        for item in logger.as_slice() {
+            let line = format!("{} {}", order_id, item);
+            subscribers.retain(|subscriber| subscriber.send(line.clone()).is_ok());
+            let _ = reply.send(line);
+        }
+        let _ = reply.send(format!("OK {}", order_id));
+    }
+}
+
+/// Starts the reader and writer threads of a connection
+fn serve(stream: TcpStream, requests: Sender<Request>) -> io::Result<()> {
+    let mut writer = BufWriter::new(stream.try_clone()?);
+    let (line_tx, line_rx) = mpsc::channel::<String>();
+    thread::spawn(move || {
+        for line in line_rx {
+            if writeln!(writer, "{}", line).and_then(|_| writer.flush()).is_err() {
+                break;
+            }
+        }
+    });
+    thread::spawn(move || {
+        let mut decoder = TextDecoder;
+        for line in BufReader::new(stream).lines() {
+            let line = match line {
+                Ok(line) => line,
+                Err(_) => break,
+            };
+            let request = if line.trim() == "Sub" {
+                Request::Subscribe(line_tx.clone())
+            } else {
+                match decoder.decode(line.as_bytes()) {
+                    Ok(command) => Request::Execute(command, line_tx.clone()),
+                    Err(e) => {
+                        let _ = line_tx.send(format!("ERR {}", e));
+                        continue;
+                    }
+                }
+            };
+            if requests.send(request).is_err() {
+                break;
+            }
+        }
+    });
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn read_line(reader: &mut impl BufRead) -> String {
+        let mut line = String::new();
+        reader.read_line(&mut line).unwrap();
+        line.trim_end().to_string()
+    }
+
+    #[test]
+    fn gateway_executes_and_streams() {
+        let handle = Gateway::bind("127.0.0.1:0", OrderBook::new()).unwrap().spawn().unwrap();
+
+        let subscriber = TcpStream::connect(handle.local_addr()).unwrap();
+        let mut subscriber_reader = BufReader::new(subscriber.try_clone().unwrap());
+        writeln!(&subscriber, "Sub").unwrap();
+        // Subscription is in effect once a reply to a later command arrives
+        writeln!(&subscriber, "Cxl o99").unwrap();
+        while read_line(&mut subscriber_reader) != "OK 99" {}
+
+        let client = TcpStream::connect(handle.local_addr()).unwrap();
+        let mut reader = BufReader::new(client.try_clone().unwrap());
+        writeln!(&client, "Lim S $100 #5 u1").unwrap();
+        assert_eq!(read_line(&mut reader), "1 Q S #5 $100 u1 o1");
+        assert_eq!(read_line(&mut reader), "OK 1");
+        writeln!(&client, "bogus").unwrap();
+        assert_eq!(read_line(&mut reader), "ERR malformed record");
+        writeln!(&client, "IoC B $100 #2 u2").unwrap();
+        assert_eq!(read_line(&mut reader), "2 F #2 $100 u1 o1");
+        assert_eq!(read_line(&mut reader), "OK 2");
+
+        assert_eq!(read_line(&mut subscriber_reader), "1 Q S #5 $100 u1 o1");
+        assert_eq!(read_line(&mut subscriber_reader), "2 F #2 $100 u1 o1");
+
+        drop(reader);
+        drop(client);
+        drop(subscriber_reader);
+        drop(subscriber);
+        let book = handle.shutdown();
+        assert_eq!(book.to_vec(), ["Lim S $100 #3 u1".parse().unwrap()]);
+    }
+}
@@ -1,21 +1,87 @@
 //! Definitions of different order structures
 
-use std::marker::PhantomData;
-use std::cmp::Ordering;
-use std::fmt;
-use std::str::FromStr;
+use core::marker::PhantomData;
+use core::cmp::Ordering;
+use core::fmt;
+#[cfg(feature = "std")]
+use std::io::{self, BufRead};
+use core::str::FromStr;
+use crate::tick::TickSize;
+use crate::config::{BookConfig, RejectReason};
+#[cfg(feature = "std")]
+use crate::decode::{Command, CsvDecoder, DecodeError, OrderDecoder};
+use alloc::vec::Vec;
+use alloc::string::String;
+
+/// Order price
+///
+/// Prices are signed so that instruments trading at zero or negative prices can be represented.
+/// Negative prices are rejected unless enabled in [BookConfig](crate::config::BookConfig).
+pub type Price = i64;
+
+/// Order identifier
+///
+/// Identifiers are assigned by [OrderBook](crate::OrderBook) sequentially starting from 1
+/// in the order of submission.
+pub type OrderId = u64;
+
+/// Order size in size units, see [size_unit](crate::config::BookConfig::size_unit)
+pub type Size = u64;
+
+/// Identifier of the user who submitted an order
+pub type UserId = u64;
 
 /// Order side (buy or sell)
 #[allow(missing_docs)]
 #[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum OrderSide {
     Buy,
     Sell,
 }
 
-/// Order kind (limit, fill-or-kill, immediate-or-cancel)
+/// Reference price of a [pegged](OrderKind::Pegged) order
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum PegReference {
+    /// Best price of the side of the order: the best bid for buy orders, the best ask for sell orders
+    Primary,
+    /// Best price of the opposite side: the best ask for buy orders, the best bid for sell orders
+    Market,
+    /// Average of the best bid and the best ask, rounded away from the opposite side
+    Midpoint,
+}
+
+impl PegReference {
+    /// Returns short name of the reference as used in the text order format
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PegReference::Primary => "pri",
+            PegReference::Market => "mkt",
+            PegReference::Midpoint => "mid",
+        }
+    }
+}
+
+impl FromStr for PegReference {
+    type Err = IncomingOrderParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "pri" => Ok(PegReference::Primary),
+            "mkt" => Ok(PegReference::Market),
+            "mid" => Ok(PegReference::Midpoint),
+            _ => Err(IncomingOrderParseError),
+        }
+    }
+}
+
+/// Order kind (limit, fill-or-kill, immediate-or-cancel, market, stop, stop-limit, iceberg, post-only, trailing stop, pegged, hidden)
+///
+/// Prices and sizes carried by the kinds have the types of the [IncomingOrder](IncomingOrder).
 #[derive(Debug, Clone, Copy, PartialEq)]
-pub enum OrderKind {
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum OrderKind<P = Price, S = Size> {
     /// Specifies a price limit at which the order must be executed.
     /// If the order cannot be executed immediately, the rest of the order is added to the order queue.
     Limit,
@@ -24,15 +90,125 @@ pub enum OrderKind {
     FillOrKill,
     /// Executes any part of the order immediately, the rest of the order is cancelled.
     ImmediateOrCancel,
+    /// Executes against the opposite queue at any price, the rest of the order is cancelled.
+    /// The price limit of the order is ignored.
+    Market,
+    /// Waits until the last trade price reaches `trigger_price`, then executes as a market order.
+    #[allow(missing_docs)]
+    Stop { trigger_price: P },
+    /// Waits until the last trade price reaches `trigger_price`, then executes as a limit order.
+    #[allow(missing_docs)]
+    StopLimit { trigger_price: P },
+    /// Limit order which displays at most `display_size` units in the order queue at a time.
+    /// When the displayed part is filled, it is refilled from the hidden reserve and moved to the end of its price level.
+    #[allow(missing_docs)]
+    Iceberg { display_size: S },
+    /// Limit order which is never matched on arrival.
+    /// If it would cross the opposite queue, it is rejected or repriced according to [PostOnlyPolicy](crate::config::PostOnlyPolicy).
+    PostOnly,
+    /// Stop order whose trigger price follows the last trade price at the distance `offset`.
+    /// The trigger price only moves in the favorable direction: up for sell orders, down for buy orders.
+    #[allow(missing_docs)]
+    TrailingStop { trigger_price: P, offset: P },
+    /// Limit order priced at `reference` plus `offset`, repriced when the reference moves.
+    /// The price limit of the order caps the price: buy orders are never priced above it, sell orders never below it.
+    #[allow(missing_docs)]
+    Pegged { reference: PegReference, offset: P },
+    /// Limit order which is not displayed: it is left out of the depth, [to_vec](crate::OrderBook::to_vec) and market data.
+    /// Displayed orders at the same price are matched first.
+    Hidden,
+}
+
+impl OrderSide {
+    /// Returns short name of the side as used in the text order format
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            OrderSide::Buy => "B",
+            OrderSide::Sell => "S",
+        }
+    }
+}
+
+impl FromStr for OrderSide {
+    type Err = IncomingOrderParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "B" => Ok(OrderSide::Buy),
+            "S" => Ok(OrderSide::Sell),
+            _ => Err(IncomingOrderParseError),
+        }
+    }
+}
+
+impl OrderKind {
+    /// Returns short name of the kind as used in the text order format
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            OrderKind::Limit => "Lim",
+            OrderKind::FillOrKill => "FoK",
+            OrderKind::ImmediateOrCancel => "IoC",
+            OrderKind::Market => "Mkt",
+            OrderKind::Stop { .. } => "Stp",
+            OrderKind::StopLimit { .. } => "StpLim",
+            OrderKind::Iceberg { .. } => "Ice",
+            OrderKind::PostOnly => "Post",
+            OrderKind::TrailingStop { .. } => "Trl",
+            OrderKind::Pegged { .. } => "Peg",
+            OrderKind::Hidden => "Hid",
+        }
+    }
+
+    /// Returns trigger price of stop kinds
+    pub fn trigger_price(&self) -> Option<Price> {
+        match *self {
+            OrderKind::Stop { trigger_price } | OrderKind::StopLimit { trigger_price }
+            | OrderKind::TrailingStop { trigger_price, .. } => Some(trigger_price),
+            _ => None,
+        }
+    }
+}
+
+/// Parses kinds without parameters; stop and iceberg kinds are only parsed as part of an order
+impl FromStr for OrderKind {
+    type Err = IncomingOrderParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "Lim" => Ok(OrderKind::Limit),
+            "FoK" => Ok(OrderKind::FillOrKill),
+            "IoC" => Ok(OrderKind::ImmediateOrCancel),
+            "Mkt" => Ok(OrderKind::Market),
+            "Post" => Ok(OrderKind::PostOnly),
+            "Hid" => Ok(OrderKind::Hidden),
+            _ => Err(IncomingOrderParseError),
+        }
+    }
 }
 
 /// Representation of the order stored in the order queue
+///
+/// The numeric types default to those of the [OrderBook](crate::OrderBook), like in [IncomingOrder](IncomingOrder).
 //#[repr(align(128))]
 #[derive(Debug, Clone)]
-pub struct Order<D> {
-    pub(crate) price_limit: u64,
-    pub(crate) size: u64,
-    pub(crate) user_id: u64,
+pub struct Order<D, P = Price, S = Size, U = UserId> {
+    pub(crate) price_limit: P,
+    pub(crate) size: S,
+    pub(crate) user_id: U,
+    pub(crate) order_id: OrderId,
+    pub(crate) expires_at: Option<u64>,
+    /// Size of the hidden reserve of an iceberg order
+    pub(crate) hidden: S,
+    /// Display size of an iceberg order, zero for other orders
+    pub(crate) display_size: S,
+    /// Whether the order is a [Hidden](OrderKind::Hidden) order
+    pub(crate) dark: bool,
+    /// Whether the order is a [PostOnly](OrderKind::PostOnly) order, which must not take liquidity when amended
+    pub(crate) post_only: bool,
+    /// Whether the order may only reduce the position of the user, checked again before it is matched
+    pub(crate) reduce_only: bool,
+    /// Time the order took its position in the queue, if the book has a clock
+    pub(crate) entered_at: Option<u64>,
     _marker: PhantomData<D>,
 }
 
@@ -41,8 +217,72 @@ pub(crate) enum TaggedOrder {
     Sell(Order<Sell>),
 }
 
+/// Error of [parse_csv](parse_csv)
+#[cfg(feature = "std")]
+#[derive(Debug)]
+pub enum CsvError {
+    #[allow(missing_docs)]
+    Io(io::Error),
+    /// Header line is missing or lacks a required column
+    Header(DecodeError),
+    /// Line `line` (counting from 1) can't be parsed
+    #[allow(missing_docs)]
+    Record { line: usize, error: DecodeError },
+}
+
+#[cfg(feature = "std")]
+impl fmt::Display for CsvError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CsvError::Io(e) => write!(f, "{}", e),
+            CsvError::Header(e) => write!(f, "invalid header: {}", e),
+            CsvError::Record { line, error } => write!(f, "line {}: {}", line, error),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl From<io::Error> for CsvError {
+    fn from(e: io::Error) -> Self {
+        CsvError::Io(e)
+    }
+}
+
+/// Reads orders from comma-separated records with a header line, e.g. `side,kind,price,size,user,id`
+///
+/// Columns may come in any order; `side`, `kind`, `price`, `size` and `user` are required, others
+/// (such as historical order `id`s, which are assigned by the book) are ignored. Empty lines are skipped.
+/// See [CsvDecoder](crate::decode::CsvDecoder) for the field values.
+#[cfg(feature = "std")]
+pub fn parse_csv(reader: impl BufRead) -> Result<Vec<IncomingOrder>, CsvError> {
+    let mut decoder = None;
+    let mut orders = Vec::new();
+    for (index, line) in reader.lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let decoder = match &mut decoder {
+            Some(decoder) => decoder,
+            None => {
+                decoder = Some(CsvDecoder::with_header(&line).map_err(CsvError::Header)?);
+                continue;
+            }
+        };
+        match decoder.decode(line.as_bytes()) {
+            Ok(Command::New(order)) => orders.push(order),
+            Ok(_) => unreachable!("CSV records are new orders"),
+            Err(error) => return Err(CsvError::Record { line: index + 1, error }),
+        }
+    }
+    if decoder.is_none() {
+        return Err(CsvError::Header(DecodeError::Malformed));
+    }
+    Ok(orders)
+}
+
 impl TaggedOrder {
-    pub fn size(&self) -> u64 {
+    pub fn size(&self) -> Size {
         match self {
             TaggedOrder::Buy(order) => order.size,
             TaggedOrder::Sell(order) => order.size,
@@ -51,32 +291,130 @@ impl TaggedOrder {
 }
 
 /// Representation of incoming order
+///
+/// Prices, sizes and user identifiers default to [Price](Price), [Size](Size) and [UserId](UserId),
+/// which the [OrderBook](crate::OrderBook) executes; other types, e.g. `u32` user ids or newtypes,
+/// can be used to represent orders outside of the book.
 #[derive(Debug, Clone, PartialEq)]
-pub struct IncomingOrder {
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct IncomingOrder<P = Price, S = Size, U = UserId> {
     /// Price limit at which the order must be executed
-    pub price_limit: u64,
+    pub price_limit: P,
     /// Order size: amount of traded goods
-    pub size: u64,
+    pub size: S,
     /// ID of the user who created the order
-    pub user_id: u64,
+    pub user_id: U,
     /// Order kind
-    pub kind: OrderKind,
+    pub kind: OrderKind<P, S>,
     /// Order side (e.g. buy or sell)
     pub side: OrderSide,
+    /// Time at which the order is removed by [expire_orders](crate::OrderBook::expire_orders),
+    /// `None` for orders which are good till cancelled
+    pub expires_at: Option<u64>,
+    /// Minimum size which must execute on arrival, otherwise the order does not match at all and
+    /// rests or is cancelled according to its kind; `None` for orders without a minimum
+    pub min_fill_size: Option<S>,
+    /// Whether the order may only reduce the position of the user, see
+    /// [PositionProvider](crate::risk::PositionProvider). A resting reduce-only order is shrunk or
+    /// cancelled before it is matched if the position of the user no longer allows its size.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub reduce_only: bool,
+}
+
+impl IncomingOrder {
+    /// Parses an order with a decimal price, e.g. `Lim B $1.25 #2 u3`, converting the prices to ticks
+    pub fn parse_with_tick_size(s: &str, tick_size: &TickSize) -> Result<Self, IncomingOrderParseError> {
+        Self::parse_scaled(s, tick_size, &TickSize::ONE)
+    }
+
+    /// Parses an order with decimal prices and sizes, e.g. `Lim B $1.25 #0.5 u3`, converting them to
+    /// ticks and size units of `config`
+    pub fn parse_with_config(s: &str, config: &BookConfig) -> Result<Self, IncomingOrderParseError> {
+        Self::parse_scaled(s, &config.tick_size, &config.size_unit)
+    }
+
+    fn parse_scaled(s: &str, tick_size: &TickSize, size_unit: &TickSize) -> Result<Self, IncomingOrderParseError> {
+        parse_order(s, |price| tick_size.to_ticks(price).map_err(|_| IncomingOrderParseError),
+                    |size| size_unit.to_units(size).map_err(|_| IncomingOrderParseError))
+    }
+
+    /// Formats the order with a decimal price converted from ticks
+    pub fn format_with_tick_size(&self, tick_size: &TickSize) -> String {
+        self.format_scaled(tick_size, &TickSize::ONE)
+    }
+
+    /// Formats the order with decimal prices and sizes converted from ticks and size units of `config`
+    pub fn format_with_config(&self, config: &BookConfig) -> String {
+        self.format_scaled(&config.tick_size, &config.size_unit)
+    }
+
+    fn format_scaled(&self, tick_size: &TickSize, size_unit: &TickSize) -> String {
+        let mut s = format!("{} {} ${} #{} u{}", self.kind_str(), self.side_letter(), tick_size.to_decimal(self.price_limit),
+                            size_unit.units_to_decimal(self.size), self.user_id);
+        if let Some(trigger_price) = self.kind.trigger_price() {
+            s += &format!(" @{}", tick_size.to_decimal(trigger_price));
+        }
+        if let OrderKind::Iceberg { display_size } = self.kind {
+            s += &format!(" ^{}", size_unit.units_to_decimal(display_size));
+        }
+        if let OrderKind::TrailingStop { offset, .. } = self.kind {
+            s += &format!(" ~{}", tick_size.to_decimal(offset));
+        }
+        if let OrderKind::Pegged { reference, offset } = self.kind {
+            s += &format!(" ={}", reference.as_str());
+            if offset != 0 {
+                s += &format!("{}{}", if offset > 0 { "+" } else { "" }, tick_size.to_decimal(offset));
+            }
+        }
+        if let Some(min_fill_size) = self.min_fill_size {
+            s += &format!(" %{}", size_unit.units_to_decimal(min_fill_size));
+        }
+        if self.reduce_only {
+            s += " ro";
+        }
+        if let Some(expires_at) = self.expires_at {
+            s += &format!(" !{}", expires_at);
+        }
+        s
+    }
+
+    fn side_letter(&self) -> &'static str {
+        self.side.as_str()
+    }
+
+    fn kind_str(&self) -> &'static str {
+        self.kind.as_str()
+    }
 }
 
 impl fmt::Display for IncomingOrder {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        let side_letter = match self.side {
-            OrderSide::Buy => "B",
-            OrderSide::Sell => "S",
-        };
-        let kind_str = match self.kind {
-            OrderKind::Limit => "Lim",
-            OrderKind::FillOrKill => "FoK",
-            OrderKind::ImmediateOrCancel => "IoC",
-        };
-        write!(f, "{} {} ${} #{} u{}", kind_str, side_letter, self.price_limit, self.size, self.user_id)
+        write!(f, "{} {} ${} #{} u{}", self.kind_str(), self.side_letter(), self.price_limit, self.size, self.user_id)?;
+        if let Some(trigger_price) = self.kind.trigger_price() {
+            write!(f, " @{}", trigger_price)?;
+        }
+        if let OrderKind::Iceberg { display_size } = self.kind {
+            write!(f, " ^{}", display_size)?;
+        }
+        if let OrderKind::TrailingStop { offset, .. } = self.kind {
+            write!(f, " ~{}", offset)?;
+        }
+        if let OrderKind::Pegged { reference, offset } = self.kind {
+            write!(f, " ={}", reference.as_str())?;
+            if offset != 0 {
+                write!(f, "{:+}", offset)?;
+            }
+        }
+        if let Some(min_fill_size) = self.min_fill_size {
+            write!(f, " %{}", min_fill_size)?;
+        }
+        if self.reduce_only {
+            write!(f, " ro")?;
+        }
+        if let Some(expires_at) = self.expires_at {
+            write!(f, " !{}", expires_at)?;
+        }
+        Ok(())
     }
 }
 
@@ -84,64 +422,198 @@ impl fmt::Display for IncomingOrder {
 #[derive(Debug)]
 pub struct IncomingOrderParseError;
 
+fn strip_prefix<'a>(s: &'a str, prefix: &str) -> Result<&'a str, IncomingOrderParseError> {
+    match s.strip_prefix(prefix) {
+        Some(rest) if !rest.is_empty() => Ok(rest),
+        _ => Err(IncomingOrderParseError),
+    }
+}
+
+fn parse_with_prefix<T: FromStr>(s: &str, prefix: &str) -> Result<T, IncomingOrderParseError> {
+    strip_prefix(s, prefix)?.parse().map_err(|_| IncomingOrderParseError)
+}
+
+fn parse_order<F, G>(s: &str, parse_price: F, parse_size: G) -> Result<IncomingOrder, IncomingOrderParseError>
+    where F: Fn(&str) -> Result<Price, IncomingOrderParseError>,
+          G: Fn(&str) -> Result<u64, IncomingOrderParseError>
+{
+    let mut parts: Vec<_> = s.split_whitespace().collect();
+    // Good-till-date orders end with the expiry time, e.g. `Lim B $100 #5 u1 !1000`
+    let expires_at = match parts.last() {
+        Some(last) if last.starts_with('!') => {
+            let expires_at = parse_with_prefix(last, "!")?;
+            parts.pop();
+            Some(expires_at)
+        }
+        _ => None,
+    };
+    // Reduce-only orders are marked with `ro`, e.g. `Lim S $100 #5 u1 ro`
+    let reduce_only = parts.last() == Some(&"ro");
+    if reduce_only {
+        parts.pop();
+    }
+    // Minimum-quantity orders are followed by the minimum fill size, e.g. `IoC B $100 #5 u1 %3`
+    let min_fill_size = match parts.last() {
+        Some(last) if last.starts_with('%') => {
+            let min_fill_size = parse_size(strip_prefix(last, "%")?)?;
+            parts.pop();
+            Some(min_fill_size)
+        }
+        _ => None,
+    };
+    // Stop kinds have the trigger price as the last part, e.g. `Stp B $0 #5 u1 @105`,
+    // iceberg orders have the display size, e.g. `Ice B $100 #50 u1 ^10`,
+    // trailing stops the trigger price and the offset, e.g. `Trl S $0 #5 u1 @95 ~5`,
+    // pegged orders the reference and the signed offset, if any, e.g. `Peg B $105 #5 u1 =mid-1`
+    let kind = match parts.len() {
+        5 => parts[0].parse()?,
+        6 => match parts[0] {
+            "Stp" | "StpLim" => {
+                let trigger_price = parse_price(parts[5].strip_prefix('@').ok_or(IncomingOrderParseError)?)?;
+                if parts[0] == "Stp" {
+                    OrderKind::Stop { trigger_price }
+                } else {
+                    OrderKind::StopLimit { trigger_price }
+                }
+            }
+            "Ice" => OrderKind::Iceberg { display_size: parse_size(strip_prefix(parts[5], "^")?)? },
+            "Peg" => {
+                let peg = strip_prefix(parts[5], "=")?;
+                let reference = peg.get(..3).ok_or(IncomingOrderParseError)?.parse()?;
+                let offset = match &peg[3..] {
+                    "" => 0,
+                    offset if offset.starts_with('-') => parse_price(offset)?,
+                    offset => parse_price(strip_prefix(offset, "+")?)?,
+                };
+                OrderKind::Pegged { reference, offset }
+            }
+            _ => return Err(IncomingOrderParseError),
+        },
+        7 if parts[0] == "Trl" => OrderKind::TrailingStop {
+            trigger_price: parse_price(strip_prefix(parts[5], "@")?)?,
+            offset: parse_price(strip_prefix(parts[6], "~")?)?,
+        },
+        _ => return Err(IncomingOrderParseError),
+    };
+    let side = parts[1].parse()?;
+
+    if parts[2].len() < 2 || !parts[2].starts_with('$') {
+        return Err(IncomingOrderParseError);
+    }
+    let price_limit = parse_price(&parts[2][1..])?;
+    let size = parse_size(strip_prefix(parts[3], "#")?)?;
+    let user_id = parse_with_prefix(parts[4], "u")?;
+
+    Ok(IncomingOrder {
+        price_limit,
+        size,
+        user_id,
+        kind,
+        side,
+        expires_at,
+        min_fill_size,
+        reduce_only,
+    })
+}
+
 impl FromStr for IncomingOrder {
     type Err = IncomingOrderParseError;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let parts: Vec<_> = s.split_whitespace().collect();
-        if parts.len() != 5 {
-            return Err(IncomingOrderParseError);
+        parse_order(s, |price| price.parse().map_err(|_| IncomingOrderParseError),
+                    |size| size.parse().map_err(|_| IncomingOrderParseError))
+    }
+}
+
+impl TaggedOrder {
+    pub fn new(order: IncomingOrder, order_id: OrderId) -> Self {
+        match order.side {
+            OrderSide::Buy => TaggedOrder::Buy(Order::from_incoming(&order, order_id)),
+            OrderSide::Sell => TaggedOrder::Sell(Order::from_incoming(&order, order_id)),
         }
+    }
+}
 
-        let kind = match parts[0] {
-            "Lim" => OrderKind::Limit,
-            "FoK" => OrderKind::FillOrKill,
-            "IoC" => OrderKind::ImmediateOrCancel,
-            _ => return Err(IncomingOrderParseError),
-        };
-        let side = match parts[1] {
-            "S" => OrderSide::Sell,
-            "B" => OrderSide::Buy,
-            _ => return Err(IncomingOrderParseError),
-        };
+/// Detailed view of an order resting in the book
+#[derive(Debug, Clone, PartialEq)]
+pub struct RestingOrderView {
+    /// Order identifier
+    pub order_id: OrderId,
+    /// Resting order with its current (remaining) size
+    pub order: IncomingOrder,
+    /// Number of orders ahead of this one in the order queue
+    pub position: usize,
+    /// Time the order took its position in the queue, if the book has a clock
+    pub entered_at: Option<u64>,
+}
 
-        fn parse_u64_with_prefix(s: &str, prefix: &str) -> Result<u64, IncomingOrderParseError> {
-            if s.len() > 1 && s.starts_with(prefix) {
-                s[1..].parse().map_err(|_| IncomingOrderParseError)
-            } else {
-                Err(IncomingOrderParseError)
-            }
-        }
+impl RestingOrderView {
+    /// Returns time the order has spent at its position by `now`, if the book has a clock
+    pub fn age(&self, now: u64) -> Option<u64> {
+        self.entered_at.map(|entered_at| now.saturating_sub(entered_at))
+    }
+}
 
-        let price_limit = parse_u64_with_prefix(parts[2], "$")?;
-        let size = parse_u64_with_prefix(parts[3], "#")?;
-        let user_id = parse_u64_with_prefix(parts[4], "u")?;
+/// Estimated position of a resting order in its order queue
+///
+/// Displayed orders at the price of the order are ahead of it if they came first, or if the order is
+/// [Hidden](OrderKind::Hidden), since hidden orders are matched after the displayed ones. Hidden
+/// reserves of the iceberg orders at that price are refilled behind the order and are not counted.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct QueuePosition {
+    /// Number of orders ahead of this one
+    pub orders_ahead: usize,
+    /// Total size of the orders at better prices
+    pub better_size: Size,
+    /// Displayed size of the orders ahead at the same price
+    pub level_size: Size,
+}
 
-        Ok(IncomingOrder {
-            price_limit,
-            size,
-            user_id,
-            kind,
-            side,
-        })
+impl QueuePosition {
+    /// Returns total size to be filled before the order, saturating at the maximum size
+    pub fn size_ahead(&self) -> Size {
+        self.better_size.saturating_add(self.level_size)
     }
 }
 
-impl From<IncomingOrder> for TaggedOrder {
-    fn from(order: IncomingOrder) -> Self {
-        match order.side {
-            OrderSide::Buy => TaggedOrder::Buy(Order {
-                price_limit: order.price_limit,
-                size: order.size,
-                user_id: order.user_id,
-                _marker: PhantomData
-            }),
-            OrderSide::Sell => TaggedOrder::Sell(Order {
-                price_limit: order.price_limit,
-                size: order.size,
-                user_id: order.user_id,
-                _marker: PhantomData
-            }),
+/// Resting order removed by [cancel_order](crate::OrderBook::cancel_order)
+#[derive(Debug, Clone, PartialEq)]
+pub struct CancelledOrder {
+    /// Order identifier
+    pub order_id: OrderId,
+    /// Cancelled order with its remaining size
+    pub order: IncomingOrder,
+}
+
+/// Error of cancelling an order
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CancelError {
+    /// There is no resting order with this identifier
+    UnknownOrder(OrderId),
+}
+
+impl fmt::Display for CancelError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CancelError::UnknownOrder(order_id) => write!(f, "unknown order o{}", order_id),
+        }
+    }
+}
+
+/// Error of amending an order
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AmendError {
+    /// There is no resting order with this identifier
+    UnknownOrder(OrderId),
+    /// Amended order does not pass validation
+    Rejected(RejectReason),
+}
+
+impl fmt::Display for AmendError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AmendError::UnknownOrder(order_id) => write!(f, "unknown order o{}", order_id),
+            AmendError::Rejected(reason) => write!(f, "amendment rejected: {}", reason),
         }
     }
 }
@@ -176,6 +648,28 @@ impl<D: Direction> Direction for Order<D> {
 }
 
 impl<D: Direction> Order<D> {
+    /// Creates an order with the whole size displayed; the side of `order` is not checked
+    pub(crate) fn from_incoming(order: &IncomingOrder, order_id: OrderId) -> Self {
+        let display_size = match order.kind {
+            OrderKind::Iceberg { display_size } => display_size,
+            _ => 0,
+        };
+        Order {
+            price_limit: order.price_limit,
+            size: order.size,
+            user_id: order.user_id,
+            order_id,
+            expires_at: order.expires_at,
+            hidden: 0,
+            display_size,
+            dark: order.kind == OrderKind::Hidden,
+            post_only: order.kind == OrderKind::PostOnly,
+            reduce_only: order.reduce_only,
+            entered_at: None,
+            _marker: PhantomData
+        }
+    }
+
     /// Checks whether given order matches `other` order by price
     pub fn price_matches(&self, other: &Order<D::Other>) -> bool {
         match D::SIDE {
@@ -184,14 +678,84 @@ impl<D: Direction> Order<D> {
         }
     }
 
-    /// Constructs equivalent [IncomingOrder](IncomingOrder)
+    /// Returns order identifier
+    pub fn order_id(&self) -> OrderId {
+        self.order_id
+    }
+
+    /// Returns time the order took its position in the queue, if the book has a clock
+    ///
+    /// Orders which lose their time priority, e.g. when amended, are stamped again.
+    pub fn entered_at(&self) -> Option<u64> {
+        self.entered_at
+    }
+
+    /// Checks whether the order is a [Hidden](OrderKind::Hidden) order, which is not displayed
+    pub fn is_dark(&self) -> bool {
+        self.dark
+    }
+
+    /// Returns size of the hidden reserve, which is zero for non-iceberg orders
+    pub fn hidden_size(&self) -> Size {
+        self.hidden
+    }
+
+    /// Returns displayed and hidden size of the order
+    pub fn total_size(&self) -> Size {
+        self.size + self.hidden
+    }
+
+    /// Moves the part of the size above the display size of an iceberg order to the hidden reserve
+    pub(crate) fn hide_reserve(&mut self) {
+        if self.display_size > 0 && self.size > self.display_size {
+            self.hidden += self.size - self.display_size;
+            self.size = self.display_size;
+        }
+    }
+
+    /// Refills the displayed part of an iceberg order from the hidden reserve
+    pub(crate) fn refill(&mut self) {
+        self.size += self.hidden;
+        self.hidden = 0;
+        self.hide_reserve();
+    }
+
+    /// Removes `size` units, filling the displayed part first and refilling it if needed
+    pub(crate) fn consume(&mut self, size: Size) {
+        let visible = core::cmp::min(size, self.size);
+        self.size -= visible;
+        self.hidden -= size - visible;
+        if self.size == 0 {
+            self.refill();
+        }
+    }
+
+    /// Sets the total size, reducing the hidden reserve first
+    pub(crate) fn set_total_size(&mut self, size: Size) {
+        self.size = core::cmp::min(self.size, size);
+        self.hidden = size - self.size;
+    }
+
+    /// Constructs equivalent [IncomingOrder](IncomingOrder) with the total size of the order
     pub fn to_incoming(&self) -> IncomingOrder {
+        let kind = if self.display_size > 0 {
+            OrderKind::Iceberg { display_size: self.display_size }
+        } else if self.dark {
+            OrderKind::Hidden
+        } else if self.post_only {
+            OrderKind::PostOnly
+        } else {
+            OrderKind::Limit
+        };
         IncomingOrder {
             price_limit: self.price_limit,
-            size: self.size,
+            size: self.total_size(),
             user_id: self.user_id,
-            kind: OrderKind::Limit,
+            kind,
             side: D::SIDE,
+            expires_at: self.expires_at,
+            min_fill_size: None,
+            reduce_only: self.reduce_only,
         }
     }
 }
@@ -229,7 +793,10 @@ fn test_from_str() {
         size: 2,
         user_id: 3,
         kind: OrderKind::Limit,
-        side: OrderSide::Buy
+        side: OrderSide::Buy,
+        expires_at: None,
+        min_fill_size: None,
+        reduce_only: false,
     };
     assert_eq!(order, order2);
 
@@ -238,7 +805,8 @@ fn test_from_str() {
 
     IncomingOrder::from_str("Lim B 1 #2 u3").unwrap_err();
     IncomingOrder::from_str("Lim B $$ #2 u3").unwrap_err();
-    IncomingOrder::from_str("Lim B $-1 #2 u3").unwrap_err();
+    IncomingOrder::from_str("Lim B $1- #2 u3").unwrap_err();
+    assert_eq!(IncomingOrder::from_str("Lim B $-1 #2 u3").unwrap().price_limit, -1);
 
     IncomingOrder::from_str("Lim B $1 2 u3").unwrap_err();
     IncomingOrder::from_str("Lim B $1 ## u3").unwrap_err();
@@ -247,4 +815,61 @@ fn test_from_str() {
     IncomingOrder::from_str("Lim B $1 #2 3").unwrap_err();
     IncomingOrder::from_str("Lim B $1 #2 uu").unwrap_err();
     IncomingOrder::from_str("Lim B $1 #2 u-3").unwrap_err();
+
+    let iceberg = IncomingOrder::from_str("Ice S $7 #20 u3 ^5").unwrap();
+    assert_eq!(iceberg.kind, OrderKind::Iceberg { display_size: 5 });
+    assert_eq!(iceberg.to_string(), "Ice S $7 #20 u3 ^5");
+    IncomingOrder::from_str("Ice S $7 #20 u3 @5").unwrap_err();
+    IncomingOrder::from_str("Lim S $7 #20 u3 ^5").unwrap_err();
+
+    let gtd = IncomingOrder::from_str("Ice S $7 #20 u3 ^5 !1000").unwrap();
+    assert_eq!((gtd.kind, gtd.expires_at), (OrderKind::Iceberg { display_size: 5 }, Some(1000)));
+    assert_eq!(gtd.to_string(), "Ice S $7 #20 u3 ^5 !1000");
+    IncomingOrder::from_str("Lim S $7 #20 u3 !").unwrap_err();
+
+    let min_qty = IncomingOrder::from_str("IoC B $7 #20 u3 %5 !1000").unwrap();
+    assert_eq!((min_qty.min_fill_size, min_qty.expires_at), (Some(5), Some(1000)));
+    assert_eq!(min_qty.to_string(), "IoC B $7 #20 u3 %5 !1000");
+    IncomingOrder::from_str("IoC B $7 #20 u3 !1000 %5").unwrap_err();
+
+    let reduce_only = IncomingOrder::from_str("Lim S $7 #20 u3 %5 ro").unwrap();
+    assert!(reduce_only.reduce_only);
+    assert_eq!(reduce_only.to_string(), "Lim S $7 #20 u3 %5 ro");
+}
+
+#[test]
+fn test_decimal_prices() {
+    let tick_size: TickSize = "0.05".parse().unwrap();
+    let order = IncomingOrder::parse_with_tick_size("Lim B $1.25 #2 u3", &tick_size).unwrap();
+    assert_eq!(order.price_limit, 25);
+    assert_eq!(order.format_with_tick_size(&tick_size), "Lim B $1.25 #2 u3");
+    assert_eq!(order.to_string(), "Lim B $25 #2 u3");
+
+    IncomingOrder::parse_with_tick_size("Lim B $1.26 #2 u3", &tick_size).unwrap_err();
+    IncomingOrder::parse_with_tick_size("Lim B $ #2 u3", &tick_size).unwrap_err();
+    IncomingOrder::parse_with_tick_size("Lim B 1.25 #2 u3", &tick_size).unwrap_err();
+
+    let config = BookConfig { tick_size, size_unit: "0.001".parse().unwrap(), ..BookConfig::default() };
+    let order = IncomingOrder::parse_with_config("Ice S $0.1 #1.5 u3 ^0.25", &config).unwrap();
+    assert_eq!((order.price_limit, order.size, order.kind), (2, 1500, OrderKind::Iceberg { display_size: 250 }));
+    assert_eq!(order.format_with_config(&config), "Ice S $0.10 #1.500 u3 ^0.250");
+    IncomingOrder::parse_with_config("Lim B $1 #0.0005 u3", &config).unwrap_err();
+    IncomingOrder::parse_with_config("Lim B $1 #-1 u3", &config).unwrap_err();
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn test_parse_csv() {
+    let csv = "id,user,side,kind,price,size\n7,1,B,Lim,100,5\n\n8,2,S,IoC,-3,1\n";
+    let orders = parse_csv(csv.as_bytes()).unwrap();
+    assert_eq!(orders, [
+        IncomingOrder::from_str("Lim B $100 #5 u1").unwrap(),
+        IncomingOrder::from_str("IoC S $-3 #1 u2").unwrap(),
+    ]);
+    assert!(parse_csv("".as_bytes()).unwrap_err().to_string().contains("header"));
+    assert!(matches!(parse_csv("side,kind,price,size\n".as_bytes()), Err(CsvError::Header(DecodeError::MissingField("user")))));
+    match parse_csv("side,kind,price,size,user\nB,Lim,100,5,1\nB,Lim,x,5,1\n".as_bytes()) {
+        Err(CsvError::Record { line: 3, error: DecodeError::InvalidField("price") }) => {}
+        result => panic!("unexpected result: {:?}", result),
+    }
 }
@@ -0,0 +1,171 @@
+//! Pegged orders
+//!
+//! A [Pegged](OrderKind::Pegged) order rests in the order queue like a limit order, at the price of its
+//! [reference](PegReference) plus its offset, capped by its price limit. References are taken from the
+//! orders which are not pegged, so pegged orders never follow each other. After every operation of the
+//! book pegged orders whose price changed are repriced like [amended](crate::OrderBook::amend_order)
+//! orders: they lose their time priority and are matched if the new price crosses the opposite queue.
+//! Orders are repriced at most once per [repeg_interval](crate::config::BookConfig::repeg_interval).
+//!
+//! If the reference is missing, e.g. the opposite queue of a market peg is empty, the order keeps its
+//! price; an order submitted without a reference rests at its price limit.
+//!
+//! Pegged prices are checked like the prices of new orders, including the price collar. An order
+//! whose pegged price is invalid is rejected on arrival and [cancelled](crate::log::LogItem::Cancelled)
+//! when repriced.
+
+use crate::order::{IncomingOrder, OrderId, OrderKind, OrderSide, PegReference, Price, Size, UserId};
+use crate::tick::{TickError, TickSize};
+use alloc::vec::Vec;
+
+/// Peg of a resting order
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Peg {
+    #[allow(missing_docs)]
+    pub side: OrderSide,
+    #[allow(missing_docs)]
+    pub reference: PegReference,
+    /// Distance of the price from the reference
+    pub offset: Price,
+    /// Price limit of the order, which caps the pegged price
+    pub price_limit: Price,
+    /// Time of the last pricing, if the book has a clock
+    pub priced_at: Option<u64>,
+}
+
+impl Peg {
+    /// Returns the peg of a pegged `order` priced at `now`
+    pub fn from_order(order: &IncomingOrder, now: Option<u64>) -> Option<Peg> {
+        match order.kind {
+            OrderKind::Pegged { reference, offset } => Some(Peg {
+                side: order.side,
+                reference,
+                offset,
+                price_limit: order.price_limit,
+                priced_at: now,
+            }),
+            _ => None,
+        }
+    }
+
+    /// Returns the pegged price given the best bid and ask of the orders which are not pegged
+    pub fn price(&self, bid: Option<Price>, ask: Option<Price>) -> Option<Price> {
+        let reference = match (self.reference, self.side) {
+            (PegReference::Primary, OrderSide::Buy) | (PegReference::Market, OrderSide::Sell) => bid?,
+            (PegReference::Primary, OrderSide::Sell) | (PegReference::Market, OrderSide::Buy) => ask?,
+            (PegReference::Midpoint, side) => {
+                let sum = i128::from(bid?) + i128::from(ask?);
+                let mid = match side {
+                    OrderSide::Buy => sum.div_euclid(2),
+                    OrderSide::Sell => (sum + 1).div_euclid(2),
+                };
+                mid as Price
+            }
+        };
+        let price = reference.saturating_add(self.offset);
+        Some(match self.side {
+            OrderSide::Buy => price.min(self.price_limit),
+            OrderSide::Sell => price.max(self.price_limit),
+        })
+    }
+
+    /// Checks whether the order may be repriced at `now`
+    pub(crate) fn may_reprice(&self, now: Option<u64>, interval: Option<u64>) -> bool {
+        match (now, self.priced_at, interval) {
+            (Some(now), Some(priced_at), Some(interval)) => now >= priced_at.saturating_add(interval),
+            _ => true,
+        }
+    }
+
+    /// Returns the pegged order with the remaining `size` of the resting order
+    pub fn to_incoming(&self, size: Size, user_id: UserId, expires_at: Option<u64>) -> IncomingOrder {
+        IncomingOrder {
+            price_limit: self.price_limit,
+            size,
+            user_id,
+            kind: OrderKind::Pegged { reference: self.reference, offset: self.offset },
+            side: self.side,
+            expires_at,
+            min_fill_size: None,
+            reduce_only: false,
+        }
+    }
+}
+
+/// Pegs of the resting pegged orders, in the order of arrival
+#[derive(Debug, Clone, Default)]
+pub struct PegStore(Vec<(OrderId, Peg)>);
+
+impl PegStore {
+    pub(crate) fn insert(&mut self, order_id: OrderId, peg: Peg) {
+        self.0.push((order_id, peg));
+    }
+
+    pub(crate) fn remove(&mut self, order_id: OrderId) -> Option<Peg> {
+        let index = self.0.iter().position(|(id, _)| *id == order_id)?;
+        Some(self.0.remove(index).1)
+    }
+
+    pub(crate) fn get_mut(&mut self, order_id: OrderId) -> Option<&mut Peg> {
+        self.0.iter_mut().find(|(id, _)| *id == order_id).map(|(_, peg)| peg)
+    }
+
+    /// Returns identifiers of the pegged orders
+    pub(crate) fn order_ids(&self) -> Vec<OrderId> {
+        self.0.iter().map(|(order_id, _)| *order_id).collect()
+    }
+
+    /// Converts offsets and price limits from tick size `from` to tick size `to`
+    pub(crate) fn rescale(&mut self, from: &TickSize, to: &TickSize) -> Result<(), TickError> {
+        for (_, peg) in &mut self.0 {
+            peg.offset = from.rescale(peg.offset, to)?;
+            peg.price_limit = from.rescale(peg.price_limit, to)?;
+        }
+        Ok(())
+    }
+
+    /// Returns the peg of the order `order_id`
+    pub fn get(&self, order_id: OrderId) -> Option<&Peg> {
+        self.0.iter().find(|(id, _)| *id == order_id).map(|(_, peg)| peg)
+    }
+
+    /// Checks whether the order `order_id` is pegged
+    pub fn contains(&self, order_id: OrderId) -> bool {
+        self.get(order_id).is_some()
+    }
+
+    /// Returns number of pegged orders
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    /// Checks whether there are no pegged orders
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Iterates over pegs in the order of arrival
+    pub fn iter(&self) -> impl Iterator<Item=(OrderId, &Peg)> {
+        self.0.iter().map(|(order_id, peg)| (*order_id, peg))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pegged_prices() {
+        let peg = |s: &str| Peg::from_order(&s.parse().unwrap(), None).unwrap();
+        assert_eq!(peg("Peg B $105 #1 u1 =pri").price(Some(100), Some(104)), Some(100));
+        assert_eq!(peg("Peg B $105 #1 u1 =mkt-1").price(Some(100), Some(104)), Some(103));
+        assert_eq!(peg("Peg B $102 #1 u1 =mkt").price(Some(100), Some(104)), Some(102));
+        assert_eq!(peg("Peg S $95 #1 u1 =pri+1").price(Some(100), Some(104)), Some(105));
+        assert_eq!(peg("Peg S $95 #1 u1 =mkt").price(None, Some(104)), None);
+
+        // Midpoints between ticks are rounded away from the opposite side
+        assert_eq!(peg("Peg B $105 #1 u1 =mid").price(Some(100), Some(103)), Some(101));
+        assert_eq!(peg("Peg S $95 #1 u1 =mid").price(Some(100), Some(103)), Some(102));
+        assert_eq!(peg("Peg S $-105 #1 u1 =mid").price(Some(-103), Some(-100)), Some(-101));
+    }
+}
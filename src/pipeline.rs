@@ -0,0 +1,320 @@
+//! Pipelined decoding with ordered execution
+//!
+//! Decoding input records is independent for every record, while execution must follow the input
+//! order. [decode_pipelined](decode_pipelined) decodes records on worker threads and hands the
+//! commands back to the calling thread strictly in input order, so parsing overlaps with matching.
+//!
+//! [spawn_matcher](spawn_matcher) moves the book to a dedicated matching thread. Commands are
+//! submitted through a bounded lock-free single-producer single-consumer queue and the results are
+//! returned through per-command [Completion](Completion) handles.
+
+use crate::OrderBook;
+use crate::decode::{Command, DecodeError, OrderDecoder};
+use crate::log::{LogItem, VectorLogger};
+use crate::order::OrderId;
+use std::cell::UnsafeCell;
+use std::collections::HashMap;
+use std::mem::MaybeUninit;
+use std::sync::{mpsc, Arc, Condvar, Mutex};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::thread::{self, JoinHandle, Thread};
+use std::time::Duration;
+
+/// Number of records sent to a worker at once
+const BATCH_SIZE: usize = 256;
+
+/// Number of batches which may wait in every channel
+const QUEUE_DEPTH: usize = 4;
+
+type Batch = Vec<Result<Command, DecodeError>>;
+
+/// Decodes `records` on `workers` threads and calls `execute` for every record in input order
+///
+/// `records` are read on a separate thread; every worker uses its own decoder created with
+/// `make_decoder`. `execute` runs on the calling thread and receives the index of the record
+/// and the decoding result.
+pub fn decode_pipelined<I, D, M, F>(records: I, workers: usize, make_decoder: M, mut execute: F)
+    where I: Iterator<Item=Vec<u8>> + Send,
+          D: OrderDecoder,
+          M: Fn() -> D + Sync,
+          F: FnMut(usize, Result<Command, DecodeError>)
+{
+    let workers = workers.max(1);
+    thread::scope(|scope| {
+        let (result_tx, result_rx) = mpsc::sync_channel::<(usize, Batch)>(workers * QUEUE_DEPTH);
+        let mut batch_txs = Vec::new();
+        for _ in 0..workers {
+            let (batch_tx, batch_rx) = mpsc::sync_channel::<(usize, Vec<Vec<u8>>)>(QUEUE_DEPTH);
+            batch_txs.push(batch_tx);
+            let result_tx = result_tx.clone();
+            let make_decoder = &make_decoder;
+            scope.spawn(move || {
+                let mut decoder = make_decoder();
+                for (index, batch) in batch_rx {
+                    let decoded = batch.iter().map(|record| decoder.decode(record)).collect();
+                    if result_tx.send((index, decoded)).is_err() {
+                        break;
+                    }
+                }
+            });
+        }
+        drop(result_tx);
+
+        scope.spawn(move || {
+            let mut records = records;
+            for index in 0.. {
+                let batch: Vec<Vec<u8>> = records.by_ref().take(BATCH_SIZE).collect();
+                if batch.is_empty() {
+                    break;
+                }
+                // Batches are distributed round-robin, so every worker receives them in order
+                if batch_txs[index % batch_txs.len()].send((index, batch)).is_err() {
+                    break;
+                }
+            }
+        });
+
+        let mut next = 0;
+        let mut record = 0;
+        let mut waiting: HashMap<usize, Batch> = HashMap::new();
+        for (index, batch) in result_rx {
+            waiting.insert(index, batch);
+            while let Some(batch) = waiting.remove(&next) {
+                for result in batch {
+                    execute(record, result);
+                    record += 1;
+                }
+                next += 1;
+            }
+        }
+    });
+}
+
+/// Bounded lock-free queue with one producer and one consumer
+struct SpscQueue<T> {
+    slots: Box<[UnsafeCell<MaybeUninit<T>>]>,
+    /// Index of the next element to pop, written by the consumer
+    head: AtomicUsize,
+    /// Index of the next element to push, written by the producer
+    tail: AtomicUsize,
+    /// Producer is gone, no more elements will be pushed
+    closed: AtomicBool,
+}
+
+// Every slot is accessed either by the producer or by the consumer, as decided by `head` and `tail`
+unsafe impl<T: Send> Sync for SpscQueue<T> {}
+
+impl<T> SpscQueue<T> {
+    fn new(capacity: usize) -> Self {
+        SpscQueue {
+            slots: (0..capacity.max(1)).map(|_| UnsafeCell::new(MaybeUninit::uninit())).collect(),
+            head: AtomicUsize::new(0),
+            tail: AtomicUsize::new(0),
+            closed: AtomicBool::new(false),
+        }
+    }
+
+    /// Pushes `value`, returns it back if the queue is full; must only be called by the producer
+    fn push(&self, value: T) -> Result<(), T> {
+        let tail = self.tail.load(Ordering::Relaxed);
+        if tail - self.head.load(Ordering::Acquire) == self.slots.len() {
+            return Err(value);
+        }
+        unsafe { (*self.slots[tail % self.slots.len()].get()).as_mut_ptr().write(value) };
+        self.tail.store(tail + 1, Ordering::Release);
+        Ok(())
+    }
+
+    /// Pops the oldest element; must only be called by the consumer
+    fn pop(&self) -> Option<T> {
+        let head = self.head.load(Ordering::Relaxed);
+        if head == self.tail.load(Ordering::Acquire) {
+            return None;
+        }
+        let value = unsafe { (*self.slots[head % self.slots.len()].get()).as_ptr().read() };
+        self.head.store(head + 1, Ordering::Release);
+        Some(value)
+    }
+}
+
+impl<T> Drop for SpscQueue<T> {
+    fn drop(&mut self) {
+        while self.pop().is_some() {}
+    }
+}
+
+/// Execution results of a submitted command
+#[derive(Debug, Clone, PartialEq)]
+pub struct Execution {
+    /// Identifier of the new order or of the cancelled or amended order
+    pub order_id: OrderId,
+    /// Logged items
+    pub items: Vec<LogItem>,
+}
+
+#[derive(Default)]
+struct CompletionSlot {
+    execution: Mutex<Option<Execution>>,
+    ready: Condvar,
+}
+
+/// Handle to the results of a submitted command
+pub struct Completion {
+    slot: Arc<CompletionSlot>,
+}
+
+impl Completion {
+    /// Returns the results if the command has been executed
+    pub fn try_take(&self) -> Option<Execution> {
+        self.slot.execution.lock().expect("completion lock is poisoned").take()
+    }
+
+    /// Waits until the command is executed and returns the results
+    pub fn wait(self) -> Execution {
+        let mut execution = self.slot.execution.lock().expect("completion lock is poisoned");
+        loop {
+            if let Some(execution) = execution.take() {
+                return execution;
+            }
+            execution = self.slot.ready.wait(execution).expect("completion lock is poisoned");
+        }
+    }
+}
+
+type Job = (Command, Arc<CompletionSlot>);
+
+/// Producer side of the matching thread queue
+///
+/// There is only one submitter per matching thread; dropping it stops the thread once the
+/// queued commands are executed.
+pub struct Submitter {
+    queue: Arc<SpscQueue<Job>>,
+    matcher: Thread,
+}
+
+impl Submitter {
+    /// Queues `command` for execution, returns it back if the queue is full
+    pub fn try_submit(&mut self, command: Command) -> Result<Completion, Command> {
+        let slot = Arc::new(CompletionSlot::default());
+        self.queue.push((command, slot.clone())).map_err(|(command, _)| command)?;
+        self.matcher.unpark();
+        Ok(Completion { slot })
+    }
+
+    /// Queues `command` for execution, waiting for a free slot if the queue is full
+    pub fn submit(&mut self, mut command: Command) -> Completion {
+        loop {
+            match self.try_submit(command) {
+                Ok(completion) => return completion,
+                Err(rejected) => {
+                    command = rejected;
+                    thread::yield_now();
+                }
+            }
+        }
+    }
+}
+
+impl Drop for Submitter {
+    fn drop(&mut self) {
+        self.queue.closed.store(true, Ordering::Release);
+        self.matcher.unpark();
+    }
+}
+
+/// Starts a matching thread owning `book` with a queue of `capacity` commands
+///
+/// The thread returns the book once the [Submitter](Submitter) is dropped and all queued
+/// commands are executed.
+pub fn spawn_matcher(mut book: OrderBook, capacity: usize) -> (Submitter, JoinHandle<OrderBook>) {
+    let queue = Arc::new(SpscQueue::<Job>::new(capacity));
+    let consumer = queue.clone();
+    let handle = thread::spawn(move || {
+        loop {
+            let (command, slot) = match consumer.pop() {
+                Some(job) => job,
+                None if consumer.closed.load(Ordering::Acquire) => match consumer.pop() {
+                    Some(job) => job,
+                    None => break,
+                },
+                None => {
+                    // Submitter unparks the thread after every push, the timeout only guards against lost wakeups
+                    thread::park_timeout(Duration::from_millis(10));
+                    continue;
+                }
+            };
+            let order_id = match command {
+                Command::Cancel(order_id) | Command::Amend { order_id, .. } => Some(order_id),
+                Command::New(_) => None,
+            };
+            let mut logger = VectorLogger::new();
+            book.execute_command(command, &mut logger);
+            let order_id = order_id.or_else(|| book.last_order_id()).expect("order id is assigned on execution");
+            let execution = Execution { order_id, items: logger.as_slice().to_vec() };
+            *slot.execution.lock().expect("completion lock is poisoned") = Some(execution);
+            slot.ready.notify_all();
+        }
+        book
+    });
+    let submitter = Submitter { queue, matcher: handle.thread().clone() };
+    (submitter, handle)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::decode::TextDecoder;
+
+    #[test]
+    fn commands_are_executed_in_input_order() {
+        let records: Vec<Vec<u8>> = (0..2000)
+            .map(|i| if i == 700 { b"bad".to_vec() } else { format!("Lim B ${} #1 u1", i).into_bytes() })
+            .collect();
+        let mut executed = Vec::new();
+        decode_pipelined(records.into_iter(), 3, || TextDecoder, |index, result| {
+            match result {
+                Ok(Command::New(order)) => assert_eq!(order.price_limit, index as i64),
+                Ok(command) => panic!("unexpected command {:?}", command),
+                Err(e) => assert_eq!((index, e), (700, DecodeError::Malformed)),
+            }
+            executed.push(index);
+        });
+        assert_eq!(executed, (0..2000).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn matching_thread_executes_in_submission_order() {
+        let commands: Vec<Command> = (0..500)
+            .map(|i| {
+                let side = if i % 3 == 0 { "S" } else { "B" };
+                Command::New(format!("Lim {} ${} #{} u{}", side, 100 + i % 5, 1 + i % 4, i).parse().unwrap())
+            })
+            .collect();
+        let mut expected_book = OrderBook::new();
+        let expected: Vec<Execution> = commands.iter().map(|command| {
+            let mut logger = VectorLogger::new();
+            expected_book.execute_command(command.clone(), &mut logger);
+            Execution { order_id: expected_book.last_order_id().unwrap(), items: logger.as_slice().to_vec() }
+        }).collect();
+
+        let (mut submitter, handle) = spawn_matcher(OrderBook::new(), 16);
+        let completions: Vec<_> = commands.into_iter().map(|command| submitter.submit(command)).collect();
+        let cancel = submitter.submit(Command::Cancel(1000));
+        drop(submitter);
+        let executions: Vec<_> = completions.into_iter().map(Completion::wait).collect();
+        assert_eq!(executions, expected);
+        assert_eq!(cancel.wait().order_id, 1000);
+        assert_eq!(handle.join().unwrap().to_vec(), expected_book.to_vec());
+    }
+
+    #[test]
+    fn full_queue_returns_command() {
+        let queue = SpscQueue::new(2);
+        assert_eq!(queue.push(1), Ok(()));
+        assert_eq!(queue.push(2), Ok(()));
+        assert_eq!(queue.push(3), Err(3));
+        assert_eq!(queue.pop(), Some(1));
+        assert_eq!(queue.push(3), Ok(()));
+        assert_eq!((queue.pop(), queue.pop(), queue.pop()), (Some(2), Some(3), None));
+    }
+}
@@ -0,0 +1,281 @@
+use crate::order::{Direction, Order, OrderId, OrderSide, Price};
+use crate::queues::Queue;
+use crate::HashMap;
+use alloc::collections::BTreeMap;
+use alloc::vec::Vec;
+
+#[derive(Clone)]
+struct Node<D> {
+    order: Order<D>,
+    prev: Option<usize>,
+    next: Option<usize>,
+}
+
+/// Doubly linked list of orders stored in a slab
+///
+/// Orders are found by their identifiers, so [find_order](Queue::find_order),
+/// [update_order](Queue::update_order) and [remove_order](Queue::remove_order) take `O(1)` and
+/// [insert_before](Queue::insert_before) appends an order to its price level in `O(log n)`. Like
+/// in [PriceLevelQueue](crate::queues::PriceLevelQueue) the requested position of
+/// [insert_before](Queue::insert_before) is ignored, other positional operations walk the list.
+///
+/// Identifiers of the orders must be unique within the queue.
+#[derive(Clone)]
+pub struct LinkedQueue<D> {
+    nodes: Vec<Option<Node<D>>>,
+    /// Slots of removed nodes, reused by the next insertions
+    free: Vec<usize>,
+    head: Option<usize>,
+    tail: Option<usize>,
+    /// Slots of the orders by identifier
+    slots: HashMap<OrderId, usize>,
+    /// Slot of the last order of every price level
+    levels: BTreeMap<Price, usize>,
+}
+
+impl<D: Direction> LinkedQueue<D> {
+    fn node(&self, slot: usize) -> &Node<D> {
+        self.nodes[slot].as_ref().expect("slot is occupied")
+    }
+
+    fn node_mut(&mut self, slot: usize) -> &mut Node<D> {
+        self.nodes[slot].as_mut().expect("slot is occupied")
+    }
+
+    /// Returns slot of the order at `index`
+    fn slot_at(&self, index: usize) -> Option<usize> {
+        let mut slot = self.head;
+        for _ in 0..index {
+            slot = self.node(slot?).next;
+        }
+        slot
+    }
+
+    /// Links `order` after the node in slot `prev`, or at the front if `prev` is `None`
+    fn link_after(&mut self, prev: Option<usize>, order: Order<D>) {
+        let next = match prev {
+            Some(prev) => self.node(prev).next,
+            None => self.head,
+        };
+        let (order_id, price) = (order.order_id, order.price_limit);
+        let node = Some(Node { order, prev, next });
+        let slot = match self.free.pop() {
+            Some(slot) => {
+                self.nodes[slot] = node;
+                slot
+            }
+            None => {
+                self.nodes.push(node);
+                self.nodes.len() - 1
+            }
+        };
+        match prev {
+            Some(prev) => self.node_mut(prev).next = Some(slot),
+            None => self.head = Some(slot),
+        }
+        match next {
+            Some(next) => self.node_mut(next).prev = Some(slot),
+            None => self.tail = Some(slot),
+        }
+        self.slots.insert(order_id, slot);
+        if next.is_none_or(|next| self.node(next).order.price_limit != price) {
+            self.levels.insert(price, slot);
+        }
+    }
+
+    fn unlink(&mut self, slot: usize) -> Order<D> {
+        let Node { order, prev, next } = self.nodes[slot].take().expect("slot is occupied");
+        self.free.push(slot);
+        match prev {
+            Some(prev) => self.node_mut(prev).next = next,
+            None => self.head = next,
+        }
+        match next {
+            Some(next) => self.node_mut(next).prev = prev,
+            None => self.tail = prev,
+        }
+        self.slots.remove(&order.order_id);
+        let price = order.price_limit;
+        if self.levels.get(&price) == Some(&slot) {
+            match prev.filter(|&prev| self.node(prev).order.price_limit == price) {
+                Some(prev) => self.levels.insert(price, prev),
+                None => self.levels.remove(&price),
+            };
+        }
+        order
+    }
+
+    fn rebuild_levels(&mut self) {
+        self.levels.clear();
+        let mut slot = self.head;
+        while let Some(current) = slot {
+            let node = self.nodes[current].as_ref().expect("slot is occupied");
+            self.levels.insert(node.order.price_limit, current);
+            slot = node.next;
+        }
+    }
+}
+
+impl<D: Direction> Queue<Order<D>> for LinkedQueue<D> {
+    type Iter<'a> = LinkedQueueIter<'a, D> where D: 'a;
+
+    fn new() -> Self {
+        Self {
+            nodes: Vec::new(),
+            free: Vec::new(),
+            head: None,
+            tail: None,
+            slots: HashMap::new(),
+            levels: BTreeMap::new(),
+        }
+    }
+
+    fn insert_position<P>(&self, predicate: P) -> Option<usize>
+        where P: FnMut(&Order<D>) -> bool
+    {
+        self.iter().position(predicate)
+    }
+
+    fn insert_before<P>(&mut self, item: Order<D>, _predicate: P)
+        where P: FnMut(&Order<D>) -> bool
+    {
+        // The order goes after the last order of its level or of the next better level
+        let price = item.price_limit;
+        let better = match D::SIDE {
+            OrderSide::Buy => self.levels.range(price..).next(),
+            OrderSide::Sell => self.levels.range(..=price).next_back(),
+        };
+        let prev = better.map(|(_, &slot)| slot);
+        self.link_after(prev, item);
+    }
+
+    fn push_back(&mut self, item: Order<D>) {
+        self.link_after(self.tail, item);
+    }
+
+    fn push_front(&mut self, item: Order<D>) {
+        self.link_after(None, item);
+    }
+
+    fn insert_at(&mut self, index: usize, item: Order<D>) {
+        let prev = index.checked_sub(1).map(|index| self.slot_at(index).expect("invalid index"));
+        self.link_after(prev, item);
+    }
+
+    fn drop_first_n(&mut self, count: usize) {
+        for _ in 0..count {
+            let head = self.head.expect("invalid count");
+            self.unlink(head);
+        }
+    }
+
+    fn remove_at(&mut self, index: usize) -> Order<D> {
+        let slot = self.slot_at(index).expect("invalid index");
+        self.unlink(slot)
+    }
+
+    fn iterate<P>(&mut self, mut predicate: P) where P: FnMut(&mut Order<D>, usize) -> bool {
+        let mut slot = self.head;
+        let mut index = 0;
+        let mut repriced = false;
+        while let Some(current) = slot {
+            let node = self.node_mut(current);
+            let price = node.order.price_limit;
+            let proceed = predicate(&mut node.order, index);
+            repriced |= node.order.price_limit != price;
+            slot = node.next;
+            index += 1;
+            if !proceed {
+                break;
+            }
+        }
+        // Prices are only changed together (e.g. on a tick size change), keeping the orders sorted
+        if repriced {
+            self.rebuild_levels();
+        }
+    }
+
+    fn find_order(&self, _price: Price, key: OrderId) -> Option<&Order<D>> {
+        let slot = *self.slots.get(&key)?;
+        Some(&self.node(slot).order)
+    }
+
+    fn update_order<F>(&mut self, _price: Price, key: OrderId, f: F) -> bool
+        where F: FnOnce(&mut Order<D>)
+    {
+        match self.slots.get(&key) {
+            Some(&slot) => {
+                f(&mut self.node_mut(slot).order);
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn remove_order(&mut self, _price: Price, key: OrderId) -> Option<Order<D>> {
+        let slot = *self.slots.get(&key)?;
+        Some(self.unlink(slot))
+    }
+
+    fn iter(&self) -> Self::Iter<'_> {
+        LinkedQueueIter {
+            queue: self,
+            front: self.head,
+            back: self.tail,
+            len: self.len(),
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.slots.len()
+    }
+}
+
+/// Iterator over the orders of a [LinkedQueue](LinkedQueue)
+pub struct LinkedQueueIter<'a, D> {
+    queue: &'a LinkedQueue<D>,
+    front: Option<usize>,
+    back: Option<usize>,
+    len: usize,
+}
+
+impl<'a, D: Direction> Iterator for LinkedQueueIter<'a, D> {
+    type Item = &'a Order<D>;
+
+    fn next(&mut self) -> Option<&'a Order<D>> {
+        if self.len == 0 {
+            return None;
+        }
+        let node = self.queue.node(self.front?);
+        self.front = node.next;
+        self.len -= 1;
+        Some(&node.order)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.len, Some(self.len))
+    }
+}
+
+impl<'a, D: Direction> DoubleEndedIterator for LinkedQueueIter<'a, D> {
+    fn next_back(&mut self) -> Option<&'a Order<D>> {
+        if self.len == 0 {
+            return None;
+        }
+        let node = self.queue.node(self.back?);
+        self.back = node.prev;
+        self.len -= 1;
+        Some(&node.order)
+    }
+}
+
+impl<'a, D: Direction> ExactSizeIterator for LinkedQueueIter<'a, D> { }
+
+impl<'a, D: Direction> IntoIterator for &'a LinkedQueue<D> {
+    type Item = &'a Order<D>;
+    type IntoIter = LinkedQueueIter<'a, D>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
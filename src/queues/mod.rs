@@ -1,28 +1,214 @@
+//! Storage of the resting orders of one side of the book
+//!
+//! Every side of an [OrderBook](crate::OrderBook) keeps its orders in a [Queue](Queue), front
+//! (best) order first. [DefaultQueue](DefaultQueue) is used unless other queues are chosen with
+//! [with_queues](crate::OrderBook::with_queues); the `price-level-queue` feature changes the default
+//! to [PriceLevelQueue](PriceLevelQueue).
+
+mod linked_queue;
+mod price_level_queue;
 mod reversed_vec;
 mod simple_vec_queue;
 mod vec_deque_queue;
 
+pub use self::linked_queue::{LinkedQueue, LinkedQueueIter};
+pub use self::price_level_queue::PriceLevelQueue;
 pub use self::reversed_vec::ReversedVec;
 pub use self::simple_vec_queue::SimpleVecQueue;
 pub use self::vec_deque_queue::VecDequeQueue;
 
-pub trait Queue<T> {
+use crate::order::{Order, OrderId, Price};
+
+/// Queue used by the book unless chosen otherwise
+#[cfg(not(feature = "price-level-queue"))]
+pub type DefaultQueue<D> = ReversedVec<D>;
+/// Queue used by the book unless chosen otherwise
+#[cfg(feature = "price-level-queue")]
+pub type DefaultQueue<D> = PriceLevelQueue<D>;
+
+/// Item which can be found by its identifier
+pub trait Keyed {
+    /// Returns identifier of the item
+    fn key(&self) -> OrderId;
+}
+
+impl<D> Keyed for Order<D> {
+    fn key(&self) -> OrderId {
+        self.order_id
+    }
+}
+
+/// Sequence of items addressed by their position from the front
+///
+/// The book keeps the orders of a queue in priority order: by price, then by time. Queues which
+/// sort by price themselves may ignore the requested positions of other prices.
+pub trait Queue<T>: Clone {
+    /// Iterator over the items from the front
+    type Iter<'a>: DoubleEndedIterator<Item=&'a T> where Self: 'a, T: 'a;
+
+    /// Constructs empty queue
     fn new() -> Self;
 
+    /// Returns position of the first item matching `predicate`, where an item is to be inserted
     fn insert_position<P>(&self, predicate: P) -> Option<usize>
         where P: FnMut(&T) -> bool;
 
+    /// Returns position of the first item matching `predicate`
+    fn position<P>(&self, predicate: P) -> Option<usize>
+        where P: FnMut(&T) -> bool
+    {
+        self.insert_position(predicate)
+    }
+
+    /// Returns position of the first item at `price` matching `predicate`
+    ///
+    /// Queues sorted by price only search the items of the price level.
+    fn position_at_price<P>(&self, _price: Price, predicate: P) -> Option<usize>
+        where P: FnMut(&T) -> bool
+    {
+        self.position(predicate)
+    }
+
+    /// Inserts `item` before the first item matching `predicate`, or at the end of the queue
+    fn insert_before<P>(&mut self, item: T, predicate: P)
+        where P: FnMut(&T) -> bool
+    {
+        match self.insert_position(predicate) {
+            Some(index) => self.insert_at(index, item),
+            None => self.push_back(item),
+        }
+    }
+
+    /// Appends `item`
     fn push_back(&mut self, item: T);
 
+    /// Inserts `item` at the front
     fn push_front(&mut self, item: T) {
         self.insert_at(0, item)
     }
 
+    /// Inserts `item` at position `index`
     fn insert_at(&mut self, index: usize, item: T);
 
+    /// Removes `count` items from the front
     fn drop_first_n(&mut self, count: usize);
 
+    /// Removes and returns the item at position `index`
+    fn remove_at(&mut self, index: usize) -> T;
+
+    /// Calls `predicate` with the items from the front and their positions until it returns `false`
     fn iterate<P>(&mut self, predicate: P) where P: FnMut(&mut T, usize) -> bool;
 
+    /// Returns the item `key` at `price`
+    fn find_order(&self, price: Price, key: OrderId) -> Option<&T>
+        where T: Keyed
+    {
+        let position = self.position_at_price(price, |item| item.key() == key)?;
+        self.iter().nth(position)
+    }
+
+    /// Calls `f` with the item `key` at `price`, which must not change the price of the item
+    ///
+    /// Returns whether the item was found.
+    fn update_order<F>(&mut self, _price: Price, key: OrderId, f: F) -> bool
+        where T: Keyed, F: FnOnce(&mut T)
+    {
+        let mut f = Some(f);
+        self.iterate(|item, _| {
+            if item.key() != key {
+                return true;
+            }
+            if let Some(f) = f.take() {
+                f(item);
+            }
+            false
+        });
+        f.is_none()
+    }
+
+    /// Removes and returns the item `key` at `price`
+    fn remove_order(&mut self, price: Price, key: OrderId) -> Option<T>
+        where T: Keyed
+    {
+        let position = self.position_at_price(price, |item| item.key() == key)?;
+        Some(self.remove_at(position))
+    }
+
+    /// Returns iterator over the items from the front
+    fn iter(&self) -> Self::Iter<'_>;
+
+    /// Returns number of items
     fn len(&self) -> usize;
+
+    /// Checks whether the queue is empty
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::order::{Buy, IncomingOrder, Order, Sell};
+
+    fn check_queue<Q: Queue<Order<Buy>>>() {
+        let mut queue = Q::new();
+        for (order_id, s) in ["Lim B $100 #1 u1", "Lim B $100 #2 u1", "Lim B $99 #3 u1", "Lim B $101 #4 u1"].iter().enumerate() {
+            let order: IncomingOrder = s.parse().unwrap();
+            let price = order.price_limit;
+            queue.insert_before(Order::from_incoming(&order, order_id as u64 + 1), |o| o.price_limit < price);
+        }
+        let ids = |queue: &Q| queue.iter().map(|o| o.order_id()).collect::<Vec<_>>();
+        assert_eq!(ids(&queue), [4, 1, 2, 3]);
+        assert_eq!(queue.iter().next_back().map(|o| o.order_id()), Some(3));
+        assert_eq!(queue.position_at_price(100, |o| o.order_id() == 2), Some(2));
+        assert_eq!(queue.find_order(99, 3).map(|o| o.size), Some(3));
+        assert!(queue.update_order(99, 3, |o| o.size = 5));
+        assert!(!queue.update_order(99, 7, |o| o.size = 5));
+        assert_eq!(queue.remove_order(99, 3).map(|o| o.size), Some(5));
+        assert!(queue.remove_order(99, 3).is_none());
+        queue.push_back(Order::from_incoming(&"Lim B $99 #3 u1".parse().unwrap(), 3));
+        assert_eq!(queue.remove_at(1).order_id(), 1);
+        queue.drop_first_n(1);
+        assert_eq!(ids(&queue), [2, 3]);
+        assert_eq!(queue.len(), 2);
+        queue.drop_first_n(2);
+        assert!(queue.is_empty());
+    }
+
+    #[test]
+    fn queues_keep_priority_order() {
+        check_queue::<ReversedVec<Buy>>();
+        check_queue::<SimpleVecQueue<Buy>>();
+        check_queue::<VecDequeQueue<Buy>>();
+        check_queue::<PriceLevelQueue<Buy>>();
+        check_queue::<LinkedQueue<Buy>>();
+    }
+
+    #[test]
+    fn linked_queue_keeps_levels() {
+        let mut queue = LinkedQueue::<Sell>::new();
+        for (order_id, s) in ["Lim S $101 #1 u1", "Lim S $103 #1 u1", "Lim S $101 #1 u1", "Lim S $102 #1 u1", "Lim S $103 #1 u1"].iter().enumerate() {
+            let order: IncomingOrder = s.parse().unwrap();
+            queue.insert_before(Order::from_incoming(&order, order_id as u64 + 1), |_| false);
+        }
+        let ids = |queue: &LinkedQueue<Sell>| queue.iter().map(|o| o.order_id()).collect::<Vec<_>>();
+        assert_eq!(ids(&queue), [1, 3, 4, 2, 5]);
+
+        // Removing the last order of a level makes the previous one the last
+        queue.remove_order(101, 3);
+        queue.insert_before(Order::from_incoming(&"Lim S $101 #1 u1".parse().unwrap(), 6), |_| false);
+        queue.remove_order(102, 4);
+        queue.insert_before(Order::from_incoming(&"Lim S $102 #1 u1".parse().unwrap(), 7), |_| false);
+        assert_eq!(ids(&queue), [1, 6, 7, 2, 5]);
+        assert_eq!(queue.iter().rev().map(|o| o.order_id()).collect::<Vec<_>>(), [5, 2, 7, 6, 1]);
+
+        queue.iterate(|order, _| {
+            order.price_limit += 10;
+            true
+        });
+        queue.insert_before(Order::from_incoming(&"Lim S $112 #1 u1".parse().unwrap(), 8), |_| false);
+        assert_eq!(ids(&queue), [1, 6, 7, 8, 2, 5]);
+        assert_eq!(queue.len(), 6);
+    }
 }
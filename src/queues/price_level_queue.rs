@@ -0,0 +1,166 @@
+use crate::order::{Order, OrderSide, Direction, Price};
+use alloc::collections::{BTreeMap, VecDeque};
+use core::ops::Bound;
+use crate::queues::Queue;
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+
+/// Queue bucketed by price level
+///
+/// Orders are always kept in price priority, so an order is placed into its price level regardless of
+/// the requested position: [insert_before](Queue::insert_before) appends it to its level in `O(log n)`,
+/// and positional insertions only choose the position within the level.
+#[derive(Clone)]
+pub struct PriceLevelQueue<D> {
+    levels: BTreeMap<Price, VecDeque<Order<D>>>,
+    len: usize,
+}
+
+type Levels<'a, D> = Box<dyn DoubleEndedIterator<Item=(&'a Price, &'a VecDeque<Order<D>>)> + 'a>;
+type LevelsMut<'a, D> = Box<dyn Iterator<Item=(&'a Price, &'a mut VecDeque<Order<D>>)> + 'a>;
+
+impl<D: Direction> PriceLevelQueue<D> {
+    /// Iterates over the levels from the best price to the worst one
+    fn levels(&self) -> Levels<'_, D> {
+        match D::SIDE {
+            OrderSide::Buy => Box::new(self.levels.iter().rev()),
+            OrderSide::Sell => Box::new(self.levels.iter()),
+        }
+    }
+
+    fn levels_mut(&mut self) -> LevelsMut<'_, D> {
+        match D::SIDE {
+            OrderSide::Buy => Box::new(self.levels.iter_mut().rev()),
+            OrderSide::Sell => Box::new(self.levels.iter_mut()),
+        }
+    }
+
+    /// Returns price level and offset within the level of the order at `index`
+    fn locate(&self, mut index: usize) -> (Price, usize) {
+        for (&price, level) in self.levels() {
+            if index < level.len() {
+                return (price, index);
+            }
+            index -= level.len();
+        }
+        panic!("invalid index");
+    }
+
+    /// Returns number of orders at prices better than `price`
+    fn ahead_of(&self, price: Price) -> usize {
+        let ahead = match D::SIDE {
+            OrderSide::Buy => self.levels.range((Bound::Excluded(price), Bound::Unbounded)),
+            OrderSide::Sell => self.levels.range(..price),
+        };
+        ahead.map(|(_, level)| level.len()).sum()
+    }
+
+    fn remove_level_if_empty(&mut self, price: Price) {
+        if self.levels.get(&price).is_some_and(VecDeque::is_empty) {
+            self.levels.remove(&price);
+        }
+    }
+}
+
+impl<D: Direction> Queue<Order<D>> for PriceLevelQueue<D> {
+    type Iter<'a> = Box<dyn DoubleEndedIterator<Item=&'a Order<D>> + 'a> where D: 'a;
+
+    fn new() -> Self {
+        Self {
+            levels: BTreeMap::new(),
+            len: 0,
+        }
+    }
+
+    fn insert_position<P>(&self, predicate: P) -> Option<usize>
+        where P: FnMut(&Order<D>) -> bool
+    {
+        self.into_iter().position(predicate)
+    }
+
+    fn position_at_price<P>(&self, price: Price, predicate: P) -> Option<usize>
+        where P: FnMut(&Order<D>) -> bool
+    {
+        let offset = self.levels.get(&price)?.iter().position(predicate)?;
+        Some(self.ahead_of(price) + offset)
+    }
+
+    fn insert_before<P>(&mut self, item: Order<D>, _predicate: P)
+        where P: FnMut(&Order<D>) -> bool
+    {
+        self.push_back(item)
+    }
+
+    fn push_back(&mut self, item: Order<D>) {
+        self.levels.entry(item.price_limit).or_default().push_back(item);
+        self.len += 1;
+    }
+
+    fn insert_at(&mut self, index: usize, item: Order<D>) {
+        let offset = index.saturating_sub(self.ahead_of(item.price_limit));
+        let level = self.levels.entry(item.price_limit).or_default();
+        let offset = core::cmp::min(offset, level.len());
+        level.insert(offset, item);
+        self.len += 1;
+    }
+
+    fn drop_first_n(&mut self, mut count: usize) {
+        self.len -= count;
+        while count > 0 {
+            let (&price, _) = self.levels().next().expect("invalid count");
+            let level = self.levels.get_mut(&price).expect("level exists");
+            let dropped = core::cmp::min(count, level.len());
+            level.drain(..dropped);
+            count -= dropped;
+            self.remove_level_if_empty(price);
+        }
+    }
+
+    fn remove_at(&mut self, index: usize) -> Order<D> {
+        let (price, offset) = self.locate(index);
+        let order = self.levels.get_mut(&price).and_then(|level| level.remove(offset)).expect("invalid index");
+        self.remove_level_if_empty(price);
+        self.len -= 1;
+        order
+    }
+
+    fn iterate<P>(&mut self, mut predicate: P) where P: FnMut(&mut Order<D>, usize) -> bool {
+        let mut index = 0;
+        // Prices changed by the predicate (e.g. on a tick size change) move their levels
+        let mut moved = Vec::new();
+        'levels: for (&price, level) in self.levels_mut() {
+            for order in level.iter_mut() {
+                let proceed = predicate(order, index);
+                index += 1;
+                if order.price_limit != price && moved.last() != Some(&price) {
+                    moved.push(price);
+                }
+                if !proceed {
+                    break 'levels;
+                }
+            }
+        }
+        // All moved levels are taken out first, as new prices may coincide with old prices of other levels
+        let moved: Vec<_> = moved.into_iter().filter_map(|price| self.levels.remove(&price)).collect();
+        for order in moved.into_iter().flatten() {
+            self.levels.entry(order.price_limit).or_default().push_back(order);
+        }
+    }
+
+    fn iter(&self) -> Self::Iter<'_> {
+        Box::new(self.levels().flat_map(|(_, level)| level.iter()))
+    }
+
+    fn len(&self) -> usize {
+        self.len
+    }
+}
+
+impl<'a, D: Direction> IntoIterator for &'a PriceLevelQueue<D> {
+    type Item = &'a Order<D>;
+    type IntoIter = <PriceLevelQueue<D> as Queue<Order<D>>>::Iter<'a>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
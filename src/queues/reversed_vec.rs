@@ -1,11 +1,14 @@
-use crate::order::{Order, Direction};
-use std::{slice, iter};
+use crate::order::{Order, OrderSide, Direction, Price};
+use core::{slice, iter};
 use crate::queues::Queue;
+use alloc::vec::Vec;
 
 #[derive(Clone)]
 pub struct ReversedVec<D>(Vec<Order<D>>);
 
 impl<D: Direction> Queue<Order<D>> for ReversedVec<D> {
+    type Iter<'a> = iter::Rev<slice::Iter<'a, Order<D>>> where D: 'a;
+
     fn new() -> Self {
         Self(Vec::new())
     }
@@ -16,6 +19,18 @@ impl<D: Direction> Queue<Order<D>> for ReversedVec<D> {
         self.0.iter().rev().position(predicate)
     }
 
+    fn position_at_price<P>(&self, price: Price, predicate: P) -> Option<usize>
+        where P: FnMut(&Order<D>) -> bool
+    {
+        // Orders are stored from the worst price to the best one
+        let (start, end) = match D::SIDE {
+            OrderSide::Buy => (self.0.partition_point(|o| o.price_limit < price), self.0.partition_point(|o| o.price_limit <= price)),
+            OrderSide::Sell => (self.0.partition_point(|o| o.price_limit > price), self.0.partition_point(|o| o.price_limit >= price)),
+        };
+        let offset = self.0[start..end].iter().rev().position(predicate)?;
+        Some(self.0.len() - end + offset)
+    }
+
     fn push_back(&mut self, item: Order<D>) {
         self.0.insert(0, item)
     }
@@ -32,6 +47,10 @@ impl<D: Direction> Queue<Order<D>> for ReversedVec<D> {
         self.0.truncate(self.0.len() - count)
     }
 
+    fn remove_at(&mut self, index: usize) -> Order<D> {
+        self.0.remove(self.0.len() - 1 - index)
+    }
+
     fn iterate<P>(&mut self, mut predicate: P) where P: FnMut(&mut Order<D>, usize) -> bool {
         for (index, order) in self.0.iter_mut().rev().enumerate() {
             if !predicate(order, index) {
@@ -40,17 +59,20 @@ impl<D: Direction> Queue<Order<D>> for ReversedVec<D> {
         }
     }
 
+    fn iter(&self) -> Self::Iter<'_> {
+        self.0.iter().rev()
+    }
+
     fn len(&self) -> usize {
         self.0.len()
     }
 }
 
-
-impl<'a, D> IntoIterator for &'a ReversedVec<D> {
+impl<'a, D: Direction> IntoIterator for &'a ReversedVec<D> {
     type Item = &'a Order<D>;
-    type IntoIter = iter::Rev<slice::Iter<'a, Order<D>>>;
+    type IntoIter = <ReversedVec<D> as Queue<Order<D>>>::Iter<'a>;
 
     fn into_iter(self) -> Self::IntoIter {
-        (&self.0).into_iter().rev()
+        self.iter()
     }
 }
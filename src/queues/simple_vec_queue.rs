@@ -1,11 +1,14 @@
 use crate::order::{Order, Direction};
-use std::slice;
+use core::slice;
 use crate::queues::Queue;
+use alloc::vec::Vec;
 
 #[derive(Clone)]
 pub struct SimpleVecQueue<D>(Vec<Order<D>>);
 
 impl<D: Direction> Queue<Order<D>> for SimpleVecQueue<D> {
+    type Iter<'a> = slice::Iter<'a, Order<D>> where D: 'a;
+
     fn new() -> Self {
         Self(Vec::new())
     }
@@ -25,9 +28,11 @@ impl<D: Direction> Queue<Order<D>> for SimpleVecQueue<D> {
     }
 
     fn drop_first_n(&mut self, count: usize) {
-        for _ in 0..count {
-            self.0.pop();
-        }
+        self.0.drain(..count);
+    }
+
+    fn remove_at(&mut self, index: usize) -> Order<D> {
+        self.0.remove(index)
     }
 
     fn iterate<P>(&mut self, mut predicate: P) where P: FnMut(&mut Order<D>, usize) -> bool {
@@ -38,17 +43,20 @@ impl<D: Direction> Queue<Order<D>> for SimpleVecQueue<D> {
         }
     }
 
+    fn iter(&self) -> Self::Iter<'_> {
+        self.0.iter()
+    }
+
     fn len(&self) -> usize {
         self.0.len()
     }
 }
 
-
-impl<'a, D> IntoIterator for &'a SimpleVecQueue<D> {
+impl<'a, D: Direction> IntoIterator for &'a SimpleVecQueue<D> {
     type Item = &'a Order<D>;
-    type IntoIter = slice::Iter<'a, Order<D>>;
+    type IntoIter = <SimpleVecQueue<D> as Queue<Order<D>>>::Iter<'a>;
 
     fn into_iter(self) -> Self::IntoIter {
-        (&self.0).into_iter()
+        self.iter()
     }
 }
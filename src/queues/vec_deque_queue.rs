@@ -1,12 +1,14 @@
 use crate::order::{Order, Direction};
-use std::collections::vec_deque::VecDeque;
-use std::collections::vec_deque;
+use alloc::collections::vec_deque::VecDeque;
+use alloc::collections::vec_deque;
 use crate::queues::Queue;
 
 #[derive(Clone)]
 pub struct VecDequeQueue<D>(VecDeque<Order<D>>);
 
 impl<D: Direction> Queue<Order<D>> for VecDequeQueue<D> {
+    type Iter<'a> = vec_deque::Iter<'a, Order<D>> where D: 'a;
+
     fn new() -> Self {
         Self(VecDeque::new())
     }
@@ -33,6 +35,10 @@ impl<D: Direction> Queue<Order<D>> for VecDequeQueue<D> {
         self.0.drain(0..count);
     }
 
+    fn remove_at(&mut self, index: usize) -> Order<D> {
+        self.0.remove(index).expect("invalid index")
+    }
+
     fn iterate<P>(&mut self, mut predicate: P) where P: FnMut(&mut Order<D>, usize) -> bool {
         for (index, order) in self.0.iter_mut().enumerate() {
             if !predicate(order, index) {
@@ -41,17 +47,20 @@ impl<D: Direction> Queue<Order<D>> for VecDequeQueue<D> {
         }
     }
 
+    fn iter(&self) -> Self::Iter<'_> {
+        self.0.iter()
+    }
+
     fn len(&self) -> usize {
         self.0.len()
     }
 }
 
-
-impl<'a, D> IntoIterator for &'a VecDequeQueue<D> {
+impl<'a, D: Direction> IntoIterator for &'a VecDequeQueue<D> {
     type Item = &'a Order<D>;
-    type IntoIter = vec_deque::Iter<'a, Order<D>>;
+    type IntoIter = <VecDequeQueue<D> as Queue<Order<D>>>::Iter<'a>;
 
     fn into_iter(self) -> Self::IntoIter {
-        (&self.0).into_iter()
+        self.iter()
     }
 }
@@ -0,0 +1,86 @@
+//! Mass quotes of market makers
+//!
+//! [apply_quote_set](crate::OrderBook::apply_quote_set) replaces all resting orders of a user with a set of
+//! [Quotes](Quote) in one transaction. Only the difference is executed: resting orders whose side and
+//! price are quoted again are kept or resized, the others are cancelled before the new quotes are inserted.
+
+use crate::order::{IncomingOrder, OrderId, OrderKind, OrderSide, Price, Size, UserId};
+use alloc::vec::Vec;
+
+/// Limit order of a quote set
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Quote {
+    #[allow(missing_docs)]
+    pub side: OrderSide,
+    #[allow(missing_docs)]
+    pub price: Price,
+    #[allow(missing_docs)]
+    pub size: Size,
+}
+
+impl Quote {
+    /// Returns the limit order of the quote placed by the user `user_id`
+    pub fn to_incoming(&self, user_id: UserId) -> IncomingOrder {
+        IncomingOrder {
+            price_limit: self.price,
+            size: self.size,
+            user_id,
+            kind: OrderKind::Limit,
+            side: self.side,
+            expires_at: None,
+            min_fill_size: None,
+            reduce_only: false,
+        }
+    }
+}
+
+/// Operation bringing a resting order in line with a quote
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum QuoteAction {
+    /// The order already has the size of the quote
+    Keep(OrderId),
+    /// The order is quoted with another size
+    Resize(OrderId),
+    /// There is no order at the price of the quote
+    Insert,
+}
+
+/// Pairs `quotes` with the `resting` orders of their side and price
+///
+/// Returns the orders to cancel and the action of every quote.
+pub(crate) fn plan(resting: &[(OrderId, IncomingOrder)], quotes: &[Quote]) -> (Vec<OrderId>, Vec<QuoteAction>) {
+    let mut unused: Vec<Option<&(OrderId, IncomingOrder)>> = resting.iter().map(Some).collect();
+    let actions = quotes.iter().map(|quote| {
+        let found = unused.iter_mut()
+            .find(|slot| slot.is_some_and(|(_, order)| order.side == quote.side && order.price_limit == quote.price));
+        match found.and_then(Option::take) {
+            Some(&(order_id, ref order)) if order.size == quote.size => QuoteAction::Keep(order_id),
+            Some(&(order_id, _)) => QuoteAction::Resize(order_id),
+            None => QuoteAction::Insert,
+        }
+    }).collect();
+    let cancels = unused.into_iter().flatten().map(|&(order_id, _)| order_id).collect();
+    (cancels, actions)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn quotes_are_paired_by_side_and_price() {
+        let resting: Vec<(OrderId, IncomingOrder)> = ["Lim B $99 #5 u1", "Lim B $98 #5 u1", "Lim S $101 #5 u1", "Lim S $102 #5 u1"]
+            .iter().enumerate()
+            .map(|(index, s)| (index as OrderId + 1, s.parse().unwrap()))
+            .collect();
+        let quote = |side, price, size| Quote { side, price, size };
+        let quotes = [
+            quote(OrderSide::Buy, 99, 5), quote(OrderSide::Sell, 101, 3),
+            quote(OrderSide::Sell, 103, 5), quote(OrderSide::Buy, 101, 5),
+        ];
+        let (cancels, actions) = plan(&resting, &quotes);
+        assert_eq!(cancels, [2, 4]);
+        assert_eq!(actions, [QuoteAction::Keep(1), QuoteAction::Resize(3), QuoteAction::Insert, QuoteAction::Insert]);
+    }
+}
@@ -0,0 +1,228 @@
+//! Request-for-quote workflow
+//!
+//! RFQs model block and OTC flows which are negotiated outside of the central book:
+//! a requester asks designated responders for a price for the whole size, responders
+//! quote within a window of time, and the request is executed against the best quote.
+//! Time is supplied by the caller, so any clock (wall time, simulated time, sequence numbers) can be used.
+
+use crate::order::{OrderSide, Price, Size, UserId};
+use crate::HashMap;
+use core::fmt;
+use alloc::vec::Vec;
+
+/// Identifier of a request for quote
+pub type RfqId = u64;
+
+/// Request for quote
+#[derive(Debug, Clone, PartialEq)]
+pub struct Rfq {
+    /// Requesting user
+    pub requester: UserId,
+    /// Side of the requester
+    pub side: OrderSide,
+    /// Requested size
+    pub size: Size,
+    /// Users allowed to respond
+    pub responders: Vec<UserId>,
+    /// Last moment at which quotes are accepted
+    pub deadline: u64,
+}
+
+/// Step of an RFQ negotiation
+#[allow(missing_docs)]
+#[derive(Debug, Clone, PartialEq)]
+pub enum RfqEvent {
+    /// Quote was requested
+    Requested { time: u64 },
+    /// Responder quoted (or requoted) a price
+    Quoted { time: u64, responder: UserId, price: Price },
+    /// Request was executed against the best quote
+    Executed { time: u64, responder: UserId, price: Price, size: Size },
+    /// Request was closed without any quotes
+    Expired { time: u64 },
+}
+
+/// Execution of an RFQ
+#[allow(missing_docs)]
+#[derive(Debug, Clone, PartialEq)]
+pub struct RfqTrade {
+    pub rfq_id: RfqId,
+    pub buyer: UserId,
+    pub seller: UserId,
+    pub price: Price,
+    pub size: Size,
+}
+
+/// RFQ error
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RfqError {
+    /// There is no such request
+    UnknownRfq,
+    /// User is not a designated responder of the request
+    NotResponder,
+    /// Quoting window has passed
+    WindowClosed,
+    /// Quoting window is still open and not all responders have quoted
+    WindowOpen,
+    /// Request was already executed or expired
+    Closed,
+}
+
+impl fmt::Display for RfqError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RfqError::UnknownRfq => f.write_str("unknown RFQ"),
+            RfqError::NotResponder => f.write_str("not a responder"),
+            RfqError::WindowClosed => f.write_str("quoting window closed"),
+            RfqError::WindowOpen => f.write_str("quoting window open"),
+            RfqError::Closed => f.write_str("RFQ closed"),
+        }
+    }
+}
+
+struct RfqEntry {
+    rfq: Rfq,
+    // Latest quote of every responder in the order of arrival
+    quotes: Vec<(u64, Price)>,
+    events: Vec<RfqEvent>,
+    closed: bool,
+}
+
+/// Collection of RFQs and their negotiations
+#[derive(Default)]
+pub struct RfqDesk {
+    requests: HashMap<RfqId, RfqEntry>,
+    next_id: RfqId,
+}
+
+impl RfqDesk {
+    /// Constructs an empty `RfqDesk`
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Opens a request for quote with the quoting window of `window` time units starting at `now`
+    pub fn request(&mut self, requester: UserId, side: OrderSide, size: Size, responders: Vec<UserId>, window: u64, now: u64) -> RfqId {
+        self.next_id += 1;
+        let rfq = Rfq {
+            requester,
+            side,
+            size,
+            responders,
+            deadline: now.saturating_add(window),
+        };
+        self.requests.insert(self.next_id, RfqEntry {
+            rfq,
+            quotes: Vec::new(),
+            events: vec![RfqEvent::Requested { time: now }],
+            closed: false,
+        });
+        self.next_id
+    }
+
+    /// Returns the request `rfq_id`
+    pub fn get(&self, rfq_id: RfqId) -> Option<&Rfq> {
+        self.requests.get(&rfq_id).map(|entry| &entry.rfq)
+    }
+
+    /// Returns the negotiation log of the request `rfq_id`
+    pub fn negotiation(&self, rfq_id: RfqId) -> Option<&[RfqEvent]> {
+        self.requests.get(&rfq_id).map(|entry| entry.events.as_slice())
+    }
+
+    fn open_entry(&mut self, rfq_id: RfqId) -> Result<&mut RfqEntry, RfqError> {
+        let entry = self.requests.get_mut(&rfq_id).ok_or(RfqError::UnknownRfq)?;
+        if entry.closed {
+            return Err(RfqError::Closed);
+        }
+        Ok(entry)
+    }
+
+    /// Submits a firm quote for the whole size of the request
+    ///
+    /// A repeated quote of the same responder replaces the previous one and loses its time priority.
+    pub fn quote(&mut self, rfq_id: RfqId, responder: UserId, price: Price, now: u64) -> Result<(), RfqError> {
+        let entry = self.open_entry(rfq_id)?;
+        if !entry.rfq.responders.contains(&responder) {
+            return Err(RfqError::NotResponder);
+        }
+        if now > entry.rfq.deadline {
+            return Err(RfqError::WindowClosed);
+        }
+        entry.quotes.retain(|&(user, _)| user != responder);
+        entry.quotes.push((responder, price));
+        entry.events.push(RfqEvent::Quoted { time: now, responder, price });
+        Ok(())
+    }
+
+    /// Executes the request against the best quote
+    ///
+    /// The request can be closed once its window has passed or every responder has quoted.
+    /// Among equal prices the earliest quote wins. Returns `None` if nobody quoted.
+    pub fn close(&mut self, rfq_id: RfqId, now: u64) -> Result<Option<RfqTrade>, RfqError> {
+        let entry = self.open_entry(rfq_id)?;
+        let rfq = &entry.rfq;
+        if now <= rfq.deadline && entry.quotes.len() < rfq.responders.len() {
+            return Err(RfqError::WindowOpen);
+        }
+        entry.closed = true;
+
+        let side = rfq.side;
+        let best = entry.quotes.iter().fold(None, |best: Option<(UserId, Price)>, &(responder, price)| match best {
+            Some((_, best_price)) if !is_better(side, price, best_price) => best,
+            _ => Some((responder, price)),
+        });
+        let (responder, price) = match best {
+            Some(best) => best,
+            None => {
+                entry.events.push(RfqEvent::Expired { time: now });
+                return Ok(None);
+            }
+        };
+        let (buyer, seller) = match side {
+            OrderSide::Buy => (rfq.requester, responder),
+            OrderSide::Sell => (responder, rfq.requester),
+        };
+        let size = rfq.size;
+        entry.events.push(RfqEvent::Executed { time: now, responder, price, size });
+        Ok(Some(RfqTrade { rfq_id, buyer, seller, price, size }))
+    }
+}
+
+/// Checks whether `price` is strictly better than `best` for the requester on `side`
+fn is_better(side: OrderSide, price: Price, best: Price) -> bool {
+    match side {
+        OrderSide::Buy => price < best,
+        OrderSide::Sell => price > best,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rfq_executes_against_best_quote() {
+        let mut desk = RfqDesk::new();
+        let rfq_id = desk.request(1, OrderSide::Buy, 500, vec![2, 3, 4], 10, 100);
+        assert_eq!(desk.quote(rfq_id, 5, 99, 101), Err(RfqError::NotResponder));
+        desk.quote(rfq_id, 2, 101, 101).unwrap();
+        desk.quote(rfq_id, 3, 100, 102).unwrap();
+        desk.quote(rfq_id, 2, 100, 103).unwrap();
+        assert_eq!(desk.close(rfq_id, 105), Err(RfqError::WindowOpen));
+        assert_eq!(desk.quote(rfq_id, 4, 90, 111), Err(RfqError::WindowClosed));
+
+        let trade = desk.close(rfq_id, 111).unwrap().unwrap();
+        assert_eq!(trade, RfqTrade { rfq_id, buyer: 1, seller: 3, price: 100, size: 500 });
+        assert_eq!(desk.close(rfq_id, 112), Err(RfqError::Closed));
+        assert_eq!(desk.negotiation(rfq_id).unwrap().len(), 5);
+
+        let rfq_id = desk.request(1, OrderSide::Sell, 5, vec![2], 10, 200);
+        desk.quote(rfq_id, 2, 95, 201).unwrap();
+        assert_eq!(desk.close(rfq_id, 202).unwrap().unwrap().buyer, 2);
+
+        let rfq_id = desk.request(1, OrderSide::Sell, 5, vec![2], 10, 300);
+        assert_eq!(desk.close(rfq_id, 311), Ok(None));
+        assert_eq!(desk.negotiation(rfq_id).unwrap().last(), Some(&RfqEvent::Expired { time: 311 }));
+    }
+}
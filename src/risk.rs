@@ -0,0 +1,262 @@
+//! Pre-trade risk checks
+//!
+//! A [RiskChecker](RiskChecker) installed with [set_risk_checker](crate::OrderBook::set_risk_checker)
+//! is asked to approve every order before it is matched or parked and is notified of every fill,
+//! including the fills of auctions. [BalanceChecker](BalanceChecker) is a simple reference implementation.
+//!
+//! [Reduce-only](IncomingOrder::reduce_only) orders are checked against the positions of a
+//! [PositionProvider](PositionProvider) installed with
+//! [set_position_provider](crate::OrderBook::set_position_provider). A reduce-only order is resized to
+//! the position it closes, or rejected if it would only increase the position; without a provider every
+//! position is zero. Orders are checked whenever they are executed, including the triggering of stop
+//! orders, but open orders of the user are not taken into account. Reduce-only orders are not parked
+//! while trading is not open.
+
+use crate::config::RejectReason;
+use crate::log::Trade;
+use crate::math::notional;
+use crate::order::{IncomingOrder, OrderKind, OrderSide, Size, UserId};
+use crate::HashMap;
+
+/// Hook approving orders and following their fills
+pub trait RiskChecker {
+    /// Checks `order` before it is matched, the order is rejected with the returned reason
+    fn check(&mut self, order: &IncomingOrder) -> Result<(), RejectReason>;
+
+    /// Receives every fill of the continuous matching and of the auctions
+    fn on_fill(&mut self, _trade: &Trade) { }
+}
+
+/// Source of the positions of users
+pub trait PositionProvider {
+    /// Returns the asset position of the user `user_id`, negative for short positions
+    fn position(&self, user_id: UserId) -> i128;
+}
+
+#[cfg(feature = "std")]
+impl<T: PositionProvider> PositionProvider for std::sync::Mutex<T> {
+    fn position(&self, user_id: UserId) -> i128 {
+        self.lock().expect("position provider lock is poisoned").position(user_id)
+    }
+}
+
+/// Returns the size to which a reduce-only order of `side` and `size` is limited by the `position` of its user
+pub fn reduce_only_size(side: OrderSide, size: Size, position: i128) -> Result<Size, RejectReason> {
+    let reducible = match side {
+        OrderSide::Buy => -position,
+        OrderSide::Sell => position,
+    };
+    if reducible <= 0 {
+        return Err(RejectReason::WouldIncreasePosition);
+    }
+    Ok(if reducible < i128::from(size) { reducible as u64 } else { size })
+}
+
+/// Cash and asset position of a user
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Balance {
+    /// Cash in price ticks times size units
+    pub cash: i128,
+    /// Asset position in size units
+    pub position: i128,
+}
+
+/// Risk checker which keeps the balances of users from going negative
+///
+/// Buy orders need cash for their whole notional at the price limit, sell orders need the position
+/// to deliver. Market buys only need a positive cash balance. Open orders do not reserve the balances,
+/// so several orders of one user may together exceed them.
+#[derive(Debug, Clone, Default)]
+pub struct BalanceChecker {
+    balances: HashMap<UserId, Balance>,
+}
+
+impl BalanceChecker {
+    /// Constructs checker where every user has an empty balance
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns balance of the user `user_id`
+    pub fn balance(&self, user_id: UserId) -> Balance {
+        self.balances.get(&user_id).copied().unwrap_or_default()
+    }
+
+    /// Sets balance of the user `user_id`
+    pub fn set_balance(&mut self, user_id: UserId, balance: Balance) {
+        self.balances.insert(user_id, balance);
+    }
+}
+
+impl RiskChecker for BalanceChecker {
+    fn check(&mut self, order: &IncomingOrder) -> Result<(), RejectReason> {
+        let balance = self.balance(order.user_id);
+        let covered = match (order.side, order.kind) {
+            (OrderSide::Buy, OrderKind::Market) => balance.cash > 0,
+            (OrderSide::Buy, _) => balance.cash >= notional(order.price_limit, order.size),
+            (OrderSide::Sell, _) => balance.position >= i128::from(order.size),
+        };
+        if covered { Ok(()) } else { Err(RejectReason::RiskLimit) }
+    }
+
+    fn on_fill(&mut self, trade: &Trade) {
+        let (buyer, seller) = match trade.taker_side {
+            OrderSide::Buy => (trade.taker_user_id, trade.maker_user_id),
+            OrderSide::Sell => (trade.maker_user_id, trade.taker_user_id),
+        };
+        let value = notional(trade.price, trade.size);
+        let size = i128::from(trade.size);
+        let buyer = self.balances.entry(buyer).or_default();
+        buyer.cash -= value;
+        buyer.position += size;
+        let seller = self.balances.entry(seller).or_default();
+        seller.cash += value;
+        seller.position -= size;
+    }
+}
+
+impl PositionProvider for BalanceChecker {
+    fn position(&self, user_id: UserId) -> i128 {
+        self.balance(user_id).position
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::*;
+    use crate::OrderBook;
+    use crate::config::BookState;
+    use crate::order::AmendError;
+    use crate::log::VectorLogger;
+    use crate::tests::check_log;
+    use std::sync::{Arc, Mutex};
+
+    #[test]
+    fn balances_are_checked_and_updated() {
+        let mut checker = BalanceChecker::new();
+        checker.set_balance(1, Balance { cash: 0, position: 10 });
+        checker.set_balance(2, Balance { cash: 1000, position: 0 });
+        let checker = Arc::new(Mutex::new(checker));
+        let mut book = OrderBook::new();
+        book.set_risk_checker(Some(checker.clone()));
+
+        let mut logger = VectorLogger::new();
+        for s in &["Lim S $100 #11 u1", "Lim S $100 #8 u1", "Lim B $100 #11 u2", "IoC B $100 #6 u2", "IoC B $100 #5 u2"] {
+            book.execute_order(s.parse().unwrap(), &mut logger);
+        }
+        check_log(logger.as_slice(), &["R #11 risk-limit", "Q S #8 $100 u1 o2", "R #11 risk-limit", "F #6 $100 u1 o2", "R #5 risk-limit"]);
+
+        let checker = checker.lock().unwrap();
+        assert_eq!(checker.balance(1), Balance { cash: 600, position: 4 });
+        assert_eq!(checker.balance(2), Balance { cash: 400, position: 6 });
+    }
+
+    #[test]
+    fn raised_amendments_are_checked() {
+        let mut checker = BalanceChecker::new();
+        checker.set_balance(1, Balance { cash: 1000, position: 0 });
+        let mut book = OrderBook::new();
+        book.set_risk_checker(Some(Arc::new(Mutex::new(checker))));
+        let mut logger = VectorLogger::new();
+        book.execute_order("Lim B $90 #10 u1".parse().unwrap(), &mut logger);
+        assert_eq!(book.amend_order(1, 90, 12, &mut logger), Err(AmendError::Rejected(RejectReason::RiskLimit)));
+        book.amend_order(1, 90, 5, &mut logger).unwrap();
+        check_log(logger.as_slice(), &["Q B #10 $90 u1 o1", "R #12 risk-limit", "M #5 $90 o1"]);
+    }
+
+    #[test]
+    fn transaction_fills_are_reported_on_commit() {
+        let mut checker = BalanceChecker::new();
+        checker.set_balance(1, Balance { cash: 0, position: 10 });
+        checker.set_balance(2, Balance { cash: 1000, position: 0 });
+        let checker = Arc::new(Mutex::new(checker));
+        let mut book = OrderBook::new();
+        book.set_risk_checker(Some(checker.clone()));
+        let mut logger = VectorLogger::new();
+        book.execute_order("Lim S $100 #10 u1".parse().unwrap(), &mut logger);
+
+        // The second order is rejected, so the fill of the first one is rolled back unreported
+        let orders = vec!["IoC B $100 #4 u2".parse().unwrap(), "IoC B $100 #20 u2".parse().unwrap()];
+        assert_eq!(book.execute_group(orders, &mut logger).unwrap_err().reason, RejectReason::RiskLimit);
+        assert_eq!(checker.lock().unwrap().balance(2), Balance { cash: 1000, position: 0 });
+
+        let orders = vec!["IoC B $100 #4 u2".parse().unwrap(), "IoC B $100 #3 u2".parse().unwrap()];
+        assert!(book.execute_group(orders, &mut logger).is_ok());
+        let checker = checker.lock().unwrap();
+        assert_eq!(checker.balance(1), Balance { cash: 700, position: 3 });
+        assert_eq!(checker.balance(2), Balance { cash: 300, position: 7 });
+    }
+
+    #[test]
+    fn auction_fills_are_reported() {
+        let mut checker = BalanceChecker::new();
+        checker.set_balance(1, Balance { cash: 0, position: 10 });
+        checker.set_balance(2, Balance { cash: 1000, position: 0 });
+        let checker = Arc::new(Mutex::new(checker));
+        let mut book = OrderBook::new();
+        book.set_risk_checker(Some(checker.clone()));
+        book.set_state(BookState::AuctionOnly, &mut VectorLogger::new());
+        let mut logger = VectorLogger::new();
+        for s in &["Lim S $100 #6 u1", "Lim B $101 #4 u2"] {
+            book.execute_order(s.parse().unwrap(), &mut logger);
+        }
+
+        // The indicative auction is not reported
+        assert_eq!(book.indicative_auction(), Some((100, 4)));
+        assert_eq!(checker.lock().unwrap().balance(2), Balance { cash: 1000, position: 0 });
+
+        book.uncross(&mut logger).unwrap();
+        let checker = checker.lock().unwrap();
+        assert_eq!(checker.balance(1), Balance { cash: 400, position: 6 });
+        assert_eq!(checker.balance(2), Balance { cash: 600, position: 4 });
+    }
+
+    #[test]
+    fn reduce_only_orders_follow_positions() {
+        let mut checker = BalanceChecker::new();
+        checker.set_balance(1, Balance { cash: 0, position: 5 });
+        checker.set_balance(2, Balance { cash: 0, position: -3 });
+        let mut book = OrderBook::new();
+        book.set_position_provider(Some(Arc::new(Mutex::new(checker))));
+
+        let mut logger = VectorLogger::new();
+        for s in &["Lim B $100 #2 u3", "Lim S $101 #8 u1 ro", "Lim B $99 #2 u1 ro", "Lim S $102 #1 u2 ro", "Lim B $101 #2 u2 ro"] {
+            book.execute_order(s.parse().unwrap(), &mut logger);
+        }
+        check_log(logger.as_slice(), &[
+            "Q B #2 $100 u3 o1", "Q S #5 $101 u1 o2", "C S #3 $101 u1 o2",
+            "R #2 would-increase-position", "R #1 would-increase-position", "F #2 $101 u1 o2",
+        ]);
+
+        // Without a provider every position is zero
+        book.set_position_provider(None);
+        let order = "Lim S $100 #1 u1 ro".parse().unwrap();
+        assert_eq!(book.try_execute_order(order, &mut logger), Err(RejectReason::WouldIncreasePosition));
+    }
+
+    #[test]
+    fn resting_reduce_only_orders_are_checked_again() {
+        let mut checker = BalanceChecker::new();
+        checker.set_balance(1, Balance { cash: 0, position: 5 });
+        checker.set_balance(3, Balance { cash: 0, position: -4 });
+        let checker = Arc::new(Mutex::new(checker));
+        let mut book = OrderBook::new();
+        book.set_position_provider(Some(checker.clone()));
+
+        let mut logger = VectorLogger::new();
+        for s in &["Lim S $101 #5 u1 ro", "Lim S $101 #3 u1 ro", "Lim B $90 #2 u3 ro"] {
+            book.execute_order(s.parse().unwrap(), &mut logger);
+        }
+        // The position shrank since the orders were placed, so they are shrunk before they are matched
+        checker.lock().unwrap().set_balance(1, Balance { cash: 0, position: 2 });
+        logger = VectorLogger::new();
+        book.execute_order("Lim B $101 #4 u2".parse().unwrap(), &mut logger);
+        check_log(logger.as_slice(), &["M #2 $101 o1", "M #0 $101 o2", "F #2 $101 u1 o1", "Q B #2 $101 u2 o4"]);
+        assert!(book.resting_order(2).is_none());
+
+        // Amendments may not enlarge a reduce-only order beyond the position
+        let rejected = AmendError::Rejected(RejectReason::WouldIncreasePosition);
+        assert_eq!(book.amend_order(3, 90, 5, &mut logger), Err(rejected));
+        assert_eq!(book.amend_order(3, 90, 4, &mut logger), Ok(()));
+    }
+}
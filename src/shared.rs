@@ -0,0 +1,126 @@
+//! Thread-safe order book wrapper
+//!
+//! [SharedOrderBook](SharedOrderBook) serializes operations of multiple producer threads on one
+//! [OrderBook](OrderBook) behind a lock. Every operation runs to completion under the write lock,
+//! so the execution log of an order is never interleaved with another one. Queries take the read
+//! lock and may run concurrently.
+
+use crate::OrderBook;
+use crate::decode::Command;
+use crate::depth::DepthSnapshot;
+use crate::log::ExecutionLogger;
+use crate::order::{AmendError, CancelError, CancelledOrder, IncomingOrder, OrderId, Price, Size};
+use std::sync::{RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+/// Order book which can be shared between threads
+#[derive(Default)]
+pub struct SharedOrderBook {
+    book: RwLock<OrderBook>,
+}
+
+impl SharedOrderBook {
+    /// Wraps `book`
+    pub fn new(book: OrderBook) -> Self {
+        SharedOrderBook { book: RwLock::new(book) }
+    }
+
+    /// Executes `order` and returns its identifier
+    pub fn execute_order(&self, order: IncomingOrder, logger: &mut impl ExecutionLogger) -> OrderId {
+        let mut book = self.write();
+        book.execute_order(order, logger);
+        book.last_order_id().expect("order id is assigned on execution")
+    }
+
+    /// Cancels the resting or stop order `order_id`
+    pub fn cancel_order(&self, order_id: OrderId, logger: &mut impl ExecutionLogger) -> Result<CancelledOrder, CancelError> {
+        self.write().cancel_order(order_id, logger)
+    }
+
+    /// Amends price and remaining size of the resting order `order_id`
+    pub fn amend_order(&self, order_id: OrderId, price: Price, size: Size, logger: &mut impl ExecutionLogger) -> Result<(), AmendError> {
+        self.write().amend_order(order_id, price, size, logger)
+    }
+
+    /// Executes `command`
+    pub fn execute_command(&self, command: Command, logger: &mut impl ExecutionLogger) {
+        self.write().execute_command(command, logger)
+    }
+
+    /// Returns aggregated view of at most `levels` best price levels of both sides
+    pub fn depth(&self, levels: usize) -> DepthSnapshot {
+        self.read().depth(levels)
+    }
+
+    /// Calls `f` with the book under the read lock
+    pub fn with_book<R>(&self, f: impl FnOnce(&OrderBook) -> R) -> R {
+        f(&self.read())
+    }
+
+    /// Calls `f` with the book under the write lock
+    ///
+    /// Operations done by `f` are not interleaved with operations of other threads.
+    pub fn with_book_mut<R>(&self, f: impl FnOnce(&mut OrderBook) -> R) -> R {
+        f(&mut self.write())
+    }
+
+    /// Returns the wrapped book
+    pub fn into_inner(self) -> OrderBook {
+        self.book.into_inner().expect("order book lock is poisoned")
+    }
+
+    fn read(&self) -> RwLockReadGuard<'_, OrderBook> {
+        self.book.read().expect("order book lock is poisoned")
+    }
+
+    fn write(&self) -> RwLockWriteGuard<'_, OrderBook> {
+        self.book.write().expect("order book lock is poisoned")
+    }
+}
+
+impl From<OrderBook> for SharedOrderBook {
+    fn from(book: OrderBook) -> Self {
+        Self::new(book)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::log::VectorLogger;
+    use crate::order::{OrderKind, OrderSide};
+    use std::thread;
+
+    #[test]
+    fn concurrent_producers() {
+        let book = SharedOrderBook::default();
+        thread::scope(|scope| {
+            for thread in 0..4 {
+                let book = &book;
+                scope.spawn(move || {
+                    let mut ids = Vec::new();
+                    for i in 0..100 {
+                        let side = if i % 2 == 0 { OrderSide::Buy } else { OrderSide::Sell };
+                        let order = IncomingOrder {
+                            price_limit: 100,
+                            size: 1,
+                            user_id: thread * 1000 + i,
+                            kind: OrderKind::Limit,
+                            side,
+                            expires_at: None,
+                            min_fill_size: None,
+                            reduce_only: false,
+                        };
+                        let mut logger = VectorLogger::new();
+                        ids.push(book.execute_order(order, &mut logger));
+                        assert_eq!(logger.as_slice().len(), 1);
+                    }
+                    assert!(ids.windows(2).all(|w| w[0] < w[1]));
+                });
+            }
+        });
+        let book = book.into_inner();
+        assert_eq!(book.last_order_id(), Some(400));
+        // Buys and sells of equal size at the same price cancel each other out
+        assert!(book.bid().is_empty() && book.ask().is_empty());
+    }
+}
@@ -0,0 +1,251 @@
+//! Order status state machine
+//!
+//! Every order starts as [New](OrderStatus::New) and moves forward only:
+//!
+//! ```text
+//! New ──> PartiallyFilled ──> Filled
+//!  │            │
+//!  │            └──────────> Cancelled | Expired
+//!  ├──> Filled | Cancelled | Expired
+//!  └──> Rejected
+//! ```
+//!
+//! [Filled](OrderStatus::Filled), [Cancelled](OrderStatus::Cancelled), [Rejected](OrderStatus::Rejected)
+//! and [Expired](OrderStatus::Expired) are final.
+
+use crate::log::LogItem;
+use crate::math;
+use crate::order::{OrderId, Price, Size};
+use crate::HashMap;
+use core::fmt;
+
+/// Status of an order
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OrderStatus {
+    /// Order was accepted and nothing was filled yet
+    New,
+    /// Order was filled in part and the remainder is still open
+    PartiallyFilled,
+    /// Order was filled completely
+    Filled,
+    /// Remainder of the order was cancelled
+    Cancelled,
+    /// Order was rejected by validation
+    Rejected,
+    /// Remainder of the order expired
+    Expired,
+}
+
+impl OrderStatus {
+    /// Checks whether the status is final
+    pub fn is_final(self) -> bool {
+        !matches!(self, OrderStatus::New | OrderStatus::PartiallyFilled)
+    }
+
+    /// Checks whether the order can move from this status to `next`
+    pub fn can_become(self, next: OrderStatus) -> bool {
+        match (self, next) {
+            (OrderStatus::New, _) => next != OrderStatus::New,
+            (OrderStatus::PartiallyFilled, next) => next != OrderStatus::New && next != OrderStatus::Rejected,
+            _ => false,
+        }
+    }
+
+    /// Returns status after a fill which leaves `open` size unfilled
+    pub fn after_fill(self, open: u64) -> OrderStatus {
+        if open == 0 {
+            OrderStatus::Filled
+        } else {
+            OrderStatus::PartiallyFilled
+        }
+    }
+
+    /// Returns status name as used in execution reports
+    pub fn as_str(self) -> &'static str {
+        match self {
+            OrderStatus::New => "NEW",
+            OrderStatus::PartiallyFilled => "PARTIALLY_FILLED",
+            OrderStatus::Filled => "FILLED",
+            OrderStatus::Cancelled => "CANCELLED",
+            OrderStatus::Rejected => "REJECTED",
+            OrderStatus::Expired => "EXPIRED",
+        }
+    }
+}
+
+impl fmt::Display for OrderStatus {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Status of a single order together with its fill progress
+#[derive(Debug, Clone, PartialEq)]
+pub struct OrderState {
+    status: OrderStatus,
+    open: u64,
+    filled: u64,
+    notional: i128,
+}
+
+impl OrderState {
+    /// Constructs state of a new order of size `size`
+    pub fn new(size: Size) -> Self {
+        OrderState {
+            status: OrderStatus::New,
+            open: size,
+            filled: 0,
+            notional: 0,
+        }
+    }
+
+    /// Returns current status
+    pub fn status(&self) -> OrderStatus {
+        self.status
+    }
+
+    /// Returns cumulative filled size
+    pub fn cum_qty(&self) -> Size {
+        self.filled
+    }
+
+    /// Returns size which is neither filled nor cancelled
+    pub fn leaves_qty(&self) -> Size {
+        self.open
+    }
+
+    /// Returns total value of the fills
+    pub fn cum_notional(&self) -> i128 {
+        self.notional
+    }
+
+    /// Returns average fill price, or `None` if nothing was filled
+    pub fn avg_price(&self) -> Option<f64> {
+        math::average_price(self.notional, self.filled)
+    }
+
+    /// Applies an item logged for this order
+    pub fn apply(&mut self, item: &LogItem) {
+        match *item {
+            LogItem::Rejected { .. } => self.set(OrderStatus::Rejected),
+            LogItem::Fulfilled { size, price, .. } => self.fill(size, price),
+            LogItem::Cancelled { size, .. } => {
+                self.open = self.open.saturating_sub(size);
+                // Cancelled odd lots leave the rest of the order open
+                if self.open == 0 && !self.status.is_final() {
+                    self.set(OrderStatus::Cancelled);
+                }
+            }
+            LogItem::ForceCancelled { .. } => {
+                self.open = 0;
+                self.set(OrderStatus::Cancelled);
+            }
+            LogItem::Expired { .. } => {
+                self.open = 0;
+                self.set(OrderStatus::Expired);
+            }
+            LogItem::Amended { size, .. } => {
+                self.open = size;
+                if size == 0 {
+                    self.set(OrderStatus::Cancelled);
+                }
+            }
+            LogItem::Enqueued { .. } | LogItem::Parked { .. } | LogItem::Triggered { .. } | LogItem::Refilled { .. }
+            | LogItem::IntegrityError { .. } | LogItem::StateChanged { .. } => {}
+        }
+    }
+
+    fn set(&mut self, status: OrderStatus) {
+        debug_assert!(self.status.can_become(status), "invalid transition {} -> {}", self.status, status);
+        self.status = status;
+    }
+
+    fn fill(&mut self, size: u64, price: Price) {
+        self.open = self.open.saturating_sub(size);
+        self.filled += size;
+        self.notional += math::notional(price, size);
+        // Passive fills of an auction order may be reported after its remainder was cancelled
+        if !self.status.is_final() {
+            let status = self.status.after_fill(self.open);
+            self.set(status);
+        }
+    }
+}
+
+/// Storage of order statuses
+#[derive(Clone, Default)]
+pub(crate) struct StatusStore(HashMap<OrderId, OrderState>);
+
+impl StatusStore {
+    pub fn get(&self, order_id: OrderId) -> Option<&OrderState> {
+        self.0.get(&order_id)
+    }
+
+    /// Records removal of the resting order `order_id`
+    pub fn record_cancel(&mut self, order_id: OrderId) {
+        if let Some(state) = self.0.get_mut(&order_id) {
+            state.apply(&LogItem::ForceCancelled { size: state.open, order_id });
+        }
+    }
+
+    /// Records the items logged for the order `order_id` of size `size`
+    pub fn record(&mut self, order_id: OrderId, size: Size, items: &[LogItem]) {
+        let mut state = OrderState::new(size);
+        self.apply(&mut state, items);
+        self.0.insert(order_id, state);
+    }
+
+    /// Records further items logged for the order `order_id` (amendment, stop trigger, expiry)
+    pub fn record_update(&mut self, order_id: OrderId, items: &[LogItem]) {
+        if let Some(mut state) = self.0.remove(&order_id) {
+            self.apply(&mut state, items);
+            self.0.insert(order_id, state);
+        }
+    }
+
+    fn apply(&mut self, state: &mut OrderState, items: &[LogItem]) {
+        for item in items {
+            state.apply(item);
+            if let LogItem::Fulfilled { size, price, order_id: maker_id, .. } = *item {
+                if let Some(maker) = self.0.get_mut(&maker_id) {
+                    maker.fill(size, price);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::order::OrderSide;
+
+    #[test]
+    fn transitions() {
+        use OrderStatus::*;
+        assert!(New.can_become(PartiallyFilled));
+        assert!(New.can_become(Rejected));
+        assert!(PartiallyFilled.can_become(PartiallyFilled));
+        assert!(PartiallyFilled.can_become(Expired));
+        assert!(!PartiallyFilled.can_become(Rejected));
+        assert!(!PartiallyFilled.can_become(New));
+        for status in &[Filled, Cancelled, Rejected, Expired] {
+            assert!(status.is_final());
+            assert!(!status.can_become(Cancelled));
+        }
+        assert_eq!(New.after_fill(3), PartiallyFilled);
+        assert_eq!(PartiallyFilled.after_fill(0), Filled);
+    }
+
+    #[test]
+    fn cumulative_quantity_and_average_price() {
+        let mut state = OrderState::new(10);
+        assert_eq!(state.avg_price(), None);
+        state.apply(&LogItem::Fulfilled { size: 3, price: 100, user_id: 1, order_id: 1 });
+        state.apply(&LogItem::Fulfilled { size: 1, price: 104, user_id: 2, order_id: 2 });
+        assert_eq!((state.cum_qty(), state.leaves_qty(), state.cum_notional()), (4, 6, 404));
+        assert_eq!(state.avg_price(), Some(101.0));
+        state.apply(&LogItem::Cancelled { size: 6, price: 100, side: OrderSide::Buy, user_id: 3, order_id: 3 });
+        assert_eq!((state.status(), state.cum_qty(), state.leaves_qty()), (OrderStatus::Cancelled, 4, 0));
+    }
+}
@@ -0,0 +1,113 @@
+//! Stop and stop-limit orders
+//!
+//! Stop orders wait in a trigger queue outside the order queues until the last trade price reaches
+//! their trigger price: a buy stop triggers when a trade happens at or above the trigger price,
+//! a sell stop at or below it. A triggered [Stop](OrderKind::Stop) order is executed as a market order,
+//! a triggered [StopLimit](OrderKind::StopLimit) order as a limit order at its price limit.
+//!
+//! The trigger price of a [TrailingStop](OrderKind::TrailingStop) order follows the trades: it is raised
+//! to `offset` below the last trade price for sell orders and lowered to `offset` above it for buy
+//! orders, but never moves back. A triggered trailing stop is executed as a market order.
+
+use crate::order::{IncomingOrder, OrderId, OrderKind, OrderSide, Price};
+use crate::tick::{TickError, TickSize};
+use alloc::vec::Vec;
+
+/// Returns the order a triggered stop order is executed as
+pub fn triggered(order: &IncomingOrder) -> IncomingOrder {
+    let kind = match order.kind {
+        OrderKind::Stop { .. } | OrderKind::TrailingStop { .. } => OrderKind::Market,
+        OrderKind::StopLimit { .. } => OrderKind::Limit,
+        kind => kind,
+    };
+    IncomingOrder { kind, ..order.clone() }
+}
+
+/// Stop orders waiting for their triggers, in the order of arrival
+#[derive(Debug, Clone, Default)]
+pub struct StopQueue(Vec<(OrderId, IncomingOrder)>);
+
+impl StopQueue {
+    pub(crate) fn insert(&mut self, order_id: OrderId, order: IncomingOrder) {
+        self.0.push((order_id, order));
+    }
+
+    pub(crate) fn remove(&mut self, order_id: OrderId) -> Option<IncomingOrder> {
+        let index = self.0.iter().position(|(id, _)| *id == order_id)?;
+        Some(self.0.remove(index).1)
+    }
+
+    /// Removes stop orders which expire by `now`
+    pub(crate) fn remove_expired(&mut self, now: u64) -> Vec<(OrderId, IncomingOrder)> {
+        self.remove_where(|order| order.expires_at.is_some_and(|expires_at| expires_at <= now))
+    }
+
+    /// Removes stop orders matching `predicate`
+    pub(crate) fn remove_where<P>(&mut self, mut predicate: P) -> Vec<(OrderId, IncomingOrder)>
+        where P: FnMut(&IncomingOrder) -> bool
+    {
+        let (removed, waiting) = core::mem::take(&mut self.0).into_iter()
+            .partition(|(_, order)| predicate(order));
+        self.0 = waiting;
+        removed
+    }
+
+    /// Moves the trigger prices of trailing stop orders after a trade at `last_price`
+    pub(crate) fn trail(&mut self, last_price: Price) {
+        for (_, order) in &mut self.0 {
+            if let OrderKind::TrailingStop { ref mut trigger_price, offset } = order.kind {
+                *trigger_price = match order.side {
+                    OrderSide::Buy => (*trigger_price).min(last_price.saturating_add(offset)),
+                    OrderSide::Sell => (*trigger_price).max(last_price.saturating_sub(offset)),
+                };
+            }
+        }
+    }
+
+    /// Removes the earliest stop order triggered by a trade at `last_price`
+    pub(crate) fn take_triggered(&mut self, last_price: Price) -> Option<(OrderId, IncomingOrder)> {
+        let index = self.0.iter().position(|(_, order)| match (order.kind.trigger_price(), order.side) {
+            (Some(trigger), OrderSide::Buy) => last_price >= trigger,
+            (Some(trigger), OrderSide::Sell) => last_price <= trigger,
+            (None, _) => false,
+        })?;
+        Some(self.0.remove(index))
+    }
+
+    /// Converts price limits and trigger prices from tick size `from` to tick size `to`
+    pub(crate) fn rescale(&mut self, from: &TickSize, to: &TickSize) -> Result<(), TickError> {
+        for (_, order) in &mut self.0 {
+            order.price_limit = from.rescale(order.price_limit, to)?;
+            order.kind = match order.kind {
+                OrderKind::Stop { trigger_price } => OrderKind::Stop { trigger_price: from.rescale(trigger_price, to)? },
+                OrderKind::StopLimit { trigger_price } => OrderKind::StopLimit { trigger_price: from.rescale(trigger_price, to)? },
+                OrderKind::TrailingStop { trigger_price, offset } => OrderKind::TrailingStop {
+                    trigger_price: from.rescale(trigger_price, to)?,
+                    offset: from.rescale(offset, to)?,
+                },
+                kind => kind,
+            };
+        }
+        Ok(())
+    }
+
+    /// Returns the stop order `order_id`
+    pub fn get(&self, order_id: OrderId) -> Option<&IncomingOrder> {
+        self.0.iter().find(|(id, _)| *id == order_id).map(|(_, order)| order)
+    }
+
+    /// Returns number of waiting stop orders
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    /// Checks whether there are no waiting stop orders
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Iterates over waiting stop orders in the order of arrival
+    pub fn iter(&self) -> impl Iterator<Item=(OrderId, &IncomingOrder)> {
+        self.0.iter().map(|(order_id, order)| (*order_id, order))
+    }
+}
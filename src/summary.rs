@@ -0,0 +1,134 @@
+//! Summaries of order executions
+
+use crate::config::RejectReason;
+use crate::log::{ExecutionLogger, LogItem, SequencedLogItem, Trade};
+use crate::order::{OrderId, Size};
+
+/// Result of executing one order
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExecutionSummary {
+    /// Identifier assigned to the order
+    pub order_id: OrderId,
+    /// Total size of the fills
+    pub filled_size: Size,
+    /// Volume-weighted average price of the fills, `None` if nothing was filled
+    pub average_price: Option<f64>,
+    /// Size left in the book, either resting or parked as a stop order
+    pub resting_size: Size,
+    /// Size which was neither filled nor left in the book
+    pub cancelled_size: Size,
+}
+
+impl ExecutionSummary {
+    /// Summarizes execution results of the order `order_id`, or returns the reason of its rejection
+    ///
+    /// Items after a [Triggered](LogItem::Triggered) item of another order belong to stop orders
+    /// triggered by this order and are ignored.
+    pub fn from_items(order_id: OrderId, items: &[LogItem]) -> Result<Self, RejectReason> {
+        let mut builder = SummaryBuilder::new(order_id);
+        for item in items {
+            builder.add(item);
+        }
+        builder.finish()
+    }
+
+    /// Returns the size which was not filled
+    pub fn remaining_size(&self) -> Size {
+        self.resting_size + self.cancelled_size
+    }
+}
+
+struct SummaryBuilder {
+    summary: ExecutionSummary,
+    notional: i128,
+    rejected: Option<RejectReason>,
+    complete: bool,
+}
+
+impl SummaryBuilder {
+    fn new(order_id: OrderId) -> Self {
+        SummaryBuilder {
+            summary: ExecutionSummary {
+                order_id,
+                filled_size: 0,
+                average_price: None,
+                resting_size: 0,
+                cancelled_size: 0,
+            },
+            notional: 0,
+            rejected: None,
+            complete: false,
+        }
+    }
+
+    fn add(&mut self, item: &LogItem) {
+        if self.complete {
+            return;
+        }
+        let summary = &mut self.summary;
+        match *item {
+            LogItem::Fulfilled { size, price, .. } => {
+                summary.filled_size += size;
+                self.notional += i128::from(price) * i128::from(size);
+            }
+            LogItem::Enqueued { size, .. } | LogItem::Parked { size } => summary.resting_size += size,
+            LogItem::Cancelled { size, .. } => summary.cancelled_size += size,
+            LogItem::Rejected { reason, .. } => self.rejected = Some(reason),
+            // A stop order triggered right after parking is executed as part of its own submission
+            LogItem::Triggered { order_id } if order_id == summary.order_id => summary.resting_size = 0,
+            LogItem::Triggered { .. } => self.complete = true,
+            _ => {}
+        }
+    }
+
+    fn finish(mut self) -> Result<ExecutionSummary, RejectReason> {
+        if let Some(reason) = self.rejected {
+            return Err(reason);
+        }
+        if self.summary.filled_size > 0 {
+            self.summary.average_price = Some(self.notional as f64 / self.summary.filled_size as f64);
+        }
+        Ok(self.summary)
+    }
+}
+
+/// Logger wrapper which summarizes execution of one order
+pub(crate) struct SummaryLogger<'a, L> {
+    inner: &'a mut L,
+    builder: SummaryBuilder,
+}
+
+impl<'a, L: ExecutionLogger> SummaryLogger<'a, L> {
+    pub fn new(inner: &'a mut L, order_id: OrderId) -> Self {
+        Self { inner, builder: SummaryBuilder::new(order_id) }
+    }
+
+    pub fn summary(self) -> Result<ExecutionSummary, RejectReason> {
+        self.builder.finish()
+    }
+}
+
+impl<'a, L: ExecutionLogger> ExecutionLogger for SummaryLogger<'a, L> {
+    fn log(&mut self, item: LogItem) {
+        self.builder.add(&item);
+        self.inner.log(item);
+    }
+
+    fn log_sequenced(&mut self, item: SequencedLogItem) {
+        self.builder.add(&item.item);
+        self.inner.log_sequenced(item);
+    }
+
+    fn trade(&mut self, trade: Trade) {
+        self.inner.trade(trade);
+    }
+
+    fn cancel(&mut self) {
+        self.builder = SummaryBuilder::new(self.builder.summary.order_id);
+        self.inner.cancel();
+    }
+
+    fn finish(&mut self) {
+        self.inner.finish();
+    }
+}
@@ -0,0 +1,192 @@
+//! Wash-trade detection
+//!
+//! Self-trade prevention guarantees that a user never trades with itself, but wash trading can still
+//! happen between accounts controlled by the same party (configured as groups) or through quick
+//! round trips where two parties trade the same size back and forth at the same price.
+
+use crate::log::LogItem;
+use crate::order::{IncomingOrder, OrderSide, Price, Size, UserId};
+use crate::HashMap;
+use alloc::vec::Vec;
+
+/// Single execution between two users
+#[allow(missing_docs)]
+#[derive(Debug, Clone, PartialEq)]
+pub struct Execution {
+    /// Sequence number of the execution, used as a proxy for time
+    pub seq: u64,
+    pub buyer: UserId,
+    pub seller: UserId,
+    pub price: Price,
+    pub size: Size,
+}
+
+/// Appends executions of the incoming `order` found in its execution log `items` to `executions`
+///
+/// Executions are numbered sequentially after the last element of `executions`.
+pub fn collect_executions(order: &IncomingOrder, items: &[LogItem], executions: &mut Vec<Execution>) {
+    let mut seq = executions.last().map_or(0, |e| e.seq);
+    for item in items {
+        if let LogItem::Fulfilled { size, price, user_id, .. } = *item {
+            seq += 1;
+            let (buyer, seller) = match order.side {
+                OrderSide::Buy => (order.user_id, user_id),
+                OrderSide::Sell => (user_id, order.user_id),
+            };
+            executions.push(Execution { seq, buyer, seller, price, size });
+        }
+    }
+}
+
+/// Kind of suspicious pattern
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum WashPattern {
+    /// Buyer and seller belong to the same group
+    SameGroup,
+    /// Two parties traded with each other in both directions within the window
+    RoundTrip,
+}
+
+/// Suspicious execution or pair of executions
+#[derive(Debug, Clone, PartialEq)]
+pub struct WashTradeAlert {
+    /// Detected pattern
+    pub pattern: WashPattern,
+    /// Sequence number of the (first) execution
+    pub seq: u64,
+    /// Sequence number of the reverse execution for [RoundTrip](WashPattern::RoundTrip)
+    pub reverse_seq: Option<u64>,
+    /// Buyer and seller of the (first) execution
+    pub users: (UserId, UserId),
+    /// Price of the (first) execution
+    pub price: Price,
+    /// Size of the (first) execution
+    pub size: Size,
+}
+
+/// Wash-trade detector configuration
+#[derive(Debug, Clone, Default)]
+pub struct WashTradeDetector {
+    groups: HashMap<UserId, u64>,
+    window: u64,
+    price_tolerance: Price,
+}
+
+impl WashTradeDetector {
+    /// Constructs detector looking for round trips within `window` executions
+    pub fn new(window: u64) -> Self {
+        WashTradeDetector {
+            window,
+            ..Self::default()
+        }
+    }
+
+    /// Assigns `user_id` to the group `group_id` (e.g. accounts of the same beneficial owner)
+    pub fn set_group(&mut self, user_id: UserId, group_id: u64) {
+        self.groups.insert(user_id, group_id);
+    }
+
+    /// Sets maximal price difference between executions of a round trip
+    pub fn set_price_tolerance(&mut self, tolerance: Price) {
+        self.price_tolerance = tolerance;
+    }
+
+    fn party(&self, user_id: UserId) -> (bool, u64) {
+        match self.groups.get(&user_id) {
+            Some(&group) => (true, group),
+            None => (false, user_id),
+        }
+    }
+
+    /// Scans `executions` (ordered by sequence number) and returns alerts in the order of detection
+    pub fn scan(&self, executions: &[Execution]) -> Vec<WashTradeAlert> {
+        let mut alerts = Vec::new();
+        for (index, execution) in executions.iter().enumerate() {
+            let buyer = self.party(execution.buyer);
+            let seller = self.party(execution.seller);
+            let alert = WashTradeAlert {
+                pattern: WashPattern::SameGroup,
+                seq: execution.seq,
+                reverse_seq: None,
+                users: (execution.buyer, execution.seller),
+                price: execution.price,
+                size: execution.size,
+            };
+            if buyer == seller {
+                alerts.push(alert);
+                continue;
+            }
+
+            let earlier = executions[..index].iter().rev()
+                .take_while(|e| execution.seq - e.seq <= self.window);
+            for previous in earlier {
+                let reverse = self.party(previous.buyer) == seller && self.party(previous.seller) == buyer;
+                let price_diff = (i128::from(previous.price) - i128::from(execution.price)).abs();
+                if reverse && previous.size == execution.size && price_diff <= i128::from(self.price_tolerance) {
+                    alerts.push(WashTradeAlert {
+                        pattern: WashPattern::RoundTrip,
+                        seq: previous.seq,
+                        reverse_seq: Some(execution.seq),
+                        users: (previous.buyer, previous.seller),
+                        price: previous.price,
+                        size: previous.size,
+                    });
+                    break;
+                }
+            }
+        }
+        alerts
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::OrderBook;
+    use crate::log::VectorLogger;
+
+    fn executions(orders: &[&str]) -> Vec<Execution> {
+        let mut book = OrderBook::new();
+        let mut executions = Vec::new();
+        for s in orders {
+            let order: IncomingOrder = s.parse().unwrap();
+            let mut logger = VectorLogger::new();
+            book.execute_order(order.clone(), &mut logger);
+            collect_executions(&order, logger.as_slice(), &mut executions);
+        }
+        executions
+    }
+
+    #[test]
+    fn detects_round_trips_and_groups() {
+        let executions = executions(&[
+            "Lim S $100 #5 u1",
+            "Lim B $100 #5 u2",
+            "Lim S $101 #5 u2",
+            "Lim B $101 #5 u1",
+            "Lim S $101 #3 u3",
+            "Lim B $101 #3 u4",
+        ]);
+        assert_eq!(executions.len(), 3);
+        assert_eq!(executions[1], Execution { seq: 2, buyer: 1, seller: 2, price: 101, size: 5 });
+
+        let mut detector = WashTradeDetector::new(5);
+        detector.set_group(3, 34);
+        detector.set_group(4, 34);
+        let alerts = detector.scan(&executions);
+        assert_eq!(alerts.len(), 1);
+        assert_eq!(alerts[0].pattern, WashPattern::SameGroup);
+
+        detector.set_price_tolerance(1);
+        let alerts = detector.scan(&executions);
+        assert_eq!(alerts.len(), 2);
+        assert_eq!(alerts[0].pattern, WashPattern::RoundTrip);
+        assert_eq!((alerts[0].seq, alerts[0].reverse_seq), (1, Some(2)));
+        assert_eq!(alerts[1].pattern, WashPattern::SameGroup);
+        assert_eq!(alerts[1].users, (4, 3));
+
+        let mut detector = WashTradeDetector::new(0);
+        detector.set_price_tolerance(1);
+        assert_eq!(detector.scan(&executions), []);
+    }
+}
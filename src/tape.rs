@@ -0,0 +1,84 @@
+//! Bounded history of trades
+
+use crate::log::Trade;
+use alloc::collections::VecDeque;
+
+/// Most recent trades of a book, oldest first
+///
+/// Enabled with [set_trade_tape](crate::OrderBook::set_trade_tape); fills of both the continuous
+/// matching and the auctions are recorded.
+#[derive(Debug, Clone)]
+pub struct TradeTape {
+    capacity: usize,
+    trades: VecDeque<Trade>,
+}
+
+impl TradeTape {
+    /// Constructs tape keeping at most `capacity` trades
+    pub fn new(capacity: usize) -> Self {
+        TradeTape {
+            capacity: capacity.max(1),
+            trades: VecDeque::new(),
+        }
+    }
+
+    /// Appends `trade`, dropping the oldest trade when the tape is full
+    pub(crate) fn push(&mut self, trade: Trade) {
+        if self.trades.len() == self.capacity {
+            self.trades.pop_front();
+        }
+        self.trades.push_back(trade);
+    }
+
+    /// Returns the last trade
+    pub fn last(&self) -> Option<&Trade> {
+        self.trades.back()
+    }
+
+    /// Returns the retained trades with sequence numbers greater than `sequence`
+    pub fn since(&self, sequence: u64) -> impl Iterator<Item=&Trade> {
+        let start = self.trades.partition_point(|trade| trade.sequence <= sequence);
+        self.trades.range(start..)
+    }
+
+    /// Returns the retained trades
+    pub fn iter(&self) -> impl Iterator<Item=&Trade> {
+        self.trades.iter()
+    }
+
+    /// Returns the number of retained trades
+    pub fn len(&self) -> usize {
+        self.trades.len()
+    }
+
+    /// Checks whether no trades are retained
+    pub fn is_empty(&self) -> bool {
+        self.trades.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::OrderBook;
+    use crate::log::DummyLogger;
+
+    #[test]
+    fn tape_keeps_last_trades() {
+        let mut book = OrderBook::new();
+        assert!(book.last_trade().is_none());
+        book.set_trade_tape(Some(2));
+        for s in &["Lim S $100 #1 u1", "Lim S $101 #1 u1", "Lim S $102 #1 u1", "Lim B $102 #3 u2"] {
+            book.execute_order(s.parse().unwrap(), &mut DummyLogger);
+        }
+        let tape = book.trade_tape().unwrap();
+        assert_eq!(tape.iter().map(|trade| trade.sequence).collect::<Vec<_>>(), [2, 3]);
+        let last = book.last_trade().unwrap();
+        assert_eq!((last.sequence, last.price, last.maker_order_id, last.taker_order_id), (3, 102, 3, 4));
+        assert_eq!(book.trades_since(2).map(|trade| trade.price).collect::<Vec<_>>(), [102]);
+        assert_eq!(book.trades_since(0).count(), 2);
+        assert_eq!(book.trades_since(3).count(), 0);
+
+        book.set_trade_tape(None);
+        assert!(book.last_trade().is_none());
+    }
+}
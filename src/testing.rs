@@ -0,0 +1,319 @@
+//! Invariant checks for testing code built on the book
+//!
+//! Enabled with the `testing` feature. The checks are the ones the crate runs in its own model
+//! checking: [check_book](check_book) verifies the resting orders and
+//! [check_execution](check_execution) verifies the log of one order. [OrderGenerator](OrderGenerator)
+//! produces random orders from a seed, so failing sequences can be reproduced.
+
+use crate::OrderBook;
+use crate::log::{LogItem, VectorLogger};
+use crate::order::{IncomingOrder, Order, OrderId, OrderKind, OrderSide, Price, Size, UserId};
+use crate::HashMap;
+use std::fmt;
+use std::ops::RangeInclusive;
+
+/// Description of a violated invariant
+#[derive(Debug, Clone, PartialEq)]
+pub struct InvariantViolation(pub String);
+
+impl fmt::Display for InvariantViolation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for InvariantViolation {}
+
+fn violation<T>(message: impl Into<String>) -> Result<T, InvariantViolation> {
+    Err(InvariantViolation(message.into()))
+}
+
+/// Returns total size of the resting orders, including hidden reserves
+pub fn resting_size(book: &OrderBook) -> Size {
+    book.bid.total_size() + book.ask.total_size()
+}
+
+/// Checks the resting orders of `book`
+///
+/// Bids must be sorted by descending and asks by ascending price, the book must not be crossed
+/// (except for orders of the same user, which self-trade prevention keeps apart) and no empty order
+/// may rest. Cached best prices and the order index must match the queues.
+pub fn check_book(book: &OrderBook) -> Result<(), InvariantViolation> {
+    let bids: Vec<_> = book.bid.into_iter().collect();
+    let asks: Vec<_> = book.ask.into_iter().collect();
+    if bids.windows(2).any(|w| w[0].price_limit < w[1].price_limit) {
+        return violation("bids are not sorted by price");
+    }
+    if asks.windows(2).any(|w| w[0].price_limit > w[1].price_limit) {
+        return violation("asks are not sorted by price");
+    }
+    if book.bid.best_price() != bids.first().map(|o| o.price_limit) || book.ask.best_price() != asks.first().map(|o| o.price_limit) {
+        return violation("cached best price is stale");
+    }
+    fn indexed<D>(index: &HashMap<OrderId, Price>, orders: &[&Order<D>]) -> bool {
+        index.len() == orders.len() && orders.iter().all(|o| index.get(&o.order_id) == Some(&o.price_limit))
+    }
+    fn user_indexed<D>(users: &HashMap<UserId, Vec<OrderId>>, orders: &[&Order<D>]) -> bool {
+        users.values().map(Vec::len).sum::<usize>() == orders.len()
+            && orders.iter().all(|o| users.get(&o.user_id).is_some_and(|ids| ids.contains(&o.order_id)))
+    }
+    if !indexed(&book.bid.index, &bids) || !indexed(&book.ask.index, &asks)
+        || !user_indexed(&book.bid.users, &bids) || !user_indexed(&book.ask.users, &asks) {
+        return violation("order index is out of sync");
+    }
+    if bids.iter().any(|o| o.size == 0) || asks.iter().any(|o| o.size == 0) {
+        return violation("empty order is resting in the book");
+    }
+    for bid in &bids {
+        for ask in &asks {
+            if bid.price_limit >= ask.price_limit && bid.user_id != ask.user_id {
+                return violation(format!("book is crossed: {} vs {}", bid.to_incoming(), ask.to_incoming()));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Checks the execution `log` of the new `order`
+///
+/// `before` and `after` are the [resting sizes](resting_size) of the book around the execution.
+/// The submitted size must be conserved between fills, the enqueued, parked and cancelled parts, the
+/// resting size must change accordingly and fills must respect the price limit and self-trade
+/// prevention. The log must contain the items of `order` alone, so logs of triggered stop orders
+/// are not supported. Limit orders must not be cancelled, which holds for books without a lot size.
+pub fn check_execution(order: &IncomingOrder, log: &[LogItem], before: Size, after: Size) -> Result<(), InvariantViolation> {
+    let mut filled = 0;
+    let mut enqueued = 0;
+    let mut parked = 0;
+    let mut cancelled = 0;
+    let mut rejected = 0;
+    for item in log {
+        match *item {
+            LogItem::Fulfilled { size, price, user_id, .. } => {
+                if user_id == order.user_id {
+                    return violation("order matched against its own user");
+                }
+                let crosses = match order.side {
+                    OrderSide::Buy => price <= order.price_limit,
+                    OrderSide::Sell => price >= order.price_limit,
+                };
+                if !crosses && order.kind != OrderKind::Market {
+                    return violation(format!("fill at ${} violates the price limit", price));
+                }
+                filled += size;
+            }
+            LogItem::Enqueued { size, .. } => enqueued += size,
+            LogItem::Parked { size } => parked += size,
+            LogItem::Cancelled { size, .. } => cancelled += size,
+            LogItem::Rejected { size, .. } => rejected += size,
+            LogItem::ForceCancelled { .. } => return violation("unexpected force cancellation"),
+            LogItem::Refilled { size, .. } => {
+                if size == 0 {
+                    return violation("iceberg order was refilled with nothing");
+                }
+            }
+            LogItem::Amended { .. } | LogItem::Triggered { .. } | LogItem::Expired { .. } | LogItem::StateChanged { .. } => {
+                return violation(format!("unexpected {}", item));
+            }
+            LogItem::IntegrityError { .. } => return violation(format!("integrity error: {}", item)),
+        }
+    }
+    if filled + enqueued + parked + cancelled + rejected != order.size {
+        return violation(format!("size is not conserved: F {} + Q {} + P {} + C {} + R {}", filled, enqueued, parked, cancelled, rejected));
+    }
+    if before + enqueued != after + filled {
+        return violation(format!("resting size changed from {} to {} with F {} and Q {}", before, after, filled, enqueued));
+    }
+    match order.kind {
+        OrderKind::Limit | OrderKind::Iceberg { .. } if cancelled != 0 => violation("limit order was cancelled"),
+        OrderKind::FillOrKill if filled != 0 && filled != order.size => violation("fill-or-kill order was partially filled"),
+        OrderKind::PostOnly if filled != 0 => violation("post-only order was matched"),
+        OrderKind::FillOrKill | OrderKind::ImmediateOrCancel | OrderKind::Market if enqueued != 0 => violation("non-limit order was enqueued"),
+        _ => Ok(()),
+    }
+}
+
+/// Executes `order` on `book` and checks the log and the book, returns the log
+pub fn execute_checked(book: &mut OrderBook, order: IncomingOrder) -> Result<Vec<LogItem>, InvariantViolation> {
+    let mut logger = VectorLogger::new();
+    let before = resting_size(book);
+    book.execute_order(order.clone(), &mut logger);
+    check_execution(&order, logger.as_slice(), before, resting_size(book))?;
+    check_book(book)?;
+    Ok(logger.as_slice().to_vec())
+}
+
+/// Source of random orders
+///
+/// Orders are drawn uniformly from the configured ranges by a xorshift generator, so the same seed
+/// always produces the same orders.
+#[derive(Debug, Clone)]
+pub struct OrderGenerator {
+    state: u64,
+    /// Users placing the orders
+    pub users: RangeInclusive<UserId>,
+    /// Price limits of the orders
+    pub prices: RangeInclusive<Price>,
+    /// Sizes of the orders
+    pub sizes: RangeInclusive<Size>,
+    /// Kinds of the orders, picked with equal probability
+    pub kinds: Vec<OrderKind>,
+}
+
+impl OrderGenerator {
+    /// Constructs generator of limit, fill-or-kill, immediate-or-cancel and market orders of three
+    /// users around the price 100
+    pub fn new(seed: u64) -> Self {
+        OrderGenerator {
+            // Zero is a fixed point of xorshift
+            state: (seed ^ 0x9E37_79B9_7F4A_7C15).max(1),
+            users: 1..=3,
+            prices: 95..=105,
+            sizes: 1..=10,
+            kinds: vec![OrderKind::Limit, OrderKind::FillOrKill, OrderKind::ImmediateOrCancel, OrderKind::Market],
+        }
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.state ^= self.state << 13;
+        self.state ^= self.state >> 7;
+        self.state ^= self.state << 17;
+        self.state
+    }
+
+    fn below(&mut self, n: u64) -> u64 {
+        if n == 0 { self.next_u64() } else { self.next_u64() % n }
+    }
+
+    /// Returns the next order
+    pub fn order(&mut self) -> IncomingOrder {
+        let (users, prices, sizes) = (self.users.clone(), self.prices.clone(), self.sizes.clone());
+        let user_id = users.start() + self.below((users.end() - users.start()).wrapping_add(1));
+        let span = prices.end().wrapping_sub(*prices.start()) as u64;
+        let price_limit = prices.start().wrapping_add(self.below(span.wrapping_add(1)) as i64);
+        let size = sizes.start() + self.below((sizes.end() - sizes.start()).wrapping_add(1));
+        let index = self.below(self.kinds.len() as u64) as usize;
+        let kind = self.kinds[index];
+        let side = if self.next_u64() & 1 == 0 { OrderSide::Buy } else { OrderSide::Sell };
+        IncomingOrder { price_limit, size, user_id, kind, side, expires_at: None, min_fill_size: None, reduce_only: false }
+    }
+}
+
+impl Iterator for OrderGenerator {
+    type Item = IncomingOrder;
+
+    fn next(&mut self) -> Option<IncomingOrder> {
+        Some(self.order())
+    }
+}
+
+/// Executes `count` random orders of `generator` on an empty book, checking every step
+///
+/// Returns the executed orders up to and including the one which violated an invariant.
+pub fn fuzz(generator: &mut OrderGenerator, count: usize) -> Result<OrderBook, (Vec<IncomingOrder>, InvariantViolation)> {
+    let mut book = OrderBook::new();
+    book.set_audit(true);
+    let mut executed = Vec::new();
+    for order in generator.take(count) {
+        executed.push(order.clone());
+        if let Err(e) = execute_checked(&mut book, order) {
+            return Err((executed, e));
+        }
+    }
+    Ok(book)
+}
+
+/// Length of one command of [fuzz_input](fuzz_input)
+pub const FUZZ_COMMAND_LEN: usize = 19;
+
+/// Executes commands decoded from arbitrary bytes on an empty book, checking the book after each one
+///
+/// Meant as the body of a fuzz target. Every [FUZZ_COMMAND_LEN](FUZZ_COMMAND_LEN) bytes are one
+/// command: a byte selecting the order kind or a cancellation, a byte with the user and side, a
+/// divisor of the iceberg display size and the price and size as little-endian integers. Orders are
+/// executed with [execute_order_checked](OrderBook::execute_order_checked), so any panic is a bug.
+/// Incomplete trailing bytes are ignored.
+pub fn fuzz_input(data: &[u8]) -> Result<OrderBook, InvariantViolation> {
+    let mut book = OrderBook::new();
+    for command in data.chunks_exact(FUZZ_COMMAND_LEN) {
+        let mut price = [0; 8];
+        price.copy_from_slice(&command[3..11]);
+        let price = i64::from_le_bytes(price);
+        let mut size = [0; 8];
+        size.copy_from_slice(&command[11..19]);
+        let size = u64::from_le_bytes(size);
+        // Refills of iceberg orders are bounded by the divisor
+        let display_size = (size / (u64::from(command[2]) + 1)).max(1);
+        let kind = match command[0] % 9 {
+            0 => OrderKind::Limit,
+            1 => OrderKind::FillOrKill,
+            2 => OrderKind::ImmediateOrCancel,
+            3 => OrderKind::Market,
+            4 => OrderKind::Stop { trigger_price: price },
+            5 => OrderKind::StopLimit { trigger_price: price },
+            6 => OrderKind::Iceberg { display_size },
+            7 => OrderKind::PostOnly,
+            _ => {
+                let _ = book.cancel_order(size, &mut VectorLogger::new());
+                check_book(&book)?;
+                continue;
+            }
+        };
+        let side = if command[1] & 1 == 0 { OrderSide::Buy } else { OrderSide::Sell };
+        let order = IncomingOrder { price_limit: price, size, user_id: UserId::from(command[1] >> 1), kind, side, expires_at: None, min_fill_size: None, reduce_only: false };
+        let _ = book.execute_order_checked(order, &mut VectorLogger::new());
+        check_book(&book)?;
+    }
+    Ok(book)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn generated_orders_keep_invariants() {
+        let mut generator = OrderGenerator::new(7);
+        generator.kinds.push(OrderKind::Iceberg { display_size: 2 });
+        generator.kinds.push(OrderKind::PostOnly);
+        let orders: Vec<_> = OrderGenerator::new(7).take(3).collect();
+        assert_eq!(orders, OrderGenerator::new(7).take(3).collect::<Vec<_>>());
+        assert!(orders.iter().all(|o| (95..=105).contains(&o.price_limit) && (1..=10).contains(&o.size)));
+
+        if let Err((orders, e)) = fuzz(&mut generator, 2000) {
+            let orders: Vec<_> = orders.iter().map(|o| o.to_string()).collect();
+            panic!("invariant violated after {:?}: {}", orders, e);
+        }
+    }
+
+    #[test]
+    fn violations_are_reported() {
+        let order: IncomingOrder = "IoC B $100 #5 u1".parse().unwrap();
+        let log = [LogItem::Fulfilled { size: 2, price: 101, user_id: 2, order_id: 1 }];
+        assert_eq!(check_execution(&order, &log, 2, 0), violation("fill at $101 violates the price limit"));
+        let log = [LogItem::Fulfilled { size: 2, price: 100, user_id: 2, order_id: 1 }];
+        assert_eq!(check_execution(&order, &log, 2, 0), violation("size is not conserved: F 2 + Q 0 + P 0 + C 0 + R 0"));
+    }
+
+    #[test]
+    fn fuzz_input_of_extreme_orders() {
+        let command = |kind: u8, user_side: u8, price: i64, size: u64| {
+            let mut bytes = vec![kind, user_side, 0];
+            bytes.extend_from_slice(&price.to_le_bytes());
+            bytes.extend_from_slice(&size.to_le_bytes());
+            bytes
+        };
+        let data = [
+            command(7, 3, i64::MAX - 1, u64::MAX),
+            command(4, 4, i64::MIN + 1, 1 << 63),
+            command(0, 3, 100, u64::MAX),
+            command(3, 2, i64::MIN + 1, 2),
+            command(8, 0, 0, 1),
+            command(6, 5, 100, u64::MAX - 1),
+        ].concat();
+        let book = fuzz_input(&data).unwrap();
+        assert_eq!(resting_size(&book), u64::MAX - 1);
+        let _ = (book.stats(5), book.checksum());
+        assert!(fuzz_input(&data[..FUZZ_COMMAND_LEN - 1]).unwrap().ask().is_empty());
+    }
+}
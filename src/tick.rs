@@ -0,0 +1,250 @@
+//! Conversions between integer tick prices and decimal display prices
+//!
+//! Prices are stored as integer numbers of ticks. [TickSize](TickSize) describes the decimal value
+//! of one tick and converts prices at the parsing and formatting boundary. Fractional sizes are
+//! handled the same way, with a [TickSize](TickSize) describing one size unit
+//! (see [size_unit](crate::config::BookConfig::size_unit)).
+
+use crate::order::Price;
+use core::fmt;
+use core::str::FromStr;
+use alloc::string::{String, ToString};
+
+const MAX_EXPONENT: u32 = 18;
+
+/// Price conversion error
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TickError {
+    /// String is not a valid decimal number
+    InvalidFormat,
+    /// Tick size is zero or has too many fractional digits
+    InvalidTickSize,
+    /// Price is not a multiple of the tick size
+    NotMultiple,
+    /// Price does not fit into the [Price](Price) type
+    Overflow,
+}
+
+impl fmt::Display for TickError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let s = match self {
+            TickError::InvalidFormat => "invalid decimal number",
+            TickError::InvalidTickSize => "invalid tick size",
+            TickError::NotMultiple => "price is not a multiple of the tick size",
+            TickError::Overflow => "price is out of range",
+        };
+        f.write_str(s)
+    }
+}
+
+/// Decimal value of one price tick: `mantissa * 10^-exponent`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TickSize {
+    mantissa: u64,
+    exponent: u32,
+}
+
+impl TickSize {
+    /// Tick size of one whole unit, which makes ticks and display prices equal
+    pub const ONE: TickSize = TickSize { mantissa: 1, exponent: 0 };
+
+    /// Constructs tick size equal to `mantissa * 10^-exponent`
+    pub fn new(mantissa: u64, exponent: u32) -> Result<Self, TickError> {
+        if mantissa == 0 || exponent > MAX_EXPONENT {
+            return Err(TickError::InvalidTickSize);
+        }
+        Ok(TickSize { mantissa, exponent })
+    }
+
+    /// Converts decimal display price to the number of ticks
+    pub fn to_ticks(&self, price: &str) -> Result<Price, TickError> {
+        let ticks = self.count_ticks(price)?;
+        if ticks < i128::from(Price::MIN) || ticks > i128::from(Price::MAX) {
+            return Err(TickError::Overflow);
+        }
+        Ok(ticks as Price)
+    }
+
+    /// Converts decimal display size to the number of size units
+    pub fn to_units(&self, size: &str) -> Result<u64, TickError> {
+        if size.starts_with('-') {
+            return Err(TickError::InvalidFormat);
+        }
+        let units = self.count_ticks(size)?;
+        if units > i128::from(u64::MAX) {
+            return Err(TickError::Overflow);
+        }
+        Ok(units as u64)
+    }
+
+    /// Returns how many times the decimal number `s` contains this tick size
+    fn count_ticks(&self, s: &str) -> Result<i128, TickError> {
+        let (mantissa, exponent) = parse_decimal(s)?;
+        let common = core::cmp::max(exponent, self.exponent);
+        let value = mantissa.checked_mul(pow10(common - exponent)).ok_or(TickError::Overflow)?;
+        let tick = i128::from(self.mantissa).checked_mul(pow10(common - self.exponent)).ok_or(TickError::Overflow)?;
+        if value % tick != 0 {
+            return Err(TickError::NotMultiple);
+        }
+        Ok(value / tick)
+    }
+
+    /// Converts `ticks` of this tick size to the number of ticks of `target` with the same decimal value
+    pub fn rescale(&self, ticks: Price, target: &TickSize) -> Result<Price, TickError> {
+        let common = core::cmp::max(self.exponent, target.exponent);
+        let value = i128::from(ticks)
+            .checked_mul(i128::from(self.mantissa))
+            .and_then(|value| value.checked_mul(pow10(common - self.exponent)))
+            .ok_or(TickError::Overflow)?;
+        let tick = i128::from(target.mantissa).checked_mul(pow10(common - target.exponent)).ok_or(TickError::Overflow)?;
+        if value % tick != 0 {
+            return Err(TickError::NotMultiple);
+        }
+        let ticks = value / tick;
+        if ticks < i128::from(Price::MIN) || ticks > i128::from(Price::MAX) {
+            return Err(TickError::Overflow);
+        }
+        Ok(ticks as Price)
+    }
+
+    /// Converts the number of ticks to decimal display price
+    pub fn to_decimal(&self, ticks: Price) -> String {
+        format_decimal(i128::from(ticks) * i128::from(self.mantissa), self.exponent)
+    }
+
+    /// Converts the number of size units to decimal display size
+    pub fn units_to_decimal(&self, units: u64) -> String {
+        format_unsigned(u128::from(units) * u128::from(self.mantissa), self.exponent)
+    }
+}
+
+impl Default for TickSize {
+    fn default() -> Self {
+        TickSize::ONE
+    }
+}
+
+impl fmt::Display for TickSize {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&format_decimal(i128::from(self.mantissa), self.exponent))
+    }
+}
+
+impl FromStr for TickSize {
+    type Err = TickError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (mantissa, exponent) = parse_decimal(s)?;
+        if mantissa <= 0 || mantissa > i128::from(u64::MAX) {
+            return Err(TickError::InvalidTickSize);
+        }
+        TickSize::new(mantissa as u64, exponent)
+    }
+}
+
+fn pow10(exponent: u32) -> i128 {
+    10i128.pow(exponent)
+}
+
+/// Parses `[-]digits[.digits]` into mantissa and number of fractional digits
+fn parse_decimal(s: &str) -> Result<(i128, u32), TickError> {
+    let (negative, digits) = if let Some(rest) = s.strip_prefix('-') {
+        (true, rest)
+    } else {
+        (false, s)
+    };
+    let (int_part, frac_part) = match digits.find('.') {
+        Some(index) => (&digits[..index], &digits[index + 1..]),
+        None => (digits, ""),
+    };
+    if int_part.is_empty() || (digits.contains('.') && frac_part.is_empty()) {
+        return Err(TickError::InvalidFormat);
+    }
+    if !int_part.bytes().chain(frac_part.bytes()).all(|b| b.is_ascii_digit()) {
+        return Err(TickError::InvalidFormat);
+    }
+    let exponent = frac_part.len() as u32;
+    if exponent > MAX_EXPONENT {
+        return Err(TickError::Overflow);
+    }
+
+    let mut mantissa: i128 = 0;
+    for b in int_part.bytes().chain(frac_part.bytes()) {
+        mantissa = mantissa.checked_mul(10)
+            .and_then(|m| m.checked_add(i128::from(b - b'0')))
+            .ok_or(TickError::Overflow)?;
+    }
+    if negative {
+        mantissa = -mantissa;
+    }
+    Ok((mantissa, exponent))
+}
+
+fn format_decimal(value: i128, exponent: u32) -> String {
+    let sign = if value < 0 { "-" } else { "" };
+    format!("{}{}", sign, format_unsigned(value.unsigned_abs(), exponent))
+}
+
+fn format_unsigned(value: u128, exponent: u32) -> String {
+    if exponent == 0 {
+        return value.to_string();
+    }
+    let scale = 10u128.pow(exponent);
+    format!("{}.{:0width$}", value / scale, value % scale, width = exponent as usize)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_to_ticks() {
+        let tick: TickSize = "0.05".parse().unwrap();
+        assert_eq!(tick.to_ticks("1.25"), Ok(25));
+        assert_eq!(tick.to_ticks("1.2"), Ok(24));
+        assert_eq!(tick.to_ticks("-0.1"), Ok(-2));
+        assert_eq!(tick.to_ticks("3"), Ok(60));
+        assert_eq!(tick.to_ticks("1.26"), Err(TickError::NotMultiple));
+        assert_eq!(tick.to_ticks("1."), Err(TickError::InvalidFormat));
+        assert_eq!(tick.to_ticks("1,2"), Err(TickError::InvalidFormat));
+        assert_eq!(tick.to_ticks("1e9"), Err(TickError::InvalidFormat));
+        assert_eq!(TickSize::ONE.to_ticks("9223372036854775808"), Err(TickError::Overflow));
+
+        let tick: TickSize = "25".parse().unwrap();
+        assert_eq!(tick.to_ticks("100"), Ok(4));
+        assert_eq!(tick.to_ticks("110"), Err(TickError::NotMultiple));
+    }
+
+    #[test]
+    fn test_to_decimal() {
+        let tick = TickSize::new(5, 2).unwrap();
+        assert_eq!(tick.to_string(), "0.05");
+        assert_eq!(tick.to_decimal(25), "1.25");
+        assert_eq!(tick.to_decimal(-2), "-0.10");
+        assert_eq!(tick.to_decimal(0), "0.00");
+        assert_eq!(TickSize::ONE.to_decimal(Price::MIN), Price::MIN.to_string());
+        assert_eq!("0".parse::<TickSize>(), Err(TickError::InvalidTickSize));
+        assert_eq!("-0.5".parse::<TickSize>(), Err(TickError::InvalidTickSize));
+    }
+
+    #[test]
+    fn test_units() {
+        let unit = TickSize::new(1, 3).unwrap();
+        assert_eq!(unit.to_units("1.5"), Ok(1500));
+        assert_eq!(unit.to_units("0.0005"), Err(TickError::NotMultiple));
+        assert_eq!(unit.to_units("-1"), Err(TickError::InvalidFormat));
+        assert_eq!(TickSize::ONE.to_units("18446744073709551616"), Err(TickError::Overflow));
+        assert_eq!(unit.units_to_decimal(u64::MAX), "18446744073709551.615");
+        assert_eq!(unit.units_to_decimal(20), "0.020");
+    }
+
+    #[test]
+    fn test_rescale() {
+        let cents = TickSize::new(1, 2).unwrap();
+        let nickels = TickSize::new(5, 2).unwrap();
+        assert_eq!(cents.rescale(125, &nickels), Ok(25));
+        assert_eq!(nickels.rescale(-3, &cents), Ok(-15));
+        assert_eq!(cents.rescale(126, &nickels), Err(TickError::NotMultiple));
+        assert_eq!(TickSize::ONE.rescale(Price::MAX, &cents), Err(TickError::Overflow));
+    }
+}
@@ -0,0 +1,162 @@
+//! Per-order audit trail
+//!
+//! When enabled, the book keeps a lifecycle record for every submitted order, so the history
+//! of a single order can be inspected without replaying the whole execution log.
+
+use crate::config::RejectReason;
+use crate::log::LogItem;
+use crate::math;
+use crate::order::{IncomingOrder, OrderId, Price, Size};
+use crate::HashMap;
+use alloc::vec::Vec;
+
+/// Single event in the lifecycle of an order
+#[allow(missing_docs)]
+#[derive(Debug, Clone, PartialEq)]
+pub enum TrailEvent {
+    /// Order was submitted to the book
+    Submitted,
+    /// Order passed validation
+    Accepted,
+    /// Order was rejected by validation
+    Rejected(RejectReason),
+    /// Order was filled against `counterparty`; `passive` is set when the order was resting in the book
+    Filled {
+        size: Size,
+        price: Price,
+        counterparty: OrderId,
+        passive: bool,
+    },
+    /// Remainder of the order was added to the order queue
+    Enqueued { size: Size },
+    /// Remainder of the order was cancelled
+    Cancelled { size: Size },
+    /// Resting order was cancelled by an administrator
+    ForceCancelled { size: Size },
+    /// Resting order was amended to `price` and remaining `size`
+    Amended { size: Size, price: Price },
+    /// Stop order was added to the trigger queue
+    Parked { size: Size },
+    /// Stop order was triggered
+    Triggered,
+    /// Resting or stop order expired
+    Expired { size: Size },
+    /// Displayed part of the resting iceberg order was refilled with `size` units from the hidden reserve
+    Refilled { size: Size },
+}
+
+/// Lifecycle record of a single order
+#[derive(Debug, Clone, PartialEq)]
+pub struct OrderTrail {
+    order: IncomingOrder,
+    events: Vec<TrailEvent>,
+}
+
+impl OrderTrail {
+    /// Returns the order as it was submitted
+    pub fn order(&self) -> &IncomingOrder {
+        &self.order
+    }
+
+    /// Returns lifecycle events in chronological order
+    pub fn events(&self) -> &[TrailEvent] {
+        &self.events
+    }
+
+    /// Returns total filled size
+    pub fn filled_size(&self) -> Size {
+        self.events.iter().map(|event| match event {
+            TrailEvent::Filled { size, .. } => *size,
+            _ => 0,
+        }).sum()
+    }
+
+    /// Returns average fill price, or `None` if nothing was filled
+    pub fn average_price(&self) -> Option<f64> {
+        let notional = self.events.iter().map(|event| match *event {
+            TrailEvent::Filled { size, price, .. } => math::notional(price, size),
+            _ => 0,
+        }).sum();
+        math::average_price(notional, self.filled_size())
+    }
+
+    /// Returns size which is neither filled nor cancelled
+    pub fn remaining_size(&self) -> Size {
+        let closed: u64 = self.events.iter().map(|event| match event {
+            TrailEvent::Filled { size, .. } | TrailEvent::Cancelled { size } | TrailEvent::ForceCancelled { size } => *size,
+            TrailEvent::Rejected(_) => self.order.size,
+            _ => 0,
+        }).sum();
+        self.order.size.saturating_sub(closed)
+    }
+
+    /// Checks whether the order reached its final state
+    pub fn is_closed(&self) -> bool {
+        self.remaining_size() == 0
+    }
+}
+
+/// Storage of order trails
+#[derive(Clone, Default)]
+pub(crate) struct TrailStore(HashMap<OrderId, OrderTrail>);
+
+impl TrailStore {
+    pub fn get(&self, order_id: OrderId) -> Option<&OrderTrail> {
+        self.0.get(&order_id)
+    }
+
+    /// Appends `event` to the trail of the order `order_id`, if the trail exists
+    pub fn append(&mut self, order_id: OrderId, event: TrailEvent) {
+        if let Some(trail) = self.0.get_mut(&order_id) {
+            trail.events.push(event);
+        }
+    }
+
+    /// Records the items logged for the order `order_id`
+    pub fn record(&mut self, order_id: OrderId, order: &IncomingOrder, items: &[LogItem]) {
+        let mut events = vec![TrailEvent::Submitted];
+        if !matches!(items.first(), Some(LogItem::Rejected { .. })) {
+            events.push(TrailEvent::Accepted);
+        }
+        self.push_events(order_id, items, &mut events);
+        self.0.insert(order_id, OrderTrail {
+            order: order.clone(),
+            events,
+        });
+    }
+
+    /// Records further items logged for the order `order_id` (amendment, stop trigger, expiry)
+    pub fn record_update(&mut self, order_id: OrderId, items: &[LogItem]) {
+        if let Some(mut trail) = self.0.remove(&order_id) {
+            self.push_events(order_id, items, &mut trail.events);
+            self.0.insert(order_id, trail);
+        }
+    }
+
+    fn push_events(&mut self, order_id: OrderId, items: &[LogItem], events: &mut Vec<TrailEvent>) {
+        for item in items {
+            match *item {
+                LogItem::Rejected { reason, .. } => events.push(TrailEvent::Rejected(reason)),
+                LogItem::Fulfilled { size, price, order_id: maker_id, .. } => {
+                    events.push(TrailEvent::Filled { size, price, counterparty: maker_id, passive: false });
+                    if let Some(maker) = self.0.get_mut(&maker_id) {
+                        maker.events.push(TrailEvent::Filled { size, price, counterparty: order_id, passive: true });
+                    }
+                }
+                LogItem::Enqueued { size, .. } => events.push(TrailEvent::Enqueued { size }),
+                LogItem::Cancelled { size, .. } => events.push(TrailEvent::Cancelled { size }),
+                LogItem::ForceCancelled { size, .. } => events.push(TrailEvent::ForceCancelled { size }),
+                LogItem::Amended { size, price, .. } => events.push(TrailEvent::Amended { size, price }),
+                LogItem::Parked { size } => events.push(TrailEvent::Parked { size }),
+                LogItem::Triggered { .. } => events.push(TrailEvent::Triggered),
+                LogItem::Expired { size, .. } => events.push(TrailEvent::Expired { size }),
+                LogItem::Refilled { size, order_id: maker_id } => {
+                    if let Some(maker) = self.0.get_mut(&maker_id) {
+                        maker.events.push(TrailEvent::Refilled { size });
+                    }
+                }
+                LogItem::IntegrityError { .. } | LogItem::StateChanged { .. } => {}
+            }
+        }
+    }
+}
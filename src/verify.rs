@@ -0,0 +1,92 @@
+//! Cross-validation of order book snapshots against replayed command logs
+
+use crate::OrderBook;
+use crate::order::IncomingOrder;
+use crate::log::DummyLogger;
+use core::fmt;
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+
+/// First mismatch between the replayed book and the expected snapshot
+#[derive(Debug, PartialEq)]
+pub struct Divergence {
+    /// Position of the mismatching order in the [to_vec](OrderBook::to_vec) representation
+    pub index: usize,
+    /// Order found in the replayed book, if any
+    pub actual: Option<Box<IncomingOrder>>,
+    /// Order found in the expected snapshot, if any
+    pub expected: Option<Box<IncomingOrder>>,
+}
+
+impl fmt::Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "divergence at order {}: ", self.index)?;
+        match &self.expected {
+            Some(order) => write!(f, "expected '{}'", order)?,
+            None => write!(f, "expected nothing")?,
+        }
+        match &self.actual {
+            Some(order) => write!(f, ", found '{}'", order),
+            None => write!(f, ", found nothing"),
+        }
+    }
+}
+
+/// Returns the first difference between two snapshots in the [to_vec](OrderBook::to_vec) representation
+pub fn compare_snapshots(actual: &[IncomingOrder], expected: &[IncomingOrder]) -> Result<(), Divergence> {
+    let len = core::cmp::max(actual.len(), expected.len());
+    for index in 0..len {
+        let actual = actual.get(index);
+        let expected = expected.get(index);
+        if actual != expected {
+            return Err(Divergence {
+                index,
+                actual: actual.cloned().map(Box::new),
+                expected: expected.cloned().map(Box::new),
+            });
+        }
+    }
+    Ok(())
+}
+
+/// Restores a book from `snapshot`, replays `commands` on it and checks the result against `expected`
+pub fn verify_replay(
+    snapshot: Vec<IncomingOrder>,
+    commands: impl IntoIterator<Item=IncomingOrder>,
+    expected: &[IncomingOrder],
+) -> Result<(), Divergence> {
+    let mut book = OrderBook::from_vec(snapshot);
+    let mut logger = DummyLogger;
+    for order in commands {
+        book.execute_order(order, &mut logger);
+    }
+    compare_snapshots(&book.to_vec_with_hidden(), expected)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(list: &[&str]) -> Vec<IncomingOrder> {
+        list.iter().map(|s| s.parse().unwrap()).collect()
+    }
+
+    #[test]
+    fn replay_matches_snapshot() {
+        let snapshot = parse(&["Lim B $100 #5 u1", "Lim S $110 #5 u2"]);
+        let commands = parse(&["Lim B $110 #3 u3", "Lim B $100 #2 u4"]);
+        let expected = parse(&["Lim B $100 #5 u1", "Lim B $100 #2 u4", "Lim S $110 #2 u2"]);
+        assert_eq!(verify_replay(snapshot, commands, &expected), Ok(()));
+    }
+
+    #[test]
+    fn replay_reports_first_divergence() {
+        let snapshot = parse(&["Lim B $100 #5 u1", "Lim S $110 #5 u2"]);
+        let commands = parse(&["Lim B $110 #3 u3"]);
+        let expected = parse(&["Lim B $100 #5 u1", "Lim S $110 #5 u2"]);
+        let divergence = verify_replay(snapshot, commands, &expected).unwrap_err();
+        assert_eq!(divergence.index, 1);
+        assert_eq!(divergence.actual.unwrap().to_string(), "Lim S $110 #2 u2");
+        assert_eq!(divergence.expected.unwrap().to_string(), "Lim S $110 #5 u2");
+    }
+}
@@ -0,0 +1,98 @@
+//! Book interface for JavaScript hosts
+//!
+//! [WasmBook](WasmBook) wraps an [OrderBook](OrderBook) with methods taking and returning only
+//! strings and numbers, the values which cross the WebAssembly boundary without glue types, so a
+//! browser runs the same matching as a server. The book and these methods are exported with
+//! `wasm-bindgen`, methods named in camel case on the JavaScript side. Commands are given in the
+//! [text format](crate::decode::TextDecoder) or as [flat JSON objects](crate::decode::JsonDecoder);
+//! execution logs are returned as [JSON arrays](crate::log::log_to_json).
+
+use crate::OrderBook;
+use crate::config::BookConfig;
+use crate::decode::{JsonDecoder, OrderDecoder, TextDecoder};
+use crate::log::{log_to_json, VectorLogger};
+use crate::order::OrderId;
+use alloc::string::{String, ToString};
+use wasm_bindgen::prelude::wasm_bindgen;
+
+/// Order book with a string-based interface
+#[wasm_bindgen]
+#[derive(Clone, Default)]
+pub struct WasmBook {
+    book: OrderBook,
+}
+
+#[wasm_bindgen]
+impl WasmBook {
+    /// Constructs empty book with the default configuration
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Executes a command in the text format, e.g. `Lim B $100 #5 u1` or `Cxl o3`, and returns the log
+    pub fn execute(&mut self, command: &str) -> Result<String, String> {
+        self.decode_and_execute(&mut TextDecoder, command)
+    }
+
+    /// Executes an order given as a flat JSON object and returns the log
+    #[wasm_bindgen(js_name = executeJson)]
+    pub fn execute_json(&mut self, order: &str) -> Result<String, String> {
+        self.decode_and_execute(&mut JsonDecoder, order)
+    }
+
+    /// Returns identifier assigned to the last submitted order
+    #[wasm_bindgen(js_name = lastOrderId)]
+    pub fn last_order_id(&self) -> Option<OrderId> {
+        self.book.last_order_id()
+    }
+
+    /// Returns up to `levels` price levels of each side as JSON
+    pub fn depth(&self, levels: usize) -> String {
+        self.book.depth(levels).to_json()
+    }
+}
+
+impl WasmBook {
+    /// Constructs empty book with `config`
+    pub fn with_config(config: BookConfig) -> Self {
+        WasmBook { book: OrderBook::with_config(config) }
+    }
+
+    fn decode_and_execute(&mut self, decoder: &mut impl OrderDecoder, input: &str) -> Result<String, String> {
+        let command = decoder.decode(input.as_bytes()).map_err(|e| e.to_string())?;
+        let mut logger = VectorLogger::new();
+        self.book.execute_command(command, &mut logger);
+        Ok(log_to_json(logger.as_slice()))
+    }
+
+    /// Returns the wrapped book
+    pub fn book(&self) -> &OrderBook {
+        &self.book
+    }
+}
+
+impl From<OrderBook> for WasmBook {
+    fn from(book: OrderBook) -> Self {
+        WasmBook { book }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn commands_as_strings() {
+        let mut book = WasmBook::new();
+        assert_eq!(book.execute("Lim S $101 #5 u1").unwrap(), r#"[{"type":"enqueued","size":5,"price":101,"side":"S","user_id":1,"order_id":1}]"#);
+        let json = r#"{"side":"B","kind":"IoC","price":101,"size":2,"user":2}"#;
+        assert_eq!(book.execute_json(json).unwrap(), r#"[{"type":"fulfilled","size":2,"price":101,"user_id":1,"order_id":1}]"#);
+        assert_eq!(book.last_order_id(), Some(2));
+        assert_eq!(book.depth(5), r#"{"bids":[],"asks":[{"price":101,"size":3,"orders":1}]}"#);
+
+        assert_eq!(book.execute("Cxl o7").unwrap(), r#"[{"type":"rejected","size":0,"reason":"unknown-order"}]"#);
+        assert_eq!(book.execute("Lim X"), Err("malformed record".to_string()));
+        assert_eq!(book.execute_json(r#"{"side":"B"}"#), Err("missing field 'kind'".to_string()));
+    }
+}
@@ -0,0 +1,463 @@
+//! Versioned binary formats for snapshots and write-ahead logs
+//!
+//! Every persisted file starts with an 8-byte header:
+//!
+//! | Offset | Size | Content                                       |
+//! |--------|------|-----------------------------------------------|
+//! | 0      | 4    | Magic `MTCH`                                  |
+//! | 4      | 1    | Kind: 1 - snapshot, 2 - write-ahead log, 3 - book state |
+//! | 5      | 2    | Format version (`u16`, little-endian)         |
+//! | 7      | 1    | Reserved, zero                                |
+//!
+//! A snapshot continues with the number of orders (`u64`) and the orders as
+//! [binary records](crate::decode::BinaryDecoder). A write-ahead log continues with commands, each
+//! prefixed by its type (`u8`, 1 - new order, 2 - cancel, 3 - amend) and payload length (`u16`).
+//! Cancels carry the order id (`u64`, little-endian), amendments the order id, price (`i64`) and size (`u64`).
+//!
+//! A book state keeps the queues as they are, so it is loaded without matching. It continues with
+//! the next order id (`u64`), the last trade price (`u8` presence flag and `i64`), sequence numbers
+//! of the last trade and the last log item (`u64` each) and four sections: bids and asks in priority
+//! order, stop orders, then pegged orders. Every section starts with the number of entries (`u64`).
+//! Resting orders are written as the order id, the displayed size (`u64`) and the binary record of
+//! the whole order, stop orders as the order id and the record. Pegged orders rest in the bids and asks;
+//! their section repeats the order ids with the records of the pegged orders. An optional fifth section
+//! lists the order ids and the times (`u64` each) at which resting orders took their queue positions.
+//! Book states without the pegged orders section have no pegged orders, those without the last
+//! section have no entry times.
+//!
+//! Files of older versions are upgraded to the current representation on load;
+//! files of newer versions are rejected with [UnsupportedVersion](WireError::UnsupportedVersion).
+
+use crate::{OrderBook, OrderQueue};
+use crate::config::BookConfig;
+use crate::decode::{BinaryDecoder, Command, DecodeError, OrderDecoder, BINARY_ORDER_LEN};
+use crate::order::{Direction, IncomingOrder, Order, OrderKind, OrderSide};
+use crate::peg::Peg;
+use crate::queues::Queue;
+use std::fmt;
+use std::io::{self, Write};
+
+/// Magic bytes of every persisted file
+pub const MAGIC: [u8; 4] = *b"MTCH";
+
+/// Version written by this crate
+pub const CURRENT_VERSION: u16 = 1;
+
+/// Length of the file header
+pub const HEADER_LEN: usize = 8;
+
+const COMMAND_NEW: u8 = 1;
+const COMMAND_CANCEL: u8 = 2;
+const COMMAND_AMEND: u8 = 3;
+
+/// Kind of a persisted file
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FormatKind {
+    /// Book snapshot
+    Snapshot,
+    /// Write-ahead log segment
+    Wal,
+    /// Book state with order identifiers and queue positions
+    BookState,
+}
+
+impl FormatKind {
+    fn to_byte(self) -> u8 {
+        match self {
+            FormatKind::Snapshot => 1,
+            FormatKind::Wal => 2,
+            FormatKind::BookState => 3,
+        }
+    }
+}
+
+/// Error of reading a persisted file
+#[derive(Debug, Clone, PartialEq)]
+pub enum WireError {
+    /// File does not start with [MAGIC](MAGIC)
+    BadMagic,
+    /// File is of another kind
+    WrongKind,
+    /// File was written by a newer or unknown format version
+    UnsupportedVersion(u16),
+    /// File ends in the middle of a record
+    Truncated,
+    /// Command type is unknown
+    UnknownCommand(u8),
+    /// Record cannot be decoded
+    InvalidRecord(DecodeError),
+    /// File cannot be read
+    Io(io::ErrorKind),
+}
+
+impl fmt::Display for WireError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            WireError::BadMagic => write!(f, "not a matcher file"),
+            WireError::WrongKind => write!(f, "unexpected file kind"),
+            WireError::UnsupportedVersion(version) => write!(f, "unsupported format version {}", version),
+            WireError::Truncated => write!(f, "truncated file"),
+            WireError::UnknownCommand(command) => write!(f, "unknown command type {}", command),
+            WireError::InvalidRecord(e) => write!(f, "invalid record: {}", e),
+            WireError::Io(kind) => write!(f, "i/o error: {:?}", kind),
+        }
+    }
+}
+
+/// Returns file header of the current version
+pub fn header(kind: FormatKind) -> [u8; HEADER_LEN] {
+    let mut header = [0; HEADER_LEN];
+    header[..4].copy_from_slice(&MAGIC);
+    header[4] = kind.to_byte();
+    header[5..7].copy_from_slice(&CURRENT_VERSION.to_le_bytes());
+    header
+}
+
+/// Checks the header of `bytes` and returns the format version and the rest of the file
+pub fn read_header(bytes: &[u8], kind: FormatKind) -> Result<(u16, &[u8]), WireError> {
+    if bytes.len() < HEADER_LEN {
+        return Err(if MAGIC.starts_with(bytes) { WireError::Truncated } else { WireError::BadMagic });
+    }
+    if bytes[..4] != MAGIC {
+        return Err(WireError::BadMagic);
+    }
+    if bytes[4] != kind.to_byte() {
+        return Err(WireError::WrongKind);
+    }
+    let version = u16::from_le_bytes([bytes[5], bytes[6]]);
+    match version {
+        1 => Ok((version, &bytes[HEADER_LEN..])),
+        _ => Err(WireError::UnsupportedVersion(version)),
+    }
+}
+
+fn take(bytes: &mut &[u8], len: usize) -> Result<Vec<u8>, WireError> {
+    if bytes.len() < len {
+        return Err(WireError::Truncated);
+    }
+    let (head, tail) = bytes.split_at(len);
+    *bytes = tail;
+    Ok(head.to_vec())
+}
+
+fn decode_order(record: &[u8]) -> Result<IncomingOrder, WireError> {
+    match BinaryDecoder.decode(record).map_err(WireError::InvalidRecord)? {
+        Command::New(order) => Ok(order),
+        Command::Cancel(_) | Command::Amend { .. } => Err(WireError::InvalidRecord(DecodeError::Malformed)),
+    }
+}
+
+/// Encodes snapshot of `book`
+pub fn encode_snapshot(book: &OrderBook) -> Vec<u8> {
+    let orders = book.to_vec_with_hidden();
+    let mut bytes = Vec::with_capacity(HEADER_LEN + 8 + orders.len() * BINARY_ORDER_LEN);
+    bytes.extend_from_slice(&header(FormatKind::Snapshot));
+    bytes.extend_from_slice(&(orders.len() as u64).to_le_bytes());
+    for order in &orders {
+        bytes.extend_from_slice(&BinaryDecoder::encode(order));
+    }
+    bytes
+}
+
+/// Decodes snapshot into orders which restore the book with [from_vec](OrderBook::from_vec)
+pub fn decode_snapshot(bytes: &[u8]) -> Result<Vec<IncomingOrder>, WireError> {
+    let (_, mut rest) = read_header(bytes, FormatKind::Snapshot)?;
+    let mut count = [0; 8];
+    count.copy_from_slice(&take(&mut rest, 8)?);
+    let count = u64::from_le_bytes(count);
+    let mut orders = Vec::new();
+    for _ in 0..count {
+        let len = BinaryDecoder::record_len(*rest.first().ok_or(WireError::Truncated)?);
+        orders.push(decode_order(&take(&mut rest, len)?)?);
+    }
+    Ok(orders)
+}
+
+fn take_u64(bytes: &mut &[u8]) -> Result<u64, WireError> {
+    let [word] = words(&take(bytes, 8)?)?;
+    Ok(word)
+}
+
+fn take_order(bytes: &mut &[u8]) -> Result<IncomingOrder, WireError> {
+    let len = BinaryDecoder::record_len(*bytes.first().ok_or(WireError::Truncated)?);
+    decode_order(&take(bytes, len)?)
+}
+
+fn encode_queue<D: Direction>(bytes: &mut Vec<u8>, queue: &OrderQueue<D>) {
+    bytes.extend_from_slice(&(queue.orders.len() as u64).to_le_bytes());
+    for order in &queue.orders {
+        bytes.extend_from_slice(&order.order_id.to_le_bytes());
+        bytes.extend_from_slice(&order.size.to_le_bytes());
+        bytes.extend_from_slice(&BinaryDecoder::encode(&order.to_incoming()));
+    }
+}
+
+fn decode_queue<D: Direction>(bytes: &mut &[u8]) -> Result<OrderQueue<D>, WireError> {
+    let malformed = WireError::InvalidRecord(DecodeError::Malformed);
+    let count = take_u64(bytes)?;
+    let mut orders: Vec<Order<D>> = Vec::new();
+    for _ in 0..count {
+        let order_id = take_u64(bytes)?;
+        let displayed = take_u64(bytes)?;
+        let incoming = take_order(bytes)?;
+        let resting = matches!(incoming.kind, OrderKind::Limit | OrderKind::Iceberg { .. } | OrderKind::Hidden);
+        if !resting || incoming.side != D::SIDE || displayed == 0 || displayed > incoming.size {
+            return Err(malformed);
+        }
+        let mut order = Order::from_incoming(&incoming, order_id);
+        order.hidden = order.size - displayed;
+        order.size = displayed;
+        let out_of_order = orders.last().is_some_and(|last| match D::SIDE {
+            OrderSide::Buy => order.price_limit > last.price_limit,
+            OrderSide::Sell => order.price_limit < last.price_limit,
+        });
+        if out_of_order {
+            return Err(malformed);
+        }
+        orders.push(order);
+    }
+    Ok(OrderQueue::restore(orders))
+}
+
+/// Encodes state of `book` which is restored by [decode_book_state](decode_book_state)
+///
+/// Indicative quotes and bookkeeping (audit, trails, metrics, statuses) are not included.
+pub fn encode_book_state(book: &OrderBook) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    bytes.extend_from_slice(&header(FormatKind::BookState));
+    bytes.extend_from_slice(&book.next_order_id.to_le_bytes());
+    bytes.push(book.last_trade_price.is_some() as u8);
+    bytes.extend_from_slice(&book.last_trade_price.unwrap_or(0).to_le_bytes());
+    bytes.extend_from_slice(&book.trade_sequence.to_le_bytes());
+    bytes.extend_from_slice(&book.log_sequence.to_le_bytes());
+    encode_queue(&mut bytes, &book.bid);
+    encode_queue(&mut bytes, &book.ask);
+    bytes.extend_from_slice(&(book.stops.len() as u64).to_le_bytes());
+    for (order_id, order) in book.stops.iter() {
+        bytes.extend_from_slice(&order_id.to_le_bytes());
+        bytes.extend_from_slice(&BinaryDecoder::encode(order));
+    }
+    bytes.extend_from_slice(&(book.pegs.len() as u64).to_le_bytes());
+    for (order_id, peg) in book.pegs.iter() {
+        let resting = book.resting_order(order_id).expect("pegged order rests");
+        bytes.extend_from_slice(&order_id.to_le_bytes());
+        bytes.extend_from_slice(&BinaryDecoder::encode(&peg.to_incoming(resting.size, resting.user_id, resting.expires_at)));
+    }
+    let bids = book.bid.orders.iter().filter_map(|order| Some((order.order_id, order.entered_at?)));
+    let asks = book.ask.orders.iter().filter_map(|order| Some((order.order_id, order.entered_at?)));
+    let entries: Vec<_> = bids.chain(asks).collect();
+    bytes.extend_from_slice(&(entries.len() as u64).to_le_bytes());
+    for (order_id, entered_at) in entries {
+        bytes.extend_from_slice(&order_id.to_le_bytes());
+        bytes.extend_from_slice(&entered_at.to_le_bytes());
+    }
+    bytes
+}
+
+/// Decodes a book state into a book with the given configuration
+pub fn decode_book_state(bytes: &[u8], config: BookConfig) -> Result<OrderBook, WireError> {
+    let (_, mut rest) = read_header(bytes, FormatKind::BookState)?;
+    let mut book = OrderBook::with_config(config);
+    book.next_order_id = take_u64(&mut rest)?;
+    let has_last_trade = take(&mut rest, 1)?[0] != 0;
+    let last_trade_price = take_u64(&mut rest)? as i64;
+    book.last_trade_price = if has_last_trade { Some(last_trade_price) } else { None };
+    book.trade_sequence = take_u64(&mut rest)?;
+    book.log_sequence = take_u64(&mut rest)?;
+    book.bid = decode_queue(&mut rest)?;
+    book.ask = decode_queue(&mut rest)?;
+    let count = take_u64(&mut rest)?;
+    for _ in 0..count {
+        let order_id = take_u64(&mut rest)?;
+        let order = take_order(&mut rest)?;
+        if !matches!(order.kind, OrderKind::Stop { .. } | OrderKind::StopLimit { .. } | OrderKind::TrailingStop { .. }) {
+            return Err(WireError::InvalidRecord(DecodeError::Malformed));
+        }
+        book.stops.insert(order_id, order);
+    }
+    let count = if rest.is_empty() { 0 } else { take_u64(&mut rest)? };
+    for _ in 0..count {
+        let order_id = take_u64(&mut rest)?;
+        let order = take_order(&mut rest)?;
+        let resting = book.resting_order(order_id).filter(|resting| resting.side == order.side);
+        match (Peg::from_order(&order, None), resting) {
+            (Some(peg), Some(_)) => book.pegs.insert(order_id, peg),
+            _ => return Err(WireError::InvalidRecord(DecodeError::Malformed)),
+        }
+    }
+    let count = if rest.is_empty() { 0 } else { take_u64(&mut rest)? };
+    for _ in 0..count {
+        let order_id = take_u64(&mut rest)?;
+        let entered_at = take_u64(&mut rest)?;
+        let found = match (book.bid.index.get(&order_id), book.ask.index.get(&order_id)) {
+            (Some(&price), _) => book.bid.orders.update_order(price, order_id, |order| order.entered_at = Some(entered_at)),
+            (_, Some(&price)) => book.ask.orders.update_order(price, order_id, |order| order.entered_at = Some(entered_at)),
+            _ => false,
+        };
+        if !found {
+            return Err(WireError::InvalidRecord(DecodeError::Malformed));
+        }
+    }
+    Ok(book)
+}
+
+/// Writer of a write-ahead log segment
+pub struct WalWriter<W> {
+    writer: W,
+}
+
+impl<W: Write> WalWriter<W> {
+    /// Starts a new segment in `writer`
+    pub fn new(mut writer: W) -> io::Result<Self> {
+        writer.write_all(&header(FormatKind::Wal))?;
+        Ok(WalWriter { writer })
+    }
+
+    /// Continues a segment whose header is already in `writer`
+    pub fn resume(writer: W) -> Self {
+        WalWriter { writer }
+    }
+
+    /// Appends `command` to the segment
+    pub fn append(&mut self, command: &Command) -> io::Result<()> {
+        let (command_type, payload) = match command {
+            Command::New(order) => (COMMAND_NEW, BinaryDecoder::encode(order)),
+            Command::Cancel(order_id) => (COMMAND_CANCEL, order_id.to_le_bytes().to_vec()),
+            Command::Amend { order_id, price, size } => {
+                let mut payload = order_id.to_le_bytes().to_vec();
+                payload.extend_from_slice(&price.to_le_bytes());
+                payload.extend_from_slice(&size.to_le_bytes());
+                (COMMAND_AMEND, payload)
+            }
+        };
+        self.writer.write_all(&[command_type])?;
+        self.writer.write_all(&(payload.len() as u16).to_le_bytes())?;
+        self.writer.write_all(&payload)
+    }
+
+    /// Flushes the underlying writer
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+
+    /// Returns a reference to the underlying writer
+    pub fn get_ref(&self) -> &W {
+        &self.writer
+    }
+
+    /// Returns the underlying writer
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+/// Splits `payload` into `N` little-endian 64-bit words
+fn words<const N: usize>(payload: &[u8]) -> Result<[u64; N], WireError> {
+    if payload.len() != N * 8 {
+        return Err(WireError::InvalidRecord(DecodeError::InvalidLength));
+    }
+    let mut words = [0; N];
+    for (word, bytes) in words.iter_mut().zip(payload.chunks(8)) {
+        let mut buf = [0; 8];
+        buf.copy_from_slice(bytes);
+        *word = u64::from_le_bytes(buf);
+    }
+    Ok(words)
+}
+
+/// Decodes commands of a write-ahead log segment
+pub fn decode_wal(bytes: &[u8]) -> Result<Vec<Command>, WireError> {
+    let (_, mut rest) = read_header(bytes, FormatKind::Wal)?;
+    let mut commands = Vec::new();
+    while !rest.is_empty() {
+        let prefix = take(&mut rest, 3)?;
+        let len = usize::from(u16::from_le_bytes([prefix[1], prefix[2]]));
+        let payload = take(&mut rest, len)?;
+        match prefix[0] {
+            COMMAND_NEW => commands.push(Command::New(decode_order(&payload)?)),
+            COMMAND_CANCEL => {
+                let [order_id] = words(&payload)?;
+                commands.push(Command::Cancel(order_id));
+            }
+            COMMAND_AMEND => {
+                let [order_id, price, size] = words(&payload)?;
+                commands.push(Command::Amend { order_id, price: price as i64, size });
+            }
+            command_type => return Err(WireError::UnknownCommand(command_type)),
+        }
+    }
+    Ok(commands)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::OrderBookExt;
+    use std::sync::Arc;
+
+    #[test]
+    fn snapshot_round_trip() {
+        let orders: Vec<IncomingOrder> = ["Lim B $99 #5 u1", "Lim B $100 #2 u2", "Lim S $101 #3 u3"].iter()
+            .map(|s| s.parse().unwrap())
+            .collect();
+        let book = OrderBook::from_vec(orders.clone());
+        let bytes = encode_snapshot(&book);
+        assert_eq!(decode_snapshot(&bytes), Ok(orders));
+
+        assert_eq!(decode_snapshot(&bytes[..bytes.len() - 1]), Err(WireError::Truncated));
+        assert_eq!(decode_snapshot(b"MTC"), Err(WireError::Truncated));
+        assert_eq!(decode_snapshot(b"{\"orders\":[]}"), Err(WireError::BadMagic));
+        let mut newer = bytes.clone();
+        newer[5] = 2;
+        assert_eq!(decode_snapshot(&newer), Err(WireError::UnsupportedVersion(2)));
+        assert_eq!(decode_wal(&bytes), Err(WireError::WrongKind));
+    }
+
+    #[test]
+    fn wal_round_trip() {
+        let mut commands: Vec<Command> = ["Lim B $99 #5 u1", "IoC S $-3 #2 u2"].iter()
+            .map(|s| Command::New(s.parse().unwrap()))
+            .collect();
+        commands.push(Command::Cancel(1));
+        commands.push(Command::Amend { order_id: 2, price: -4, size: 3 });
+        let mut wal = WalWriter::new(Vec::new()).unwrap();
+        for command in &commands {
+            wal.append(command).unwrap();
+        }
+        let mut bytes = wal.into_inner();
+        assert_eq!(decode_wal(&bytes), Ok(commands));
+        bytes[HEADER_LEN] = 9;
+        assert_eq!(decode_wal(&bytes), Err(WireError::UnknownCommand(9)));
+    }
+
+    #[test]
+    fn book_state_round_trip() {
+        let mut book = OrderBook::from_orders(&[
+            "Lim B $99 #5 u1",
+            "Lim B $100 #2 u2",
+            "Ice S $101 #10 u3 ^3 !50",
+            "Lim S $101 #4 u4",
+            "StpLim S $95 #1 u5 @96",
+            "Peg B $99 #3 u7 =pri",
+            "Hid S $102 #2 u8",
+        ]);
+        // Partially fill the iceberg order
+        book.execute_order("IoC B $101 #4 u6".parse().unwrap(), &mut crate::log::DummyLogger);
+        book.set_clock(Some(Arc::new(|| 7)));
+        book.execute_order("Lim B $98 #1 u9".parse().unwrap(), &mut crate::log::DummyLogger);
+        let mut bytes = Vec::new();
+        book.write_snapshot(&mut bytes).unwrap();
+        let restored = OrderBook::read_snapshot(&bytes[..]).unwrap();
+        assert_eq!(restored.to_vec_with_hidden(), book.to_vec_with_hidden());
+        assert_eq!(restored.last_order_id(), book.last_order_id());
+        assert_eq!((restored.last_trade_price, restored.trade_sequence), (Some(101), 2));
+        assert_eq!(restored.log_sequence(), book.log_sequence());
+        assert_eq!(restored.inspect_order(3), book.inspect_order(3));
+        assert_eq!(restored.inspect_order(9).unwrap().entered_at, Some(7));
+        assert_eq!(restored.depth(1), book.depth(1));
+        assert_eq!(restored.pegs().get(6), book.pegs().get(6));
+
+        assert_eq!(decode_book_state(&bytes[..bytes.len() - 1], BookConfig::default()).err(), Some(WireError::Truncated));
+        assert_eq!(decode_snapshot(&bytes), Err(WireError::WrongKind));
+    }
+}